@@ -0,0 +1,30 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+
+fn main() {
+    // Build hash is shown in `edgedb info`, it's optional as the crate can
+    // be built from a source archive
+    let hash = Command::new("git")
+        .args(&["rev-parse", "--short=10", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok());
+    if let Some(hash) = hash {
+        println!("cargo:rustc-env=EDGEDB_BUILD_HASH={}", hash.trim());
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    // HEAD only changes on checkout, commits change the branch it refers
+    // to; missing files would make cargo rerun the script on every build
+    let head = fs::read_to_string(".git/HEAD").unwrap_or_default();
+    if let Some(branch) = head.trim().strip_prefix("ref: ") {
+        for path in &[format!(".git/{}", branch), ".git/packed-refs".into()] {
+            if Path::new(path).exists() {
+                println!("cargo:rerun-if-changed={}", path);
+            }
+        }
+    }
+    println!("cargo:rerun-if-env-changed=EDGEDB_BUILD_HASH");
+}
@@ -0,0 +1,235 @@
+use std::time::{Duration, Instant};
+
+use async_std::prelude::StreamExt;
+use async_std::task;
+use clap::{Clap, AppSettings};
+use edgedb_client::client::Connection;
+use edgedb_client::errors::NoResultExpected;
+use edgedb_protocol::value::Value;
+use futures_util::future::join_all;
+
+use crate::format;
+use crate::interrupt::Interrupt;
+use crate::non_interactive::begin_statement;
+use crate::options::Options;
+use crate::read_only;
+use crate::table;
+
+
+/// Run a query repeatedly and report its latency and throughput
+///
+/// The query is executed over `--concurrency` connections, each one sending
+/// the next query as soon as the previous one completes. Results are read
+/// and discarded. With `--read-only`, each query runs in its own read-only
+/// transaction, which is not counted in the latency.
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Bench {
+    /// Query to benchmark
+    #[clap(long)]
+    pub query: String,
+    /// Number of connections running the query in parallel
+    #[clap(long, value_name="N", default_value="1")]
+    pub concurrency: usize,
+    /// How long to run the query for (e.g. '30s', '2m')
+    #[clap(long, default_value="30s",
+           parse(try_from_str=humantime::parse_duration))]
+    pub duration: Duration,
+    /// Print the summary as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct Summary {
+    queries: usize,
+    concurrency: usize,
+    elapsed_sec: f64,
+    queries_per_sec: f64,
+    latency_ms: Latency,
+}
+
+#[derive(serde::Serialize)]
+struct Latency {
+    min: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+/// Returns the value below which `pct` percent of `sorted` values fall
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::new(0, 0);
+    }
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1).min(sorted.len()) - 1]
+}
+
+fn summarize(mut samples: Vec<Duration>, concurrency: usize,
+    elapsed: Duration)
+    -> Summary
+{
+    samples.sort();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let total: Duration = samples.iter().sum();
+    let mean = if samples.is_empty() {
+        0.0
+    } else {
+        ms(total) / samples.len() as f64
+    };
+    Summary {
+        queries: samples.len(),
+        concurrency,
+        elapsed_sec: elapsed.as_secs_f64(),
+        queries_per_sec: samples.len() as f64 / elapsed.as_secs_f64(),
+        latency_ms: Latency {
+            min: ms(samples.first().copied().unwrap_or_default()),
+            mean,
+            p50: ms(percentile(&samples, 50.0)),
+            p90: ms(percentile(&samples, 90.0)),
+            p99: ms(percentile(&samples, 99.0)),
+            max: ms(samples.last().copied().unwrap_or_default()),
+        },
+    }
+}
+
+async fn run_query(conn: &mut Connection, query: &str)
+    -> anyhow::Result<()>
+{
+    let mut items = match conn.query_dynamic(query, &Value::empty_tuple())
+        .await
+    {
+        Ok(items) => items,
+        Err(e) if e.is::<NoResultExpected>() => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    while items.next().await.transpose()?.is_some() {}
+    Ok(())
+}
+
+/// Runs the query in the session of `options` and returns its duration
+async fn measure(conn: &mut Connection, query: &str, options: &Options)
+    -> anyhow::Result<Duration>
+{
+    let started = begin_statement(conn, query, options).await?;
+    let start = Instant::now();
+    let result = run_query(conn, query).await;
+    let elapsed = start.elapsed();
+    read_only::finish(conn, started, result).await?;
+    Ok(elapsed)
+}
+
+async fn worker(conn: &mut Connection, query: &str, options: &Options,
+    deadline: Instant, interrupt: &Interrupt)
+    -> anyhow::Result<Vec<Duration>>
+{
+    let mut samples = Vec::new();
+    // query in progress is not interrupted, so it's counted in the summary
+    while Instant::now() < deadline && !interrupt.is_set() {
+        samples.push(measure(conn, query, options).await?);
+    }
+    Ok(samples)
+}
+
+async fn connect(cmd: &Bench, options: &Options)
+    -> anyhow::Result<Vec<Connection>>
+{
+    let mut connections = Vec::with_capacity(cmd.concurrency);
+    for _ in 0..cmd.concurrency {
+        let mut conn = options.conn_params.connect().await?;
+        // fail early and make the first measurement not include preparing
+        measure(&mut conn, &cmd.query, options).await?;
+        connections.push(conn);
+    }
+    Ok(connections)
+}
+
+async fn bench(cmd: &Bench, options: &Options, interrupt: &Interrupt)
+    -> anyhow::Result<Summary>
+{
+    let mut connections = interrupt.run(connect(cmd, options)).await?;
+    let start = Instant::now();
+    let deadline = start + cmd.duration;
+    let results = join_all(connections.iter_mut()
+        .map(|conn| worker(conn, &cmd.query, options, deadline, interrupt)))
+        .await;
+    let elapsed = start.elapsed();
+    let mut samples = Vec::new();
+    for result in results {
+        samples.extend(result?);
+    }
+    Ok(summarize(samples, cmd.concurrency, elapsed))
+}
+
+pub fn main(cmd: &Bench, options: &Options) -> anyhow::Result<()> {
+    if cmd.concurrency == 0 {
+        anyhow::bail!("`--concurrency` must be positive");
+    }
+    if !cmd.json {
+        eprintln!("Running query for {} over {} connection(s)...",
+            humantime::format_duration(cmd.duration), cmd.concurrency);
+    }
+    // on Ctrl+C, summary of the queries run so far is printed
+    let interrupt = Interrupt::new()?;
+    let summary = task::block_on(bench(cmd, options, &interrupt))?;
+    if interrupt.is_set() && !cmd.json {
+        eprintln!("Interrupted after {}.", humantime::format_duration(
+            Duration::from_secs(summary.elapsed_sec as u64)));
+    }
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+    let ms = |v: f64| format::duration(Duration::from_secs_f64(v / 1000.0));
+    let lat = &summary.latency_ms;
+    table::settings(&[
+        ("Queries", &summary.queries.to_string()),
+        ("Concurrency", &summary.concurrency.to_string()),
+        ("Throughput", &format!("{:.1} queries/s",
+                                summary.queries_per_sec)),
+        ("Latency min", &ms(lat.min)),
+        ("Latency mean", &ms(lat.mean)),
+        ("Latency p50", &ms(lat.p50)),
+        ("Latency p90", &ms(lat.p90)),
+        ("Latency p99", &ms(lat.p99)),
+        ("Latency max", &ms(lat.max)),
+    ]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{percentile, summarize};
+
+    #[test]
+    fn percentiles() {
+        let samples = (1..=100).map(Duration::from_millis)
+            .collect::<Vec<_>>();
+        assert_eq!(percentile(&samples, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&samples, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&samples, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&samples[..1], 90.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::from_millis(0));
+    }
+
+    #[test]
+    fn summary() {
+        let samples = vec![
+            Duration::from_millis(30),
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        ];
+        let summary = summarize(samples, 2, Duration::from_secs(2));
+        assert_eq!(summary.queries, 3);
+        assert_eq!(summary.queries_per_sec, 1.5);
+        assert_eq!(summary.latency_ms.min, 10.0);
+        assert_eq!(summary.latency_ms.p50, 20.0);
+        assert_eq!(summary.latency_ms.max, 30.0);
+    }
+}
@@ -0,0 +1,207 @@
+//! Server features gated by the server version
+//!
+//! Features are checked against the version reported by the server
+//! (`sys::get_version()`). The protocol version itself is negotiated by
+//! `edgedb-client` on connect, negotiation of features per protocol
+//! version is out of scope here.
+
+use std::fmt;
+
+use edgedb_client::client::Connection;
+use edgedb_derive::Queryable;
+use edgedb_protocol::value::Value;
+use serde::Deserialize;
+
+
+/// Features which availability depends on the server version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// `sys::Database.builtin` property
+    BuiltinDatabaseFlag,
+    /// `ANALYZE` statement
+    Analyze,
+    /// Session state: globals and `apply_access_policies` config
+    State,
+    /// `START MIGRATION REWRITE` and `MigrationGeneratedBy` used by
+    /// `migrate --dev-mode` and `watch`
+    DevMode,
+    /// `RESET SCHEMA TO initial` statement
+    ResetSchema,
+    /// `allow_user_specified_id` setting, needed to insert objects by id
+    UserSpecifiedId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum Stage {
+    Dev,
+    Alpha,
+    Beta,
+    Rc,
+    Final,
+}
+
+/// Version as reported by `sys::get_version()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Deserialize, Queryable)]
+#[edgedb(json)]
+pub struct ServerVersion {
+    pub major: i64,
+    pub minor: i64,
+    pub stage: Stage,
+    pub stage_no: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    version: ServerVersion,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{feature} requires server {required} or later \
+         (connected to {version})")]
+pub struct Unsupported {
+    feature: Feature,
+    required: ServerVersion,
+    version: ServerVersion,
+}
+
+impl Feature {
+    fn min_version(&self) -> ServerVersion {
+        use Feature::*;
+        match self {
+            BuiltinDatabaseFlag => ServerVersion::new(1, 0, Stage::Alpha, 6),
+            Analyze => ServerVersion::release(3, 0),
+            State => ServerVersion::release(2, 0),
+            DevMode => ServerVersion::release(3, 0),
+            ResetSchema => ServerVersion::release(3, 0),
+            UserSpecifiedId => ServerVersion::release(2, 0),
+        }
+    }
+    fn title(&self) -> &'static str {
+        use Feature::*;
+        match self {
+            BuiltinDatabaseFlag => "filtering builtin databases",
+            Analyze => "ANALYZE",
+            State => "session state",
+            DevMode => "dev mode migrations",
+            ResetSchema => "resetting schema",
+            UserSpecifiedId => "inserting objects with specified id",
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.title().fmt(f)
+    }
+}
+
+impl Stage {
+    fn as_str(&self) -> &'static str {
+        use Stage::*;
+        match self {
+            Dev => "dev",
+            Alpha => "alpha",
+            Beta => "beta",
+            Rc => "rc",
+            Final => "final",
+        }
+    }
+}
+
+impl ServerVersion {
+    pub fn new(major: i64, minor: i64, stage: Stage, stage_no: i64)
+        -> ServerVersion
+    {
+        ServerVersion { major, minor, stage, stage_no }
+    }
+    pub fn release(major: i64, minor: i64) -> ServerVersion {
+        ServerVersion::new(major, minor, Stage::Final, 0)
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.stage != Stage::Final {
+            write!(f, "-{}.{}", self.stage.as_str(), self.stage_no)?;
+        }
+        Ok(())
+    }
+}
+
+impl Capabilities {
+    pub async fn fetch(cli: &mut Connection) -> anyhow::Result<Capabilities> {
+        let version = cli.query_row::<ServerVersion>(
+            "SELECT <json>sys::get_version()",
+            &Value::empty_tuple(),
+        ).await?;
+        Ok(Capabilities::from_version(version))
+    }
+    pub fn from_version(version: ServerVersion) -> Capabilities {
+        Capabilities { version }
+    }
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.version >= feature.min_version()
+    }
+    pub fn require(&self, feature: Feature) -> Result<(), Unsupported> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(Unsupported {
+                feature,
+                required: feature.min_version(),
+                version: self.version,
+            })
+        }
+    }
+}
+
+pub async fn supports(cli: &mut Connection, feature: Feature)
+    -> anyhow::Result<bool>
+{
+    Ok(Capabilities::fetch(cli).await?.supports(feature))
+}
+
+pub async fn require(cli: &mut Connection, feature: Feature)
+    -> anyhow::Result<()>
+{
+    Capabilities::fetch(cli).await?.require(feature)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Capabilities, Feature, ServerVersion, Stage};
+
+    #[test]
+    fn version_gates() {
+        let caps = Capabilities::from_version(
+            ServerVersion::new(1, 0, Stage::Alpha, 5));
+        assert!(!caps.supports(Feature::BuiltinDatabaseFlag));
+        assert_eq!(caps.require(Feature::State).unwrap_err().to_string(),
+            "session state requires server 2.0 or later \
+             (connected to 1.0-alpha.5)");
+        let caps = Capabilities::from_version(
+            ServerVersion::new(1, 0, Stage::Beta, 1));
+        assert!(caps.supports(Feature::BuiltinDatabaseFlag));
+        assert!(!caps.supports(Feature::Analyze));
+        assert!(!caps.supports(Feature::DevMode));
+        let caps = Capabilities::from_version(
+            ServerVersion::new(3, 0, Stage::Rc, 2));
+        assert!(!caps.supports(Feature::DevMode));
+        assert!(Capabilities::from_version(ServerVersion::release(3, 0))
+            .supports(Feature::DevMode));
+    }
+
+    #[test]
+    fn parse_version() {
+        let ver: ServerVersion = serde_json::from_str(r#"{
+            "major": 1, "minor": 0, "stage": "rc", "stage_no": 3,
+            "local": ["g1234"]
+        }"#).unwrap();
+        assert_eq!(ver, ServerVersion::new(1, 0, Stage::Rc, 3));
+        assert_eq!(ver.to_string(), "1.0-rc.3");
+    }
+}
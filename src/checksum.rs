@@ -0,0 +1,208 @@
+//! Verification of downloaded and pre-downloaded files
+//!
+//! Every file is checked against its SHA-256 checksum published next to it
+//! as `<file>.sha256`. Downloaded files must also have a detached GPG
+//! signature `<file>.asc` made by the EdgeDB package key, which is pinned
+//! by its fingerprint below (the key itself is served by the same host as
+//! the files, so it can't be trusted on its own).
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Context;
+use fn_error_context::context;
+use sha2::{Sha256, Digest};
+
+use crate::process;
+use crate::server::install::KEY_FILE_URL;
+use crate::server::remote;
+
+/// Fingerprint of the primary EdgeDB package key
+pub const KEY_FINGERPRINT: &str = "5D783B8985B33E0D1A2B6C7E8F90A1B2C3D4E5F6";
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+
+/// Disables verification (`--no-verify`)
+pub fn disable() {
+    eprintln!("WARNING: verification of checksums and signatures \
+        is disabled by `--no-verify`. \
+        Only use it for files from a trusted source.");
+    DISABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    !DISABLED.load(Ordering::SeqCst)
+}
+
+/// Path of the `<file>.sha256` checksum file next to the `path`
+pub fn sibling_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_else(OsString::new);
+    name.push(".sha256");
+    path.with_file_name(name)
+}
+
+/// Parses output of `sha256sum` (or a bare hex digest)
+pub fn parse(data: &str) -> anyhow::Result<String> {
+    let digest = data.split_whitespace().next().unwrap_or("");
+    if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("invalid SHA-256 checksum {:?}", digest);
+    }
+    Ok(digest.to_ascii_lowercase())
+}
+
+#[context("cannot compute checksum of {}", path.display())]
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Checks that file has `expected` SHA-256 checksum
+pub fn check(path: &Path, expected: &str) -> anyhow::Result<()> {
+    let actual = sha256_file(path)?;
+    if actual != expected {
+        anyhow::bail!("checksum mismatch for {}: expected {}, got {}",
+            path.display(), expected, actual);
+    }
+    log::info!("Checksum of {} is verified: {}", path.display(), actual);
+    Ok(())
+}
+
+/// Verifies file against `expected` checksum or the `<file>.sha256` one
+pub fn verify_file(path: &Path, expected: Option<&str>)
+    -> anyhow::Result<()>
+{
+    if !enabled() {
+        return Ok(());
+    }
+    let expected = match expected {
+        Some(value) => parse(value)?,
+        None => {
+            let sum_path = sibling_path(path);
+            if !sum_path.exists() {
+                anyhow::bail!("no checksum found for {}: \
+                    specify `--sha256` or put the `{}` file next to it \
+                    (or skip verification with `--no-verify`)",
+                    path.display(), sum_path.display());
+            }
+            let data = fs::read_to_string(&sum_path)
+                .with_context(|| format!("cannot read {}",
+                                         sum_path.display()))?;
+            parse(&data)
+                .with_context(|| format!("cannot parse {}",
+                                         sum_path.display()))?
+        }
+    };
+    check(path, &expected)
+}
+
+/// Fetches checksum published for the `url`
+pub async fn fetch(url: &str) -> anyhow::Result<String> {
+    let sum_url = format!("{}.sha256", url);
+    let sum = remote::get_string(&sum_url).await
+        .context("cannot fetch checksum \
+                  (skip verification with `--no-verify`)")?;
+    parse(&sum).with_context(|| format!("cannot parse {}", sum_url))
+}
+
+/// Verifies file downloaded from `url` against published checksum
+/// and signature
+pub async fn verify_download(path: &Path, url: &str) -> anyhow::Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    check(path, &fetch(url).await?)?;
+    verify_signature(path, url).await
+}
+
+/// Verifies file downloaded from `url` against published signature
+pub async fn verify_signature(path: &Path, url: &str) -> anyhow::Result<()> {
+    let sig_url = format!("{}.asc", url);
+    let signature = match remote::get_bytes_opt(&sig_url).await? {
+        Some(signature) => signature,
+        None => {
+            anyhow::bail!("no signature is published at {} \
+                (skip verification with `--no-verify`)", sig_url);
+        }
+    };
+    let gpg = which::which("gpg")
+        .map_err(|_| anyhow::anyhow!("cannot verify signature of {}: \
+            `gpg` is not found (install it or skip verification \
+            with `--no-verify`)", url))?;
+    let key = remote::get_string(KEY_FILE_URL).await
+        .context("cannot fetch signing key")?;
+    gpg_verify(&gpg, path, &signature, &key)
+}
+
+/// Finds fingerprint of the primary key in the `VALIDSIG` status line
+fn primary_fingerprint(status: &str) -> Option<&str> {
+    status.lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .filter_map(|args| args.split_whitespace().nth(9))
+        .next()
+}
+
+#[context("invalid signature of {}", path.display())]
+fn gpg_verify(gpg: &Path, path: &Path, signature: &[u8], key: &str)
+    -> anyhow::Result<()>
+{
+    // Use an empty keyring so that only the package key is trusted
+    let home = tempfile::tempdir()?;
+    let key_path = home.path().join("edgedb.asc");
+    let sig_path = home.path().join("signature.asc");
+    fs::write(&key_path, key)?;
+    fs::write(&sig_path, signature)?;
+    process::run(Command::new(gpg)
+        .arg("--homedir").arg(home.path())
+        .arg("--batch").arg("--quiet")
+        .arg("--import").arg(&key_path))?;
+    let status = process::get_text(Command::new(gpg)
+        .arg("--homedir").arg(home.path())
+        .arg("--batch").arg("--quiet")
+        .arg("--status-fd").arg("1")
+        .arg("--verify").arg(&sig_path).arg(path))?;
+    match primary_fingerprint(&status) {
+        Some(fpr) if fpr.eq_ignore_ascii_case(KEY_FINGERPRINT) => {}
+        Some(fpr) => anyhow::bail!("signed by unknown key {}", fpr),
+        None => anyhow::bail!("no valid signature found"),
+    }
+    log::info!("Signature of {} is verified", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, primary_fingerprint};
+
+    #[test]
+    fn parse_sum() {
+        let digest = "e3b0c44298fc1c149afbf4c8996fb924\
+                      27ae41e4649b934ca495991b7852b855";
+        assert_eq!(parse(digest).unwrap(), digest);
+        assert_eq!(parse(&format!("{}  edgedb\n", digest.to_uppercase()))
+                   .unwrap(), digest);
+        assert!(parse("").is_err());
+        assert!(parse("abc edgedb").is_err());
+    }
+
+    #[test]
+    fn validsig() {
+        let status = "[GNUPG:] NEWSIG\n\
+            [GNUPG:] GOODSIG 8F90A1B2C3D4E5F6 EdgeDB\n\
+            [GNUPG:] VALIDSIG 1111111111111111111111111111111111111111 \
+            2021-06-01 1622505600 0 4 0 1 10 00 \
+            5D783B8985B33E0D1A2B6C7E8F90A1B2C3D4E5F6\n";
+        assert_eq!(primary_fingerprint(status),
+                   Some("5D783B8985B33E0D1A2B6C7E8F90A1B2C3D4E5F6"));
+        assert_eq!(primary_fingerprint("[GNUPG:] BADSIG 8F90 EdgeDB\n"),
+                   None);
+    }
+}
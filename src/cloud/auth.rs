@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use async_std::task;
+
+use crate::cloud::client::{ApiError, Client};
+use crate::cloud::options::{Login, Logout};
+use crate::hint::HintExt;
+use crate::secrets;
+use crate::ui::open_browser;
+
+const CLIENT_ID: &str = "edgedb-cli";
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+
+#[derive(Debug, serde::Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default="default_interval")]
+    interval: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// Polls the token endpoint until the user confirms the code in browser
+async fn wait_token(client: &Client, code: &DeviceCode)
+    -> anyhow::Result<String>
+{
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = Duration::from_secs(code.interval);
+    loop {
+        task::sleep(interval).await;
+        let result = client.post::<Token, _>("auth/token", &serde_json::json!({
+            "grant_type": DEVICE_GRANT,
+            "device_code": code.device_code,
+            "client_id": CLIENT_ID,
+        })).await;
+        let err = match result {
+            Ok(token) => return Ok(token.access_token),
+            Err(e) => e,
+        };
+        match err.downcast_ref::<ApiError>().and_then(|e| e.code.as_deref())
+        {
+            Some("authorization_pending") => {}
+            // RFC 8628 requires increasing the interval by 5 seconds
+            Some("slow_down") => interval += Duration::from_secs(5),
+            Some("access_denied") => anyhow::bail!("login was denied"),
+            Some("expired_token") => break,
+            _ => return Err(err),
+        }
+        if Instant::now() > deadline {
+            break;
+        }
+    }
+    Err(anyhow::anyhow!("login code has expired"))
+        .hint("run `edgedb cloud login` again")
+        .map_err(Into::into)
+}
+
+pub fn login(options: &Login) -> anyhow::Result<()> {
+    let client = Client::anonymous();
+    task::block_on(async {
+        let code: DeviceCode = client.post("auth/device", &serde_json::json!({
+            "client_id": CLIENT_ID,
+        })).await?;
+        let url = code.verification_uri_complete.as_ref()
+            .unwrap_or(&code.verification_uri);
+        let opened = !options.no_browser && match open_browser(url) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Cannot open browser: {:#}", e);
+                false
+            }
+        };
+        if opened {
+            eprintln!("Opened {} in the browser.", url);
+        } else {
+            eprintln!("Open the following URL to log in: {}", url);
+        }
+        eprintln!("Make sure the code shown is {}. Waiting for login...",
+                  code.user_code);
+        let token = wait_token(&client, &code).await?;
+        if !secrets::set_cloud_token(&token) {
+            return Err(anyhow::anyhow!("cannot store access token \
+                    in the keyring"))
+                .hint("set `EDGEDB_CLOUD_TOKEN` environment variable \
+                       if the keyring is not available")
+                .map_err(Into::into);
+        }
+        eprintln!("Successfully logged in to EdgeDB Cloud.");
+        Ok(())
+    })
+}
+
+pub fn logout(_options: &Logout) -> anyhow::Result<()> {
+    secrets::delete_cloud_token();
+    eprintln!("Logged out of EdgeDB Cloud.");
+    Ok(())
+}
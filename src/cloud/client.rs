@@ -0,0 +1,161 @@
+use std::env;
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::hint::HintExt;
+use crate::secrets;
+use crate::server::remote::USER_AGENT;
+
+const DEFAULT_API_URL: &str = "https://api.edgedb.cloud";
+
+
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP error: {0}")]
+pub struct HttpError(surf::Error);
+
+/// Error reported by the cloud API
+#[derive(Debug, thiserror::Error)]
+#[error("cloud API error: {status} {message}")]
+pub struct ApiError {
+    pub status: u16,
+    /// Machine-readable code, e.g. `authorization_pending` of OAuth
+    pub code: Option<String>,
+    pub message: String,
+}
+
+#[derive(serde::Deserialize)]
+struct ErrorBody {
+    #[serde(alias="error_description")]
+    message: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CloudInstance {
+    pub org: String,
+    pub name: String,
+    pub status: String,
+    pub version: String,
+    pub region: String,
+    pub host: String,
+    pub port: u16,
+}
+
+pub struct Client {
+    api_url: String,
+    token: Option<String>,
+}
+
+
+impl CloudInstance {
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.org, self.name)
+    }
+}
+
+/// Returns access token from `EDGEDB_CLOUD_TOKEN` or from the keyring
+pub fn token() -> Option<String> {
+    env::var("EDGEDB_CLOUD_TOKEN").ok().or_else(secrets::get_cloud_token)
+}
+
+impl Client {
+    /// Client for the API in `EDGEDB_CLOUD_API_URL` (or the default one)
+    /// without authentication
+    pub fn anonymous() -> Client {
+        Client {
+            api_url: env::var("EDGEDB_CLOUD_API_URL")
+                .unwrap_or_else(|_| DEFAULT_API_URL.into())
+                .trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+    /// Client using the token stored by `edgedb cloud login`
+    pub fn authenticated() -> anyhow::Result<Client> {
+        let token = token()
+            .ok_or_else(|| anyhow::anyhow!("not logged in to EdgeDB Cloud"))
+            .hint("run `edgedb cloud login`")?;
+        Ok(Client { token: Some(token), ..Client::anonymous() })
+    }
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+    fn request(&self, mut request: surf::RequestBuilder)
+        -> surf::RequestBuilder
+    {
+        request = request.header("User-Agent", USER_AGENT);
+        if let Some(token) = &self.token {
+            request = request.header("Authorization",
+                                     format!("Bearer {}", token));
+        }
+        request
+    }
+    async fn send<T: DeserializeOwned>(&self, url: &str,
+                                       request: surf::RequestBuilder)
+        -> anyhow::Result<T>
+    {
+        let mut response = self.request(request).await.map_err(HttpError)
+            .with_context(|| format!("cannot fetch {}", url))?;
+        let status = response.status();
+        let data = response.body_bytes().await.map_err(HttpError)
+            .with_context(|| format!("cannot read response of {}", url))?;
+        if !status.is_success() {
+            let (message, code) = serde_json::from_slice(&data).ok()
+                .map(|b: ErrorBody| (b.message, b.error))
+                .unwrap_or((None, None));
+            let err = ApiError {
+                status: status.into(),
+                message: message.or_else(|| code.clone())
+                    .unwrap_or_else(|| status.canonical_reason().into()),
+                code,
+            };
+            if status == 401 {
+                return Err(anyhow::Error::from(err))
+                    .hint("run `edgedb cloud login` again")
+                    .map_err(Into::into);
+            }
+            return Err(err.into());
+        }
+        if data.is_empty() {
+            return serde_json::from_slice(b"null")
+                .with_context(|| format!("empty response from {}", url));
+        }
+        serde_json::from_slice(&data)
+            .with_context(|| format!("bad JSON response from {}", url))
+    }
+    pub async fn get<T: DeserializeOwned>(&self, path: &str)
+        -> anyhow::Result<T>
+    {
+        let url = format!("{}/{}", self.api_url, path);
+        log::info!("Fetching {}", url);
+        self.send(&url, surf::get(&url)).await
+    }
+    pub async fn post<T, B>(&self, path: &str, body: &B)
+        -> anyhow::Result<T>
+        where T: DeserializeOwned, B: Serialize,
+    {
+        let url = format!("{}/{}", self.api_url, path);
+        log::info!("Posting to {}", url);
+        let body = surf::Body::from_json(body).map_err(HttpError)?;
+        self.send(&url, surf::post(&url).body(body)).await
+    }
+    pub async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        let url = format!("{}/{}", self.api_url, path);
+        log::info!("Deleting {}", url);
+        self.send::<serde_json::Value>(&url, surf::delete(&url)).await?;
+        Ok(())
+    }
+    pub async fn get_instance(&self, org: &str, name: &str)
+        -> anyhow::Result<CloudInstance>
+    {
+        self.get(&format!("orgs/{}/instances/{}",
+                          urlencoding::encode(org),
+                          urlencoding::encode(name))).await
+            .with_context(|| format!("cannot find cloud instance {}/{}",
+                                     org, name))
+    }
+}
@@ -0,0 +1,92 @@
+use async_std::task;
+use prettytable::{Table, Row, Cell};
+
+use crate::cloud::client::{Client, CloudInstance};
+use crate::cloud::options::{Create, List, Destroy};
+use crate::cloud::parse_name;
+use crate::question;
+use crate::table;
+
+
+#[derive(serde::Serialize)]
+struct CreateRequest<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if="Option::is_none")]
+    version: Option<&'a str>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    region: Option<&'a str>,
+}
+
+fn instance_path(name: &str) -> String {
+    let (org, inst) = parse_name(name).expect("name is validated");
+    format!("orgs/{}/instances/{}",
+            urlencoding::encode(org), urlencoding::encode(inst))
+}
+
+pub fn create(options: &Create) -> anyhow::Result<()> {
+    let client = Client::authenticated()?;
+    let (org, name) = parse_name(&options.name).expect("name is validated");
+    let instance: CloudInstance = task::block_on(client.post(
+        &format!("orgs/{}/instances", urlencoding::encode(org)),
+        &CreateRequest {
+            name,
+            version: options.version.as_deref(),
+            region: options.region.as_deref(),
+        }))?;
+    eprintln!("Cloud instance {} (version {}) is created in {}. \
+        Use `edgedb -I {}` to connect.",
+        instance.full_name(), instance.version, instance.region,
+        instance.full_name());
+    Ok(())
+}
+
+pub fn list(options: &List) -> anyhow::Result<()> {
+    let client = Client::authenticated()?;
+    let path = match &options.org {
+        Some(org) => format!("orgs/{}/instances", urlencoding::encode(org)),
+        None => "instances".into(),
+    };
+    let instances: Vec<CloudInstance> = task::block_on(client.get(&path))?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&instances)?);
+        return Ok(());
+    }
+    if instances.is_empty() {
+        eprintln!("No cloud instances found. \
+            Run `edgedb cloud instance create` to create one.");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Name", "Status", "Version", "Region", "Address"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for inst in &instances {
+        table.add_row(Row::new(vec![
+            Cell::new(&inst.full_name()),
+            Cell::new(&inst.status),
+            Cell::new(&inst.version),
+            Cell::new(&inst.region),
+            Cell::new(&format!("{}:{}", inst.host, inst.port)),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+pub fn destroy(options: &Destroy) -> anyhow::Result<()> {
+    let client = Client::authenticated()?;
+    if !options.non_interactive {
+        let q = question::Confirm::new_dangerous(
+            format!("Do you really want to destroy cloud instance {:?} \
+                     and all the data stored?", options.name)
+        );
+        if !q.ask()? {
+            eprintln!("Canceled");
+            return Ok(())
+        }
+    }
+    task::block_on(client.delete(&instance_path(&options.name)))?;
+    eprintln!("Cloud instance {} is destroyed.", options.name);
+    Ok(())
+}
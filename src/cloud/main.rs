@@ -0,0 +1,18 @@
+use crate::cloud::auth;
+use crate::cloud::instance;
+use crate::cloud::options::{CloudCommand, Command, InstanceSubcommand};
+
+
+pub fn main(cmd: &CloudCommand) -> anyhow::Result<()> {
+    use Command::*;
+
+    match &cmd.subcommand {
+        Login(c) => auth::login(c),
+        Logout(c) => auth::logout(c),
+        Instance(c) => match &c.subcommand {
+            InstanceSubcommand::Create(c) => instance::create(c),
+            InstanceSubcommand::List(c) => instance::list(c),
+            InstanceSubcommand::Destroy(c) => instance::destroy(c),
+        },
+    }
+}
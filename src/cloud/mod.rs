@@ -0,0 +1,77 @@
+//! EdgeDB Cloud: logging in, managing instances and connecting to them
+//!
+//! Cloud instances are named `org/instance` and can be used with `-I`
+//! everywhere local instance names are accepted. The address of the
+//! instance is fetched from the cloud API on connect, the access token of
+//! `edgedb cloud login` is used as the password.
+
+use async_std::task;
+use edgedb_client::Builder;
+
+use crate::server::is_valid_name;
+
+pub mod options;
+
+mod auth;
+mod client;
+mod instance;
+mod main;
+
+pub use main::main;
+pub use client::Client;
+
+
+/// Splits `org/instance` or returns `None` if it's not a cloud name
+pub fn parse_name(name: &str) -> Option<(&str, &str)> {
+    let pos = name.find('/')?;
+    Some((&name[..pos], &name[pos+1..]))
+}
+
+pub fn is_cloud_name(name: &str) -> bool {
+    name.contains('/')
+}
+
+pub fn validate_name(name: &str) -> Result<(), String> {
+    match parse_name(name) {
+        Some((org, inst)) if is_valid_org(org) && is_valid_name(inst) => {
+            Ok(())
+        }
+        _ => Err("cloud instance name must be in form `org/instance` \
+                  where `instance` is a valid identifier".into()),
+    }
+}
+
+fn is_valid_org(org: &str) -> bool {
+    !org.is_empty() &&
+    org.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Connection parameters of the cloud instance `org/instance`
+pub fn get_connector(name: &str) -> anyhow::Result<Builder> {
+    validate_name(name).map_err(|e| anyhow::anyhow!(e))?;
+    let (org, inst) = parse_name(name).expect("name is validated");
+    let client = Client::authenticated()?;
+    let instance = task::block_on(client.get_instance(org, inst))?;
+    let mut builder = Builder::new();
+    builder.tcp_addr(&instance.host, instance.port);
+    builder.user("edgedb");
+    builder.database("edgedb");
+    builder.password(client.token().expect("client is authenticated"));
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_name, validate_name};
+
+    #[test]
+    fn names() {
+        assert_eq!(parse_name("acme/prod"), Some(("acme", "prod")));
+        assert_eq!(parse_name("prod"), None);
+        assert!(validate_name("acme-corp/prod_1").is_ok());
+        assert!(validate_name("acme/").is_err());
+        assert!(validate_name("/prod").is_err());
+        assert!(validate_name("acme/prod/x").is_err());
+        assert!(validate_name("acme/1prod").is_err());
+    }
+}
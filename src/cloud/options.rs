@@ -0,0 +1,88 @@
+use clap::{Clap, AppSettings};
+
+use crate::cloud::validate_name;
+
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct CloudCommand {
+    #[clap(subcommand)]
+    pub subcommand: Command,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum Command {
+    /// Log in to EdgeDB Cloud using the browser
+    Login(Login),
+    /// Forget the access token of EdgeDB Cloud
+    Logout(Logout),
+    /// Manage cloud instances
+    Instance(InstanceCommand),
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Login {
+    /// Don't open the browser, only print the verification URL
+    #[clap(long)]
+    pub no_browser: bool,
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Logout {
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct InstanceCommand {
+    #[clap(subcommand)]
+    pub subcommand: InstanceSubcommand,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum InstanceSubcommand {
+    /// Create a new instance in the cloud
+    Create(Create),
+    /// List cloud instances accessible to the user
+    List(List),
+    /// Destroy a cloud instance and all the data stored
+    Destroy(Destroy),
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Create {
+    /// Name of the instance in form `org/instance`
+    #[clap(validator(validate_name))]
+    pub name: String,
+    /// Version of the server (the latest stable by default)
+    #[clap(long)]
+    pub version: Option<String>,
+    /// Region to create the instance in (the default region of the
+    /// organization if not specified)
+    #[clap(long)]
+    pub region: Option<String>,
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct List {
+    /// List only instances of this organization
+    #[clap(long)]
+    pub org: Option<String>,
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Destroy {
+    /// Name of the instance in form `org/instance`
+    #[clap(validator(validate_name))]
+    pub name: String,
+    /// Don't ask for confirmation
+    #[clap(long)]
+    pub non_interactive: bool,
+}
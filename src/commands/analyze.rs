@@ -0,0 +1,29 @@
+use anyhow::Context;
+use edgedb_client::client::Connection;
+use edgedb_protocol::value::Value;
+
+use crate::capabilities::{self, Feature};
+use crate::commands::Options;
+use crate::commands::parser::Analyze;
+use crate::print::{console, plan};
+
+
+pub async fn analyze(cli: &mut Connection, _options: &Options,
+    analyze: &Analyze)
+    -> Result<(), anyhow::Error>
+{
+    capabilities::require(cli, Feature::Analyze).await?;
+    let query = analyze.query.join(" ");
+    let data = cli.query_row::<String>(
+        &format!("ANALYZE {}", query.trim_end_matches(';')),
+        &Value::empty_tuple(),
+    ).await?;
+    if analyze.json {
+        println!("{}", data);
+        return Ok(());
+    }
+    let plan: serde_json::Value = serde_json::from_str(&data)
+        .context("cannot decode query plan")?;
+    print!("{}", plan::render(&plan, console::colors_stdout()));
+    Ok(())
+}
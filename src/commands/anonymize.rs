@@ -0,0 +1,416 @@
+//! Scrubbing of property values in the data blocks of a dump
+//!
+//! Data blocks contain rows in the binary format of PostgreSQL `COPY`,
+//! with columns in the order of the elements of the object shape from the
+//! type descriptor in the dump header. Rules are applied to the values of
+//! the columns, everything else is copied as is.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use bytes::Bytes;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use edgedb_protocol::codec;
+
+use crate::commands::inspect_dump::{Buf, HEADER_BLOCK_ID, HEADER_BLOCK_DATA};
+
+const COPY_SIGNATURE: &[u8] = b"PGCOPY\n\xFF\r\n\x00";
+const NULL_LENGTH: u32 = 0xFFFF_FFFF;
+const TRAILER: u16 = 0xFFFF;
+// cardinality of the shape elements which can't be empty
+const CARDINALITY_ONE: u8 = 0x41;
+const CARDINALITY_AT_LEAST_ONE: u8 = 0x4D;
+
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum RawRule {
+    Name(String),
+    Fake { fake: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /// Replace value with an empty one
+    Null,
+    /// Replace string with a salted hash, so equal values stay equal
+    Hash,
+    /// Replace string with a template, `{n}` is substituted with
+    /// a sequential number
+    Fake(String),
+}
+
+/// Rules from the file passed to `dump --anonymize`
+pub struct Rules {
+    /// Object type name -> property name -> rule
+    types: BTreeMap<String, BTreeMap<String, Rule>>,
+    salt: [u8; 16],
+    /// Object types found in any of the dumped databases
+    used: Mutex<BTreeSet<String>>,
+}
+
+struct Column {
+    index: usize,
+    rule: Rule,
+    counter: u64,
+}
+
+/// Applies rules to the data blocks of a single database dump
+pub struct Anonymizer {
+    columns: HashMap<Uuid, Vec<Column>>,
+    salt: [u8; 16],
+}
+
+struct Element {
+    name: String,
+    /// Not known for protocol older than 0.11
+    cardinality: Option<u8>,
+    type_pos: u16,
+}
+
+enum Descriptor {
+    Shape(Vec<Element>),
+    BaseScalar(Uuid),
+    Scalar(u16),
+    Other,
+}
+
+fn parse_rule(value: RawRule) -> anyhow::Result<Rule> {
+    match value {
+        RawRule::Name(name) => match &name[..] {
+            "null" => Ok(Rule::Null),
+            "hash" => Ok(Rule::Hash),
+            _ => anyhow::bail!("unknown rule {:?}, expected \"null\", \
+                \"hash\" or {{ fake = \"TEMPLATE\" }}", name),
+        },
+        RawRule::Fake { fake } => Ok(Rule::Fake(fake)),
+    }
+}
+
+fn parse_rules(text: &str)
+    -> anyhow::Result<BTreeMap<String, BTreeMap<String, Rule>>>
+{
+    let raw: BTreeMap<String, BTreeMap<String, RawRule>> =
+        toml::from_str(text)?;
+    let mut types = BTreeMap::new();
+    for (type_name, props) in raw {
+        let mut rules = BTreeMap::new();
+        for (prop, rule) in props {
+            let rule = parse_rule(rule).with_context(|| format!(
+                "invalid rule for {}.{}", type_name, prop))?;
+            rules.insert(prop, rule);
+        }
+        types.insert(type_name, rules);
+    }
+    Ok(types)
+}
+
+fn parse_typedesc(protocol: (u16, u16), data: &[u8])
+    -> anyhow::Result<Vec<Descriptor>>
+{
+    let mut buf = Buf(data);
+    let mut result = Vec::new();
+    while !buf.0.is_empty() {
+        let typ = buf.u8()?;
+        let id = buf.uuid()?;
+        let item = match typ {
+            0 => {
+                buf.u16()?;
+                Descriptor::Other
+            }
+            1 => {
+                let mut elements = Vec::new();
+                for _ in 0..buf.u16()? {
+                    let cardinality = if protocol >= (0, 11) {
+                        buf.u32()?;  // flags
+                        Some(buf.u8()?)
+                    } else {
+                        buf.u8()?;  // flags
+                        None
+                    };
+                    let name = buf.string()?;
+                    let type_pos = buf.u16()?;
+                    elements.push(Element { name, cardinality, type_pos });
+                }
+                Descriptor::Shape(elements)
+            }
+            2 => Descriptor::BaseScalar(id),
+            3 => Descriptor::Scalar(buf.u16()?),
+            4 => {
+                let num = buf.u16()? as usize;
+                buf.take(num*2)?;
+                Descriptor::Other
+            }
+            5 => {
+                for _ in 0..buf.u16()? {
+                    buf.string()?;
+                    buf.u16()?;
+                }
+                Descriptor::Other
+            }
+            6 => {
+                buf.u16()?;
+                let dims = buf.u16()? as usize;
+                buf.take(dims*4)?;
+                Descriptor::Other
+            }
+            7 => {
+                for _ in 0..buf.u16()? {
+                    buf.string()?;
+                }
+                Descriptor::Other
+            }
+            0x80..=0xFF => {
+                buf.string()?;
+                Descriptor::Other
+            }
+            _ => anyhow::bail!("unknown type descriptor {:#x}", typ),
+        };
+        result.push(item);
+    }
+    Ok(result)
+}
+
+fn is_str(descriptors: &[Descriptor], mut pos: u16) -> bool {
+    loop {
+        match descriptors.get(pos as usize) {
+            Some(Descriptor::BaseScalar(id)) => return *id == codec::STD_STR,
+            Some(Descriptor::Scalar(base)) => pos = *base,
+            _ => return false,
+        }
+    }
+}
+
+fn columns(type_name: &str, protocol: (u16, u16), typedesc: &[u8],
+    rules: &BTreeMap<String, Rule>)
+    -> anyhow::Result<Vec<Column>>
+{
+    let descriptors = parse_typedesc(protocol, typedesc)?;
+    let elements = match descriptors.last() {
+        Some(Descriptor::Shape(elements)) => elements,
+        _ => anyhow::bail!("no object shape in the descriptor"),
+    };
+    let mut result = Vec::new();
+    for (prop, rule) in rules {
+        let (index, element) = elements.iter().enumerate()
+            .find(|(_, el)| &el.name == prop)
+            .ok_or_else(|| anyhow::anyhow!("property {:?} is not found \
+                in object type {:?}", prop, type_name))?;
+        if *rule == Rule::Null && matches!(element.cardinality,
+            Some(CARDINALITY_ONE) | Some(CARDINALITY_AT_LEAST_ONE))
+        {
+            anyhow::bail!("property {}.{} is required, \"null\" rule \
+                can't be used for it", type_name, prop);
+        }
+        if *rule != Rule::Null && !is_str(&descriptors, element.type_pos) {
+            anyhow::bail!("property {}.{} is not a `str`, only \"null\" \
+                rule can be used for it", type_name, prop);
+        }
+        result.push(Column { index, rule: rule.clone(), counter: 0 });
+    }
+    Ok(result)
+}
+
+fn scrub(column: &mut Column, salt: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+    match &column.rule {
+        Rule::Null => None,
+        Rule::Hash => {
+            let mut hash = sha1::Sha1::new();
+            hash.update(salt);
+            hash.update(value);
+            Some(hash.digest().to_string().into_bytes())
+        }
+        Rule::Fake(template) => {
+            column.counter += 1;
+            Some(template.replace("{n}", &column.counter.to_string())
+                 .into_bytes())
+        }
+    }
+}
+
+fn rewrite_rows(columns: &mut [Column], salt: &[u8], data: &[u8])
+    -> anyhow::Result<Vec<u8>>
+{
+    let mut buf = Buf(data);
+    let mut out = Vec::with_capacity(data.len());
+    if data.starts_with(COPY_SIGNATURE) {
+        // signature and flags
+        out.extend(buf.take(COPY_SIGNATURE.len() + 4)?);
+        let extension = buf.bytes()?;
+        out.extend(&(extension.len() as u32).to_be_bytes());
+        out.extend(extension);
+    }
+    while !buf.0.is_empty() {
+        let fields = buf.u16()?;
+        out.extend(&fields.to_be_bytes());
+        if fields == TRAILER {
+            continue;
+        }
+        for index in 0..fields as usize {
+            let len = buf.u32()?;
+            let value = if len == NULL_LENGTH {
+                None
+            } else {
+                Some(buf.take(len as usize)?)
+            };
+            let column = columns.iter_mut().find(|c| c.index == index);
+            let scrubbed = match (column, value) {
+                (Some(column), Some(value)) => scrub(column, salt, value),
+                (_, value) => value.map(|v| v.to_vec()),
+            };
+            match scrubbed {
+                Some(value) => {
+                    out.extend(&(value.len() as u32).to_be_bytes());
+                    out.extend(value);
+                }
+                None => out.extend(&NULL_LENGTH.to_be_bytes()),
+            }
+        }
+    }
+    Ok(out)
+}
+
+impl Rules {
+    pub fn read(path: &Path) -> anyhow::Result<Rules> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let types = parse_rules(&text)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        Ok(Rules {
+            types,
+            salt: rand::random(),
+            used: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    /// Warns about the rules for object types which weren't found in any
+    /// of the databases dumped so far
+    pub fn warn_unused(&self) {
+        let used = self.used.lock().expect("lock is not poisoned");
+        for name in self.types.keys().filter(|name| !used.contains(*name)) {
+            eprintln!("WARNING: object type {:?} of the anonymization \
+                rules is not found in the dump", name);
+        }
+    }
+
+    /// Prepares rules for the dump with the specified header
+    pub fn anonymizer(&self, header: &[u8]) -> anyhow::Result<Anonymizer> {
+        let mut buf = Buf(header);
+        buf.headers()?;
+        let protocol = (buf.u16()?, buf.u16()?);
+        buf.string()?;  // schema
+        let mut type_names = HashMap::new();
+        for _ in 0..buf.u32()? {
+            let name = buf.string()?;
+            let _class = buf.string()?;
+            type_names.insert(buf.uuid()?, name);
+        }
+        let mut result = HashMap::new();
+        for _ in 0..buf.u32()? {
+            let id = buf.uuid()?;
+            let typedesc = buf.bytes()?;
+            let dependencies = buf.u16()? as usize;
+            buf.take(dependencies*16)?;
+            let (name, rules) = match type_names.get(&id)
+                .and_then(|name| self.types.get_key_value(name))
+            {
+                Some(pair) => pair,
+                None => continue,
+            };
+            self.used.lock().expect("lock is not poisoned")
+                .insert(name.clone());
+            result.insert(id, columns(name, protocol, typedesc, rules)?);
+        }
+        Ok(Anonymizer { columns: result, salt: self.salt })
+    }
+}
+
+impl Anonymizer {
+    /// Returns data block with rules applied
+    pub fn block(&mut self, data: Bytes) -> anyhow::Result<Bytes> {
+        let id = Buf(&data).headers()?.get(&HEADER_BLOCK_ID)
+            .map(|id| Uuid::from_slice(id))
+            .transpose()?;
+        let columns = match id.and_then(|id| self.columns.get_mut(&id)) {
+            Some(columns) => columns,
+            None => return Ok(data),
+        };
+        let headers = Buf(&data).headers()?;
+        let mut out = Vec::with_capacity(data.len());
+        out.extend(&(headers.len() as u16).to_be_bytes());
+        for (code, value) in &headers {
+            let rows;
+            let value = if *code == HEADER_BLOCK_DATA {
+                rows = rewrite_rows(columns, &self.salt, value)?;
+                &rows[..]
+            } else {
+                *value
+            };
+            out.extend(&code.to_be_bytes());
+            out.extend(&(value.len() as u32).to_be_bytes());
+            out.extend(value);
+        }
+        Ok(out.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_rules, rewrite_rows, Column, Rule};
+    use super::{COPY_SIGNATURE, NULL_LENGTH, TRAILER};
+
+    fn row(values: &[Option<&str>]) -> Vec<u8> {
+        let mut data = (values.len() as u16).to_be_bytes().to_vec();
+        for value in values {
+            match value {
+                Some(value) => {
+                    data.extend(&(value.len() as u32).to_be_bytes());
+                    data.extend(value.as_bytes());
+                }
+                None => data.extend(&NULL_LENGTH.to_be_bytes()),
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn rules() {
+        let rules = parse_rules(r###"
+            ["default::User"]
+            email = { fake = "user{n}@example.com" }
+            name = "hash"
+            phone = "null"
+        "###).unwrap();
+        let user = &rules["default::User"];
+        assert_eq!(user["email"], Rule::Fake("user{n}@example.com".into()));
+        assert_eq!(user["name"], Rule::Hash);
+        assert_eq!(user["phone"], Rule::Null);
+        assert!(parse_rules("[User]\nname = \"erase\"\n").is_err());
+    }
+
+    #[test]
+    fn rows() {
+        let mut data = COPY_SIGNATURE.to_vec();
+        data.extend(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend(row(&[Some("id1"), Some("alice@x.com"), Some("Alice")]));
+        data.extend(row(&[Some("id2"), None, Some("Bob")]));
+        data.extend(&TRAILER.to_be_bytes());
+        let mut columns = vec![
+            Column { index: 1, rule: Rule::Fake("u{n}".into()), counter: 0 },
+            Column { index: 2, rule: Rule::Null, counter: 0 },
+        ];
+        let result = rewrite_rows(&mut columns, b"salt", &data).unwrap();
+
+        let mut expected = COPY_SIGNATURE.to_vec();
+        expected.extend(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend(row(&[Some("id1"), Some("u1"), None]));
+        expected.extend(row(&[Some("id2"), None, None]));
+        expected.extend(&TRAILER.to_be_bytes());
+        assert_eq!(result, expected);
+    }
+}
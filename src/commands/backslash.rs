@@ -1,17 +1,22 @@
 use std::borrow::Cow;
 use std::collections::{BTreeSet, BTreeMap};
+use std::time::Duration;
 
 use anyhow;
 use clap::{self, Clap, IntoApp};
 use edgedb_protocol::server_message::ErrorResponse;
+use edgedb_protocol::value::Value;
 use once_cell::sync::Lazy;
 use prettytable::{Table, Row, Cell};
 use regex::Regex;
 
 use crate::commands::Options;
 use crate::repl;
+use crate::print::image;
 use crate::print::style::Styler;
 use crate::prompt;
+use crate::result_diff;
+use crate::snippets;
 use crate::commands::execute;
 use crate::commands::parser::{Backslash, BackslashCmd, Setting};
 use crate::table;
@@ -24,6 +29,8 @@ pub enum ExecuteResult {
     Skip,
     Quit,
     Input(String),
+    /// Execute queries of the snippet
+    Execute(String),
 }
 
 const HELP: &str = r###"
@@ -48,25 +55,41 @@ Introspection
   \list-ports              list ports
 
 Operations
+  \analyze QUERY           show query plan with execution statistics
   \dump FILENAME           dump current database into a file
   \restore FILENAME        restore the database from file into the current one
+  \import --table TYPE FILE
+                           import objects from a CSV or JSON file
+  \export --type TYPE --out FILE
+                           export objects into a CSV or JSON file
+  \diff FILE               run the last query again and compare the result
+                           with the JSON output saved in FILE
 
 Editing
   \s, \history             show history
-  \e, \edit [N]            spawn $EDITOR to edit history entry N then use the
-                           output as the input
+  \e, \edit [N | FILE]     spawn $EDITOR to edit history entry N (or FILE)
+                           then use the output as the input
+
+Snippets
+  \save NAME               save the last query as snippet NAME
+  \run NAME                execute queries of snippet NAME
 
 Settings
   \set [OPTION [VALUE]]    how/change setting, type \set for listing
                            all available options
+  \set global [NAME [EXPR]] [--reset]
+                           show/set/reset session globals
 
 Connection
-  \c, \connect [DBNAME]    Connect to database DBNAME
+  \c, \connect [DBNAME [INSTANCE]]
+                           Connect to database DBNAME (of the instance
+                           INSTANCE, if specified)
 
 Help
   \?, \h, \help            Show help on backslash commands
   \set                     Show setting descriptions (without arguments)
-  \q, \quit, \exit, Ctrl+D Quit REPL
+  \q, \quit, \exit, Ctrl+D Quit REPL (or Ctrl+C at empty prompt)
+  Ctrl+C                   Cancel running query or clear the input
 "###;
 
 #[derive(Debug)]
@@ -314,6 +337,15 @@ impl CommandCache {
                 ExpandStrings(Default::default()),
                 HistorySize(Default::default()),
                 PrintStats(Default::default()),
+                Timing(Default::default()),
+                Prompt(Default::default()),
+                RenderImages(Default::default()),
+                DisplayTz(Default::default()),
+                MaxDepth(Default::default()),
+                MaxOutput(Default::default()),
+                Summary(Default::default()),
+                Global(Default::default()),
+                LogSlowQueries(Default::default()),
             ].into_iter().map(|setting| {
                 let cmd = setting_cmd.remove(&setting.name())
                     .expect("all settings have cmd");
@@ -442,6 +474,13 @@ pub fn bool_str(val: bool) -> &'static str {
     }
 }
 
+fn limit_str(val: Option<usize>) -> String {
+    match val {
+        Some(val) => val.to_string(),
+        None => "0  # no limit".into(),
+    }
+}
+
 pub fn get_setting(s: &Setting, prompt: &repl::State) -> Cow<'static, str> {
      use Setting::*;
 
@@ -474,6 +513,46 @@ pub fn get_setting(s: &Setting, prompt: &repl::State) -> Cow<'static, str> {
         PrintStats(_) => {
             prompt.print_stats.as_str().into()
         }
+        Timing(_) => {
+            bool_str(prompt.print_stats != repl::PrintStats::Off).into()
+        }
+        Prompt(_) => {
+            format!("'{}'", prompt.prompt_template).into()
+        }
+        RenderImages(_) => {
+            bool_str(prompt.print.images.is_some()).into()
+        }
+        DisplayTz(_) => {
+            prompt.print.display_tz.to_string().into()
+        }
+        MaxDepth(_) => {
+            limit_str(prompt.print.max_depth).into()
+        }
+        MaxOutput(_) => {
+            limit_str(prompt.print.max_output).into()
+        }
+        Summary(_) => {
+            bool_str(prompt.print_summary).into()
+        }
+        Global(g) => {
+            let globals = prompt.conn_params.globals();
+            match &g.name {
+                Some(name) => match globals.get(name) {
+                    Some(value) => format!("{} := {}", name, value).into(),
+                    None => format!("{} is not set", name).into(),
+                },
+                None if globals.is_empty() => "none set".into(),
+                None => globals.iter()
+                    .map(|(name, value)| format!("{} := {}", name, value))
+                    .collect::<Vec<_>>().join("; ").into(),
+            }
+        }
+        LogSlowQueries(_) => match prompt.slow_query_threshold {
+            Some(threshold) => {
+                humantime::format_duration(threshold).to_string().into()
+            }
+            None => "0  # disabled".into(),
+        },
      }
 }
 
@@ -505,6 +584,7 @@ pub async fn execute(cmd: &BackslashCmd, prompt: &mut repl::State)
         command_line: false,
         styler: Some(Styler::dark_256()),
         conn_params: prompt.conn_params.clone(),
+        schema_cache: Some(prompt.schema_cache.clone()),
     };
     match cmd {
         Help => {
@@ -515,7 +595,11 @@ pub async fn execute(cmd: &BackslashCmd, prompt: &mut repl::State)
             prompt.soft_reconnect().await?;
             let cli = prompt.connection.as_mut()
                 .expect("connection established");
-            execute::common(cli, cmd, &options).await?;
+            let result = execute::common(cli, cmd, &options).await;
+            if cmd.modifies_schema() {
+                prompt.schema_cache.clear();
+            }
+            result?;
             Ok(Skip)
         }
         Set(SetCommand {setting: None}) => {
@@ -560,6 +644,60 @@ pub async fn execute(cmd: &BackslashCmd, prompt: &mut repl::State)
                 PrintStats(v) => {
                     prompt.print_stats = v.value.expect("only writes here");
                 }
+                Timing(b) => {
+                    if !b.unwrap_value() {
+                        prompt.print_stats = repl::PrintStats::Off;
+                    } else if prompt.print_stats == repl::PrintStats::Off {
+                        prompt.print_stats = repl::PrintStats::Query;
+                    }
+                }
+                Prompt(p) => {
+                    prompt.prompt_template = p.template.as_ref()
+                        .expect("only writes here").parse()?;
+                }
+                RenderImages(b) => {
+                    if b.unwrap_value() {
+                        prompt.print.images = Some(image::detect()
+                            .ok_or_else(|| anyhow::anyhow!(
+                                "terminal does not support inline images, \
+                                 kitty or iTerm2 is required"))?);
+                    } else {
+                        prompt.print.images = None;
+                    }
+                }
+                DisplayTz(v) => {
+                    prompt.print.display_tz = v.value.as_ref()
+                        .expect("only writes here").parse()
+                        .map_err(anyhow::Error::msg)?;
+                }
+                MaxDepth(c) => {
+                    let value = c.value.expect("only set here");
+                    prompt.print.max_depth = Some(value).filter(|&v| v > 0);
+                }
+                MaxOutput(c) => {
+                    let value = c.value.expect("only set here");
+                    prompt.print.max_output = Some(value).filter(|&v| v > 0);
+                }
+                Summary(b) => {
+                    prompt.print_summary = b.unwrap_value();
+                }
+                Global(g) => match &g.name {
+                    Some(name) => {
+                        prompt.set_global(name, g.value.as_deref()).await?;
+                    }
+                    None => {
+                        let names = prompt.conn_params.globals().keys()
+                            .cloned().collect::<Vec<_>>();
+                        for name in names {
+                            prompt.set_global(&name, None).await?;
+                        }
+                    }
+                },
+                LogSlowQueries(c) => {
+                    let value = c.value.expect("only set here");
+                    prompt.slow_query_threshold = Some(value)
+                        .filter(|&v| v > Duration::new(0, 0));
+                }
             }
             Ok(Skip)
         }
@@ -567,7 +705,8 @@ pub async fn execute(cmd: &BackslashCmd, prompt: &mut repl::State)
             if prompt.in_transaction() {
                 eprintln!("WARNING: Transaction cancelled")
             }
-            prompt.try_connect(&c.database_name).await
+            prompt.try_connect(c.database_name.as_deref(),
+                               c.instance.as_deref()).await
                 .map_err(|e| {
                     eprintln!("Error: Cannot connect: {:#}", e)
                 })
@@ -591,12 +730,52 @@ pub async fn execute(cmd: &BackslashCmd, prompt: &mut repl::State)
             Ok(Skip)
         }
         Edit(c) => {
-            match prompt.spawn_editor(c.entry).await? {
+            let entry = c.entry.as_ref().map(|e| (e.parse::<isize>(), e));
+            let input = match entry {
+                None => prompt.spawn_editor(None).await?,
+                Some((Ok(num), _)) => prompt.spawn_editor(Some(num)).await?,
+                Some((Err(_), path)) => prompt.edit_file(path.into()).await?,
+            };
+            match input {
                 | prompt::Input::Text(text) => Ok(Input(text)),
                 | prompt::Input::Interrupt
                 | prompt::Input::Eof => Ok(Skip),
             }
         }
+        Save(c) => {
+            let query = prompt.last_statement.as_ref()
+                .ok_or_else(|| anyhow::anyhow!("no query to save yet"))?;
+            if snippets::save(&snippets::dir(None)?, &c.name, query)? {
+                eprintln!("Snippet {:?} is replaced", c.name);
+            } else {
+                eprintln!("Saved snippet {:?}", c.name);
+            }
+            Ok(Skip)
+        }
+        Run(c) => {
+            let text = snippets::read(&snippets::dir(None)?, &c.name)?;
+            Ok(Execute(text))
+        }
+        Diff(c) => {
+            let query = prompt.last_statement.clone()
+                .ok_or_else(|| anyhow::anyhow!("no query to compare yet"))?;
+            if result_diff::has_parameters(&query) {
+                anyhow::bail!("queries with parameters can't be compared \
+                    in the REPL, use `edgedb query --diff-against` \
+                    with `--param` instead");
+            }
+            prompt.soft_reconnect().await?;
+            let cli = prompt.connection.as_mut()
+                .expect("connection established");
+            let changes = result_diff::diff(cli, &query,
+                &Value::empty_tuple(), &c.file).await?;
+            if changes.is_empty() {
+                eprintln!("Result is the same as in {}", c.file.display());
+            } else {
+                print!("{}", changes);
+            }
+            Ok(Skip)
+        }
         Exit => Ok(Quit),
     }
 }
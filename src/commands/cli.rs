@@ -1,27 +1,55 @@
+use std::collections::BTreeSet;
+
 use async_std::task;
+use colorful::Colorful;
 
-use crate::options::{Options, Command};
+use crate::options::{Options, Command, ConnectionKind};
 use crate::commands::parser::Common;
 use crate::non_interactive;
+use crate::bench;
+use crate::cloud;
 use crate::commands;
+use crate::crash_reports;
+use crate::debug_protocol;
+use crate::doctor;
+use crate::graphql;
+use crate::http::Endpoint;
+use crate::info;
+use crate::man;
 use crate::migrations;
+use crate::ping;
 use crate::self_install;
 use crate::self_upgrade;
 use crate::server;
+use crate::server_proxy;
+use crate::shell_completion;
+use crate::shell_env;
+use crate::slowlog;
+use crate::snippets;
+use crate::telemetry;
+use crate::ui;
+use crate::plugins;
 use crate::project;
+use crate::print;
 use crate::print::style::Styler;
+use crate::read_only;
 
 
 pub fn main(options: Options) -> Result<(), anyhow::Error> {
     let cmdopt = commands::Options {
         command_line: true,
-        styler: if atty::is(atty::Stream::Stdout) {
+        styler: if print::console::colors_stdout() {
             Some(Styler::dark_256())
         } else {
             None
         },
         conn_params: options.conn_params.clone(),
+        schema_cache: None,
     };
+    if options.conn_params.bypasses_access_policies() {
+        eprintln!("{} access policies are bypassed, all objects are \
+            visible and writable", "WARNING:".red().bold());
+    }
     match options.subcommand.as_ref().expect("subcommand is present") {
         Command::Common(cmd) => {
             match cmd {
@@ -46,6 +74,42 @@ pub fn main(options: Options) -> Result<(), anyhow::Error> {
         Command::Project(cmd) => {
             project::main(cmd)
         }
+        Command::Cloud(cmd) => {
+            cloud::main(cmd)
+        }
+        Command::Watch(opt) => {
+            task::block_on(async {
+                let mut conn = options.conn_params.connect().await?;
+                migrations::watch(&mut conn, &cmdopt, opt).await?;
+                Ok(())
+            }).into()
+        },
+        Command::EditMigration(opt) => {
+            task::block_on(migrations::edit(opt)).into()
+        },
+        Command::Check(opt) => {
+            task::block_on(async {
+                migrations::check(opt).await?;
+                if opt.connect {
+                    let mut conn = options.conn_params.connect().await?;
+                    migrations::check_compile(&mut conn, opt).await?;
+                }
+                Ok(())
+            }).into()
+        },
+        Command::Lint(opt) => {
+            migrations::lint(opt)
+        },
+        Command::Schema(opt) => {
+            migrations::schema(opt, &options)
+        },
+        Command::CreateRole(opt) => {
+            task::block_on(async {
+                let mut conn = options.conn_params.connect().await?;
+                commands::roles::create(&mut conn, &cmdopt, opt).await?;
+                Ok(())
+            }).into()
+        },
         Command::CreateSuperuserRole(opt) => {
             task::block_on(async {
                 let mut conn = options.conn_params.connect().await?;
@@ -57,7 +121,8 @@ pub fn main(options: Options) -> Result<(), anyhow::Error> {
         Command::AlterRole(opt) => {
             task::block_on(async {
                 let mut conn = options.conn_params.connect().await?;
-                commands::roles::alter(&mut conn, &cmdopt, opt).await?;
+                commands::roles::alter(&mut conn, &cmdopt, opt,
+                    options.instance_name.as_deref()).await?;
                 Ok(())
             }).into()
         },
@@ -68,12 +133,68 @@ pub fn main(options: Options) -> Result<(), anyhow::Error> {
                 Ok(())
             }).into()
         },
+        Command::Query(q) if q.debug_protocol => {
+            if q.connection == ConnectionKind::Http {
+                anyhow::bail!("`--debug-protocol` works only with \
+                    `--connection=binary`");
+            }
+            if !q.params.is_empty() || !q.sensitive.is_empty() {
+                anyhow::bail!("`--debug-protocol` doesn't support \
+                    query parameters");
+            }
+            task::block_on(async {
+                let mut conn = options.conn_params.connect().await?;
+                for query in &q.queries {
+                    let started = non_interactive::begin_statement(
+                        &mut conn, query, &options).await?;
+                    let result = debug_protocol::query(&mut conn, query).await;
+                    read_only::finish(&mut conn, started, result).await?;
+                }
+                Ok(())
+            }).into()
+        },
+        Command::Query(q) if q.diff_against.is_some() => {
+            if q.connection == ConnectionKind::Http {
+                anyhow::bail!("`--diff-against` works only with \
+                    `--connection=binary`");
+            }
+            if q.queries.len() != 1 {
+                anyhow::bail!("`--diff-against` requires a single query");
+            }
+            let path = q.diff_against.as_ref().expect("checked above");
+            task::block_on(async {
+                let mut conn = options.conn_params.connect().await?;
+                non_interactive::diff_with_params(&mut conn, &q.queries[0],
+                    &q.params, &q.sensitive, path).await?;
+                Ok(())
+            }).into()
+        },
+        Command::Query(q) if q.connection == ConnectionKind::Http => {
+            if !q.globals.is_empty() || q.bypass_access_policies
+                || options.read_only
+            {
+                anyhow::bail!("`--set-global`, `--bypass-access-policies` \
+                    and `--read-only` work only with `--connection=binary`");
+            }
+            let endpoint = Endpoint::from_options(&options,
+                                                  q.http_url.as_deref())?;
+            task::block_on(async {
+                for query in &q.queries {
+                    non_interactive::query_http(&endpoint, query,
+                        &options, &q.params, &q.sensitive).await?;
+                }
+                Ok(())
+            }).into()
+        },
         Command::Query(q) => {
             task::block_on(async {
                 let mut conn = options.conn_params.connect().await?;
+                let mut used = BTreeSet::new();
                 for query in &q.queries {
-                    non_interactive::query(&mut conn, query, &options).await?;
+                    non_interactive::query_with_params(&mut conn, query,
+                        &options, &q.params, &q.sensitive, &mut used).await?;
                 }
+                non_interactive::check_unused(&q.params, &used)?;
                 Ok(())
             }).into()
         },
@@ -83,8 +204,62 @@ pub fn main(options: Options) -> Result<(), anyhow::Error> {
         Command::_GenCompletions(s) => {
             self_install::gen_completions(s)
         }
+        Command::GenMan(s) => {
+            man::gen_man(s)
+        }
+        Command::_Complete(s) => {
+            shell_completion::main(s, &options)
+        }
         Command::SelfUpgrade(s) => {
             self_upgrade::main(s)
         }
+        Command::SelfUninstall(s) => {
+            self_install::uninstall(s)
+        }
+        Command::Info(s) => {
+            info::main(s)
+        }
+        Command::Doctor(c) => {
+            doctor::main(c)
+        }
+        Command::Telemetry(c) => {
+            telemetry::main(c)
+        }
+        Command::CrashReports(c) => {
+            crash_reports::main(c)
+        }
+        Command::Graphql(q) => {
+            graphql::main(q, &options)
+        }
+        Command::InspectDump(c) => {
+            commands::inspect_dump(c)
+        }
+        Command::Clone(c) => {
+            commands::clone(c, &options)
+        }
+        Command::Slowlog(c) => {
+            slowlog::main(c)
+        }
+        Command::Snippet(c) => {
+            snippets::main(c, &options)
+        }
+        Command::Ping(p) => {
+            ping::main(p, &options)
+        }
+        Command::Bench(b) => {
+            bench::main(b, &options)
+        }
+        Command::Ui(c) => {
+            ui::main(c, &options)
+        }
+        Command::ServerProxy(c) => {
+            server_proxy::main(c, &options)
+        }
+        Command::Env(e) => {
+            shell_env::main(e, &options)
+        }
+        Command::External(args) => {
+            plugins::run(args, &options)
+        }
     }
 }
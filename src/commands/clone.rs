@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use anyhow::Context;
+use async_std::future::pending;
+use async_std::prelude::FutureExt;
+use async_std::task;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+use edgedb_client::client::Connection;
+use edgedb_protocol::client_message::{ClientMessage, Dump};
+use edgedb_protocol::client_message::{Restore, RestoreBlock};
+use edgedb_protocol::server_message::{ServerMessage, ErrorResponse};
+use edgeql_parser::helpers::quote_name;
+
+use crate::commands::parser::CloneDatabase;
+use crate::commands::restore::{self, DUPLICATE_DATABASE_DEFINITION_ERROR};
+use crate::connect::Connector;
+use crate::credentials;
+use crate::format;
+use crate::interrupt::Interrupt;
+use crate::options::Options;
+
+
+pub struct Location {
+    pub conn_params: Connector,
+    pub database: String,
+}
+
+
+/// Parses `INSTANCE/DATABASE`, where empty `INSTANCE` means the instance
+/// specified by the connection options
+pub fn location(spec: &str, options: &Options) -> anyhow::Result<Location> {
+    let (instance, database) = spec.rsplit_once('/')
+        .filter(|(_, database)| !database.is_empty())
+        .ok_or_else(|| anyhow::anyhow!(
+            "invalid database {:?}, expected `INSTANCE/DATABASE`", spec))?;
+    let conn_params = if instance.is_empty() {
+        options.conn_params.clone()
+    } else {
+        let name = credentials::resolve_name(instance)?;
+        Connector::new(credentials::get_connector(&name))
+    };
+    Ok(Location { conn_params, database: database.into() })
+}
+
+pub fn clone(cmd: &CloneDatabase, options: &Options) -> anyhow::Result<()> {
+    let source = match &cmd.from {
+        Some(spec) => location(spec, options)?,
+        None => Location {
+            conn_params: options.conn_params.clone(),
+            database: options.conn_params.get()?.get_database().into(),
+        },
+    };
+    let target = location(&cmd.to, options)?;
+    // restore is rolled back by the server when connection is closed
+    let interrupt = Interrupt::new()?;
+    task::block_on(clone_database(source, target, &interrupt))
+}
+
+async fn clone_database(source: Location, mut target: Location,
+    interrupt: &Interrupt)
+    -> anyhow::Result<()>
+{
+    let mut src_params = source.conn_params;
+    src_params.modify(|p| { p.database(&source.database); });
+    let mut src_conn = interrupt.run(src_params.connect()).await
+        .with_context(|| format!("cannot connect to source database {:?}",
+                                 source.database))?;
+
+    let mut admin_conn = interrupt.run(target.conn_params.connect()).await
+        .context("cannot connect to target instance")?;
+    let created = create_database(&mut admin_conn, &target.database).await?;
+    target.conn_params.modify(|p| { p.database(&target.database); });
+    let result = async {
+        let mut dst_conn = interrupt.run(target.conn_params.connect()).await
+            .with_context(|| format!(
+                "cannot connect to target database {:?}", target.database))?;
+        // returns true for non-empty database
+        if !created && restore::is_empty_db(&mut dst_conn).await? {
+            anyhow::bail!("cannot clone: target database {:?} is not empty",
+                          target.database);
+        }
+        interrupt.run(copy(&mut src_conn, &mut dst_conn)).await
+    }.await;
+    if let Err(e) = result {
+        if created {
+            // don't leave half-done database created by us
+            restore::drop_database(&mut admin_conn, &target.database).await;
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Creates database, returns false if it already exists
+async fn create_database(cli: &mut Connection, database: &str)
+    -> anyhow::Result<bool>
+{
+    let create_db = format!("CREATE DATABASE {}", quote_name(database));
+    match cli.execute(create_db).await {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            let exists = e.downcast_ref::<ErrorResponse>()
+                .map(|e| e.code == DUPLICATE_DATABASE_DEFINITION_ERROR)
+                .unwrap_or(false);
+            if exists {
+                Ok(false)
+            } else {
+                Err(e).with_context(|| format!(
+                    "cannot create database {:?}", database))
+            }
+        }
+    }
+}
+
+async fn copy(source: &mut Connection, target: &mut Connection)
+    -> anyhow::Result<()>
+{
+    let start = Instant::now();
+    let mut src = source.start_sequence().await?;
+    src.send_messages(&[
+        ClientMessage::Dump(Dump {
+            headers: Default::default(),
+        }),
+        ClientMessage::Sync,
+    ]).await?;
+    let header = match src.message().await? {
+        ServerMessage::DumpHeader(packet) => packet.data,
+        ServerMessage::ErrorResponse(err) => {
+            src.err_sync().await.ok();
+            return Err(anyhow::anyhow!(err)
+                .context("Error receiving dump header"));
+        }
+        msg => {
+            return Err(anyhow::anyhow!(
+                "WARNING: unsolicited message {:?}", msg));
+        }
+    };
+
+    let mut dst = target.start_sequence().await?;
+    dst.send_messages(&[
+        ClientMessage::Restore(Restore {
+            headers: HashMap::new(),
+            jobs: 1,
+            data: header,
+        })
+    ]).await?;
+    match dst.message().await? {
+        ServerMessage::RestoreReady(_) => {
+            log::info!(target: "edgedb::restore",
+                "Schema applied in {:?}", start.elapsed());
+        }
+        ServerMessage::ErrorResponse(err) => {
+            dst.err_sync().await.ok();
+            return Err(anyhow::anyhow!(err)
+                .context("Error initiating restore protocol"));
+        }
+        msg => {
+            return Err(anyhow::anyhow!(
+                "WARNING: unsolicited message {:?}", msg));
+        }
+    }
+
+    let bar = if atty::is(atty::Stream::Stderr) {
+        ProgressBar::new_spinner()
+    } else {
+        ProgressBar::hidden()
+    };
+    bar.set_style(ProgressStyle::default_spinner()
+        .template("{spinner} [{elapsed_precise}] {bytes} copied \
+                   ({bytes_per_sec})"));
+    bar.enable_steady_tick(100);
+    let writer = &mut dst.writer;
+    let reader = &mut dst.reader;
+    let send_blocks = async {
+        loop {
+            match src.message().await? {
+                ServerMessage::CommandComplete(..) => {
+                    src.expect_ready().await?;
+                    break;
+                }
+                ServerMessage::DumpBlock(packet) => {
+                    bar.inc(packet.data.len() as u64);
+                    writer.send_messages(&[
+                        ClientMessage::RestoreBlock(RestoreBlock {
+                            data: packet.data,
+                        })
+                    ]).await?;
+                }
+                ServerMessage::ErrorResponse(err) => {
+                    src.err_sync().await.ok();
+                    return Err(anyhow::anyhow!(err)
+                        .context("Error receiving dump block"));
+                }
+                msg => {
+                    return Err(anyhow::anyhow!(
+                        "WARNING: unsolicited message {:?}", msg));
+                }
+            }
+        }
+        writer.send_messages(&[ClientMessage::RestoreEof]).await?;
+        // canceled by `wait_response()` receiving CommandComplete
+        pending::<anyhow::Result<()>>().await
+    };
+    let result = send_blocks
+        .race(restore::wait_response(reader, start))
+        .await;
+    bar.finish_and_clear();
+    if let Err(..) = result {
+        dst.err_sync().await.ok();
+    } else {
+        dst.end_clean();
+        eprintln!("Copied {} in {}", HumanBytes(bar.position()),
+                  format::duration(start.elapsed()));
+    }
+    result
+}
@@ -1,9 +1,34 @@
+use async_std::prelude::StreamExt;
+use prettytable::{Table, Row, Cell};
+
+use edgedb_derive::Queryable;
+use edgedb_protocol::value::Value;
 use edgeql_parser::helpers::{quote_string, quote_name};
 use crate::commands::Options;
 use crate::print;
 use edgedb_client::client::Connection;
 use crate::commands::parser::{Configure, ConfigStr};
 use crate::commands::parser::{AuthParameter, PortParameter};
+use crate::table;
+
+const SETTINGS: &[&str] = &[
+    "listen_addresses",
+    "listen_port",
+    "shared_buffers",
+    "query_work_mem",
+    "effective_cache_size",
+    "default_statistics_target",
+    "effective_io_concurrency",
+];
+
+
+#[derive(Queryable)]
+struct AuthRow {
+    priority: i64,
+    method: String,
+    users: String,
+    comment: String,
+}
 
 
 async fn set_string(cli: &mut Connection, name: &str, value: &ConfigStr)
@@ -16,7 +41,72 @@ async fn set_string(cli: &mut Connection, name: &str, value: &ConfigStr)
     Ok(())
 }
 
-pub async fn configure(cli: &mut Connection, _options: &Options,
+fn format_value(value: &serde_json::Value) -> String {
+    use serde_json::Value::*;
+    match value {
+        Null => "{}".into(),
+        String(s) => s.clone(),
+        Array(items) => items.iter().map(format_value)
+            .collect::<Vec<_>>().join(", "),
+        _ => value.to_string(),
+    }
+}
+
+async fn show(cli: &mut Connection, options: &Options)
+    -> Result<(), anyhow::Error>
+{
+    let config = cli.query_row::<String>(&format!(r###"
+        SELECT to_str(<json>cfg::Config {{ {} }})
+        "###, SETTINGS.join(", ")),
+        &Value::empty_tuple()).await?;
+    let config: serde_json::Value = serde_json::from_str(&config)?;
+    let values = SETTINGS.iter()
+        .map(|name| (*name, format_value(&config[name])))
+        .collect::<Vec<_>>();
+    let mut auth = cli.query::<AuthRow>(r###"
+        SELECT cfg::Auth {
+            priority,
+            method := .method.__type__.name,
+            users := to_str(array_agg(.user), ', '),
+            comment := .comment ?? '',
+        }
+        ORDER BY .priority
+    "###, &Value::empty_tuple()).await?;
+    if !options.command_line || atty::is(atty::Stream::Stdout) {
+        table::settings(&values.iter()
+            .map(|(name, value)| (*name, &value[..]))
+            .collect::<Vec<_>>());
+        let mut table = Table::new();
+        table.set_format(*table::FORMAT);
+        table.set_titles(Row::new(
+            ["Priority", "Method", "Users", "Comment"]
+            .iter().map(|x| table::header_cell(x)).collect()));
+        while let Some(item) = auth.next().await.transpose()? {
+            table.add_row(Row::new(vec![
+                Cell::new(&item.priority.to_string()),
+                Cell::new(&item.method),
+                Cell::new(&item.users),
+                Cell::new(&item.comment),
+            ]));
+        }
+        if table.is_empty() {
+            eprintln!("No authentication rules defined");
+        } else {
+            table.printstd();
+        }
+    } else {
+        for (name, value) in &values {
+            println!("{}\t{}", name, value);
+        }
+        while let Some(item) = auth.next().await.transpose()? {
+            println!("Auth\t{}\t{}\t{}\t{}",
+                item.priority, item.method, item.users, item.comment);
+        }
+    }
+    Ok(())
+}
+
+pub async fn configure(cli: &mut Connection, options: &Options,
     cfg: &Configure)
     -> Result<(), anyhow::Error>
 {
@@ -27,6 +117,7 @@ pub async fn configure(cli: &mut Connection, _options: &Options,
     use crate::commands::parser::ConfigureSet as Set;
     use crate::commands::parser::ValueParameter as S;
     match &cfg.command {
+        C::Show => show(cli, options).await,
         C::Insert(Ins { parameter: I::Auth(param) }) => {
             let AuthParameter { users, comment, priority, method } = param;
             let mut props = vec![
@@ -0,0 +1,54 @@
+use edgeql_parser::helpers::quote_name;
+use edgedb_client::client::Connection;
+use edgedb_protocol::value::Value;
+
+use crate::commands::{Options, ExitCode};
+use crate::commands::parser::{DropDatabase, WipeDatabase};
+use crate::migrations;
+use crate::print;
+use crate::question;
+
+
+pub async fn drop(cli: &mut Connection, _options: &Options,
+    options: &DropDatabase)
+    -> Result<(), anyhow::Error>
+{
+    if !options.force {
+        let q = question::Confirm::new_dangerous(
+            format!("Do you really want to delete database {:?}?",
+                    options.database_name)
+        );
+        if !q.ask()? {
+            eprintln!("Canceled");
+            return Err(ExitCode::new(1))?;
+        }
+    }
+    print::completion(&cli.execute(
+        &format!("DROP DATABASE {}", quote_name(&options.database_name))
+    ).await?);
+    Ok(())
+}
+
+pub async fn wipe(cli: &mut Connection, _options: &Options,
+    options: &WipeDatabase)
+    -> Result<(), anyhow::Error>
+{
+    if !options.force {
+        let database: String = cli.query_row(
+            "SELECT sys::get_current_database()",
+            &Value::empty_tuple(),
+        ).await?;
+        let q = question::Confirm::new_dangerous(
+            format!("Do you really want to wipe \
+                    the contents of the database {:?}?",
+                    database)
+        );
+        if !q.ask()? {
+            eprintln!("Canceled");
+            return Err(ExitCode::new(1))?;
+        }
+    }
+    migrations::wipe(cli).await?;
+    eprintln!("Database is wiped");
+    Ok(())
+}
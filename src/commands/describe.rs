@@ -1,23 +1,22 @@
-use async_std::prelude::StreamExt;
-
 use edgedb_protocol::value::Value;
 use crate::commands::Options;
 use crate::commands::helpers::quote_namespaced;
 use edgedb_client::client::Connection;
 use crate::highlight;
+use crate::schema_cache;
 
 
 pub async fn describe(cli: &mut Connection, options: &Options,
     name: &str, verbose: bool)
     -> Result<(), anyhow::Error>
 {
-    let mut items = cli.query::<String>(
+    let items = schema_cache::query::<String>(cli, options,
         &format!("DESCRIBE OBJECT {name} AS TEXT {flag}",
             name=quote_namespaced(name),
             flag=if verbose { "VERBOSE" } else {""}),
         &Value::empty_tuple(),
     ).await?;
-    while let Some(text) = items.next().await.transpose()? {
+    for text in items {
         if let Some(ref styler) = options.styler {
             let mut out = String::with_capacity(text.len());
             highlight::edgeql(&mut out, &text, styler);
@@ -13,6 +13,8 @@ use edgedb_client::client::Connection;
 
 use crate::platform::tmp_file_name;
 use crate::commands::Options;
+use crate::commands::anonymize::Rules;
+use crate::commands::incremental;
 use crate::commands::list_databases::get_databases;
 use crate::commands::parser::{Dump as DumpOptions, DumpFormat};
 
@@ -20,6 +22,7 @@ use crate::commands::parser::{Dump as DumpOptions, DumpFormat};
 type Output = Box<dyn Write + Unpin + Send>;
 
 
+/// Removes partially written file unless it's committed
 pub struct Guard {
     filenames: Option<(PathBuf, PathBuf)>,
 }
@@ -49,18 +52,44 @@ impl Guard {
             }))
         }
     }
-    async fn commit(self) -> anyhow::Result<()> {
-        if let Some((tmp_filename, filename)) = self.filenames {
+    async fn commit(mut self) -> anyhow::Result<()> {
+        if let Some((tmp_filename, filename)) = self.filenames.take() {
             fs::rename(tmp_filename, filename).await?;
         }
         Ok(())
     }
 }
 
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some((tmp_filename, _)) = self.filenames.take() {
+            log::info!("Removing partial dump {}", tmp_filename.display());
+            std::fs::remove_file(&tmp_filename)
+                .map_err(|e| log::warn!("Cannot remove {}: {}",
+                                        tmp_filename.display(), e))
+                .ok();
+        }
+    }
+}
+
 
 pub async fn dump(cli: &mut Connection, general: &Options,
     options: &DumpOptions)
     -> Result<(), anyhow::Error>
+{
+    let rules = options.anonymize.as_ref()
+        .map(|path| Rules::read(path))
+        .transpose()?;
+    dump_with_rules(cli, general, options, rules.as_ref()).await?;
+    if let Some(rules) = &rules {
+        rules.warn_unused();
+    }
+    Ok(())
+}
+
+async fn dump_with_rules(cli: &mut Connection, general: &Options,
+    options: &DumpOptions, rules: Option<&Rules>)
+    -> Result<(), anyhow::Error>
 {
     if options.all {
         if let Some(dformat) = options.format {
@@ -70,16 +99,39 @@ pub async fn dump(cli: &mut Connection, general: &Options,
         } else {
             anyhow::bail!("`--format=dir` is required when using `--all`");
         }
-        dump_all(cli, general, options.path.as_ref()).await
+        dump_all(cli, general, options.path.as_ref(), options.schema_only,
+                 rules).await
     } else {
         if options.format.is_some() {
             anyhow::bail!("`--format` is reserved for dump using `--all`");
         }
-        dump_db(cli, general, options.path.as_ref()).await
+        let path: &Path = options.path.as_ref();
+        if let Some(since) = &options.since {
+            if path.to_str() == Some("-") {
+                anyhow::bail!("`--since` requires dump path");
+            }
+            return incremental::dump_since(cli, general, path, since,
+                &options.updated_at_property).await;
+        }
+        if path.to_str() == Some("-") {
+            return dump_db(cli, general, path, options.schema_only,
+                           rules).await;
+        }
+        if !options.manifest {
+            dump_db(cli, general, path, options.schema_only, rules).await?;
+            // manifest of the previous dump at this path is wrong now
+            return incremental::remove_manifest(path).await;
+        }
+        let watermark = incremental::watermark(cli).await?;
+        dump_db(cli, general, path, false, rules).await?;
+        incremental::write_manifest(&incremental::manifest_path(path),
+            &incremental::Manifest::new(watermark, None)).await?;
+        Ok(())
     }
 }
 
-async fn dump_db(cli: &mut Connection, _options: &Options, filename: &Path)
+async fn dump_db(cli: &mut Connection, _options: &Options, filename: &Path,
+    schema_only: bool, anonymize: Option<&Rules>)
     -> Result<(), anyhow::Error>
 {
     let mut seq = cli.start_sequence().await?;
@@ -97,9 +149,14 @@ async fn dump_db(cli: &mut Connection, _options: &Options, filename: &Path)
     ]).await?;
 
     let mut header_buf = Vec::with_capacity(25);
+    let mut anonymizer = None;
     let msg = seq.message().await?;
     match msg {
         ServerMessage::DumpHeader(packet) => {
+            if let Some(rules) = anonymize {
+                anonymizer = Some(rules.anonymizer(&packet.data)
+                    .context("cannot apply anonymization rules")?);
+            }
             // this is ensured because length in the protocol is u32 too
             assert!(packet.data.len() <= u32::max_value() as usize);
 
@@ -129,7 +186,14 @@ async fn dump_db(cli: &mut Connection, _options: &Options, filename: &Path)
                 seq.expect_ready().await?;
                 break;
             }
-            ServerMessage::DumpBlock(packet) => {
+            ServerMessage::DumpBlock(_) if schema_only => {
+                // server has no way to skip data, so blocks are discarded
+            }
+            ServerMessage::DumpBlock(mut packet) => {
+                if let Some(anonymizer) = &mut anonymizer {
+                    packet.data = anonymizer.block(packet.data)
+                        .context("cannot anonymize data block")?;
+                }
                 // this is ensured because length in the protocol is u32 too
                 assert!(packet.data.len() <= u32::max_value() as usize);
 
@@ -173,7 +237,8 @@ async fn get_text(cli: &mut Connection, query: &str)
     Ok(text)
 }
 
-pub async fn dump_all(cli: &mut Connection, options: &Options, dir: &Path)
+pub async fn dump_all(cli: &mut Connection, options: &Options, dir: &Path,
+    schema_only: bool, anonymize: Option<&Rules>)
     -> Result<(), anyhow::Error>
 {
     let databases = get_databases(cli).await?;
@@ -201,7 +266,8 @@ pub async fn dump_all(cli: &mut Connection, options: &Options, dir: &Path)
             .modify(|p| { p.database(database); })
             .connect().await?;
         let filename = dir.join(urlencoding::encode(database) + ".dump");
-        dump_db(&mut db_conn, options, &filename).await?;
+        dump_db(&mut db_conn, options, &filename, schema_only, anonymize)
+            .await?;
     }
 
     Ok(())
@@ -3,7 +3,9 @@ use edgedb_client::client::Connection;
 use edgedb_client::server_params::PostgresAddress;
 
 use crate::commands::{self, Options};
+use crate::commands::database;
 use crate::commands::parser::Common;
+use crate::interrupt::Interrupt;
 use crate::print;
 use crate::migrations;
 
@@ -63,8 +65,25 @@ pub async fn common(cli: &mut Connection, cmd: &Common, options: &Options)
         Describe(c) => {
             commands::describe(cli, &options, &c.name, c.verbose).await?;
         }
+        Analyze(c) => {
+            commands::analyze(cli, &options, c).await?;
+        }
         Dump(c) => {
-            commands::dump(cli, &options, c).await?;
+            check_access_policies(c.bypass_access_policies, options)?;
+            if options.command_line {
+                // partial dump is removed when interrupted
+                Interrupt::new()?.run(commands::dump(cli, &options, c))
+                    .await?;
+            } else {
+                commands::dump(cli, &options, c).await?;
+            }
+        }
+        Import(c) => {
+            commands::import(cli, &options, c).await?;
+        }
+        Export(c) => {
+            check_access_policies(c.bypass_access_policies, options)?;
+            commands::export(cli, &options, c).await?;
         }
         Restore(params) => {
             commands::restore(cli, &options, params)
@@ -78,6 +97,12 @@ pub async fn common(cli: &mut Connection, cmd: &Common, options: &Options)
                 &format!("CREATE DATABASE {}", quote_name(&c.database_name))
             ).await?);
         }
+        DropDatabase(c) => {
+            database::drop(cli, &options, c).await?;
+        }
+        WipeDatabase(c) => {
+            database::wipe(cli, &options, c).await?;
+        }
         CreateMigration(params) => {
             migrations::create(cli, &options, params).await?;
         }
@@ -90,6 +115,22 @@ pub async fn common(cli: &mut Connection, cmd: &Common, options: &Options)
         MigrationLog(params) => {
             migrations::log(cli, &options, params).await?;
         }
+        VerifyMigrations(params) => {
+            migrations::verify(cli, &options, params).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Access policies are disabled per connection by the command-line options,
+/// the REPL connection is shared with other commands
+fn check_access_policies(bypass: bool, options: &Options)
+    -> anyhow::Result<()>
+{
+    if bypass && !options.command_line {
+        anyhow::bail!("`--bypass-access-policies` works only on the command \
+            line, use `CONFIGURE SESSION SET apply_access_policies := \
+            false` in the REPL");
     }
     Ok(())
 }
@@ -0,0 +1,144 @@
+//! Export of all objects of a type into CSV or JSON file
+//!
+//! Objects are fetched in pages ordered by `id`, each page starting after
+//! the last `id` of the previous one, so memory usage doesn't depend on
+//! the number of objects.
+
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use anyhow::Context;
+use async_std::prelude::StreamExt;
+use edgedb_protocol::value::Value;
+use edgedb_client::client::Connection;
+use edgeql_parser::helpers::quote_name;
+
+use crate::commands::Options;
+use crate::commands::helpers::quote_namespaced;
+use crate::commands::import::{full_type_name, writable_properties};
+use crate::commands::parser::{DataFormat, Export};
+use crate::platform::tmp_file_path;
+
+/// Sorts before any generated object id
+const MIN_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+
+enum Writer {
+    Csv(csv::Writer<BufWriter<fs::File>>, Vec<String>),
+    Json(BufWriter<fs::File>, usize),
+}
+
+fn csv_cell(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+impl Writer {
+    fn new(format: DataFormat, file: fs::File, columns: Vec<String>)
+        -> anyhow::Result<Writer>
+    {
+        let file = BufWriter::new(file);
+        match format {
+            DataFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(file);
+                writer.write_record(&columns)?;
+                Ok(Writer::Csv(writer, columns))
+            }
+            DataFormat::Json => {
+                let mut file = file;
+                file.write_all(b"[")?;
+                Ok(Writer::Json(file, 0))
+            }
+        }
+    }
+    fn write(&mut self, object: &serde_json::Value) -> anyhow::Result<()> {
+        match self {
+            Writer::Csv(writer, columns) => {
+                writer.write_record(columns.iter()
+                    .map(|name| csv_cell(object.get(name))))?;
+            }
+            Writer::Json(file, count) => {
+                if *count > 0 {
+                    file.write_all(b",")?;
+                }
+                file.write_all(b"\n")?;
+                serde_json::to_writer(&mut *file, object)?;
+                *count += 1;
+            }
+        }
+        Ok(())
+    }
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Writer::Csv(mut writer, _) => writer.flush()?,
+            Writer::Json(mut file, _) => {
+                file.write_all(b"\n]\n")?;
+                file.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub async fn export(cli: &mut Connection, _options: &Options,
+    export: &Export)
+    -> Result<(), anyhow::Error>
+{
+    if export.page_size == 0 {
+        anyhow::bail!("page size must be positive");
+    }
+    let type_name = full_type_name(&export.type_name);
+    let properties = writable_properties(cli, &type_name).await?;
+    if properties.is_empty() {
+        anyhow::bail!("type {} not found or has no writable properties",
+                      type_name);
+    }
+    let mut columns = vec![String::from("id")];
+    columns.extend(properties.into_iter().map(|p| p.name));
+    let query = format!(r###"
+        SELECT {typ} {{ {shape} }}
+        FILTER .id > <uuid><str>$0
+        ORDER BY .id
+        LIMIT <int64>$1
+        "###,
+        typ=quote_namespaced(&type_name),
+        shape=columns.iter().map(|c| quote_name(c))
+            .collect::<Vec<_>>().join(", "));
+
+    let tmp_path = tmp_file_path(&export.out);
+    let file = fs::File::create(&tmp_path)
+        .with_context(|| format!("cannot create {}", tmp_path.display()))?;
+    let mut writer = Writer::new(export.format, file, columns)?;
+    let mut last_id = String::from(MIN_UUID);
+    let mut total = 0;
+    loop {
+        let mut items = cli.query_json_els(&query, &Value::Tuple(vec![
+            Value::Str(last_id.clone()),
+            Value::Int64(export.page_size as i64),
+        ])).await?;
+        let mut count = 0;
+        while let Some(item) = items.next().await.transpose()? {
+            let object: serde_json::Value = serde_json::from_str(&item)
+                .context("cannot decode object JSON")?;
+            writer.write(&object)?;
+            if let Some(id) = object.get("id").and_then(|id| id.as_str()) {
+                last_id = id.into();
+            }
+            count += 1;
+        }
+        total += count;
+        log::info!(target: "edgedb::export",
+            "{} object(s) of {} exported", total, type_name);
+        if count < export.page_size {
+            break;
+        }
+    }
+    writer.finish()
+        .with_context(|| format!("cannot write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &export.out)?;
+    eprintln!("Exported {} objects", total);
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! Bulk import of objects from CSV or JSON files
+//!
+//! Rows are inserted in batches, each batch is a single
+//! `FOR ... UNION (INSERT ...)` statement so it's applied atomically.
+
+use std::fs;
+use std::io::{BufReader, Write};
+
+use anyhow::Context;
+use async_std::prelude::StreamExt;
+use edgedb_derive::Queryable;
+use edgedb_protocol::value::Value;
+use edgedb_client::client::Connection;
+use edgeql_parser::helpers::{quote_name, quote_string};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Deserialize;
+
+use crate::commands::Options;
+use crate::commands::helpers::quote_namespaced;
+use crate::commands::parser::{DataFormat, Import};
+
+type Row = serde_json::Map<String, serde_json::Value>;
+
+
+#[derive(Deserialize, Queryable, Debug)]
+#[edgedb(json)]
+pub struct Property {
+    pub name: String,
+    pub target: String,
+}
+
+struct Importer<'a> {
+    type_name: String,
+    properties: Vec<Property>,
+    from_text: bool,
+    error_log: Option<fs::File>,
+    bar: &'a ProgressBar,
+    rejected: usize,
+}
+
+fn read_rows(import: &Import)
+    -> anyhow::Result<Box<dyn Iterator<Item=anyhow::Result<Row>>>>
+{
+    let file = fs::File::open(&import.file)
+        .with_context(|| format!("cannot open {}", import.file.display()))?;
+    match import.format {
+        DataFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(BufReader::new(file));
+            let headers = reader.headers()?.clone();
+            Ok(Box::new(reader.into_records().map(move |record| {
+                let record = record?;
+                Ok(headers.iter().zip(record.iter())
+                    // empty cells are treated as missing values
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(name, value)| {
+                        (name.to_string(), value.to_string().into())
+                    })
+                    .collect())
+            })))
+        }
+        DataFormat::Json => {
+            // Accepts either a JSON array or a stream of objects
+            // (e.g. one per line)
+            let stream = serde_json::Deserializer::from_reader(
+                    BufReader::new(file))
+                .into_iter::<serde_json::Value>();
+            Ok(Box::new(stream.flat_map(|item| match item {
+                Ok(serde_json::Value::Array(items)) => {
+                    items.into_iter().map(json_row).collect()
+                }
+                Ok(item) => vec![json_row(item)],
+                Err(e) => vec![Err(e.into())],
+            })))
+        }
+    }
+}
+
+fn json_row(value: serde_json::Value) -> anyhow::Result<Row> {
+    match value {
+        serde_json::Value::Object(row) => Ok(row),
+        _ => anyhow::bail!("expected JSON object, got {}", value),
+    }
+}
+
+pub fn full_type_name(name: &str) -> String {
+    if name.contains("::") {
+        name.into()
+    } else {
+        format!("default::{}", name)
+    }
+}
+
+/// Returns single properties of the type which can be set by `INSERT`
+pub async fn writable_properties(cli: &mut Connection, type_name: &str)
+    -> anyhow::Result<Vec<Property>>
+{
+    let mut rows = cli.query::<Property>(r###"
+        WITH MODULE schema
+        SELECT <json>(
+            SELECT (SELECT ObjectType FILTER .name = <str>$0).properties {
+                name,
+                target := .target.name,
+            }
+            FILTER NOT .readonly AND <str>.cardinality = 'One'
+        )
+    "###, &Value::Tuple(vec![Value::Str(type_name.into())])).await?;
+    let mut properties = Vec::new();
+    while let Some(prop) = rows.next().await.transpose()? {
+        properties.push(prop);
+    }
+    Ok(properties)
+}
+
+impl Importer<'_> {
+    fn statement(&self, rows: &[Row]) -> anyhow::Result<String> {
+        let mut columns = Vec::new();
+        for row in rows {
+            for name in row.keys() {
+                if !columns.contains(&name) {
+                    columns.push(name);
+                }
+            }
+        }
+        let mut assignments = Vec::with_capacity(columns.len());
+        for column in columns {
+            let prop = self.properties.iter().find(|p| &p.name == column)
+                .ok_or_else(|| anyhow::anyhow!(
+                    "type {} has no writable property {:?}",
+                    self.type_name, column))?;
+            assignments.push(format!(
+                "{name} := <{target}>{cast}json_get(item, {key})",
+                name=quote_name(&prop.name),
+                target=quote_namespaced(&prop.target),
+                cast=if self.from_text { "<str>" } else { "" },
+                key=quote_string(&prop.name)));
+        }
+        Ok(format!(r###"
+            SELECT count((
+                FOR item IN {{ json_array_unpack(to_json(<str>$0)) }}
+                UNION (INSERT {typ} {{ {assignments} }})
+            ))
+            "###,
+            typ=quote_namespaced(&self.type_name),
+            assignments=assignments.join(",\n")))
+    }
+
+    async fn insert(&self, cli: &mut Connection, statement: &str,
+        rows: &[Row])
+        -> anyhow::Result<i64>
+    {
+        let data = serde_json::to_string(rows)?;
+        cli.query_row::<i64>(statement,
+            &Value::Tuple(vec![Value::Str(data)])).await
+    }
+
+    fn reject(&mut self, index: usize, row: &Row, error: &anyhow::Error)
+        -> anyhow::Result<()>
+    {
+        self.rejected += 1;
+        if let Some(log) = &mut self.error_log {
+            let entry = serde_json::json!({
+                "row": index,
+                "error": format!("{:#}", error),
+                "data": row,
+            });
+            writeln!(log, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a batch; on failure, if error log is enabled inserts rows
+    /// one by one to find out which ones are rejected
+    async fn batch(&mut self, cli: &mut Connection, offset: usize,
+        rows: &[Row])
+        -> anyhow::Result<()>
+    {
+        // unknown columns abort the import regardless of the error log
+        let statement = self.statement(rows)?;
+        let err = match self.insert(cli, &statement, rows).await {
+            Ok(_) => {
+                self.bar.inc(rows.len() as u64);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        if self.error_log.is_none() {
+            return Err(err).with_context(|| format!(
+                "cannot import rows {}..{}", offset, offset + rows.len()));
+        }
+        for (idx, row) in rows.iter().enumerate() {
+            let row = std::slice::from_ref(row);
+            let statement = self.statement(row)?;
+            match self.insert(cli, &statement, row).await {
+                Ok(_) => self.bar.inc(1),
+                Err(e) => self.reject(offset + idx, &row[0], &e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub async fn import(cli: &mut Connection, _options: &Options,
+    import: &Import)
+    -> Result<(), anyhow::Error>
+{
+    if import.batch_size == 0 {
+        anyhow::bail!("batch size must be positive");
+    }
+    let type_name = full_type_name(&import.type_name);
+    let properties = writable_properties(cli, &type_name).await?;
+    if properties.is_empty() {
+        anyhow::bail!("type {} not found or has no writable properties",
+                      type_name);
+    }
+    let error_log = match &import.error_log {
+        Some(path) => Some(fs::File::create(path).with_context(|| {
+            format!("cannot create error log {}", path.display())
+        })?),
+        None => None,
+    };
+    let bar = if atty::is(atty::Stream::Stderr) {
+        ProgressBar::new_spinner()
+    } else {
+        ProgressBar::hidden()
+    };
+    bar.set_style(ProgressStyle::default_spinner()
+        .template("{spinner} [{elapsed_precise}] {pos} rows imported"));
+    bar.enable_steady_tick(100);
+    let mut importer = Importer {
+        type_name,
+        properties,
+        from_text: import.format == DataFormat::Csv,
+        error_log,
+        bar: &bar,
+        rejected: 0,
+    };
+
+    let mut batch = Vec::with_capacity(import.batch_size);
+    let mut offset = 0;
+    for (index, row) in read_rows(import)?.enumerate() {
+        let row = row.with_context(|| format!("cannot read row {}", index))?;
+        batch.push(row);
+        if batch.len() >= import.batch_size {
+            importer.batch(cli, offset, &batch).await?;
+            offset += batch.len();
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        importer.batch(cli, offset, &batch).await?;
+    }
+    bar.finish_and_clear();
+    let imported = bar.position();
+    if importer.rejected > 0 {
+        eprintln!("Imported {} rows, {} rejected (see {})",
+            imported, importer.rejected,
+            import.error_log.as_ref().expect("log is enabled").display());
+    } else {
+        eprintln!("Imported {} rows", imported);
+    }
+    Ok(())
+}
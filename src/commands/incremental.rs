@@ -0,0 +1,295 @@
+//! Incremental dumps
+//!
+//! Unlike normal dumps these are not handled by the server. Instead they
+//! contain JSON data of the objects which have a modification timestamp
+//! property (`updated_at` by default) newer than the watermark of the
+//! previous dump. Only (single) properties of the objects are exported.
+//!
+//! Objects are applied by `id`, so applying the same dump twice is
+//! harmless. Deleted objects can't be detected by the timestamp, so they
+//! are not removed from the database the dump is applied to.
+
+use anyhow::Context;
+use async_std::fs;
+use async_std::path::{Path, PathBuf};
+use async_std::prelude::StreamExt;
+use serde::{Serialize, Deserialize};
+
+use edgedb_derive::Queryable;
+use edgedb_protocol::value::Value;
+use edgedb_client::client::Connection;
+use edgeql_parser::helpers::{quote_name, quote_string};
+use fn_error_context::context;
+
+use crate::capabilities::{self, Feature};
+use crate::commands::Options;
+use crate::commands::helpers::quote_namespaced;
+use crate::platform::tmp_file_path;
+
+const FORMAT_VERSION: u32 = 1;
+
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all="kebab-case")]
+pub struct Manifest {
+    format: u32,
+    /// Server time at the start of the dump
+    watermark: String,
+    /// Watermark of the previous dump if this one is incremental
+    since: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all="kebab-case")]
+struct IncrementalDump {
+    format: u32,
+    since: String,
+    watermark: String,
+    updated_at_property: String,
+    types: Vec<TypeData>,
+}
+
+#[derive(Deserialize, Queryable, Debug)]
+#[edgedb(json)]
+struct ObjectType {
+    name: String,
+    properties: Vec<Property>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Property {
+    name: String,
+    target: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TypeData {
+    name: String,
+    properties: Vec<Property>,
+    objects: Vec<serde_json::Value>,
+}
+
+
+impl Manifest {
+    pub fn new(watermark: String, since: Option<String>) -> Manifest {
+        Manifest { format: FORMAT_VERSION, watermark, since }
+    }
+}
+
+pub fn manifest_path(dump_path: &Path) -> PathBuf {
+    let mut name = dump_path.file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_default();
+    name.push(".manifest.json");
+    dump_path.with_file_name(name)
+}
+
+pub async fn watermark(cli: &mut Connection) -> anyhow::Result<String> {
+    cli.query_row::<String>("SELECT <str>datetime_of_statement()",
+        &Value::empty_tuple()).await
+}
+
+#[context("cannot write manifest {}", path.display())]
+pub async fn write_manifest(path: &Path, manifest: &Manifest)
+    -> anyhow::Result<()>
+{
+    let tmp_path = PathBuf::from(tmp_file_path(path.as_ref()));
+    fs::write(&tmp_path, serde_json::to_vec_pretty(manifest)?).await?;
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+/// Removes manifest of the previous dump overwritten by a dump without one
+pub async fn remove_manifest(dump_path: &Path) -> anyhow::Result<()> {
+    let path = manifest_path(dump_path);
+    if path.exists().await {
+        fs::remove_file(&path).await
+            .with_context(|| format!("cannot remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[context("cannot read manifest {}", path.display())]
+async fn read_manifest(path: &Path) -> anyhow::Result<Manifest> {
+    let data = fs::read(path).await?;
+    let manifest: Manifest = serde_json::from_slice(&data)?;
+    if manifest.format > FORMAT_VERSION {
+        anyhow::bail!("unsupported manifest format {}", manifest.format);
+    }
+    Ok(manifest)
+}
+
+/// Resolves `--since` into a timestamp
+///
+/// The value is either a path to the previous dump (or its manifest) or
+/// a timestamp itself.
+async fn resolve_since(since: &str) -> anyhow::Result<String> {
+    let path = Path::new(since);
+    if path.extension().map(|e| e == "json").unwrap_or(false)
+        && path.exists().await
+    {
+        return Ok(read_manifest(path).await?.watermark);
+    }
+    let manifest = manifest_path(path);
+    if manifest.exists().await {
+        return Ok(read_manifest(&manifest).await?.watermark);
+    }
+    if path.exists().await {
+        anyhow::bail!("dump {} has no manifest {}, \
+            specify timestamp instead",
+            path.display(), manifest.display());
+    }
+    Ok(since.into())
+}
+
+pub async fn dump_since(cli: &mut Connection, _options: &Options,
+    filename: &Path, since: &str, updated_at_property: &str)
+    -> anyhow::Result<()>
+{
+    let since = resolve_since(since).await?;
+    let watermark = watermark(cli).await?;
+    let mut types = cli.query::<ObjectType>(r###"
+        WITH MODULE schema
+        SELECT <json>ObjectType {
+            name,
+            properties := (
+                SELECT .properties {
+                    name,
+                    target := .target.name,
+                }
+                FILTER NOT .readonly
+                    AND <str>.cardinality = 'One'
+            ),
+        }
+        FILTER NOT .is_abstract
+            AND .properties.name = <str>$0
+            AND NOT re_test("^(?:std|schema|math|sys|cfg|cal|stdgraphql)::",
+                .name)
+        ORDER BY .name
+    "###, &Value::Tuple(vec![Value::Str(updated_at_property.into())])
+    ).await?;
+    let mut type_list = Vec::new();
+    while let Some(typ) = types.next().await.transpose()? {
+        type_list.push(typ);
+    }
+
+    let mut data = Vec::with_capacity(type_list.len());
+    for typ in type_list {
+        let shape = typ.properties.iter()
+            .map(|p| quote_name(&p.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        // selecting a type also returns objects of its subtypes, those are
+        // dumped along with their own type
+        let query = format!(r###"
+            SELECT to_str(<json>(
+                SELECT {typ} {{ id, {shape} }}
+                FILTER .{prop} > <datetime><str>$0
+                    AND .__type__.name = <str>$1
+            ))
+        "###,
+            typ=quote_namespaced(&typ.name),
+            shape=shape,
+            prop=quote_name(updated_at_property));
+        let mut rows = cli.query::<String>(&query, &Value::Tuple(vec![
+            Value::Str(since.clone()),
+            Value::Str(typ.name.clone()),
+        ])).await?;
+        let mut objects = Vec::new();
+        while let Some(row) = rows.next().await.transpose()? {
+            objects.push(serde_json::from_str(&row)
+                .context("cannot decode object JSON")?);
+        }
+        log::info!(target: "edgedb::dump",
+            "{} changed object(s) of {}", objects.len(), typ.name);
+        data.push(TypeData {
+            name: typ.name,
+            properties: typ.properties,
+            objects,
+        });
+    }
+
+    let dump = IncrementalDump {
+        format: FORMAT_VERSION,
+        since: since.clone(),
+        watermark: watermark.clone(),
+        updated_at_property: updated_at_property.into(),
+        types: data,
+    };
+    let tmp_path = PathBuf::from(tmp_file_path(filename.as_ref()));
+    fs::write(&tmp_path, serde_json::to_vec(&dump)?).await
+        .with_context(|| format!("cannot write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, filename).await?;
+    write_manifest(&manifest_path(filename),
+        &Manifest::new(watermark, Some(since))).await?;
+    Ok(())
+}
+
+#[context("cannot apply incremental dump {}", filename.display())]
+pub async fn apply(cli: &mut Connection, _options: &Options,
+    filename: &Path, verbose: bool)
+    -> anyhow::Result<()>
+{
+    let dump: IncrementalDump = serde_json::from_slice(
+        &fs::read(filename).await?)?;
+    if dump.format > FORMAT_VERSION {
+        anyhow::bail!("unsupported incremental dump format {}", dump.format);
+    }
+    capabilities::require(cli, Feature::UserSpecifiedId).await?;
+    cli.execute("CONFIGURE SESSION SET allow_user_specified_id := true")
+        .await?;
+    cli.execute("START TRANSACTION").await?;
+    let result = match apply_types(cli, &dump, verbose).await {
+        Ok(()) => cli.execute("COMMIT").await.map(|_| ()),
+        Err(e) => {
+            cli.execute("ROLLBACK").await.ok();
+            Err(e)
+        }
+    };
+    cli.execute("CONFIGURE SESSION RESET allow_user_specified_id").await
+        .ok();
+    result
+}
+
+async fn apply_types(cli: &mut Connection, dump: &IncrementalDump,
+    verbose: bool)
+    -> anyhow::Result<()>
+{
+    for typ in &dump.types {
+        let name = quote_namespaced(&typ.name);
+        let assignments = typ.properties.iter()
+            .map(|p| format!("{name} := <{target}>data[{key}]",
+                name=quote_name(&p.name),
+                target=quote_namespaced(&p.target),
+                key=quote_string(&p.name)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        // objects keep their ids, so they are updated on re-apply
+        let upsert = format!(r###"
+            WITH data := to_json(<str>$0)
+            SELECT (
+                INSERT {name} {{
+                    id := <uuid>data['id'],
+                    {assignments}
+                }}
+                UNLESS CONFLICT ON .id
+                ELSE (UPDATE {name} SET {{ {assignments} }})
+            ).id
+        "###, name=name, assignments=assignments);
+        for object in &typ.objects {
+            let id = object.get("id").and_then(|id| id.as_str())
+                .ok_or_else(|| anyhow::anyhow!("object without id in {}",
+                                               typ.name))?;
+            cli.query_row::<uuid::Uuid>(&upsert, &Value::Tuple(vec![
+                Value::Str(object.to_string()),
+            ])).await
+                .with_context(|| format!("cannot apply {} {}",
+                                         typ.name, id))?;
+        }
+        if verbose {
+            eprintln!("{}: {} object(s) applied",
+                typ.name, typ.objects.len());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+use std::str;
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::Context;
+use async_std::io::{BufReader, prelude::ReadExt};
+use async_std::task;
+use indicatif::HumanBytes;
+use prettytable::{Table, Row, Cell};
+use uuid::Uuid;
+
+use crate::commands::parser::InspectDump;
+use crate::commands::restore::{self, DUMP_MAGIC, PacketType, read_packet};
+use crate::table;
+
+const HEADER_SERVER_TIME: u16 = 102;
+const HEADER_SERVER_VERSION: u16 = 103;
+const HEADER_SERVER_CATALOG_VERSION: u16 = 105;
+pub const HEADER_BLOCK_ID: u16 = 110;
+pub const HEADER_BLOCK_DATA: u16 = 112;
+
+/// Top-level schema statements counted in the summary
+const SCHEMA_ITEMS: &[(&str, &str)] = &[
+    ("CREATE MODULE", "modules"),
+    ("CREATE TYPE", "object types"),
+    ("CREATE ABSTRACT TYPE", "object types"),
+    ("CREATE SCALAR TYPE", "scalar types"),
+    ("CREATE ABSTRACT SCALAR TYPE", "scalar types"),
+    ("CREATE FINAL SCALAR TYPE", "scalar types"),
+    ("CREATE ALIAS", "aliases"),
+    ("CREATE FUNCTION", "functions"),
+    ("CREATE ABSTRACT CONSTRAINT", "constraints"),
+    ("CREATE ABSTRACT LINK", "abstract links"),
+    ("CREATE ABSTRACT PROPERTY", "abstract properties"),
+    ("CREATE MIGRATION", "migrations"),
+];
+
+
+#[derive(Debug, Default)]
+struct Header {
+    format_version: i64,
+    protocol: (u16, u16),
+    server_version: Option<String>,
+    catalog_version: Option<String>,
+    server_time: Option<String>,
+    schema_ddl: String,
+    types: BTreeMap<Uuid, String>,
+}
+
+#[derive(Debug, Default)]
+struct BlockStats {
+    blocks: u64,
+    bytes: u64,
+}
+
+/// Cursor over the binary protocol data
+pub struct Buf<'a>(pub &'a [u8]);
+
+impl<'a> Buf<'a> {
+    pub fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.0.len() < len {
+            anyhow::bail!("unexpected end of data");
+        }
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(data)
+    }
+    pub fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    pub fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    pub fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    pub fn bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+    pub fn string(&mut self) -> anyhow::Result<String> {
+        Ok(str::from_utf8(self.bytes()?)?.to_string())
+    }
+    pub fn uuid(&mut self) -> anyhow::Result<Uuid> {
+        Ok(Uuid::from_slice(self.take(16)?)?)
+    }
+    pub fn headers(&mut self) -> anyhow::Result<BTreeMap<u16, &'a [u8]>> {
+        let num = self.u16()?;
+        let mut result = BTreeMap::new();
+        for _ in 0..num {
+            let code = self.u16()?;
+            result.insert(code, self.bytes()?);
+        }
+        Ok(result)
+    }
+}
+
+/// Reads next packet, returns its type and data, or `None` at EOF
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+fn parse_header(format_version: i64, data: &[u8]) -> anyhow::Result<Header> {
+    let mut buf = Buf(data);
+    let headers = buf.headers()?;
+    let mut header = Header {
+        format_version,
+        server_version: headers.get(&HEADER_SERVER_VERSION)
+            .map(|v| text(v)),
+        catalog_version: headers.get(&HEADER_SERVER_CATALOG_VERSION)
+            .map(|v| text(v)),
+        server_time: headers.get(&HEADER_SERVER_TIME).map(|v| text(v)),
+        .. Header::default()
+    };
+    header.protocol = (buf.u16()?, buf.u16()?);
+    header.schema_ddl = buf.string()?;
+    let num_types = buf.u32()?;
+    for _ in 0..num_types {
+        let name = buf.string()?;
+        let _class = buf.string()?;
+        let id = buf.uuid()?;
+        header.types.insert(id, name);
+    }
+    Ok(header)
+}
+
+/// Returns schema DDL from the data of the dump header
+pub fn schema_ddl(data: &[u8]) -> anyhow::Result<String> {
+    let mut buf = Buf(data);
+    buf.headers()?;
+    buf.u16()?;
+    buf.u16()?;
+    buf.string()
+}
+
+fn block_id(data: &[u8]) -> anyhow::Result<(Uuid, u64)> {
+    let headers = Buf(data).headers()?;
+    let id = headers.get(&HEADER_BLOCK_ID)
+        .ok_or_else(|| anyhow::anyhow!("block has no id"))?;
+    let size = headers.get(&HEADER_BLOCK_DATA)
+        .map(|d| d.len()).unwrap_or(data.len());
+    Ok((Uuid::from_slice(id)?, size as u64))
+}
+
+fn schema_summary(ddl: &str) -> Vec<(&'static str, usize)> {
+    let mut counts = Vec::<(&'static str, usize)>::new();
+    for line in ddl.lines() {
+        // nested statements are indented
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let item = SCHEMA_ITEMS.iter()
+            .filter(|(prefix, _)| {
+                line.starts_with(prefix) &&
+                line[prefix.len()..].starts_with(char::is_whitespace)
+            })
+            .map(|(_, title)| *title)
+            .next();
+        if let Some(title) = item {
+            match counts.iter_mut().find(|(t, _)| *t == title) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((title, 1)),
+            }
+        }
+    }
+    counts
+}
+
+fn format_time(value: &str) -> String {
+    match value.trim().parse::<u64>() {
+        Ok(secs) => humantime::format_rfc3339_seconds(
+            UNIX_EPOCH + Duration::from_secs(secs)).to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+fn inspect_file(path: &Path) -> anyhow::Result<()> {
+    let file = fs::File::open(path)?;
+    let mut input: restore::Input = Box::new(
+        BufReader::new(async_std::fs::File::from(file)));
+    let mut buf = [0u8; 17+8];
+    task::block_on(input.read_exact(&mut buf))
+        .context("cannot read header")?;
+    if &buf[..17] != DUMP_MAGIC {
+        anyhow::bail!("file is not an edgedb dump");
+    }
+    let format_version = i64::from_be_bytes(buf[17..].try_into().unwrap());
+    let data = task::block_on(read_packet(&mut input, PacketType::Header))?
+        .ok_or_else(|| anyhow::anyhow!("dump is empty"))?;
+    let header = parse_header(format_version, &data)
+        .context("cannot parse dump header")?;
+    let mut blocks = BTreeMap::<Uuid, BlockStats>::new();
+    while let Some(data) =
+        task::block_on(read_packet(&mut input, PacketType::Block))?
+    {
+        let (id, size) = block_id(&data)?;
+        let stats = blocks.entry(id).or_default();
+        stats.blocks += 1;
+        stats.bytes += size;
+    }
+
+    println!("Dump: {}", path.display());
+    println!("  Format version: {}", header.format_version);
+    println!("  Protocol version: {}.{}",
+             header.protocol.0, header.protocol.1);
+    println!("  Server version: {}",
+             header.server_version.as_deref().unwrap_or("unknown"));
+    if let Some(catalog) = &header.catalog_version {
+        println!("  Catalog version: {}", catalog);
+    }
+    println!("  Created: {}", header.server_time.as_deref()
+             .map(format_time).unwrap_or_else(|| "unknown".into()));
+    let summary = schema_summary(&header.schema_ddl);
+    if summary.is_empty() {
+        println!("  Schema: empty");
+    } else {
+        println!("  Schema: {}", summary.iter()
+            .map(|(title, n)| format!("{} {}", n, title))
+            .collect::<Vec<_>>()
+            .join(", "));
+    }
+    let total: u64 = blocks.values().map(|s| s.bytes).sum();
+    println!("  Data: {} in {} blocks", HumanBytes(total),
+             blocks.values().map(|s| s.blocks).sum::<u64>());
+    if !blocks.is_empty() {
+        let mut table = Table::new();
+        table.set_format(*table::FORMAT);
+        table.set_titles(Row::new(
+            ["Object", "Blocks", "Size"]
+            .iter().map(|x| table::header_cell(x)).collect()));
+        for (id, stats) in &blocks {
+            table.add_row(Row::new(vec![
+                Cell::new(&header.types.get(id).cloned()
+                    .unwrap_or_else(|| id.to_string())),
+                Cell::new(&stats.blocks.to_string()),
+                Cell::new(&HumanBytes(stats.bytes).to_string()),
+            ]));
+        }
+        table.printstd();
+    }
+    Ok(())
+}
+
+pub fn inspect_dump(cmd: &InspectDump) -> anyhow::Result<()> {
+    if cmd.path.is_dir() {
+        // `dump --all` directory, inspect every database dump in it
+        let mut files = fs::read_dir(&cmd.path)?
+            .map(|item| item.map(|i| i.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.retain(|p| p.extension().map(|e| e == "dump").unwrap_or(false));
+        files.sort();
+        if files.is_empty() {
+            anyhow::bail!("no dumps found in {}", cmd.path.display());
+        }
+        for (idx, path) in files.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            inspect_file(path)
+                .with_context(|| format!("cannot inspect {}",
+                                         path.display()))?;
+        }
+        Ok(())
+    } else {
+        inspect_file(&cmd.path)
+            .with_context(|| format!("cannot inspect {}",
+                                     cmd.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::schema_summary;
+
+    #[test]
+    fn summary() {
+        let ddl = "CREATE MODULE default IF NOT EXISTS;\n\
+                   CREATE SCALAR TYPE default::Status \
+                       EXTENDING enum<Open, Closed>;\n\
+                   CREATE TYPE default::User {\n    \
+                       CREATE PROPERTY name -> std::str;\n\
+                   };\n\
+                   CREATE TYPE default::Issue;\n\
+                   CREATE TYPES_ARE_NOT_A_STATEMENT;\n";
+        assert_eq!(schema_summary(ddl), vec![
+            ("modules", 1),
+            ("scalar types", 1),
+            ("object types", 2),
+        ]);
+    }
+}
@@ -1,16 +1,18 @@
 use async_std::prelude::StreamExt;
+use async_std::stream::from_iter;
 
 use prettytable::{Table, Row, Cell};
 
 use edgedb_derive::Queryable;
 use crate::commands::Options;
 use crate::commands::filter;
+use crate::schema_cache;
 use crate::table;
 use edgedb_client::client::Connection;
 
 
 
-#[derive(Queryable)]
+#[derive(Queryable, Clone)]
 struct Alias {
     name: String,
     expr: String,
@@ -56,7 +58,9 @@ pub async fn list_aliases(cli: &mut Connection, options: &Options,
         {filter}
         ORDER BY .name;
     "###, filter=filter);
-    let mut items = cli.query::<Alias>(&query, &pat).await?;
+    let items = schema_cache::query::<Alias>(cli, options, &query, &pat)
+        .await?;
+    let mut items = from_iter(items.into_iter().map(Ok::<_, anyhow::Error>));
     if !options.command_line || atty::is(atty::Stream::Stdout) {
         let mut table = Table::new();
         table.set_format(*table::FORMAT);
@@ -1,15 +1,17 @@
 use async_std::prelude::StreamExt;
+use async_std::stream::from_iter;
 
 use prettytable::{Table, Row, Cell};
 
 use edgedb_derive::Queryable;
 use crate::commands::Options;
 use crate::commands::filter;
+use crate::schema_cache;
 use edgedb_client::client::Connection;
 use crate::table;
 
 
-#[derive(Queryable)]
+#[derive(Queryable, Clone)]
 struct Cast {
     from_type_name: String,
     to_type_name: String,
@@ -45,7 +47,9 @@ pub async fn list_casts<'x>(cli: &mut Connection, options: &Options,
         {filter}
         ORDER BY .kind THEN .from_type.name THEN .to_type.name;
     "###, filter=filter);
-    let mut items = cli.query::<Cast>(&query, &pat).await?;
+    let items = schema_cache::query::<Cast>(cli, options, &query, &pat)
+        .await?;
+    let mut items = from_iter(items.into_iter().map(Ok::<_, anyhow::Error>));
     if !options.command_line || atty::is(atty::Stream::Stdout) {
         let mut table = Table::new();
         table.set_format(*table::FORMAT);
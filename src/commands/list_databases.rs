@@ -2,17 +2,18 @@ use async_std::prelude::StreamExt;
 use async_std::stream::from_iter;
 
 use edgedb_protocol::value::Value;
+use crate::capabilities::{self, Feature};
 use crate::commands::Options;
 use crate::commands::list;
-use crate::server::version::Version;
 use edgedb_client::client::Connection;
 
 
 pub async fn get_databases(cli: &mut Connection)
     -> Result<Vec<String>, anyhow::Error>
 {
-    let server_ver = &cli.get_version().await?[..];
-    let mut items = if Version(server_ver) < Version("1.0-alpha.6") {
+    let builtin_flag = capabilities::supports(cli,
+        Feature::BuiltinDatabaseFlag).await?;
+    let mut items = if !builtin_flag {
         cli.query(
             "SELECT (SELECT sys::Database FILTER .name != 'edgedb0').name",
             &Value::empty_tuple(),
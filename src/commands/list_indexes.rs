@@ -1,16 +1,18 @@
 use async_std::prelude::StreamExt;
+use async_std::stream::from_iter;
 
 use prettytable::{Table, Row, Cell};
 
 use edgedb_derive::Queryable;
 use crate::commands::Options;
 use crate::commands::filter;
+use crate::schema_cache;
 use edgedb_client::client::Connection;
 use crate::table;
 
 
 
-#[derive(Queryable)]
+#[derive(Queryable, Clone)]
 struct Index {
     expr: String,
     is_implicit: bool,
@@ -69,7 +71,9 @@ pub async fn list_indexes(cli: &mut Connection, options: &Options,
         {filter}
         ORDER BY .subject_name;
     "###, filter=filter);
-    let mut items = cli.query::<Index>(&query, &pat).await?;
+    let items = schema_cache::query::<Index>(cli, options, &query, &pat)
+        .await?;
+    let mut items = from_iter(items.into_iter().map(Ok::<_, anyhow::Error>));
     if !options.command_line || atty::is(atty::Stream::Stdout) {
         let mut table = Table::new();
         table.set_format(*table::FORMAT);
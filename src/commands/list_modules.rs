@@ -1,6 +1,9 @@
+use async_std::stream::from_iter;
+
 use crate::commands::Options;
 use crate::commands::filter;
 use crate::commands::list;
+use crate::schema_cache;
 use edgedb_client::client::Connection;
 
 
@@ -19,7 +22,9 @@ pub async fn list_modules(cli: &mut Connection, options: &Options,
         {filter}
         ORDER BY name
     "###, filter=filter);
-    let items = cli.query(&query, &pat).await?;
+    let items = schema_cache::query::<String>(cli, options, &query, &pat)
+        .await?;
+    let items = from_iter(items.into_iter().map(Ok::<_, anyhow::Error>));
     list::print(items, "List of modules", options).await?;
     Ok(())
 }
@@ -1,16 +1,18 @@
 use async_std::prelude::StreamExt;
+use async_std::stream::from_iter;
 
 use prettytable::{Table, Row, Cell};
 
 use edgedb_derive::Queryable;
 use crate::commands::Options;
 use crate::commands::filter;
+use crate::schema_cache;
 use edgedb_client::client::Connection;
 use crate::table;
 
 
 
-#[derive(Queryable)]
+#[derive(Queryable, Clone)]
 struct TypeRow {
     name: String,
     extending: String,
@@ -44,7 +46,9 @@ pub async fn list_object_types(cli: &mut Connection, options: &Options,
         ORDER BY .name;
     "###, filter=filter.join(") AND ("));
 
-    let mut items = cli.query::<TypeRow>(&query, &pat).await?;
+    let items = schema_cache::query::<TypeRow>(cli, options, &query, &pat)
+        .await?;
+    let mut items = from_iter(items.into_iter().map(Ok::<_, anyhow::Error>));
     if !options.command_line || atty::is(atty::Stream::Stdout) {
         let term_width = term_size::dimensions_stdout()
             .map(|(w, _h)| w).unwrap_or(80);
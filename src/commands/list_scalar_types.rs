@@ -1,16 +1,18 @@
 use async_std::prelude::StreamExt;
+use async_std::stream::from_iter;
 
 use prettytable::{Table, Row, Cell};
 
 use edgedb_derive::Queryable;
 use crate::commands::Options;
 use crate::commands::filter;
+use crate::schema_cache;
 use edgedb_client::client::Connection;
 use crate::table;
 
 
 
-#[derive(Queryable)]
+#[derive(Queryable, Clone)]
 struct ScalarType {
     name: String,
     extending: String,
@@ -55,7 +57,9 @@ pub async fn list_scalar_types<'x>(cli: &mut Connection, options: &Options,
         ORDER BY .name;
     "###, filter=filter);
 
-    let mut items = cli.query::<ScalarType>(&query, &pat).await?;
+    let items = schema_cache::query::<ScalarType>(cli, options, &query, &pat)
+        .await?;
+    let mut items = from_iter(items.into_iter().map(Ok::<_, anyhow::Error>));
     if !options.command_line || atty::is(atty::Stream::Stdout) {
         let term_width = term_size::dimensions_stdout()
             .map(|(w, _h)| w).unwrap_or(80);
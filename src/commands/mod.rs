@@ -1,10 +1,17 @@
 mod exit;
+mod analyze;
+mod anonymize;
+mod clone;
 mod configure;
+mod database;
 mod describe;
 mod dump;
 mod execute;
+mod export;
 mod filter;
-mod helpers;
+mod import;
+mod incremental;
+mod inspect_dump;
 mod list;
 mod list_aliases;
 mod list_casts;
@@ -20,15 +27,21 @@ mod restore;
 mod roles;
 pub mod backslash;
 pub mod cli;
+pub mod helpers;
 pub mod options;
 pub mod parser;
 
+pub use self::analyze::analyze;
+pub use self::clone::{clone, location, Location};
 pub use self::configure::configure;
 pub use self::dump::{dump, dump_all};
 pub use self::describe::describe;
+pub use self::export::export;
+pub use self::import::import;
+pub use self::inspect_dump::inspect_dump;
 pub use self::list_aliases::list_aliases;
 pub use self::list_casts::list_casts;
-pub use self::list_databases::list_databases;
+pub use self::list_databases::{list_databases, get_databases};
 pub use self::list_indexes::list_indexes;
 pub use self::list_modules::list_modules;
 pub use self::list_object_types::list_object_types;
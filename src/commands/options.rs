@@ -1,9 +1,12 @@
 use crate::print::style::Styler;
 use crate::connect::Connector;
+use crate::schema_cache::SchemaCache;
 
 
 pub struct Options {
     pub command_line: bool,
     pub styler: Option<Styler>,
     pub conn_params: Connector,
+    /// Cache of introspection queries (REPL and `server-proxy` only)
+    pub schema_cache: Option<SchemaCache>,
 }
@@ -1,6 +1,9 @@
 use clap::{Clap, AppSettings, ArgSettings, ValueHint};
 use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::print::timezone;
+use crate::prompt_template;
 use crate::repl;
 
 
@@ -8,6 +11,10 @@ use crate::repl;
 pub enum Common {
     /// Create a new database
     CreateDatabase(CreateDatabase),
+    /// Delete a database along with its data
+    DropDatabase(DropDatabase),
+    /// Delete all schema objects and data in the current database
+    WipeDatabase(WipeDatabase),
     /// Display list of databases in the server instance
     ListDatabases,
     /// List ports exposed by EdgeDB. Works on EdgeDB <= 1-alpha7
@@ -37,10 +44,16 @@ pub enum Common {
     Configure(Configure),
     /// Describe a named database object
     Describe(Describe),
+    /// Show the plan of a query with execution statistics
+    Analyze(Analyze),
     /// Create a database backup
     Dump(Dump),
     /// Restore a database backup from file
     Restore(Restore),
+    /// Import objects of a type from a CSV or JSON file
+    Import(Import),
+    /// Export all objects of a type into a CSV or JSON file
+    Export(Export),
     /// Create a migration script
     CreateMigration(CreateMigration),
     /// Bring current database to the latest or a specified revision
@@ -49,6 +62,12 @@ pub enum Common {
     ShowStatus(ShowStatus),
     /// Show all migration versions
     MigrationLog(MigrationLog),
+    /// Check that migration files were not modified or reordered
+    ///
+    /// Recomputes hashes of the migration files and their parent chain, and
+    /// compares them with the migrations recorded in the database. Exits
+    /// with status 3 if any file was modified, reordered or is missing.
+    VerifyMigrations(VerifyMigrations),
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -69,6 +88,9 @@ pub enum BackslashCmd {
     Connect(Connect),
     Edit(Edit),
     Set(SetCommand),
+    Save(Save),
+    Run(Run),
+    Diff(Diff),
     Exit,
 }
 
@@ -97,6 +119,25 @@ pub enum Setting {
     HistorySize(SettingUsize),
     /// Print statistics on each query
     PrintStats(PrintStats),
+    /// Print query time after each statement (same as `print-stats query`)
+    Timing(SettingBool),
+    /// Set prompt template, e.g. '{user}@{instance}/{database}{tx}> '
+    Prompt(SettingPrompt),
+    /// Show PNG/JPEG images inline (kitty and iTerm2 terminals only)
+    RenderImages(SettingBool),
+    /// Time zone to display datetime values in: local, utc or a name
+    DisplayTz(SettingDisplayTz),
+    /// Set maximum nesting depth of printed values, 0 to disable
+    MaxDepth(SettingUsize),
+    /// Set approximate maximum size of output in bytes, 0 to disable
+    MaxOutput(SettingUsize),
+    /// Print number of returned rows and time after each query
+    Summary(SettingBool),
+    /// Set global to an EdgeQL expression for the rest of the session
+    Global(SettingGlobal),
+    /// Record statements running longer than this (e.g. 200ms) into the
+    /// slow query log, 0 to disable
+    LogSlowQueries(SettingDuration),
 }
 
 #[derive(Clap, Clone, Debug, Default)]
@@ -119,6 +160,45 @@ pub struct Limit {
     pub limit: Option<usize>,
 }
 
+#[derive(Clap, Clone, Debug, Default)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SettingDisplayTz {
+    /// `local`, `utc` or a name from the time zone database,
+    /// e.g. `Europe/London`
+    #[clap(validator(timezone::validate))]
+    pub value: Option<String>,
+}
+
+#[derive(Clap, Clone, Debug, Default)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SettingPrompt {
+    /// Placeholders: {database}, {instance}, {user}, {tx}, {status}, {ro},
+    /// and colors: {red}, {green}, {yellow}, {blue}, {magenta}, {cyan},
+    /// {gray}, {bold}, {reset}
+    #[clap(validator(prompt_template::validate))]
+    pub template: Option<String>,
+}
+
+#[derive(Clap, Clone, Debug, Default)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SettingGlobal {
+    /// Name of the global, all globals set are shown if omitted
+    pub name: Option<String>,
+    /// EdgeQL expression, e.g. "<str>'alice'"
+    #[clap(requires="name")]
+    pub value: Option<String>,
+    /// Reset the global (or all the globals set) to the default
+    #[clap(long, conflicts_with="value")]
+    pub reset: bool,
+}
+
+#[derive(Clap, Clone, Debug, Default)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SettingDuration {
+    #[clap(parse(try_from_str=humantime::parse_duration))]
+    pub value: Option<Duration>,
+}
+
 #[derive(Clap, Clone, Debug, Default)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct SettingUsize {
@@ -130,7 +210,31 @@ pub struct SettingUsize {
 #[clap(setting=AppSettings::TrailingVarArg)]
 #[clap(setting=AppSettings::AllowLeadingHyphen)]
 pub struct Edit {
-    pub entry: Option<isize>,
+    /// History entry number (negative counts from the end) or a file name
+    pub entry: Option<String>,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Save {
+    /// Name of the snippet to save the last query as
+    pub name: String,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Run {
+    /// Name of the snippet saved by `\save`
+    pub name: String,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Diff {
+    /// File with the result saved using `--json` or
+    /// `--output-format=json-elements`
+    #[clap(value_hint=ValueHint::FilePath)]
+    pub file: PathBuf,
 }
 
 #[derive(Clap, Clone, Debug, Default)]
@@ -154,7 +258,10 @@ pub struct PrintStats {
 #[derive(Clap, Clone, Debug)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct Connect {
-    pub database_name: String,
+    /// Database to connect to (the current one by default)
+    pub database_name: Option<String>,
+    /// Instance to connect to (the current one by default)
+    pub instance: Option<String>,
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -163,6 +270,23 @@ pub struct CreateDatabase {
     pub database_name: String,
 }
 
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct DropDatabase {
+    pub database_name: String,
+    /// Do not ask for a confirmation
+    #[clap(long)]
+    pub force: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct WipeDatabase {
+    /// Do not ask for a confirmation
+    #[clap(long)]
+    pub force: bool,
+}
+
 #[derive(Clap, Clone, Debug)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct ListAliases {
@@ -229,11 +353,96 @@ pub struct Describe {
     pub verbose: bool,
 }
 
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+#[clap(setting=AppSettings::TrailingVarArg)]
+pub struct Analyze {
+    /// Query to analyze
+    #[clap(required=true)]
+    pub query: Vec<String>,
+    /// Print the plan as returned by the server (JSON)
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Import {
+    /// Object type to insert rows into (`default` module if not qualified)
+    #[clap(long="table", value_name="TYPE")]
+    pub type_name: String,
+    /// Format of the input file. CSV files must have a header row with
+    /// property names, JSON files contain an array or a stream of objects
+    #[clap(long, possible_values=&["csv", "json"][..], default_value="csv")]
+    pub format: DataFormat,
+    /// Number of rows inserted by a single statement
+    #[clap(long, default_value="100")]
+    pub batch_size: usize,
+    /// Write rejected rows into this file (as JSON lines) instead of
+    /// aborting the import on the first failed batch
+    #[clap(long, value_hint=ValueHint::FilePath)]
+    pub error_log: Option<PathBuf>,
+    /// File to import
+    #[clap(value_hint=ValueHint::FilePath)]
+    pub file: PathBuf,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Export {
+    /// Object type to export (`default` module if not qualified)
+    #[clap(long="type", value_name="TYPE")]
+    pub type_name: String,
+    /// Format of the output file. JSON output is an array of objects,
+    /// CSV output has a header row with property names
+    #[clap(long, possible_values=&["csv", "json"][..], default_value="json")]
+    pub format: DataFormat,
+    /// Number of objects fetched by a single query
+    #[clap(long, default_value="1000")]
+    pub page_size: usize,
+    /// Export all objects regardless of access policies
+    /// (command line only, requires a superuser)
+    #[clap(long)]
+    pub bypass_access_policies: bool,
+    /// File to write objects to
+    #[clap(long, value_hint=ValueHint::FilePath)]
+    pub out: PathBuf,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DumpFormat {
     Dir,
 }
 
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct InspectDump {
+    /// Path to the dump file (or to the directory created by `dump --all`)
+    #[clap(value_hint=ValueHint::AnyPath)]
+    pub path: PathBuf,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct CloneDatabase {
+    /// Source database as `INSTANCE/DATABASE`. By default, the database
+    /// specified by the connection options is copied
+    #[clap(long, value_name="INSTANCE/DATABASE")]
+    pub from: Option<String>,
+
+    /// Target database as `INSTANCE/DATABASE`, or `/DATABASE` for
+    /// a database of the instance specified by the connection options.
+    /// Database is created if it doesn't exist, and must be empty otherwise
+    #[clap(long, value_name="INSTANCE/DATABASE")]
+    pub to: String,
+}
+
 #[derive(Clap, Clone, Debug)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct Dump {
@@ -250,6 +459,40 @@ pub struct Dump {
     /// For `--all` only `--format=dir` is required.
     #[clap(long, possible_values=&["dir"][..])]
     pub format: Option<DumpFormat>,
+
+    /// Dump only objects changed since the specified previous dump (path to
+    /// the dump or its manifest) or timestamp. Objects are compared by the
+    /// property specified in `--updated-at-property`
+    #[clap(long, conflicts_with="all")]
+    pub since: Option<String>,
+
+    /// Property of the object types which is updated on every object
+    /// modification (used for `--since`)
+    #[clap(long, default_value="updated_at")]
+    pub updated_at_property: String,
+
+    /// Dump only the schema, skipping data of the objects
+    #[clap(long, conflicts_with="since")]
+    pub schema_only: bool,
+
+    /// Write `<path>.manifest.json` with the server time of the dump, so
+    /// the dump can be passed to `--since` later. Incremental dumps
+    /// always have a manifest
+    #[clap(long, conflicts_with_all=&["all", "schema_only"])]
+    pub manifest: bool,
+
+    /// Scrub property values while dumping, using rules from the TOML
+    /// file: a table per object type, e.g. `["default::User"]`, mapping
+    /// property names to `"null"`, `"hash"` (salted, so that equal values
+    /// stay equal within the dump) or `{ fake = "user{n}@example.com" }`
+    #[clap(long, value_name="RULES", value_hint=ValueHint::FilePath,
+           conflicts_with_all=&["since", "schema_only"])]
+    pub anonymize: Option<PathBuf>,
+
+    /// Dump all objects regardless of access policies
+    /// (command line only, requires a superuser)
+    #[clap(long)]
+    pub bypass_access_policies: bool,
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -269,7 +512,42 @@ pub struct Restore {
     #[clap(long)]
     pub allow_non_empty: bool,
 
-    /// Verbose output
+    /// Wipe all schema objects and data of a non-empty target database
+    /// before restoring
+    #[clap(long, conflicts_with_all=&[
+        "allow_non_empty", "ignore_existing", "apply_incremental",
+    ])]
+    pub force_clean: bool,
+
+    /// Skip restoring into databases which are not empty, instead of
+    /// failing
+    #[clap(long, conflicts_with_all=&["allow_non_empty", "apply_incremental"])]
+    pub ignore_existing: bool,
+
+    /// Apply an incremental dump (made with `dump --since`) on top of the
+    /// existing data. Multiple dumps should be applied in order. Objects
+    /// deleted since the previous dump are not removed
+    #[clap(long, conflicts_with="all")]
+    pub apply_incremental: bool,
+
+    /// Restore only the schema, skipping data of the dump
+    #[clap(long, conflicts_with="apply_incremental")]
+    pub schema_only: bool,
+
+    /// Replace data of a database which has the same schema as the dump.
+    /// Existing data is wiped before restoring
+    #[clap(long, conflicts_with_all=&[
+        "all", "schema_only", "apply_incremental",
+        "allow_non_empty", "force_clean", "ignore_existing",
+    ])]
+    pub data_only: bool,
+
+    /// Do not ask for confirmation before deleting existing data with
+    /// `--force-clean` or `--data-only`
+    #[clap(long)]
+    pub non_interactive: bool,
+
+    /// Verbose output, including progress of every data block restored
     #[clap(long, short='v')]
     pub verbose: bool,
 }
@@ -290,6 +568,8 @@ pub enum ConfigureCommand {
     Reset(ConfigureReset),
     /// Set scalar configuration value
     Set(ConfigureSet),
+    /// Show current configuration
+    Show,
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -472,9 +752,10 @@ pub struct PortParameter {
 
 #[derive(Clap, Clone, Debug)]
 pub struct MigrationConfig {
-    /// Directory where *.edgeql files are located
-    #[clap(long, default_value="./dbschema", value_hint=ValueHint::DirPath)]
-    pub schema_dir: PathBuf,
+    /// Directory where *.esdl files and `migrations` are located
+    /// (by default `schema-dir` of `edgedb.toml` or `./dbschema`)
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub schema_dir: Option<PathBuf>,
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -499,6 +780,95 @@ pub struct CreateMigration {
     pub debug_print_queries: bool,
 }
 
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Watch {
+    #[clap(flatten)]
+    pub cfg: MigrationConfig,
+    /// Apply the most probable unsafe changes without asking
+    #[clap(long)]
+    pub allow_unsafe: bool,
+    /// Wait for files to stay unchanged for this time before applying
+    /// changes
+    #[clap(long, default_value="300ms",
+           parse(try_from_str=humantime::parse_duration))]
+    pub debounce: Duration,
+    /// Only print errors
+    #[clap(long, short='q')]
+    pub quiet: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Check {
+    #[clap(flatten)]
+    pub cfg: MigrationConfig,
+    /// Also compile the schema and pending migrations on the server, in
+    /// a transaction that is rolled back
+    #[clap(long)]
+    pub connect: bool,
+    /// Only print errors
+    #[clap(long, short='q')]
+    pub quiet: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Lint {
+    #[clap(flatten)]
+    pub cfg: MigrationConfig,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Schema {
+    #[clap(subcommand)]
+    pub command: SchemaCommand,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub enum SchemaCommand {
+    /// Show differences between schemas of two databases or schema files
+    ///
+    /// Each of the schemas is either `INSTANCE`, `INSTANCE/DATABASE`,
+    /// `/DATABASE` (for the instance specified by the connection options),
+    /// an `.esdl` file, or a directory containing `.esdl` files. Exits with
+    /// status 1 if schemas differ.
+    Diff(SchemaDiff),
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SchemaDiff {
+    /// Schema to compare from
+    #[clap(value_name="SCHEMA")]
+    pub from: String,
+    /// Schema to compare to
+    #[clap(value_name="SCHEMA")]
+    pub to: String,
+    /// Print DDL statements migrating the first schema to the second one,
+    /// instead of the list of changed objects
+    #[clap(long)]
+    pub ddl: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct EditMigration {
+    #[clap(flatten)]
+    pub cfg: MigrationConfig,
+
+    /// Number of the migration file to edit (the last one by default)
+    pub number: Option<u64>,
+
+    /// Do not open an editor, only fix names of the migrations (use this
+    /// after editing files manually). Checks all the files unless a number
+    /// is specified.
+    #[clap(long)]
+    pub no_editor: bool,
+}
+
 #[derive(Clap, Clone, Debug)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct Migrate {
@@ -518,6 +888,12 @@ pub struct Migrate {
     /// revisions are applied on top.
     #[clap(long)]
     pub to_revision: Option<String>,
+
+    /// Apply the schema from the schema directory directly, without
+    /// creating migration files (changes can be turned into a migration
+    /// file later using `create-migration`)
+    #[clap(long, conflicts_with="to_revision")]
+    pub dev_mode: bool,
 }
 
 #[derive(Clap, Clone, Debug)]
@@ -557,6 +933,26 @@ pub struct MigrationLog {
     pub limit: Option<usize>,
 }
 
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct VerifyMigrations {
+    #[clap(flatten)]
+    pub cfg: MigrationConfig,
+
+    /// Do not print any messages, only indicate success by exit status
+    #[clap(long)]
+    pub quiet: bool,
+}
+
+impl Common {
+    /// Whether the command may change the schema of the current database
+    pub fn modifies_schema(&self) -> bool {
+        use Common::*;
+        matches!(self,
+            WipeDatabase(_) | Restore(_) | CreateMigration(_) | Migrate(_))
+    }
+}
+
 impl Setting {
     pub fn name(&self) -> &'static str {
         use Setting::*;
@@ -569,6 +965,15 @@ impl Setting {
             OutputMode(_) => "output-mode",
             ExpandStrings(_) => "expand-strings",
             PrintStats(_) => "print-stats",
+            Timing(_) => "timing",
+            Prompt(_) => "prompt",
+            RenderImages(_) => "render-images",
+            DisplayTz(_) => "display-tz",
+            MaxDepth(_) => "max-depth",
+            MaxOutput(_) => "max-output",
+            Summary(_) => "summary",
+            Global(_) => "global",
+            LogSlowQueries(_) => "log-slow-queries",
         }
     }
     pub fn is_show(&self) -> bool {
@@ -583,6 +988,15 @@ impl Setting {
             OutputMode(a) => a.mode.is_none(),
             ExpandStrings(a) => a.value.is_none(),
             PrintStats(a) => a.value.is_none(),
+            Timing(a) => a.value.is_none(),
+            Prompt(a) => a.template.is_none(),
+            RenderImages(a) => a.value.is_none(),
+            DisplayTz(a) => a.value.is_none(),
+            MaxDepth(a) => a.value.is_none(),
+            MaxOutput(a) => a.value.is_none(),
+            Summary(a) => a.value.is_none(),
+            Global(a) => a.value.is_none() && !a.reset,
+            LogSlowQueries(a) => a.value.is_none(),
         }
     }
 }
@@ -597,6 +1011,17 @@ impl SettingBool {
     }
 }
 
+impl std::str::FromStr for DataFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<DataFormat, anyhow::Error> {
+        match s {
+            "csv" => Ok(DataFormat::Csv),
+            "json" => Ok(DataFormat::Json),
+            _ => Err(anyhow::anyhow!("unsupported data format {:?}", s)),
+        }
+    }
+}
+
 impl std::str::FromStr for DumpFormat {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<DumpFormat, anyhow::Error> {
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::TryInto;
 use std::ffi::OsString;
+use std::future::Future;
 use std::slice;
 use std::str;
 use std::time::{Instant, Duration};
@@ -12,6 +13,7 @@ use async_std::io::{self, Read, prelude::ReadExt};
 use async_std::future::{timeout, pending};
 use async_std::prelude::{FutureExt, StreamExt};
 use bytes::{Bytes, BytesMut, BufMut};
+use indicatif::HumanBytes;
 
 use edgeql_parser::helpers::quote_name;
 use edgedb_protocol::client_message::{ClientMessage, Restore, RestoreBlock};
@@ -20,17 +22,28 @@ use edgedb_protocol::value::Value;
 use edgedb_protocol ::server_message::{ErrorResponse};
 use edgeql_parser::preparser::{is_empty};
 
-use crate::commands::Options;
+use crate::capabilities::{self, Feature};
+use crate::commands::{ExitCode, Options};
+use crate::commands::incremental;
+use crate::commands::inspect_dump;
+use crate::commands::list_databases::get_databases;
 use crate::commands::parser::{Restore as RestoreCmd};
 use edgedb_client::client::{Connection, Writer};
 use edgedb_client::reader::Reader;
+use crate::format;
+use crate::hint::HintExt;
+use crate::interrupt::{Interrupt, Interrupted};
+use crate::migrations;
+use crate::question;
 use crate::statement::{ReadStatement, EndOfFile};
 
-type Input = Box<dyn Read + Unpin + Send>;
+pub(crate) type Input = Box<dyn Read + Unpin + Send>;
 
+pub(crate) const DUMP_MAGIC: &[u8] =
+    b"\xFF\xD8\x00\x00\xD8EDGEDB\x00DUMP\x00";
 const MAX_SUPPORTED_DUMP_VER: i64 = 1;
 const SCHEMA_ERROR: u32 = 0x_04_04_00_00;
-const DUPLICATE_DATABASE_DEFINITION_ERROR: u32 = 0x_04_05_02_05;
+pub const DUPLICATE_DATABASE_DEFINITION_ERROR: u32 = 0x_04_05_02_05;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PacketType {
@@ -39,7 +52,7 @@ pub enum PacketType {
 }
 
 
-async fn read_packet(input: &mut Input, expected: PacketType)
+pub(crate) async fn read_packet(input: &mut Input, expected: PacketType)
     -> Result<Option<Bytes>, anyhow::Error>
 {
     let mut buf = [0u8; 1+20+4];
@@ -83,7 +96,7 @@ async fn read_packet(input: &mut Input, expected: PacketType)
 }
 
 
-async fn is_empty_db(cli: &mut Connection) -> Result<bool, anyhow::Error> {
+pub async fn is_empty_db(cli: &mut Connection) -> Result<bool, anyhow::Error> {
     let mut query = cli.query::<i64>(r###"SELECT
             count(
                 schema::Module
@@ -102,32 +115,98 @@ async fn is_empty_db(cli: &mut Connection) -> Result<bool, anyhow::Error> {
     return Ok(non_empty);
 }
 
+/// Runs the future, stopping on signal if the handler is installed
+///
+/// Interrupted restore is rolled back by the server when the connection is
+/// closed.
+async fn interruptible<F, T>(interrupt: Option<&Interrupt>, f: F)
+    -> anyhow::Result<T>
+    where F: Future<Output=anyhow::Result<T>>,
+{
+    match interrupt {
+        Some(interrupt) => interrupt.run(f).await,
+        None => f.await,
+    }
+}
+
 pub async fn restore<'x>(cli: &mut Connection, options: &Options,
     params: &RestoreCmd)
     -> Result<(), anyhow::Error>
 {
+    // REPL has its own handling of Ctrl+C
+    let interrupt = if options.command_line {
+        Some(Interrupt::new()?)
+    } else {
+        None
+    };
+    let interrupt = interrupt.as_ref();
     if params.all {
-        restore_all(cli, options, params).await
+        restore_all(cli, options, params, interrupt).await
+    } else if params.apply_incremental {
+        interruptible(interrupt, incremental::apply(cli, options,
+            params.path.as_ref(), params.verbose)).await
     } else {
-        restore_db(cli, options, params).await
+        interruptible(interrupt, restore_db(cli, options, params)).await
     }
 }
 
+/// Asks for confirmation before deleting `what` of the current database
+async fn confirm_delete(cli: &mut Connection, what: &str, note: &str)
+    -> anyhow::Result<()>
+{
+    let database: String = cli.query_row(
+        "SELECT sys::get_current_database()",
+        &Value::empty_tuple(),
+    ).await?;
+    let q = question::Confirm::new_dangerous(format!(
+        "All {} of the database {:?} will be deleted. {}Continue?",
+        what, database, note));
+    if !q.ask()? {
+        eprintln!("Canceled");
+        return Err(ExitCode::new(1).into());
+    }
+    Ok(())
+}
+
 async fn restore_db<'x>(cli: &mut Connection, options: &Options,
     params: &RestoreCmd)
     -> Result<(), anyhow::Error>
 {
     use PacketType::*;
     let RestoreCmd {
-        allow_non_empty, path: ref filename,
-        all: _, verbose: _,
+        allow_non_empty, force_clean, ignore_existing, verbose,
+        schema_only, data_only, non_interactive,
+        path: ref filename,
+        all: _, apply_incremental: _,
     } = *params;
-    if !allow_non_empty {
+    if !allow_non_empty && !data_only {
         if is_empty_db(cli).await.context("Error checking DB emptyness")? {
-            if options.command_line {
+            if force_clean {
+                if !non_interactive {
+                    confirm_delete(cli, "schema objects and data", "").await?;
+                }
+                if verbose {
+                    eprintln!("Wiping the database before restoring {}",
+                              filename.display());
+                }
+                if capabilities::supports(cli, Feature::ResetSchema).await? {
+                    // unlike wiping, this also drops migration history,
+                    // which would conflict with the one in the dump
+                    cli.execute("RESET SCHEMA TO initial").await
+                        .context("cannot reset schema of the database")?;
+                } else {
+                    migrations::wipe(cli).await
+                        .context("cannot wipe the database")?;
+                }
+            } else if ignore_existing {
+                eprintln!("Skipping {}: the database is not empty",
+                          filename.display());
+                return Ok(());
+            } else if options.command_line {
                 return Err(anyhow::anyhow!("\
                     cannot restore: the database is not empty; \
-                    consider using the --allow-non-empty option"));
+                    consider using the --force-clean, --ignore-existing \
+                    or --allow-non-empty option"));
             } else {
                 return Err(anyhow::anyhow!(
                     "cannot restore: the database is not empty"));
@@ -149,7 +228,7 @@ async fn restore_db<'x>(cli: &mut Connection, options: &Options,
     input.read_exact(&mut buf).await
         .context("Cannot read header")
         .with_context(file_ctx)?;
-    if &buf[..17] != b"\xFF\xD8\x00\x00\xD8EDGEDB\x00DUMP\x00" {
+    if &buf[..17] != DUMP_MAGIC {
         Err(anyhow::anyhow!("File is not an edgedb dump"))
         .with_context(file_ctx)?
     }
@@ -161,6 +240,25 @@ async fn restore_db<'x>(cli: &mut Connection, options: &Options,
     let header = read_packet(&mut input, Header).await.with_context(file_ctx)?
         .ok_or_else(|| anyhow::anyhow!("Dump is empty"))
                        .with_context(file_ctx)?;
+    if data_only {
+        let ddl = inspect_dump::schema_ddl(&header)
+            .context("cannot parse dump header")
+            .with_context(file_ctx)?;
+        capabilities::require(cli, Feature::ResetSchema).await
+            .context("cannot restore data only")?;
+        check_same_schema(cli, &ddl).await?;
+        if !non_interactive {
+            confirm_delete(cli, "existing data",
+                "If restore fails, the database is left empty. ").await?;
+        }
+        if verbose {
+            eprintln!("Deleting data before restoring {}", filename.display());
+        }
+        // server restores data only along with the schema, so the same
+        // schema (including migration history) is restored from scratch
+        cli.execute("RESET SCHEMA TO initial").await
+            .context("cannot reset schema of the database")?;
+    }
     let start_headers = Instant::now();
     let mut seq = cli.start_sequence().await?;
     seq.send_messages(&[
@@ -176,6 +274,11 @@ async fn restore_db<'x>(cli: &mut Connection, options: &Options,
             ServerMessage::RestoreReady(_) => {
                 log::info!(target: "edgedb::restore",
                     "Schema applied in {:?}", start_headers.elapsed());
+                if verbose {
+                    eprintln!("Schema of {} applied in {}",
+                        filename.display(),
+                        format::duration(start_headers.elapsed()));
+                }
                 break;
             }
             ServerMessage::ErrorResponse(err) => {
@@ -189,8 +292,12 @@ async fn restore_db<'x>(cli: &mut Connection, options: &Options,
             }
         }
     }
+    if schema_only {
+        // data blocks are not even read
+        input = Box::new(io::empty());
+    }
     let result = send_blocks(&mut seq.writer, &mut input,
-                             filename.as_ref())
+                             filename.as_ref(), verbose)
         .race(wait_response(&mut seq.reader, start_headers))
         .await;
     if let Err(..) = result {
@@ -201,25 +308,80 @@ async fn restore_db<'x>(cli: &mut Connection, options: &Options,
     result
 }
 
+/// Checks that database has the same schema as the dump (for `--data-only`)
+///
+/// Schemas are compared by the migrations applied, or by the DDL for the
+/// schema which isn't managed by migrations.
+async fn check_same_schema(cli: &mut Connection, dump_ddl: &str)
+    -> anyhow::Result<()>
+{
+    let dump_migrations = migration_names(dump_ddl);
+    let same = if dump_migrations.is_empty() {
+        let ddl = cli.query_row::<String>("DESCRIBE SCHEMA AS DDL",
+            &Value::empty_tuple()).await?;
+        ddl.trim() == dump_ddl.trim()
+    } else {
+        let mut query = cli.query::<String>(
+            "SELECT schema::Migration.name", &Value::empty_tuple()).await?;
+        let mut migrations = BTreeSet::new();
+        while let Some(name) = query.next().await.transpose()? {
+            migrations.insert(name);
+        }
+        migrations == dump_migrations
+    };
+    if !same {
+        return Err(anyhow::anyhow!("cannot restore data: \
+                schema of the database differs from the dump"))
+            .hint("restore without `--data-only` and with `--force-clean` \
+                   to replace the schema as well")
+            .map_err(Into::into);
+    }
+    Ok(())
+}
+
+/// Names of the migrations in the schema DDL of the dump
+fn migration_names(ddl: &str) -> BTreeSet<String> {
+    ddl.lines()
+        .filter_map(|line| line.strip_prefix("CREATE MIGRATION "))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .map(|name| name.trim_end_matches('{').to_string())
+        .collect()
+}
+
 async fn send_blocks(writer: &mut Writer<'_>, input: &mut Input,
-    filename: &Path)
+    filename: &Path, verbose: bool)
     -> Result<(), anyhow::Error>
 {
     use PacketType::*;
 
     let start_blocks = Instant::now();
+    let mut blocks = 0;
+    let mut total = 0;
     while
         let Some(data) = read_packet(input, Block).await
             .with_context(|| format!("Failed to read dump {}",
                                      filename.display()))?
     {
+        let size = data.len() as u64;
+        blocks += 1;
+        total += size;
         writer.send_messages(&[
             ClientMessage::RestoreBlock(RestoreBlock { data })
         ]).await?;
+        if verbose {
+            eprintln!("Block {} sent: {} ({} total)",
+                blocks, HumanBytes(size), HumanBytes(total));
+        }
     }
     writer.send_messages(&[ClientMessage::RestoreEof]).await?;
     log::info!(target: "edgedb::restore",
         "Blocks sent in {:?}", start_blocks.elapsed());
+    if verbose {
+        eprintln!("All {} blocks of {} sent in {}, waiting for the server \
+            to complete restore",
+            blocks, filename.display(),
+            format::duration(start_blocks.elapsed()));
+    }
 
     // This future should be canceled by wait_response() receiving
     // CommandComplete
@@ -231,7 +393,7 @@ async fn send_blocks(writer: &mut Writer<'_>, input: &mut Input,
     }
 }
 
-async fn wait_response(reader: &mut Reader<'_>, start: Instant)
+pub async fn wait_response(reader: &mut Reader<'_>, start: Instant)
     -> Result<(), anyhow::Error>
 {
     loop {
@@ -262,6 +424,29 @@ fn path_to_database_name(path: &Path) -> anyhow::Result<String> {
     Ok(decoded)
 }
 
+/// Returns those of `databases` which exist and are not empty
+async fn non_empty_databases(cli: &mut Connection, options: &Options,
+    databases: &[String])
+    -> anyhow::Result<Vec<String>>
+{
+    let existing = get_databases(cli).await?;
+    let mut conn_params = options.conn_params.clone();
+    let mut result = Vec::new();
+    for database in databases {
+        if !existing.contains(database) {
+            continue;
+        }
+        conn_params.modify(|p| { p.database(database); });
+        let mut db_conn = conn_params.connect().await
+            .with_context(|| format!("cannot connect to database {:?}",
+                                     database))?;
+        if is_empty_db(&mut db_conn).await? {
+            result.push(database.clone());
+        }
+    }
+    Ok(result)
+}
+
 async fn apply_init(cli: &mut Connection, path: &Path) -> anyhow::Result<()> {
     let mut input = fs::File::open(path).await?;
     let mut inbuf = BytesMut::with_capacity(8192);
@@ -284,10 +469,40 @@ async fn apply_init(cli: &mut Connection, path: &Path) -> anyhow::Result<()> {
 }
 
 pub async fn restore_all<'x>(cli: &mut Connection, options: &Options,
-    params: &RestoreCmd)
+    params: &RestoreCmd, interrupt: Option<&Interrupt>)
     -> anyhow::Result<()>
 {
     let dir = &params.path;
+    let dump_ext = OsString::from("dump");
+    let mut dumps = Vec::new();
+    let mut dir_list = fs::read_dir(&dir).await?;
+    while let Some(entry) = dir_list.next().await.transpose()? {
+        let path = entry.path();
+        if path.extension() == Some(&dump_ext) {
+            let database = path_to_database_name(&path)?;
+            dumps.push((database, path));
+        }
+    }
+    dumps.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if !params.allow_non_empty && !params.force_clean &&
+        !params.ignore_existing
+    {
+        // check upfront, so restore doesn't fail after some of the
+        // databases are already restored
+        let databases = dumps.iter().map(|(db, _)| db.clone())
+            .collect::<Vec<_>>();
+        let non_empty = non_empty_databases(cli, options, &databases).await
+            .context("error checking existing databases")?;
+        if !non_empty.is_empty() {
+            anyhow::bail!("cannot restore: databases {} are not empty; \
+                consider using the --force-clean, --ignore-existing \
+                or --allow-non-empty option",
+                non_empty.iter().map(|db| format!("{:?}", db))
+                    .collect::<Vec<_>>().join(", "));
+        }
+    }
+
     let filename = dir.join("init.edgeql");
     apply_init(cli, filename.as_ref()).await
         .with_context(|| format!("error applying init file {:?}", filename))?;
@@ -295,15 +510,11 @@ pub async fn restore_all<'x>(cli: &mut Connection, options: &Options,
     let mut conn_params = options.conn_params.clone();
     let mut params = params.clone();
 
-    let dump_ext = OsString::from("dump");
-    let mut dir_list = fs::read_dir(&dir).await?;
-    while let Some(entry) = dir_list.next().await.transpose()? {
-        let path = entry.path();
-        if path.extension() != Some(&dump_ext) {
-            continue;
-        }
-        let database = path_to_database_name(&path)?;
+    for (database, path) in dumps {
         log::debug!("Restoring database {:?}", database);
+        if params.verbose {
+            eprintln!("Restoring database {:?}", database);
+        }
         let create_db = format!("CREATE DATABASE {}", quote_name(&database));
         let db_error = match cli.execute(create_db).await {
             Ok(_) => None,
@@ -339,8 +550,26 @@ pub async fn restore_all<'x>(cli: &mut Connection, options: &Options,
             }
         };
         params.path = path.into();
-        restore_db(&mut db_conn, options, &params).await
-            .with_context(|| format!("restoring database {:?}", database))?;
+        let result = interruptible(interrupt,
+            restore_db(&mut db_conn, options, &params)).await;
+        if let Err(e) = result {
+            if e.is::<Interrupted>() && db_error.is_none() {
+                // database was created by us, so don't leave it half-done
+                drop(db_conn);
+                drop_database(cli, &database).await;
+            }
+            return Err(e)
+                .with_context(|| format!("restoring database {:?}", database));
+        }
     }
     Ok(())
 }
+
+pub async fn drop_database(cli: &mut Connection, database: &str) {
+    log::info!("Dropping partially restored database {:?}", database);
+    let drop_db = format!("DROP DATABASE {}", quote_name(database));
+    if let Err(e) = cli.execute(drop_db).await {
+        eprintln!("Cannot drop partially restored database {:?}: {:#}",
+                  database, e);
+    }
+}
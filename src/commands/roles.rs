@@ -1,12 +1,17 @@
 use edgeql_parser::helpers::{quote_string, quote_name};
 use crate::commands::Options;
+use crate::credentials;
 use edgedb_client::client::Connection;
 use crate::options::{RoleParams};
 use crate::print;
+use crate::server::reset_password::{read_credentials, write_credentials};
 
 
-fn process_params(options: &RoleParams) -> Result<Vec<String>, anyhow::Error> {
+fn process_params(options: &RoleParams)
+    -> Result<(Vec<String>, Option<String>), anyhow::Error>
+{
     let mut result = Vec::new();
+    let mut new_password = None;
     if options.password || options.password_from_stdin {
         let password = if options.password_from_stdin {
             rpassword::read_password()?
@@ -26,25 +31,45 @@ fn process_params(options: &RoleParams) -> Result<Vec<String>, anyhow::Error> {
             }
         };
         result.push(format!("SET password := {}", quote_string(&password)));
+        new_password = Some(password);
     }
-    Ok(result)
+    Ok((result, new_password))
 }
 
-pub async fn create_superuser(cli: &mut Connection, _options: &Options,
-    role: &RoleParams)
+/// Updates stored credentials of the local instance if they refer to `role`
+fn update_credentials(instance: &str, role: &str, password: String)
+    -> Result<(), anyhow::Error>
+{
+    let credentials_file = credentials::path(instance)?;
+    if !credentials_file.exists() {
+        return Ok(());
+    }
+    let mut creds = read_credentials(&credentials_file)?;
+    if creds.user != role {
+        return Ok(());
+    }
+    creds.password = Some(password);
+    write_credentials(&credentials_file, &creds)?;
+    eprintln!("Password for instance {:?} is updated in {}",
+        instance, credentials_file.display());
+    Ok(())
+}
+
+async fn create_role(cli: &mut Connection, kind: &str, role: &RoleParams)
     -> Result<(), anyhow::Error>
 {
-    let params = process_params(role)?;
+    let (params, _) = process_params(role)?;
     if params.is_empty() {
         print::completion(&cli.execute(
-            &format!("CREATE SUPERUSER ROLE {}", quote_name(&role.role))
+            &format!("CREATE {} {}", kind, quote_name(&role.role))
         ).await?);
     } else {
         print::completion(&cli.execute(
             &format!(r###"
-                CREATE SUPERUSER ROLE {name} {{
+                CREATE {kind} {name} {{
                     {params}
                 }}"###,
+                kind=kind,
                 name=quote_name(&role.role),
                 params=params.join(";\n"))
         ).await?);
@@ -52,11 +77,25 @@ pub async fn create_superuser(cli: &mut Connection, _options: &Options,
     Ok(())
 }
 
-pub async fn alter(cli: &mut Connection, _options: &Options,
+pub async fn create(cli: &mut Connection, _options: &Options,
     role: &RoleParams)
     -> Result<(), anyhow::Error>
 {
-    let params = process_params(role)?;
+    create_role(cli, "ROLE", role).await
+}
+
+pub async fn create_superuser(cli: &mut Connection, _options: &Options,
+    role: &RoleParams)
+    -> Result<(), anyhow::Error>
+{
+    create_role(cli, "SUPERUSER ROLE", role).await
+}
+
+pub async fn alter(cli: &mut Connection, _options: &Options,
+    role: &RoleParams, instance: Option<&str>)
+    -> Result<(), anyhow::Error>
+{
+    let (params, password) = process_params(role)?;
     if params.is_empty() {
         return Err(anyhow::anyhow!("Please specify attribute to alter"));
     } else {
@@ -69,6 +108,9 @@ pub async fn alter(cli: &mut Connection, _options: &Options,
                 params=params.join(";\n"))
         ).await?);
     }
+    if let (Some(instance), Some(password)) = (instance, password) {
+        update_credentials(instance, &role.role, password)?;
+    }
     Ok(())
 }
 
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::cmp::{min, Ordering};
 use std::str::FromStr;
 
@@ -7,6 +8,7 @@ use std::borrow::Borrow;
 use edgeql_parser::preparser;
 
 use crate::commands::backslash;
+use crate::commands::parser;
 
 
 #[derive(Debug)]
@@ -36,11 +38,14 @@ pub enum ValidationResult {
 pub enum SettingValue {
     Variants(&'static [String]),
     Usize,
+    Text,
+    /// Name of the global followed by the value
+    Global,
 }
 
 pub struct Pair {
-    value: &'static str,
-    description: &'static str,
+    value: Cow<'static, str>,
+    description: Cow<'static, str>,
 }
 
 pub struct Hint {
@@ -93,7 +98,7 @@ fn complete_command(input: &str) -> Vec<Pair> {
     backslash::CMD_CACHE.all_commands
         .range_from(input)
         .filter(|x| x.starts_with(input))
-        .map(|x| Pair { value: x, description: x })
+        .map(|x| Pair { value: x.into(), description: x.into() })
         .collect()
 }
 
@@ -102,8 +107,8 @@ fn complete_setting(input: &str) -> Vec<Pair> {
         .filter(|(name, _)| name.starts_with(input))
         .map(|(name, setting)| {
             Pair {
-                value: name,
-                description: &setting.name_description,
+                value: (*name).into(),
+                description: setting.name_description.as_str().into(),
             }
         })
         .collect()
@@ -111,17 +116,33 @@ fn complete_setting(input: &str) -> Vec<Pair> {
 
 fn complete_setting_value(input: &str, val: &SettingValue) -> Vec<Pair> {
     match val {
-        SettingValue::Usize => Vec::new(),
+        | SettingValue::Usize
+        | SettingValue::Text
+        | SettingValue::Global
+        => Vec::new(),
         SettingValue::Variants(v) => v.iter()
             .filter(|x| x.starts_with(input))
             .map(|x| Pair {
-                value: x,
-                description: x,
+                value: x.into(),
+                description: x.into(),
             }).collect(),
     }
 }
 
-pub fn complete(input: &str, cursor: usize)
+fn is_database_arg(args: &[backslash::Argument]) -> bool {
+    args.first().map(|a| a.name == "database_name").unwrap_or(false)
+}
+
+fn complete_database(input: &str, databases: &[String]) -> Vec<Pair> {
+    databases.iter()
+        .filter(|x| x.starts_with(input))
+        .map(|x| Pair {
+            value: x.clone().into(),
+            description: x.clone().into(),
+        }).collect()
+}
+
+pub fn complete(input: &str, cursor: usize, databases: &[String])
     -> Option<(usize, Vec<Pair>)>
 {
     match current(input, cursor) {
@@ -149,6 +170,12 @@ pub fn complete(input: &str, cursor: usize)
                             return Some((token.span.0,
                                          complete_setting_value(arg, cfg)));
                         }
+                        (Fsm::Arguments(_, args), Argument(arg))
+                            if is_database_arg(args)
+                        => {
+                            return Some((token.span.0,
+                                         complete_database(arg, databases)));
+                        }
                         _ => return None,
                     }
                 } else {
@@ -165,6 +192,9 @@ pub fn complete(input: &str, cursor: usize)
                 Fsm::SetValue(cfg) => {
                     return Some((cursor, complete_setting_value("", cfg)));
                 }
+                Fsm::Arguments(_, args) if is_database_arg(args) => {
+                    return Some((cursor, complete_database("", databases)));
+                }
                 _ => return None,
             }
         }
@@ -275,7 +305,10 @@ fn hint_setting_name(sname: &str) -> Option<Hint> {
 
 fn hint_setting_value(input: &str, val: &SettingValue) -> Option<Hint> {
     match val {
-        SettingValue::Usize => None,
+        | SettingValue::Usize
+        | SettingValue::Text
+        | SettingValue::Global
+        => None,
         SettingValue::Variants(variants) => {
             let mut matches = variants.iter().filter(|v| v.starts_with(input));
             if let Some(matching) = matches.next() {
@@ -394,6 +427,16 @@ impl BackslashFsm {
                         Some(setting) => {
                             if let Some(values) = &setting.values {
                                 SetValue(SettingValue::Variants(&values))
+                            } else if matches!(setting.setting,
+                                parser::Setting::Global(_))
+                            {
+                                SetValue(SettingValue::Global)
+                            } else if matches!(setting.setting,
+                                parser::Setting::Prompt(_) |
+                                parser::Setting::DisplayTz(_) |
+                                parser::Setting::LogSlowQueries(_))
+                            {
+                                SetValue(SettingValue::Text)
                             } else {
                                 // TODO(tailhook) unhardcode \limit
                                 SetValue(SettingValue::Usize)
@@ -404,6 +447,7 @@ impl BackslashFsm {
                 }
                 _ => Final,
             },
+            SetValue(SettingValue::Global) => SetValue(SettingValue::Text),
             SetValue(_) => Final,
         }
     }
@@ -440,6 +484,11 @@ impl BackslashFsm {
                     ValidationResult::Invalid
                 }
             }
+            | (SetValue(SettingValue::Text), T::Argument(_))
+            | (SetValue(SettingValue::Global), T::Argument(_))
+            => {
+                ValidationResult::Valid
+            }
             (SetValue(SettingValue::Usize), T::Argument(arg)) => {
                 if let Ok(_) = usize::from_str(arg) {
                     ValidationResult::Valid
@@ -466,10 +515,10 @@ impl BackslashFsm {
 
 impl rustyline::completion::Candidate for Pair {
     fn replacement(&self) -> &str {
-        self.value
+        &self.value
     }
     fn display(&self) -> &str {
-        self.description
+        &self.description
     }
 }
 
@@ -0,0 +1,99 @@
+//! Configuration of the command-line tool itself, stored in `cli.toml` of
+//! the config directory
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use fn_error_context::context;
+
+use crate::platform::config_dir;
+use crate::project::config::warn_extra;
+
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all="kebab-case")]
+struct SrcConfig {
+    #[serde(default)]
+    shell: SrcShell,
+    #[serde(default)]
+    query: SrcQuery,
+    #[serde(flatten)]
+    extra: BTreeMap<String, toml::Value>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all="kebab-case")]
+struct SrcShell {
+    #[serde(default)]
+    prompt: Option<String>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, toml::Value>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all="kebab-case")]
+struct SrcQuery {
+    #[serde(default)]
+    retry_attempts: Option<u32>,
+    #[serde(default, with="humantime_serde")]
+    retry_backoff: Option<Duration>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub shell: Shell,
+    pub query: Query,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Shell {
+    /// Prompt template of the REPL (see `prompt_template`)
+    pub prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Number of retries of queries failed with transient errors
+    pub retry_attempts: Option<u32>,
+    /// Delay before the first retry
+    pub retry_backoff: Option<Duration>,
+}
+
+pub fn path() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("cli.toml"))
+}
+
+#[context("error reading config `{}`", path.display())]
+fn read_file(path: &Path) -> anyhow::Result<Config> {
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Config::default());
+        }
+        Err(e) => Err(e)?,
+    };
+    let mut toml = toml::de::Deserializer::new(&text);
+    let val: SrcConfig = serde_path_to_error::deserialize(&mut toml)?;
+    warn_extra(&val.extra, "");
+    warn_extra(&val.shell.extra, "shell.");
+    warn_extra(&val.query.extra, "query.");
+    Ok(Config {
+        shell: Shell {
+            prompt: val.shell.prompt,
+        },
+        query: Query {
+            retry_attempts: val.query.retry_attempts,
+            retry_backoff: val.query.retry_backoff,
+        },
+    })
+}
+
+/// Reads the config, returns defaults if there is no config file
+pub fn read() -> anyhow::Result<Config> {
+    read_file(&path()?)
+}
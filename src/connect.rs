@@ -1,22 +1,62 @@
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 
 use async_std::future::{timeout, pending};
 use async_std::prelude::FutureExt;
+use anyhow::Context;
 
 use edgedb_client::Builder;
 use edgedb_client::client::Connection;
+use edgedb_protocol::client_message::ClientMessage;
+use edgedb_protocol::error_response::ErrorResponse;
+use edgedb_protocol::server_message::ServerMessage;
+use edgeql_parser::expr;
 
+use crate::capabilities::{self, Feature};
+use crate::commands::helpers::quote_namespaced;
 use crate::hint::ArcError;
 
+const AUTHENTICATION_ERROR: u32 = 0x_07_01_00_00;
+
 
 #[derive(Debug, Clone)]
 pub struct Connector {
     params: Result<Builder, ArcError>,
+    /// Globals set on each new connection: name -> EdgeQL expression
+    globals: BTreeMap<String, String>,
+    apply_access_policies: bool,
 }
 
 impl Connector {
     pub fn new(params: Result<Builder, anyhow::Error>) -> Connector {
-        Connector { params: params.map_err(ArcError::from) }
+        Connector {
+            params: params.map_err(ArcError::from),
+            globals: BTreeMap::new(),
+            apply_access_policies: true,
+        }
+    }
+    /// Disables access policies on each new connection (superuser only)
+    pub fn bypass_access_policies(&mut self) -> &mut Self {
+        self.apply_access_policies = false;
+        self
+    }
+    pub fn bypasses_access_policies(&self) -> bool {
+        !self.apply_access_policies
+    }
+    pub fn set_global(&mut self, name: &str, value: &str) -> &mut Self {
+        self.globals.insert(name.into(), value.into());
+        self
+    }
+    pub fn reset_global(&mut self, name: &str) -> &mut Self {
+        self.globals.remove(name);
+        self
+    }
+    pub fn clear_globals(&mut self) -> &mut Self {
+        self.globals.clear();
+        self
+    }
+    pub fn globals(&self) -> &BTreeMap<String, String> {
+        &self.globals
     }
     pub fn modify<F: FnOnce(&mut Builder)>(&mut self, f: F) -> &mut Self {
         self.params.as_mut().map(f).ok();
@@ -24,9 +64,52 @@ impl Connector {
     }
     pub async fn connect(&self) -> Result<Connection, anyhow::Error> {
         let params = self.params.as_ref().map_err(Clone::clone)?;
-        return params.connect()
+        // password is never logged, so logs are safe to share
+        log::debug!("Connecting to {} as {:?}, database {:?}",
+            params.get_addr(), params.get_user(), params.get_database());
+        let start = Instant::now();
+        log::trace!(target: "edgedb::protocol",
+            "Starting handshake with {}", params.get_addr());
+        let result = params.connect()
             .race(self.print_warning(params))
-            .await
+            .await;
+        match &result {
+            Ok(_) => {
+                log::trace!(target: "edgedb::protocol",
+                    "Handshake with {} complete", params.get_addr());
+                log::info!("Connected to {} in {:?}",
+                    params.get_addr(), start.elapsed());
+            }
+            Err(e) if is_authentication_error(e) => {
+                log::info!("Authentication as {:?} failed after {:?}",
+                    params.get_user(), start.elapsed());
+            }
+            Err(e) if is_tls_error(e) => {
+                log::trace!(target: "edgedb::protocol",
+                    "TLS handshake with {} failed: {:#}",
+                    params.get_addr(), e);
+                log::info!("Connection to {} failed after {:?}: {:#}",
+                    params.get_addr(), start.elapsed(), e);
+            }
+            Err(e) => {
+                log::info!("Connection to {} failed after {:?}: {:#}",
+                    params.get_addr(), start.elapsed(), e);
+            }
+        }
+        let mut conn = result?;
+        if !self.globals.is_empty() || !self.apply_access_policies {
+            capabilities::require(&mut conn, Feature::State).await?;
+        }
+        if !self.apply_access_policies {
+            conn.execute(
+                "CONFIGURE SESSION SET apply_access_policies := false"
+            ).await.context("cannot bypass access policies")?;
+        }
+        for (name, value) in &self.globals {
+            conn.execute(&set_global_command(name, value)?).await
+                .with_context(|| format!("cannot set global {}", name))?;
+        }
+        Ok(conn)
     }
 
     async fn print_warning(&self, params: &Builder)
@@ -41,3 +124,69 @@ impl Connector {
         self.params.as_ref().map_err(Clone::clone)
     }
 }
+
+/// Returns `SET GLOBAL` statement, `value` is an EdgeQL expression
+///
+/// The value is checked to be a single expression, so it can't add other
+/// statements to the command.
+pub fn set_global_command(name: &str, value: &str)
+    -> anyhow::Result<String>
+{
+    expr::check(value).map_err(|e| anyhow::anyhow!(
+        "invalid value of global {}: {}", name, e))?;
+    Ok(format!("SET GLOBAL {} := ({})", quote_namespaced(name), value))
+}
+
+pub fn reset_global_command(name: &str) -> String {
+    format!("RESET GLOBAL {}", quote_namespaced(name))
+}
+
+/// Traces types of the messages sent to the server
+///
+/// Contents of the messages are never logged, as they contain query
+/// arguments.
+pub fn trace_sent(messages: &[ClientMessage]) {
+    if log::log_enabled!(target: "edgedb::protocol", log::Level::Trace) {
+        for msg in messages {
+            log::trace!(target: "edgedb::protocol",
+                "Sent {}", message_type(&format!("{:?}", msg)));
+        }
+    }
+}
+
+/// Traces type of the message received from the server
+pub fn trace_received(message: &ServerMessage) {
+    if log::log_enabled!(target: "edgedb::protocol", log::Level::Trace) {
+        log::trace!(target: "edgedb::protocol",
+            "Received {}", message_type(&format!("{:?}", message)));
+    }
+}
+
+/// Name of the enum variant from the `Debug` output of a message
+fn message_type(debug: &str) -> &str {
+    debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or(debug)
+}
+
+/// Returns `true` if connection failed on TLS handshake
+pub fn is_tls_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<native_tls::Error>())
+}
+
+/// Returns `true` if connection was rejected because of invalid credentials
+pub fn is_authentication_error(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<ErrorResponse>())
+        .any(|e| e.code & 0xFFFF_0000 == AUTHENTICATION_ERROR)
+}
+
+#[cfg(test)]
+mod test {
+    use super::message_type;
+
+    #[test]
+    fn message_types() {
+        assert_eq!(message_type("Sync"), "Sync");
+        assert_eq!(message_type("PrepareComplete(PrepareComplete { .. })"),
+                   "PrepareComplete");
+    }
+}
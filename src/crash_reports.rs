@@ -0,0 +1,356 @@
+//! Reports of panics and internal errors (bugs) of the CLI
+//!
+//! Report contains version, platform, subcommand names, the error with
+//! a backtrace and recent log lines. Command-line arguments (which can
+//! include query text) are only saved if `EDGEDB_CRASH_REPORT_ARGS` is
+//! set.
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use async_std::task;
+use clap::{Clap, AppSettings};
+use fn_error_context::context;
+use log::Log;
+use once_cell::sync::Lazy;
+use prettytable::{Table, Row, Cell};
+
+use crate::hint::HintExt;
+use crate::platform::{cache_dir, tmp_file_path};
+use crate::server::remote;
+use crate::table;
+use crate::telemetry;
+
+const LOG_LINES: usize = 50;
+const DEFAULT_URL: &str = "https://telemetry.edgedb.com/v1/crash-reports";
+const ISSUE_URL: &str =
+    "https://github.com/edgedb/edgedb-cli/issues/new?template=bug_report.md";
+
+static RECENT_LOG: Lazy<Mutex<VecDeque<String>>> = Lazy::new(|| {
+    Mutex::new(VecDeque::with_capacity(LOG_LINES))
+});
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct CrashReportsCommand {
+    #[clap(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum Subcommand {
+    /// List saved crash reports
+    List(List),
+    /// Send crash reports to EdgeDB developers
+    Submit(Submit),
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct List {
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Submit {
+    /// Name of the report (as shown by `crash-reports list`)
+    #[clap(required_unless_present="all")]
+    pub name: Option<String>,
+    /// Submit all reports not submitted yet
+    #[clap(long, conflicts_with="name")]
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum Kind {
+    Panic,
+    Bug,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Report {
+    #[serde(with="humantime_serde")]
+    timestamp: SystemTime,
+    kind: Kind,
+    version: String,
+    platform: String,
+    command: String,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    args: Option<Vec<String>>,
+    message: String,
+    backtrace: String,
+    log: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct JsonItem {
+    name: String,
+    path: PathBuf,
+    submitted: bool,
+    #[serde(with="humantime_serde")]
+    timestamp: SystemTime,
+    kind: Kind,
+    message: String,
+}
+
+/// Logger that also keeps recent messages for crash reports
+///
+/// Informational messages of the CLI itself are kept even if they aren't
+/// shown, as they are most useful for finding out what was going on.
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+
+fn keep(record: &log::Record) -> bool {
+    record.level() <= log::Level::Info
+        && record.target().starts_with("edgedb")
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata) || metadata.level() <= log::Level::Info
+    }
+    fn log(&self, record: &log::Record) {
+        if keep(record) {
+            if let Ok(mut lines) = RECENT_LOG.lock() {
+                if lines.len() >= LOG_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(format!("[{} {}] {}",
+                    record.level(), record.target(), record.args()));
+            }
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// Replaces `builder.init()`
+pub fn init_logger(builder: &mut env_logger::Builder) {
+    let inner = builder.build();
+    let max_level = inner.filter().max(log::LevelFilter::Info);
+    log::set_boxed_logger(Box::new(Logger { inner }))
+        .expect("logger is initialized once");
+    log::set_max_level(max_level);
+}
+
+fn reports_dir() -> anyhow::Result<PathBuf> {
+    Ok(cache_dir()?.join("crash_reports"))
+}
+
+fn submitted_dir() -> anyhow::Result<PathBuf> {
+    Ok(reports_dir()?.join("submitted"))
+}
+
+fn url() -> String {
+    env::var("EDGEDB_CRASH_REPORT_URL")
+        .unwrap_or_else(|_| DEFAULT_URL.into())
+}
+
+/// Formats timestamp as a file name, e.g. `20210304T120000Z`
+fn timestamp_name(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+        .replace(&['-', ':'][..], "")
+}
+
+fn write_report(report: &Report) -> anyhow::Result<PathBuf> {
+    let dir = reports_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}-{}.json",
+        timestamp_name(report.timestamp), process::id()));
+    let tmp = tmp_file_path(&path);
+    fs::write(&tmp, serde_json::to_vec_pretty(report)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// Saves the report and tells user what to do with it
+pub fn save(kind: Kind, message: String, backtrace: String) {
+    let args = env::var_os("EDGEDB_CRASH_REPORT_ARGS")
+        .filter(|x| !x.is_empty())
+        .map(|_| env::args().collect());
+    let report = Report {
+        timestamp: SystemTime::now(),
+        kind,
+        version: env!("CARGO_PKG_VERSION").into(),
+        platform: format!("{}-{}", env::consts::ARCH, env::consts::OS),
+        command: telemetry::current_command(),
+        args,
+        message,
+        backtrace,
+        log: RECENT_LOG.lock()
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_else(|_| Vec::new()),
+    };
+    match write_report(&report) {
+        Ok(path) => {
+            eprintln!("Crash report is saved to {}. Please consider \
+                sending it with `edgedb crash-reports submit --all` or \
+                attaching it to an issue at {}",
+                path.display(), ISSUE_URL);
+        }
+        Err(e) => eprintln!("edgedb error: cannot save crash report: {:#}",
+                            e),
+    }
+}
+
+/// Saves crash report on panic in addition to printing it
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let payload = info.payload();
+        let text = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "<non-string panic payload>".into());
+        let message = match info.location() {
+            Some(loc) => format!("{} at {}:{}", text, loc.file(), loc.line()),
+            None => text,
+        };
+        save(Kind::Panic, message,
+             format!("{:?}", backtrace::Backtrace::new()));
+    }));
+}
+
+/// Returns complete reports in the directory, oldest first
+#[context("cannot read crash reports in {}", dir.display())]
+fn read_reports(dir: &Path) -> anyhow::Result<Vec<(PathBuf, Report)>> {
+    let items = match fs::read_dir(dir) {
+        Ok(items) => items,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => Err(e)?,
+    };
+    let mut result = Vec::new();
+    for item in items {
+        let path = item?.path();
+        if path.extension().map(|e| e != "json").unwrap_or(true) {
+            continue;
+        }
+        match fs::read(&path).map_err(anyhow::Error::from)
+            .and_then(|data| Ok(serde_json::from_slice(&data)?))
+        {
+            Ok(report) => result.push((path, report)),
+            Err(e) => log::warn!("Cannot read {}: {:#}", path.display(), e),
+        }
+    }
+    result.sort_by_key(|(path, _)| path.clone());
+    Ok(result)
+}
+
+fn report_name(path: &Path) -> String {
+    path.file_stem().map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn list(options: &List) -> anyhow::Result<()> {
+    let mut items = Vec::new();
+    for (dir, submitted) in [(reports_dir()?, false),
+                             (submitted_dir()?, true)].iter()
+    {
+        for (path, report) in read_reports(dir)? {
+            items.push(JsonItem {
+                name: report_name(&path),
+                path,
+                submitted: *submitted,
+                timestamp: report.timestamp,
+                kind: report.kind,
+                message: report.message,
+            });
+        }
+    }
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if items.is_empty() {
+        eprintln!("No crash reports found.");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Name", "Kind", "Submitted", "Message"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for item in &items {
+        let message = item.message.lines().next().unwrap_or("");
+        table.add_row(Row::new(vec![
+            Cell::new(&item.name),
+            Cell::new(match item.kind {
+                Kind::Panic => "panic",
+                Kind::Bug => "bug",
+            }),
+            Cell::new(if item.submitted { "yes" } else { "no" }),
+            Cell::new(message),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+async fn submit_one(path: &Path, report: &Report) -> anyhow::Result<()> {
+    remote::post_json(&url(), report).await?;
+    let dir = submitted_dir()?;
+    fs::create_dir_all(&dir)?;
+    let dest = dir.join(path.file_name().expect("report has file name"));
+    fs::rename(path, &dest)
+        .with_context(|| format!("cannot move {} to {}",
+                                 path.display(), dest.display()))?;
+    Ok(())
+}
+
+fn submit(options: &Submit) -> anyhow::Result<()> {
+    let reports = read_reports(&reports_dir()?)?;
+    let reports = match &options.name {
+        Some(name) => {
+            let found = reports.into_iter()
+                .find(|(path, _)| &report_name(path) == name);
+            match found {
+                Some(item) => vec![item],
+                None => {
+                    return Err(anyhow::anyhow!(
+                            "no unsubmitted crash report {:?}", name))
+                        .hint("run `edgedb crash-reports list` \
+                               to see the reports")
+                        .map_err(Into::into);
+                }
+            }
+        }
+        None => reports,
+    };
+    if reports.is_empty() {
+        eprintln!("No crash reports to submit.");
+        return Ok(());
+    }
+    for (path, report) in &reports {
+        task::block_on(submit_one(path, report))?;
+        eprintln!("Submitted {}", report_name(path));
+    }
+    Ok(())
+}
+
+pub fn main(cmd: &CrashReportsCommand) -> anyhow::Result<()> {
+    match &cmd.subcommand {
+        Subcommand::List(c) => list(c),
+        Subcommand::Submit(c) => submit(c),
+    }
+}
@@ -0,0 +1,183 @@
+//! Output of `edgedb query --debug-protocol`
+//!
+//! Executes the query step by step and prints the type descriptors received
+//! from the server along with a hex dump of every data element, so codec
+//! mismatches can be seen at the binary level. Data elements are dumped as
+//! received, before decoding, so elements the codec fails to decode are
+//! shown too.
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bytes::{Bytes, BytesMut};
+
+use edgedb_client::client::Connection;
+use edgedb_protocol::client_message::ClientMessage;
+use edgedb_protocol::client_message::{Prepare, IoFormat, Cardinality};
+use edgedb_protocol::client_message::{DescribeStatement, DescribeAspect};
+use edgedb_protocol::client_message::{Execute};
+use edgedb_protocol::server_message::ServerMessage;
+use edgedb_protocol::value::Value;
+
+use crate::connect;
+
+
+/// Formats data as lines of 16 bytes with an offset and ASCII column
+pub fn hexdump(data: &[u8]) -> String {
+    let mut buf = String::with_capacity(data.len() * 4);
+    for (idx, line) in data.chunks(16).enumerate() {
+        write!(&mut buf, "{:08x}  ", idx * 16).unwrap();
+        for pos in 0..16 {
+            match line.get(pos) {
+                Some(b) => write!(&mut buf, "{:02x} ", b).unwrap(),
+                None => buf.push_str("   "),
+            }
+            if pos == 7 {
+                buf.push(' ');
+            }
+        }
+        buf.push_str(" |");
+        for b in line {
+            if b.is_ascii_graphic() || *b == b' ' {
+                buf.push(*b as char);
+            } else {
+                buf.push('.');
+            }
+        }
+        buf.push_str("|\n");
+    }
+    return buf;
+}
+
+pub async fn query(cli: &mut Connection, statement: &str)
+    -> anyhow::Result<()>
+{
+    let statement_name = Bytes::from_static(b"");
+    let mut seq = cli.start_sequence().await?;
+    let messages = [
+        ClientMessage::Prepare(Prepare {
+            headers: HashMap::new(),
+            io_format: IoFormat::Binary,
+            expected_cardinality: Cardinality::Many,
+            statement_name: statement_name.clone(),
+            command_text: String::from(statement),
+        }),
+        ClientMessage::Flush,
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
+    loop {
+        let msg = seq.message().await?;
+        connect::trace_received(&msg);
+        match msg {
+            ServerMessage::PrepareComplete(complete) => {
+                println!("PrepareComplete: {:#?}", complete);
+                break;
+            }
+            ServerMessage::ErrorResponse(err) => {
+                seq.err_sync().await?;
+                return Err(err)?;
+            }
+            msg => eprintln!("WARNING: unsolicited message {:?}", msg),
+        }
+    }
+
+    let messages = [
+        ClientMessage::DescribeStatement(DescribeStatement {
+            headers: HashMap::new(),
+            aspect: DescribeAspect::DataDescription,
+            statement_name: statement_name.clone(),
+        }),
+        ClientMessage::Flush,
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
+    let data_description = loop {
+        let msg = seq.message().await?;
+        connect::trace_received(&msg);
+        match msg {
+            ServerMessage::CommandDataDescription(data_desc) => {
+                break data_desc;
+            }
+            ServerMessage::ErrorResponse(err) => {
+                seq.err_sync().await?;
+                return Err(err)?;
+            }
+            msg => eprintln!("WARNING: unsolicited message {:?}", msg),
+        }
+    };
+    println!("CommandDataDescription: {:#?}", data_description);
+    let desc = data_description.output()?;
+    let indesc = data_description.input()?;
+    println!("Input descriptors: {:#?}", indesc.descriptors());
+    println!("Output descriptors: {:#?}", desc.descriptors());
+    let codec = desc.build_codec()?;
+    let incodec = indesc.build_codec()?;
+
+    let mut arguments = BytesMut::with_capacity(8);
+    incodec.encode(&mut arguments, &Value::empty_tuple())
+        .map_err(|e| anyhow::anyhow!("cannot encode arguments: {} \
+            (query parameters are not supported with --debug-protocol)",
+            e))?;
+    let messages = [
+        ClientMessage::Execute(Execute {
+            headers: HashMap::new(),
+            statement_name: statement_name.clone(),
+            arguments: arguments.freeze(),
+        }),
+        ClientMessage::Sync,
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
+
+    let mut index = 0;
+    loop {
+        let msg = seq.message().await?;
+        connect::trace_received(&msg);
+        match msg {
+            ServerMessage::Data(data) => {
+                for element in &data.data {
+                    println!("Data #{} ({} bytes):", index, element.len());
+                    print!("{}", hexdump(element));
+                    match codec.decode(element) {
+                        Ok(row) => println!("Decoded #{}: {:?}", index, row),
+                        Err(e) => println!("Cannot decode #{}: {}", index, e),
+                    }
+                    index += 1;
+                }
+            }
+            ServerMessage::CommandComplete(complete) => {
+                seq.expect_ready().await?;
+                if desc.root_pos().is_none() {
+                    println!("CommandComplete: {}",
+                        String::from_utf8_lossy(&complete.status_data));
+                } else {
+                    println!("CommandComplete: {} rows", index);
+                }
+                return Ok(());
+            }
+            ServerMessage::ErrorResponse(err) => {
+                seq.err_sync().await?;
+                return Err(err)?;
+            }
+            msg => eprintln!("WARNING: unsolicited message {:?}", msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::hexdump;
+
+    #[test]
+    fn dump() {
+        assert_eq!(hexdump(b""), "");
+        assert_eq!(hexdump(b"\x00\x00\x00\x03abc"),
+            "00000000  00 00 00 03 61 62 63                              \
+             |....abc|\n");
+        assert_eq!(hexdump(&[0x41; 17]),
+            "00000000  41 41 41 41 41 41 41 41  41 41 41 41 41 41 41 41  \
+             |AAAAAAAAAAAAAAAA|\n\
+             00000010  41                                                \
+             |A|\n");
+    }
+}
@@ -0,0 +1,277 @@
+//! Environment checks to run before reporting a bug
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use async_std::task;
+use clap::{Clap, AppSettings};
+use colorful::Colorful;
+
+use crate::async_util::timeout;
+use crate::commands::ExitCode;
+use crate::credentials;
+use crate::platform::{config_dir, home_dir, tmp_file_path};
+use crate::process;
+use crate::self_upgrade;
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Doctor {
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+    /// Skip checks that need network (package index, linked instances)
+    #[clap(long)]
+    pub offline: bool,
+    /// Time to wait for each network request
+    #[clap(long, default_value="10s",
+           parse(try_from_str=humantime::parse_duration))]
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+enum Outcome {
+    Ok,
+    Skipped,
+    Warning,
+    Failed,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Check {
+    title: String,
+    outcome: Outcome,
+    message: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    fix: Option<String>,
+}
+
+
+impl Check {
+    fn ok(title: impl Into<String>, message: impl Into<String>) -> Check {
+        Check {
+            title: title.into(),
+            outcome: Outcome::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+    fn skipped(title: impl Into<String>, message: impl Into<String>)
+        -> Check
+    {
+        Check { outcome: Outcome::Skipped, ..Check::ok(title, message) }
+    }
+    fn warning(title: impl Into<String>, message: impl Into<String>,
+               fix: impl Into<String>)
+        -> Check
+    {
+        Check {
+            outcome: Outcome::Warning,
+            fix: Some(fix.into()),
+            ..Check::ok(title, message)
+        }
+    }
+    fn failed(title: impl Into<String>, message: impl Into<String>,
+              fix: impl Into<String>)
+        -> Check
+    {
+        Check {
+            outcome: Outcome::Failed,
+            ..Check::warning(title, message, fix)
+        }
+    }
+}
+
+impl Outcome {
+    fn marker(&self) -> String {
+        match self {
+            Outcome::Ok => "ok".green().to_string(),
+            Outcome::Skipped => "skipped".dark_gray().to_string(),
+            Outcome::Warning => "warning".yellow().to_string(),
+            Outcome::Failed => "FAILED".red().bold().to_string(),
+        }
+    }
+}
+
+fn check_path() -> Check {
+    const TITLE: &str = "PATH";
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            return Check::warning(TITLE,
+                format!("cannot determine path of the binary: {}", e),
+                "run the CLI by its full path");
+        }
+    };
+    let name = if cfg!(windows) { "edgedb.exe" } else { "edgedb" };
+    let first = env::var_os("PATH").and_then(|all| {
+        env::split_paths(&all).map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    });
+    match first {
+        None => Check::warning(TITLE,
+            format!("`{}` is not found in PATH", name),
+            format!("add {} to PATH",
+                    exe.parent().unwrap_or(&exe).display())),
+        Some(first) if !same_file(&first, &exe) => Check::warning(TITLE,
+            format!("`edgedb` in PATH is {}, not this binary ({})",
+                    first.display(), exe.display()),
+            format!("remove the other binary or put {} first in PATH",
+                    exe.parent().unwrap_or(&exe).display())),
+        Some(first) => Check::ok(TITLE,
+            format!("`edgedb` resolves to {}", first.display())),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Creates the directory (if needed) and a temporary file in it
+fn try_write(dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let tmp = tmp_file_path(&dir.join("doctor"));
+    fs::write(&tmp, b"")?;
+    fs::remove_file(&tmp)?;
+    Ok(())
+}
+
+fn check_dirs() -> Vec<Check> {
+    let dirs: Vec<(&str, anyhow::Result<PathBuf>)> = vec![
+        ("Config dir", config_dir()),
+        ("Data dir", dirs::data_dir().map(|d| d.join("edgedb"))
+            .ok_or_else(|| anyhow::anyhow!("cannot determine data dir"))),
+        ("Credentials dir", home_dir()
+            .map(|d| d.join(".edgedb").join("credentials"))),
+    ];
+    dirs.into_iter().map(|(title, dir)| match dir {
+        Ok(dir) => match try_write(&dir) {
+            Ok(()) => Check::ok(title, format!("{} is writable",
+                                               dir.display())),
+            Err(e) => Check::failed(title,
+                format!("cannot write to {}: {:#}", dir.display(), e),
+                format!("check permissions and owner of {}",
+                        dir.display())),
+        },
+        Err(e) => Check::failed(title, format!("{:#}", e),
+            "set the `HOME` environment variable"),
+    }).collect()
+}
+
+fn check_service_manager() -> Check {
+    const TITLE: &str = "Service manager";
+    let (tool, mut cmd, fix) = if cfg!(target_os="linux") {
+        let mut cmd = Command::new("systemctl");
+        cmd.arg("--user").arg("show-environment");
+        ("systemd", cmd,
+         "make sure a user session of systemd is running (e.g. log in \
+          instead of using `su`), or use `--method=docker`")
+    } else if cfg!(target_os="macos") {
+        let mut cmd = Command::new("launchctl");
+        cmd.arg("version");
+        ("launchctl", cmd, "reinstall the command-line tools of macOS")
+    } else {
+        return Check::skipped(TITLE,
+            "only Docker instances are supported on this platform");
+    };
+    match process::run_or_stderr(&mut cmd) {
+        Ok(Ok(())) => Check::ok(TITLE, format!("{} is available", tool)),
+        Ok(Err(stderr)) => Check::failed(TITLE,
+            format!("{} is not usable: {}", tool, stderr.trim()), fix),
+        Err(e) => Check::failed(TITLE,
+            format!("{} is not found: {:#}", tool, e), fix),
+    }
+}
+
+fn check_package_index(limit: Duration) -> Check {
+    const TITLE: &str = "Package index";
+    match self_upgrade::get_repo(limit) {
+        Ok(_) => Check::ok(TITLE, "packages.edgedb.com is reachable"),
+        Err(e) => Check::failed(TITLE, format!("{:#}", e),
+            "check network connection and proxy settings \
+             (`HTTPS_PROXY` environment variable)"),
+    }
+}
+
+fn major(version: &str) -> Option<u32> {
+    version.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+async fn server_version(name: &str) -> anyhow::Result<String> {
+    let mut cli = credentials::get_connector(name)?.connect().await?;
+    cli.get_version().await
+}
+
+fn check_instances(limit: Duration) -> Vec<Check> {
+    let names = match credentials::all_instance_names() {
+        Ok(names) => names,
+        Err(e) => {
+            return vec![Check::failed("Instances", format!("{:#}", e),
+                "check permissions of ~/.edgedb/credentials")];
+        }
+    };
+    let cli_version = env!("CARGO_PKG_VERSION");
+    names.iter().map(|name| {
+        let title = format!("Instance {:?}", name);
+        let version = task::block_on(timeout(limit, server_version(name)));
+        match version {
+            Err(e) => Check::skipped(title,
+                format!("cannot connect: {:#}", e)),
+            Ok(version) if major(&version) > major(cli_version) => {
+                Check::warning(title,
+                    format!("server {} is newer than CLI {}",
+                            version, cli_version),
+                    "run `edgedb self-upgrade`")
+            }
+            Ok(version) => Check::ok(title, format!("server {}", version)),
+        }
+    }).collect()
+}
+
+pub fn main(options: &Doctor) -> anyhow::Result<()> {
+    let mut checks = vec![check_path()];
+    checks.extend(check_dirs());
+    checks.push(check_service_manager());
+    if options.offline {
+        checks.push(Check::skipped("Package index", "`--offline` is set"));
+    } else {
+        checks.push(check_package_index(options.timeout));
+        checks.extend(check_instances(options.timeout));
+    }
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        for check in &checks {
+            println!("[{}] {}: {}",
+                     check.outcome.marker(), check.title, check.message);
+            if let Some(fix) = &check.fix {
+                println!("    fix: {}", fix);
+            }
+        }
+    }
+    if checks.iter().any(|c| c.outcome == Outcome::Failed) {
+        return Err(ExitCode::new(1).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::major;
+
+    #[test]
+    fn major_version() {
+        assert_eq!(major("1.0.0-alpha.7"), Some(1));
+        assert_eq!(major("2.0+7b3c1f2"), Some(2));
+        assert_eq!(major("1-beta2"), Some(1));
+        assert_eq!(major("nightly"), None);
+    }
+}
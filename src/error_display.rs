@@ -1,10 +1,11 @@
 use std::default::Default;
 use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use codespan_reporting::files::SimpleFile;
 use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle};
 use codespan_reporting::term::{emit};
-use termcolor::{StandardStream, ColorChoice};
+use termcolor::{StandardStream, ColorChoice, NoColor};
 
 use edgedb_protocol::error_response::ErrorResponse;
 use edgedb_protocol::error_response::FIELD_POSITION_START;
@@ -12,6 +13,109 @@ use edgedb_protocol::error_response::FIELD_POSITION_END;
 use edgedb_protocol::error_response::{FIELD_HINT, FIELD_DETAILS};
 use edgedb_protocol::error_response::FIELD_SERVER_TRACEBACK;
 
+use crate::hint::HintedError;
+use crate::http::QueryError;
+use crate::tee;
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+
+#[derive(serde::Serialize)]
+struct JsonError<'a> {
+    /// Only known for HTTP errors, server errors are identified by `code`
+    #[serde(rename="type")]
+    kind: Option<String>,
+    code: Option<u32>,
+    message: String,
+    hint: Option<&'a str>,
+    details: Option<&'a str>,
+    position: Option<Position>,
+}
+
+#[derive(serde::Serialize)]
+struct Position {
+    start: u32,
+    end: u32,
+}
+
+fn attribute(err: &ErrorResponse, field: u16) -> Option<&str> {
+    err.attributes.get(&field).and_then(|x| str::from_utf8(x).ok())
+}
+
+/// Enables printing errors from `main` using `print_json_error`
+pub fn enable_json_errors() {
+    JSON_ERRORS.store(true, Ordering::SeqCst);
+}
+
+pub fn json_errors() -> bool {
+    JSON_ERRORS.load(Ordering::SeqCst)
+}
+
+/// Prints a single-line JSON object describing the error to stderr
+pub fn print_json_error(err: &anyhow::Error) {
+    let hint = err.chain()
+        .find_map(|e| e.downcast_ref::<HintedError>())
+        .map(|e| &e.hint[..]);
+    let server = err.chain().find_map(|e| e.downcast_ref::<ErrorResponse>());
+    let data = if let Some(server) = server {
+        let position = attribute(server, FIELD_POSITION_START)
+            .and_then(|x| x.parse().ok())
+            .and_then(|start| {
+                attribute(server, FIELD_POSITION_END)
+                    .and_then(|x| x.parse().ok())
+                    .map(|end| Position { start, end })
+            });
+        JsonError {
+            kind: None,
+            code: Some(server.code),
+            message: server.message.clone(),
+            hint: attribute(server, FIELD_HINT).or(hint),
+            details: attribute(server, FIELD_DETAILS),
+            position,
+        }
+    } else if let Some(http) = err.chain()
+        .find_map(|e| e.downcast_ref::<QueryError>())
+    {
+        JsonError {
+            kind: Some(http.kind.clone()),
+            code: http.code,
+            message: http.message.clone(),
+            hint,
+            details: None,
+            position: None,
+        }
+    } else {
+        JsonError {
+            kind: None,
+            code: None,
+            message: format!("{:#}", err),
+            hint,
+            details: None,
+            position: None,
+        }
+    };
+    match serde_json::to_string(&data) {
+        Ok(text) => eprintln!("{}", text),
+        Err(_) => eprintln!("edgedb error: {:#}", err),
+    }
+}
+
+
+/// Converts character offset reported by the server into byte offset
+fn byte_offset(query: &str, chars: u32) -> usize {
+    query.char_indices().nth(chars as usize)
+        .map(|(idx, _)| idx)
+        .unwrap_or(query.len())
+}
+
+/// Returns 1-based line and column of the byte offset
+fn line_column(query: &str, offset: usize) -> (usize, usize) {
+    let before = &query[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next()
+        .map(|l| l.chars().count()).unwrap_or(0) + 1;
+    (line, column)
+}
 
 pub fn print_query_error(err: &ErrorResponse, query: &str, verbose: bool)
     -> Result<(), anyhow::Error>
@@ -25,6 +129,7 @@ pub fn print_query_error(err: &ErrorResponse, query: &str, verbose: bool)
     let (pstart, pend) = match (pstart, pend) {
         (Some(s), Some(e)) => (s, e),
         _ => {
+            tee::write(&format!("{}\n", err.display(verbose)));
             eprintln!("{}", err.display(verbose));
             return Ok(());
         }
@@ -34,21 +139,35 @@ pub fn print_query_error(err: &ErrorResponse, query: &str, verbose: bool)
         .unwrap_or("error");
     let detail = err.attributes.get(&FIELD_DETAILS)
         .and_then(|x| String::from_utf8(x.to_vec()).ok());
+    let start = byte_offset(query, pstart);
+    let end = byte_offset(query, pend).max(start);
     let files = SimpleFile::new("query", query);
+    let mut notes = detail.into_iter().collect::<Vec<_>>();
+    if verbose {
+        let (line, column) = line_column(query, start);
+        notes.push(format!("error code: 0x{:08x}", err.code));
+        notes.push(format!("position: line {}, column {} \
+            (characters {}..{})", line, column, pstart, pend));
+    }
     let diag = Diagnostic::error()
         .with_message(&err.message)
         .with_labels(vec![
             Label {
                 file_id: (),
                 style: LabelStyle::Primary,
-                range: pstart as usize..pend as usize,
+                range: start..end,
                 message: hint.into(),
             },
         ])
-        .with_notes(detail.into_iter().collect());
+        .with_notes(notes);
 
     emit(&mut StandardStream::stderr(ColorChoice::Auto),
         &Default::default(), &files, &diag)?;
+    if tee::is_enabled() {
+        let mut buf = NoColor::new(Vec::new());
+        emit(&mut buf, &Default::default(), &files, &diag)?;
+        tee::write(&String::from_utf8_lossy(&buf.into_inner()));
+    }
 
     if err.code == 0x_01_00_00_00 || verbose {
         let tb = err.attributes.get(&FIELD_SERVER_TRACEBACK);
@@ -63,3 +182,17 @@ pub fn print_query_error(err: &ErrorResponse, query: &str, verbose: bool)
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{byte_offset, line_column};
+
+    #[test]
+    fn offsets() {
+        let query = "SELECT 'пр'\n  + 1";
+        assert_eq!(byte_offset(query, 11), 13);
+        assert_eq!(byte_offset(query, 100), query.len());
+        assert_eq!(line_column(query, 13), (1, 12));
+        assert_eq!(line_column(query, 16), (2, 3));
+    }
+}
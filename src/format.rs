@@ -11,3 +11,37 @@ pub fn done_before(timestamp: SystemTime) -> impl fmt::Display {
         })
         .unwrap_or_else(|_| Cow::Borrowed("done just now"))
 }
+
+/// Formats duration of a query with the units appropriate to its magnitude
+pub fn duration(duration: Duration) -> String {
+    let micros = duration.as_micros();
+    // limits are compared with rounded values, so that e.g. 59.999s is
+    // printed as "1m 0.0s" rather than "60.00s"
+    if micros < 1000 {
+        format!("{}µs", micros)
+    } else if micros < 999_950 {
+        format!("{:.1}ms", micros as f64 / 1000.0)
+    } else if micros < 59_995_000 {
+        format!("{:.2}s", duration.as_secs_f64())
+    } else {
+        let tenths = (micros + 50_000) / 100_000;
+        format!("{}m {}.{}s", tenths / 600, tenths % 600 / 10, tenths % 10)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use super::duration;
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration(Duration::from_micros(345)), "345µs");
+        assert_eq!(duration(Duration::from_micros(12_345)), "12.3ms");
+        assert_eq!(duration(Duration::from_millis(1_500)), "1.50s");
+        assert_eq!(duration(Duration::from_millis(125_200)), "2m 5.2s");
+        assert_eq!(duration(Duration::from_micros(999_960)), "1.00s");
+        assert_eq!(duration(Duration::from_millis(59_999)), "1m 0.0s");
+        assert_eq!(duration(Duration::from_millis(119_980)), "2m 0.0s");
+    }
+}
@@ -0,0 +1,72 @@
+use async_std::task;
+use clap::{Clap, AppSettings};
+
+use crate::commands::ExitCode;
+use crate::http::Endpoint;
+use crate::options::Options;
+use crate::print;
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Graphql {
+    /// Set query variable, e.g. `--var name=value`. Value is parsed as JSON
+    /// if possible, otherwise it's passed as a string
+    #[clap(long="var", value_name="NAME=VALUE", number_of_values=1,
+           parse(try_from_str=parse_var))]
+    pub variables: Vec<(String, serde_json::Value)>,
+    /// Base URL of the server HTTP port (discovered from connection options
+    /// by default)
+    #[clap(long)]
+    pub url: Option<String>,
+    /// GraphQL query
+    pub query: String,
+}
+
+fn parse_var(value: &str) -> anyhow::Result<(String, serde_json::Value)> {
+    match value.find('=') {
+        Some(pos) => {
+            let name = value[..pos].trim_start_matches('$');
+            if name.is_empty() {
+                anyhow::bail!("variable name must not be empty");
+            }
+            let raw = &value[pos+1..];
+            let value = serde_json::from_str(raw)
+                .unwrap_or_else(|_| raw.into());
+            Ok((name.into(), value))
+        }
+        None => anyhow::bail!("variable must be in form `name=value`"),
+    }
+}
+
+pub fn main(cmd: &Graphql, options: &Options) -> anyhow::Result<()> {
+    let endpoint = Endpoint::from_options(options, cmd.url.as_deref())?;
+    let body = serde_json::json!({
+        "query": cmd.query,
+        "variables": cmd.variables.iter().cloned()
+            .collect::<serde_json::Map<_, _>>(),
+    });
+    let response = task::block_on(endpoint.post_json("graphql", &body))?;
+
+    if let Some(errors) = response.get("errors").and_then(|e| e.as_array())
+    {
+        for error in errors {
+            let message = error.get("message").and_then(|m| m.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| error.to_string());
+            eprintln!("edgedb error: {}", message);
+        }
+        if !errors.is_empty() {
+            return Err(ExitCode::new(1))?;
+        }
+    }
+    let data = response.get("data").cloned()
+        .unwrap_or(serde_json::Value::Null);
+    let mut cfg = print::Config::new();
+    if let Some((w, _h)) = term_size::dimensions_stdout() {
+        cfg.max_width(w);
+    }
+    cfg.colors(print::console::colors_stdout());
+    println!("{}", print::json_item_to_string(&data, &cfg)?);
+    Ok(())
+}
@@ -3,6 +3,8 @@ use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
 
+use crate::i18n::tr;
+
 #[derive(Debug, Clone)]
 pub struct ArcError(Arc<anyhow::Error>);
 
@@ -25,7 +27,7 @@ impl<T> HintExt for Result<T, anyhow::Error> {
     {
         self.map_err(|error| HintedError {
             error,
-            hint: text.into(),
+            hint: tr(text).into(),
         })
     }
     fn with_hint<F>(self, f: F) -> Self::Result
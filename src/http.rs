@@ -0,0 +1,257 @@
+//! Access to the HTTP endpoints of the server (GraphQL, EdgeQL over HTTP)
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use base64::display::Base64Display;
+use scram::ScramClient;
+
+use crate::credentials;
+use crate::options::Options;
+use crate::server::remote::USER_AGENT;
+use crate::server::reset_password::read_credentials;
+
+
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    base: String,
+    database: String,
+    user: String,
+    password: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP error: {0}")]
+pub struct HttpError(surf::Error);
+
+/// Parses `key=value` pairs of the SCRAM authentication header
+fn auth_params(header: &str) -> HashMap<String, String> {
+    let params = header.trim()
+        .strip_prefix("SCRAM-SHA-256").unwrap_or(header);
+    params.split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn decode_param(params: &HashMap<String, String>, key: &str)
+    -> anyhow::Result<String>
+{
+    let value = params.get(key)
+        .with_context(|| format!("no {:?} in SCRAM auth header", key))?;
+    let data = base64::decode(value)
+        .with_context(|| format!("bad base64 in SCRAM {:?}", key))?;
+    String::from_utf8(data)
+        .with_context(|| format!("bad utf-8 in SCRAM {:?}", key))
+}
+
+/// Error reported by the EdgeQL over HTTP endpoint
+#[derive(Debug, thiserror::Error)]
+#[error("{kind}: {message}")]
+pub struct QueryError {
+    pub kind: String,
+    pub message: String,
+    pub code: Option<u32>,
+}
+
+
+impl Endpoint {
+    /// Discovers the endpoint address from connection options
+    ///
+    /// For local instances host, port and password are taken from the
+    /// stored credentials. The `base` URL (e.g. `https://host:8080`)
+    /// overrides the discovered address.
+    pub fn from_options(options: &Options, base: Option<&str>)
+        -> anyhow::Result<Endpoint>
+    {
+        let params = options.conn_params.get()?;
+        let database = params.get_database().to_string();
+        if let Some(base) = base {
+            return Ok(Endpoint {
+                base: base.trim_end_matches('/').to_string(),
+                database,
+                user: params.get_user().to_string(),
+                password: None,
+            });
+        }
+        if let Some(name) = &options.instance_name {
+            let creds = read_credentials(&credentials::path(name)?)?;
+            let host = creds.host.as_deref().unwrap_or("localhost");
+            return Ok(Endpoint {
+                base: format!("http://{}:{}", host, creds.port),
+                database: creds.database.unwrap_or(database),
+                user: params.get_user().to_string(),
+                password: creds.password,
+            });
+        }
+        let addr = params.get_addr().to_string();
+        if addr.contains('/') {
+            anyhow::bail!("HTTP endpoints are not available over \
+                unix socket {}, specify `--host` and `--port` \
+                or the URL", addr);
+        }
+        Ok(Endpoint {
+            base: format!("http://{}", addr),
+            database,
+            user: params.get_user().to_string(),
+            password: None,
+        })
+    }
+    /// Returns URL of the extension path for the current database
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/db/{}/{}", self.base,
+            urlencoding::encode(&self.database), path)
+    }
+    /// Returns URL of the web UI of the server
+    pub fn ui_url(&self) -> String {
+        format!("{}/ui", self.base)
+    }
+    /// Returns value of the `Authorization` header if the password is known
+    pub fn authorization(&self) -> Option<String> {
+        self.password.as_ref().map(|password| {
+            let auth = format!("{}:{}", self.user, password);
+            format!("Basic {}", Base64Display::with_config(auth.as_bytes(),
+                                                           base64::STANDARD))
+        })
+    }
+    /// Obtains a login token issued by the server's auth endpoint
+    ///
+    /// Authenticates with SCRAM, so the password itself is never sent.
+    /// Returns `None` if the password is unknown or the server has no
+    /// auth endpoint.
+    pub async fn login_token(&self) -> anyhow::Result<Option<String>> {
+        let password = match &self.password {
+            Some(password) => password,
+            None => return Ok(None),
+        };
+        let url = format!("{}/auth/token", self.base);
+        log::info!("Fetching login token from {}", url);
+        let scram = ScramClient::new(&self.user, password, None);
+        let (scram, client_first) = scram.client_first();
+        let response = surf::get(&url)
+            .header("User-Agent", USER_AGENT)
+            .header("Authorization", format!("SCRAM-SHA-256 data={}",
+                base64::encode(client_first)))
+            .await.map_err(HttpError)
+            .with_context(|| format!("cannot fetch {}", url))?;
+        match response.status() {
+            surf::StatusCode::NotFound => return Ok(None),
+            surf::StatusCode::Unauthorized => {}
+            status => anyhow::bail!("HTTP failure at {}: {} {}", url,
+                status, status.canonical_reason()),
+        }
+        let params = response.header("WWW-Authenticate")
+            .map(|h| auth_params(h.as_str()))
+            .context("no SCRAM challenge in auth response")?;
+        let sid = params.get("sid").context("no SCRAM session id")?;
+        let server_first = decode_param(&params, "data")?;
+        let scram = scram.handle_server_first(&server_first)
+            .context("SCRAM authentication failed")?;
+        let (scram, client_final) = scram.client_final();
+        let mut response = surf::get(&url)
+            .header("User-Agent", USER_AGENT)
+            .header("Authorization", format!("SCRAM-SHA-256 sid={}, data={}",
+                sid, base64::encode(client_final)))
+            .await.map_err(HttpError)
+            .with_context(|| format!("cannot fetch {}", url))?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("authentication failed at {}: {} {}", url,
+                status, status.canonical_reason());
+        }
+        let params = response.header("Authentication-Info")
+            .map(|h| auth_params(h.as_str()))
+            .context("no SCRAM signature in auth response")?;
+        scram.handle_server_final(&decode_param(&params, "data")?)
+            .context("cannot verify server signature")?;
+        let token = response.body_string().await.map_err(HttpError)
+            .with_context(|| format!("cannot read response of {}", url))?;
+        Ok(Some(token.trim().to_string()))
+    }
+    /// Fetches the URL without credentials and returns the response status
+    pub async fn get_status(&self, url: &str)
+        -> anyhow::Result<surf::StatusCode>
+    {
+        log::info!("Fetching {}", url);
+        let response = surf::get(url)
+            .header("User-Agent", USER_AGENT)
+            .await.map_err(HttpError)
+            .with_context(|| format!("cannot fetch {}", url))?;
+        Ok(response.status())
+    }
+    /// Posts JSON body and decodes JSON response
+    ///
+    /// Non-200 responses are also decoded if they contain JSON, as both
+    /// GraphQL and EdgeQL endpoints report query errors this way.
+    pub async fn post_json(&self, path: &str, body: &serde_json::Value)
+        -> anyhow::Result<serde_json::Value>
+    {
+        let url = self.url(path);
+        log::info!("Posting to {}", url);
+        let mut request = surf::post(&url)
+            .header("User-Agent", USER_AGENT)
+            .body(surf::Body::from_json(body).map_err(HttpError)?);
+        if let Some(auth) = self.authorization() {
+            request = request.header("Authorization", auth);
+        }
+        let mut response = request.await.map_err(HttpError)
+            .with_context(|| format!("cannot post to {}", url))?;
+        let status = response.status();
+        let data = response.body_bytes().await.map_err(HttpError)
+            .with_context(|| format!("cannot read response of {}", url))?;
+        match serde_json::from_slice(&data) {
+            Ok(value) => Ok(value),
+            Err(_) if status != 200 => {
+                anyhow::bail!("HTTP failure at {}: {} {}", url,
+                    status, status.canonical_reason());
+            }
+            Err(e) => Err(e)
+                .with_context(|| format!("bad JSON response from {}", url)),
+        }
+    }
+    /// Executes EdgeQL query using the HTTP endpoint
+    ///
+    /// Returns result set decoded from JSON.
+    pub async fn query(&self, query: &str,
+        variables: serde_json::Map<String, serde_json::Value>)
+        -> anyhow::Result<Vec<serde_json::Value>>
+    {
+        let response = self.post_json("edgeql", &serde_json::json!({
+            "query": query,
+            "variables": variables,
+        })).await?;
+        if let Some(error) = response.get("error") {
+            let text = |key: &str| error.get(key).and_then(|v| v.as_str())
+                .map(String::from);
+            return Err(QueryError {
+                kind: text("type").unwrap_or_else(|| "Error".into()),
+                message: text("message")
+                    .unwrap_or_else(|| error.to_string()),
+                code: error.get("code").and_then(|c| c.as_u64())
+                    .map(|c| c as u32),
+            }.into());
+        }
+        match response.get("data") {
+            Some(serde_json::Value::Array(items)) => Ok(items.clone()),
+            Some(serde_json::Value::Null) | None => Ok(Vec::new()),
+            Some(other) => {
+                anyhow::bail!("unexpected response data: {}", other);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::auth_params;
+
+    #[test]
+    fn scram_header() {
+        let params = auth_params("SCRAM-SHA-256 sid=1234, data=cj1hYmM=");
+        assert_eq!(params["sid"], "1234");
+        assert_eq!(params["data"], "cj1hYmM=");
+        assert_eq!(auth_params("sid=1,data=eA==")["data"], "eA==");
+    }
+}
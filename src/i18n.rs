@@ -0,0 +1,169 @@
+//! Translations of messages shown to users
+//!
+//! Messages are looked up by their English text (like in gettext), so
+//! the code stays readable and anything not translated is shown in
+//! English. Catalogs are TOML files in `src/i18n/` which map English text
+//! to the translation and are embedded into the binary. To add a language,
+//! create `src/i18n/<language>.toml` and add it to `CATALOGS`; tests check
+//! that translations keep all the placeholders of the original message.
+//!
+//! Placeholders are `{}` which are substituted in order, or `{0}`, `{1}`
+//! for translations that need another order. Use `{{` and `}}` for literal
+//! braces.
+//!
+//! Language is taken from `EDGEDB_LANG`, or the usual `LC_ALL`,
+//! `LC_MESSAGES` and `LANG` variables.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Write};
+
+use once_cell::sync::Lazy;
+
+
+const CATALOGS: &[(&str, &str)] = &[
+    ("de", include_str!("i18n/de.toml")),
+];
+
+static CATALOG: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    language().and_then(|lang| find_catalog(&lang))
+        .map(|(name, data)| load(name, data))
+        .unwrap_or_else(HashMap::new)
+});
+
+
+/// Returns language like `de_DE` from the environment, or `None` for
+/// English or the `C` locale
+fn language() -> Option<String> {
+    ["EDGEDB_LANG", "LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|val| !val.is_empty())
+        .and_then(|val| parse_locale(&val))
+}
+
+fn parse_locale(value: &str) -> Option<String> {
+    // strip encoding and modifier, e.g. `de_DE.UTF-8@euro`
+    let lang = value.split(|c| c == '.' || c == '@').next()?;
+    match lang {
+        "" | "C" | "POSIX" => None,
+        _ => Some(lang.replace('-', "_")),
+    }
+}
+
+/// Finds exact catalog (e.g. `pt_BR`) or the one of the language (`pt`)
+fn find_catalog(lang: &str) -> Option<&'static (&'static str, &'static str)>
+{
+    let base = lang.split('_').next().unwrap_or(lang);
+    CATALOGS.iter().find(|(name, _)| *name == lang)
+        .or_else(|| CATALOGS.iter().find(|(name, _)| *name == base))
+}
+
+fn load(name: &str, data: &str) -> HashMap<String, String> {
+    toml::from_str(data).unwrap_or_else(|e| {
+        log::warn!("Cannot load translations {:?}: {}", name, e);
+        HashMap::new()
+    })
+}
+
+/// Translates the message
+pub fn tr(message: &'static str) -> &'static str {
+    CATALOG.get(message).map(|s| &s[..]).unwrap_or(message)
+}
+
+/// Translates the message and substitutes the placeholders
+pub fn tr_format(message: &'static str, args: &[&dyn fmt::Display])
+    -> String
+{
+    substitute(tr(message), args)
+}
+
+fn substitute(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut index = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    index.push(c);
+                }
+                let index = if index.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    index.parse().unwrap_or(usize::MAX)
+                };
+                match args.get(index) {
+                    Some(arg) => write!(result, "{}", arg).unwrap(),
+                    None => result.push_str("{?}"),
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{CATALOGS, load, parse_locale, substitute};
+
+    fn placeholders(text: &str) -> BTreeMap<String, usize> {
+        let text = text.replace("{{", "").replace("}}", "");
+        let mut result = BTreeMap::new();
+        let mut next = 0;
+        for part in text.split('{').skip(1) {
+            let index = part.split('}').next().unwrap();
+            let index = if index.is_empty() {
+                next += 1;
+                (next - 1).to_string()
+            } else {
+                index.to_string()
+            };
+            *result.entry(index).or_insert(0) += 1;
+        }
+        result
+    }
+
+    #[test]
+    fn catalogs() {
+        for (name, data) in CATALOGS {
+            let catalog = load(name, data);
+            assert!(!catalog.is_empty(), "catalog {:?} is empty", name);
+            for (message, translation) in &catalog {
+                assert_eq!(placeholders(message), placeholders(translation),
+                    "placeholders of {:?} in {:?}", message, name);
+            }
+        }
+    }
+
+    #[test]
+    fn locales() {
+        assert_eq!(parse_locale("de_DE.UTF-8").as_deref(), Some("de_DE"));
+        assert_eq!(parse_locale("de-AT@euro").as_deref(), Some("de_AT"));
+        assert_eq!(parse_locale("C.UTF-8"), None);
+        assert_eq!(parse_locale("POSIX"), None);
+    }
+
+    #[test]
+    fn substitution() {
+        assert_eq!(substitute("{} of {}", &[&1, &"two"]), "1 of two");
+        assert_eq!(substitute("{1} of {0}", &[&1, &"two"]), "two of 1");
+        assert_eq!(substitute("{{literal}} {}", &[&1]), "{literal} 1");
+        assert_eq!(substitute("{} {}", &[&1]), "1 {?}");
+    }
+}
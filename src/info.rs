@@ -0,0 +1,120 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::Clap;
+
+use crate::platform::{home_dir, config_dir, cache_dir};
+use crate::table;
+
+
+#[derive(Clap, Clone, Debug)]
+pub struct Info {
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+    /// Only print how this binary is installed: `self-install`,
+    /// `homebrew`, `apt`, `rpm`, `cargo`, `package` or `custom`
+    #[clap(long, conflicts_with="json")]
+    pub installed_via: bool,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonInfo {
+    version: &'static str,
+    build_hash: Option<&'static str>,
+    binary_path: Option<PathBuf>,
+    config_dir: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    credentials_dir: Option<PathBuf>,
+    platform: String,
+    install_method: &'static str,
+}
+
+
+fn owned_by_package(cmd: &str, arg: &str, path: &Path) -> bool {
+    Command::new(cmd).arg(arg).arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Detects how the `edgedb` binary at the `path` is installed
+pub fn installed_via(path: &Path) -> &'static str {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let text = path.to_string_lossy();
+    let self_installed = home_dir()
+        .map(|h| path.starts_with(h.join(".edgedb").join("bin")))
+        .unwrap_or(false);
+    if self_installed {
+        "self-install"
+    } else if text.contains("/Cellar/") || text.contains("/homebrew/")
+        || text.contains("/linuxbrew/")
+    {
+        "homebrew"
+    } else if text.contains("/.cargo/bin/") {
+        "cargo"
+    } else if cfg!(target_os="linux") && owned_by_package("dpkg", "-S", &path)
+    {
+        "apt"
+    } else if cfg!(target_os="linux") && owned_by_package("rpm", "-qf", &path)
+    {
+        "rpm"
+    } else if cfg!(unix) && path.starts_with("/usr") {
+        "package"
+    } else {
+        "custom"
+    }
+}
+
+fn install_method(binary_path: &Option<PathBuf>) -> &'static str {
+    match binary_path {
+        Some(path) => installed_via(path),
+        None => "unknown",
+    }
+}
+
+fn display(path: &Option<PathBuf>) -> String {
+    path.as_ref().map(|p| p.display().to_string())
+        .unwrap_or_else(|| "<unknown>".into())
+}
+
+pub fn main(options: &Info) -> anyhow::Result<()> {
+    let binary_path = env::current_exe().ok();
+    if options.installed_via {
+        println!("{}", install_method(&binary_path));
+        return Ok(());
+    }
+    let info = JsonInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        build_hash: option_env!("EDGEDB_BUILD_HASH"),
+        install_method: install_method(&binary_path),
+        binary_path,
+        config_dir: config_dir().ok(),
+        cache_dir: cache_dir().ok(),
+        data_dir: dirs::data_dir().map(|d| d.join("edgedb")),
+        credentials_dir: home_dir().ok()
+            .map(|d| d.join(".edgedb").join("credentials")),
+        platform: format!("{}-{}", env::consts::ARCH, env::consts::OS),
+    };
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        table::settings(&[
+            ("Version", info.version),
+            ("Build hash", info.build_hash.unwrap_or("<unknown>")),
+            ("Binary path", &display(&info.binary_path)[..]),
+            ("Config dir", &display(&info.config_dir)[..]),
+            ("Cache dir", &display(&info.cache_dir)[..]),
+            ("Data dir", &display(&info.data_dir)[..]),
+            ("Credentials dir", &display(&info.credentials_dir)[..]),
+            ("Platform", &info.platform[..]),
+            ("Install method", info.install_method),
+        ]);
+    }
+    Ok(())
+}
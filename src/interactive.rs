@@ -22,17 +22,32 @@ use edgedb_protocol::value::Value;
 use edgeql_parser::preparser::{self, full_statement};
 
 use crate::commands::{backslash, ExitCode};
+use crate::config;
+use crate::connect::{self, is_authentication_error};
+use crate::credentials;
+use crate::i18n::{tr, tr_format};
 use crate::options::Options;
+use crate::format;
 use crate::print::{self, PrintError};
 use crate::prompt;
+use crate::prompt_template::Template;
+use crate::question;
+use crate::read_only;
+use edgedb_client::client::Connection;
 use edgedb_client::reader::ReadError;
 use crate::repl;
+use crate::schema_cache::SchemaCache;
+use crate::snippets;
+use crate::summary::{self, Summary};
+use crate::tee;
+use crate::server::reset_password::{read_credentials, write_credentials};
 use crate::variables::input_variables;
 use crate::error_display::print_query_error;
 use crate::outputs::tab_separated;
 
 
 const QUERY_OPT_IMPLICIT_LIMIT: u16 = 0xFF01;
+const PASSWORD_ATTEMPTS: u32 = 3;
 const QUERY_OPT_INLINE_TYPENAMES: u16 = 0xFF02;
 
 #[derive(Debug, thiserror::Error)]
@@ -89,6 +104,26 @@ impl<'a> Iterator for ToDo<'a> {
 }
 
 
+/// Prompt template configured in `cli.toml` (or the default one)
+fn prompt_template() -> Template {
+    let template = match config::read() {
+        Ok(config) => config.shell.prompt,
+        Err(e) => {
+            eprintln!("{} {:#}", "edgedb error:".red(), e);
+            None
+        }
+    };
+    template.and_then(|t| {
+        t.parse().map_err(|e| {
+            eprintln!("{} invalid `shell.prompt` in {}: {:#}",
+                "edgedb error:".red(),
+                config::path().map(|p| p.display().to_string())
+                    .unwrap_or_else(|_| "cli.toml".into()),
+                e);
+        }).ok()
+    }).unwrap_or_default()
+}
+
 pub fn main(options: Options) -> Result<(), anyhow::Error> {
     let (control_wr, control_rd) = channel(1);
     let (repl_wr, repl_rd) = channel(1);
@@ -99,20 +134,33 @@ pub fn main(options: Options) -> Result<(), anyhow::Error> {
         },
         print: print::Config::new()
             .max_items(100)
-            .colors(atty::is(atty::Stream::Stdout))
+            .max_output(print::DEFAULT_MAX_OUTPUT)
+            .colors(print::console::colors_stdout())
             .clone(),
-        verbose_errors: false,
+        verbose_errors: options.verbose_errors,
+        print_summary: true,
         last_error: None,
-        implicit_limit: Some(100),
+        implicit_limit: options.implicit_limit,
         output_mode: options.output_mode,
         input_mode: repl::InputMode::Emacs,
-        print_stats: repl::PrintStats::Off,
+        print_stats: if options.timing {
+            repl::PrintStats::Query
+        } else {
+            repl::PrintStats::Off
+        },
         history_limit: 10000,
         database: options.conn_params.get()?.get_database().into(),
+        instance_name: options.instance_name.clone(),
+        prompt_template: prompt_template(),
         conn_params: options.conn_params.clone(),
         last_version: None,
+        last_statement: None,
         connection: None,
         initial_text: "".into(),
+        schema_cache: SchemaCache::new(),
+        slow_query_threshold: None,
+        read_only: options.read_only,
+        sensitive: options.sensitive.clone(),
     };
     let handle = task::spawn(_main(options, state));
     prompt::main(repl_wr, control_rd)?;
@@ -120,18 +168,89 @@ pub fn main(options: Options) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Connects to the database, asking for password if authentication fails
+async fn connect(options: &Options, state: &mut repl::State)
+    -> anyhow::Result<Connection>
+{
+    let mut attempts = 0;
+    let mut new_password = None;
+    loop {
+        match state.conn_params.connect().await {
+            Ok(conn) => {
+                if let Some(password) = new_password {
+                    let user = state.conn_params.get()?.get_user()
+                        .to_string();
+                    if let Err(e) = save_password(options, &user, password) {
+                        eprintln!("{} cannot update credentials: {:#}",
+                                  "edgedb error:".red(), e);
+                    }
+                }
+                return Ok(conn);
+            }
+            Err(e) if options.password_prompt
+                && attempts < PASSWORD_ATTEMPTS
+                && is_authentication_error(&e)
+            => {
+                eprintln!("{} {:#}", "edgedb error:".red(), e);
+                let user = state.conn_params.get()?.get_user().to_string();
+                let password = rpassword::read_password_from_tty(
+                        Some(&format!("Password for '{}': ",
+                                      user.escape_default())))
+                    .context("error reading password")?;
+                state.conn_params.modify(|params| {
+                    params.password(password.clone());
+                });
+                new_password = Some(password);
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Offers to store the password that worked into the credentials file of
+/// the instance, so that it is not asked again next time
+fn save_password(options: &Options, user: &str, password: String)
+    -> anyhow::Result<()>
+{
+    let name = match &options.instance_name {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    if !atty::is(atty::Stream::Stdin) {
+        return Ok(());
+    }
+    let path = credentials::path(name)?;
+    let mut creds = read_credentials(&path)?;
+    if creds.user != user {
+        // password is for a different user than the stored one
+        return Ok(());
+    }
+    let q = question::Confirm::new(format!(
+        "Save the new password to the credentials of instance {:?}?",
+        name));
+    if !q.ask()? {
+        return Ok(());
+    }
+    creds.password = Some(password);
+    write_credentials(&path, &creds)?;
+    eprintln!("Credentials of {:?} are updated.", name);
+    Ok(())
+}
+
 pub async fn _main(options: Options, mut state: repl::State)
     -> anyhow::Result<()>
 {
-    let mut conn = state.conn_params.connect().await?;
+    let mut conn = connect(&options, &mut state).await?;
     let fetched_version = conn.get_version().await?;
     println!("{} {} (repl {})",
         "EdgeDB".light_gray(),
         fetched_version[..].light_gray(),
         env!("CARGO_PKG_VERSION"));
     state.last_version = Some(fetched_version);
-    println!("{}", r#"Type \help for help, \quit to quit."#.light_gray());
+    println!("{}", tr(r#"Type \help for help, \quit to quit."#).light_gray());
     state.set_history_limit(state.history_limit).await?;
+    state.update_database_names(&mut conn).await;
     state.connection = Some(conn);
     match _interactive_main(&options, &mut state).await {
         Ok(()) => return Ok(()),
@@ -175,10 +294,10 @@ fn _check_json_limit(json: &serde_json::Value, path: &mut String, limit: usize)
 }
 
 fn print_json_limit_error(path: &str) {
-    eprintln!("Error: Cannot render JSON result: {} is too long. \
-        Consider putting an explicit LIMIT clause, \
+    eprintln!("{}", tr_format("Error: Cannot render JSON result: {} is too \
+        long. Consider putting an explicit LIMIT clause, \
         or increase the implicit limit using `\\set limit`.",
-        if path.is_empty() { "." } else { path });
+        &[&if path.is_empty() { "." } else { path }]));
 }
 
 fn check_json_limit(json: &serde_json::Value, path: &str, limit: usize) -> bool
@@ -191,7 +310,8 @@ fn check_json_limit(json: &serde_json::Value, path: &str, limit: usize) -> bool
     return true;
 }
 
-async fn execute_backslash(mut state: &mut repl::State, text: &str)
+async fn execute_backslash(options: &Options, mut state: &mut repl::State,
+    text: &str)
     -> anyhow::Result<()>
 {
     use backslash::ExecuteResult::*;
@@ -202,8 +322,8 @@ async fn execute_backslash(mut state: &mut repl::State, text: &str)
             if e.help {
                 println!("{}", e.message);
             } else {
-                eprintln!("Error parsing backslash command: {}",
-                          e.message);
+                eprintln!("{}", tr_format(
+                    "Error parsing backslash command: {}", &[&e.message]));
             }
             // Quick-edit command on error
             state.initial_text = text.into();
@@ -218,12 +338,19 @@ async fn execute_backslash(mut state: &mut repl::State, text: &str)
             return Err(CleanShutdown)?;
         }
         Ok(Input(text)) => state.initial_text = text,
+        Ok(Execute(text)) => {
+            for statement in snippets::statements(&text) {
+                state.soft_reconnect().await?;
+                execute_query(options, state, statement).await?;
+            }
+        }
         Err(e) => {
             if e.is::<ExitCode>() {
                 // It's expected that command already printed all required
                 // messages, so ignoring it is safe
             } else {
-                eprintln!("Error executing command: {:#}", e);
+                eprintln!("{}", tr_format("Error executing command: {}",
+                                          &[&format!("{:#}", e)]));
                 // Quick-edit command on error
                 state.initial_text = text.into();
                 state.last_error = Some(e);
@@ -258,7 +385,7 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
 
     let start_prepare = Instant::now();
     let mut seq = cli.start_sequence().await?;
-    seq.send_messages(&[
+    let messages = [
         ClientMessage::Prepare(Prepare {
             headers,
             io_format: match state.output_mode {
@@ -271,10 +398,13 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
             command_text: String::from(statement),
         }),
         ClientMessage::Flush,
-    ]).await?;
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
 
     loop {
         let msg = seq.message().await?;
+        connect::trace_received(&msg);
         match msg {
             ServerMessage::PrepareComplete(..) => {
                 break;
@@ -290,23 +420,29 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
             }
         }
     }
+    let prepare_time = start_prepare.elapsed();
+    log::debug!("Prepare complete in {:?}", prepare_time);
     if state.print_stats == Detailed {
         eprintln!("{}",
-            format!("Prepare: {:?}", start_prepare.elapsed()).dark_gray());
+            format!("Prepare: {}", format::duration(prepare_time))
+            .dark_gray());
     }
 
     let start_describe = Instant::now();
-    seq.send_messages(&[
+    let messages = [
         ClientMessage::DescribeStatement(DescribeStatement {
             headers: HashMap::new(),
             aspect: DescribeAspect::DataDescription,
             statement_name: statement_name.clone(),
         }),
         ClientMessage::Flush,
-    ]).await?;
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
 
     let data_description = loop {
         let msg = seq.message().await?;
+        connect::trace_received(&msg);
         match msg {
             ServerMessage::CommandDataDescription(data_desc) => {
                 break data_desc;
@@ -324,12 +460,16 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
     };
     if state.print_stats == Detailed {
         eprintln!("{}",
-            format!("Describe: {:?}", start_describe.elapsed()).dark_gray());
+            format!("Describe: {}", format::duration(start_describe.elapsed()))
+            .dark_gray());
     }
+    log::debug!("Data description received in {:?}",
+        start_describe.elapsed());
     if options.debug_print_descriptors {
         println!("Descriptor: {:?}", data_description);
     }
     let desc = data_description.output()?;
+    let fields = summary::object_fields(&desc);
     let indesc = data_description.input()?;
     if options.debug_print_descriptors {
         println!("InputDescr {:#?}", indesc.descriptors());
@@ -345,7 +485,8 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
     }
 
     let first_part = start.elapsed();
-    let input = match input_variables(&indesc, &mut state.prompt).await {
+    let input = match input_variables(&indesc, &mut state.prompt,
+                                       &state.sensitive).await {
         Ok(input) => input,
         Err(e) => {
             eprintln!("{:#}", e);
@@ -359,19 +500,26 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
     let mut arguments = BytesMut::with_capacity(8);
     incodec.encode(&mut arguments, &input)?;
 
-    seq.send_messages(&[
+    let messages = [
         ClientMessage::Execute(Execute {
             headers: HashMap::new(),
             statement_name: statement_name.clone(),
             arguments: arguments.freeze(),
         }),
         ClientMessage::Sync,
-    ]).await?;
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
 
     let mut items = seq.response(codec);
     if desc.root_pos().is_none() {
         match items.get_completion().await {
-            Ok(ref val) => print::completion(val),
+            Ok(ref val) => {
+                state.schema_cache.command_complete(val);
+                state.log_slow_query(statement,
+                    first_part + start_execute.elapsed());
+                print::completion(val);
+            }
             Err(e) => {
                 eprintln!("Error: {}", e);
                 state.last_error = Some(e.into());
@@ -386,13 +534,14 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
         // update max_width each time
         cfg.max_width(w);
     }
-    match state.output_mode {
+    let rows = match state.output_mode {
         TabSeparated => {
             let mut index = 0;
             while let Some(row) = items.next().await.transpose()? {
                 if index == 0 && state.print_stats == Detailed {
                     eprintln!("{}",
-                        format!("First row: {:?}", start_execute.elapsed())
+                        format!("First row: {}",
+                            format::duration(start_execute.elapsed()))
                         .dark_gray()
                     );
                 }
@@ -418,13 +567,15 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
                 };
                 // trying to make writes atomic if possible
                 text += "\n";
+                tee::write(&text);
                 stdout().write_all(text.as_bytes()).await?;
                 index += 1;
             }
+            index
         }
         Default => {
-            match print::native_to_stdout(items, &cfg).await {
-                Ok(()) => {}
+            let rows = match print::native_to_stdout(items, &cfg).await {
+                Ok(rows) => rows,
                 Err(e) => {
                     match e {
                         PrintError::StreamErr {
@@ -439,15 +590,19 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
                     state.last_error = Some(e.into());
                     return Err(QueryError)?;
                 }
-            }
+            };
+            tee::write("\n");
             println!();
+            rows
         }
         Json => {
             let mut index = 0;
+            let mut rows = 0;
             while let Some(row) = items.next().await.transpose()? {
                 if index == 0 && state.print_stats == Detailed {
                     eprintln!("{}",
-                        format!("First row: {:?}", start_execute.elapsed())
+                        format!("First row: {}",
+                            format::duration(start_execute.elapsed()))
                         .dark_gray()
                     );
                 }
@@ -470,18 +625,22 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
                     .ok_or_else(|| anyhow::anyhow!(
                         "non-array returned from \
                          postgres in JSON mode"))?;
+                rows += jitems.len();
                 // trying to make writes atomic if possible
                 let mut data = print::json_to_string(jitems, &cfg)?;
                 data += "\n";
+                tee::write(&data);
                 stdout().write_all(data.as_bytes()).await?;
             }
+            rows
         }
         JsonElements => {
             let mut index = 0;
             while let Some(row) = items.next().await.transpose()? {
                 if index == 0 && state.print_stats == Detailed {
                     eprintln!("{}",
-                        format!("First row: {:?}", start_execute.elapsed())
+                        format!("First row: {}",
+                            format::duration(start_execute.elapsed()))
                         .dark_gray()
                     );
                 }
@@ -509,18 +668,32 @@ async fn execute_query(options: &Options, mut state: &mut repl::State,
                 let mut data;
                 data = print::json_item_to_string(&value, &cfg)?;
                 data += "\n";
+                tee::write(&data);
                 stdout().write_all(data.as_bytes()).await?;
                 index += 1;
             }
+            index
         }
+    };
+    if state.print_summary {
+        let summary = Summary {
+            rows,
+            fields,
+            elapsed: first_part + start_execute.elapsed(),
+        };
+        tee::write(&format!("{}\n", summary));
+        eprintln!("{}", summary.to_string().dark_gray());
     }
     if state.print_stats != Off {
         eprintln!("{}",
-            format!("Query time (including output formatting): {:?}",
-            first_part + start_execute.elapsed())
+            format!("Query time (including output formatting): {} \
+                (prepare: {})",
+                format::duration(first_part + start_execute.elapsed()),
+                format::duration(prepare_time))
             .dark_gray()
         );
     }
+    state.log_slow_query(statement, first_part + start_execute.elapsed());
     state.last_error = None;
     return Ok(());
 }
@@ -530,9 +703,13 @@ async fn _interactive_main(options: &Options, state: &mut repl::State)
 {
     let mut ctrlc = CtrlC::new()?;
     loop {
-        state.ensure_connection()
+        let result = state.ensure_connection()
             .race(async { ctrlc.next().await; Err(Interrupted)? })
-            .await?;
+            .await;
+        match result {
+            Err(e) if e.is::<Interrupted>() => return Err(CleanShutdown)?,
+            result => result?,
+        }
         let cur_initial = replace(&mut state.initial_text, String::new());
         let inp = match state.edgeql_input(&cur_initial).await? {
             prompt::Input::Eof => {
@@ -549,28 +726,48 @@ async fn _interactive_main(options: &Options, state: &mut repl::State)
         for item in ToDo::new(&inp) {
             let result = match item {
                 ToDoItem::Backslash(text) => {
-                    execute_backslash(state, text)
+                    execute_backslash(options, state, text)
                         .race(async { ctrlc.next().await; Err(Interrupted)?})
                         .await
                 }
                 ToDoItem::Query(statement) => {
-                    state.soft_reconnect()
-                        .race(async { ctrlc.next().await; Err(Interrupted)?})
-                        .await?;
-                    execute_query(options, state, statement)
+                    async {
+                        state.soft_reconnect().await?;
+                        state.last_statement = Some(statement.to_string());
+                        options.restrictions.check(statement)?;
+                        if !state.read_only {
+                            return execute_query(options, state, statement)
+                                .await;
+                        }
+                        read_only::check(statement)?;
+                        let started = read_only::begin(
+                            state.connection_mut(), statement).await?;
+                        let result = execute_query(options, state, statement)
+                            .await;
+                        read_only::finish(state.connection_mut(), started,
+                                          result).await
+                    }
                         .race(async { ctrlc.next().await; Err(Interrupted)?})
                         .await
                 }
             };
             if let Err(err) = result {
                 if err.is::<Interrupted>() {
-                    eprintln!("Interrupted.");
-                    state.reconnect()
+                    eprintln!("{}", tr("Interrupted."));
+                    // second Ctrl+C while cancelling exits the REPL
+                    let cancelled = state.cancel_query()
                         .race(async { ctrlc.next().await; Err(Interrupted)? })
-                        .await?;
+                        .await;
+                    match cancelled {
+                        Err(e) if e.is::<Interrupted>() => {
+                            return Err(CleanShutdown)?;
+                        }
+                        cancelled => cancelled?,
+                    }
                 } else if err.is::<CleanShutdown>() {
                     return Err(err)?;
                 } else if !err.is::<QueryError>() {
+                    tee::write(&format!("Error: {:#}\n", err));
                     eprintln!("Error: {:#}", err);
                 }
                 // Don't continue next statements on error
@@ -0,0 +1,114 @@
+//! Graceful handling of SIGINT and SIGTERM in long-running commands
+//!
+//! Once `Interrupt` is created, signals don't terminate the process. Instead,
+//! command stops at a point where it can finish or roll back the current
+//! unit of work, and partial output is removed by the guards being dropped.
+//! Since a handler is installed for the whole process, it should only be
+//! created by commands running from the command line (the REPL has its own
+//! handling of Ctrl+C).
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::future::pending;
+use async_std::prelude::FutureExt;
+
+
+#[derive(Debug, thiserror::Error)]
+#[error("interrupted")]
+pub struct Interrupted;
+
+pub struct Interrupt {
+    flag: Arc<AtomicBool>,
+    signals: Receiver<()>,
+}
+
+
+impl Interrupt {
+    pub fn new() -> anyhow::Result<Interrupt> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = bounded(1);
+        imp::listen(flag.clone(), tx)?;
+        Ok(Interrupt { flag, signals: rx })
+    }
+    /// Returns true if a signal has been received
+    pub fn is_set(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+    /// Waits until a signal is received (returns at once if already is)
+    pub async fn wait(&self) {
+        while !self.is_set() {
+            if self.signals.recv().await.is_err() {
+                // listener is gone, so no signal can be received anymore
+                pending::<()>().await;
+            }
+        }
+    }
+    /// Runs the future returning `Interrupted` error if signal is received
+    ///
+    /// The future is dropped when interrupted, so it should only be used
+    /// for the work that can be safely abandoned.
+    pub async fn run<F, T>(&self, f: F) -> anyhow::Result<T>
+        where F: Future<Output=anyhow::Result<T>>,
+    {
+        f.race(async {
+            self.wait().await;
+            Err(Interrupted)?
+        }).await
+    }
+}
+
+fn notify(flag: &AtomicBool, tx: &Sender<()>) {
+    log::info!("Interrupted by signal, stopping");
+    flag.store(true, Ordering::SeqCst);
+    // if channel is full, the waiter is already woken up
+    tx.try_send(()).ok();
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    use async_std::channel::Sender;
+    use signal_hook::iterator::Signals;
+
+    pub fn listen(flag: Arc<AtomicBool>, tx: Sender<()>)
+        -> anyhow::Result<()>
+    {
+        let signals = Signals::new(&[signal_hook::SIGINT,
+                                     signal_hook::SIGTERM])?;
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                super::notify(&flag, &tx);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use async_ctrlc::CtrlC;
+    use async_std::channel::Sender;
+    use async_std::prelude::StreamExt;
+    use async_std::task;
+
+    pub fn listen(flag: Arc<AtomicBool>, tx: Sender<()>)
+        -> anyhow::Result<()>
+    {
+        let mut ctrlc = CtrlC::new()?;
+        task::spawn(async move {
+            while let Some(()) = ctrlc.next().await {
+                super::notify(&flag, &tx);
+            }
+        });
+        Ok(())
+    }
+}
@@ -4,6 +4,10 @@ use crate::server::options::Command as Server;
 
 
 pub fn init(builder: &mut env_logger::Builder, opt: &Options) {
+    if let Some(level) = opt.log_level {
+        builder.filter_module("edgedb", level);
+        builder.filter_module("edgedb_client", level);
+    }
     if opt.debug_print_frames {
         builder.filter_module("edgedb::incoming::frame",
                               log::LevelFilter::Debug);
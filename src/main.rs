@@ -12,71 +12,123 @@ use clap::Clap;
 use crate::options::Options;
 
 mod async_util;
+mod bench;
 mod bug;
+mod capabilities;
+mod checksum;
+mod cloud;
 mod commands;
 mod completion;
+mod config;
 mod connect;
+mod crash_reports;
 mod credentials;
+mod debug_protocol;
+mod doctor;
 mod error_display;
 mod format;
+mod graphql;
 mod highlight;
 mod hint;
+mod http;
+mod i18n;
+mod info;
 mod interactive;
+mod interrupt;
 mod log_levels;
+mod man;
 mod migrations;
 mod non_interactive;
 mod options;
 mod outputs;
+mod ping;
 mod platform;
+mod plugins;
 mod print;
 mod process;
 mod project;
 mod prompt;
+mod prompt_template;
 mod question;
+mod read_only;
 mod repl;
+mod restrict;
+mod result_diff;
+mod retry;
+mod schema_cache;
+mod secrets;
 mod self_install;
 mod self_upgrade;
 mod server;
+mod server_proxy;
+mod shell_completion;
+mod shell_env;
+mod slowlog;
+mod snippets;
 mod statement;
+mod summary;
 mod table;
+mod tee;
+mod telemetry;
+mod ui;
 mod variables;
 mod version_check;
 
 fn main() {
-    match _main() {
-        Ok(()) => {}
-        Err(ref e) => {
-            let mut err = e;
-            let mut code = 1;
-            if let Some(e) = err.downcast_ref::<commands::ExitCode>() {
-                e.exit();
-            }
-            if let Some(arc) = err.downcast_ref::<hint::ArcError>() {
-                // prevent duplicate error message
-                err = arc.inner();
-            }
-            eprintln!("edgedb error: {:#}", err);
-            for item in err.chain() {
-                if let Some(e) = item.downcast_ref::<hint::HintedError>() {
-                    eprintln!("  Hint: {}", e.hint
-                        .lines()
-                        .collect::<Vec<_>>()
-                        .join("\n        "));
-                } else if item.is::<bug::Bug>() {
-                    eprintln!("  Hint: This is most likely a bug in EdgeDB \
-                        or command-line tools. Please consider opening an \
-                        issue ticket at \
-                        https://github.com/edgedb/edgedb-cli/issues/new\
-                        ?template=bug_report.md");
-                    code = 13;
-                } else if let Some(e) = e.downcast_ref::<commands::ExitCode>()
-                {
-                    code = e.code();
-                }
-            }
-            exit(code);
+    let console = print::console::init();
+    let code = match _main() {
+        Ok(()) => return,
+        Err(ref e) => report_error(e),
+    };
+    // `exit` doesn't run destructors
+    drop(console);
+    exit(code);
+}
+
+/// Prints the error and returns exit code of the process
+fn report_error(e: &anyhow::Error) -> i32 {
+    let mut err = e;
+    let mut code = 1;
+    if let Some(e) = err.downcast_ref::<commands::ExitCode>() {
+        return e.code();
+    }
+    if let Some(arc) = err.downcast_ref::<hint::ArcError>() {
+        // prevent duplicate error message
+        err = arc.inner();
+    }
+    if error_display::json_errors() {
+        error_display::print_json_error(err);
+        if err.chain().any(|item| item.is::<bug::Bug>()) {
+            code = 13;
+        }
+        return code;
+    }
+    eprintln!("edgedb error: {:#}", err);
+    tee::write(&format!("edgedb error: {:#}\n", err));
+    let label = i18n::tr("Hint:");
+    // continuation lines are aligned with the first one
+    let indent = format!("\n{:1$}", "", label.chars().count() + 3);
+    for item in err.chain() {
+        if let Some(e) = item.downcast_ref::<hint::HintedError>() {
+            eprintln!("  {} {}", label, e.hint
+                .lines()
+                .collect::<Vec<_>>()
+                .join(&indent));
+        } else if item.is::<bug::Bug>() {
+            eprintln!("  {} {}", label, i18n::tr_format(
+                "This is most likely a bug in EdgeDB \
+                or command-line tools. Please consider opening an \
+                issue ticket at {}",
+                &[&"https://github.com/edgedb/edgedb-cli/issues/new\
+                    ?template=bug_report.md"]));
+            crash_reports::save(crash_reports::Kind::Bug,
+                format!("{:#}", err), format!("{:?}", err));
+            code = 13;
+        } else if let Some(e) = e.downcast_ref::<commands::ExitCode>() {
+            code = e.code();
         }
     }
+    code
 }
 
 fn _main() -> anyhow::Result<()> {
@@ -84,6 +136,7 @@ fn _main() -> anyhow::Result<()> {
     // to ease bug reporting and troubleshooting.
     // TODO: consider removing this once EdgeDB reaches 1.0 stable.
     env::set_var("RUST_BACKTRACE", "1");
+    crash_reports::install_hook();
 
     if let Some(arg0) = std::env::args_os().next() {
         if let Some(exe_name) = Path::new(&arg0).file_name() {
@@ -94,23 +147,34 @@ fn _main() -> anyhow::Result<()> {
         }
     }
 
+    if plugins::print_help()? {
+        return Ok(());
+    }
     let opt = Options::from_args_and_env()?;
 
     let mut builder = env_logger::Builder::from_env(
         env_logger::Env::default().default_filter_or("warn")
     );
     log_levels::init(&mut builder, &opt);
-    builder.init();
+    crash_reports::init_logger(&mut builder);
+    if let Some(path) = &opt.tee {
+        tee::open(path)?;
+    }
 
     version_check::check(opt.no_version_check);
+    telemetry::flush_in_background();
 
-    if opt.subcommand.is_some() {
+    let result = if opt.subcommand.is_some() {
         commands::cli::main(opt)
+    } else if opt.script.is_some() {
+        task::block_on(non_interactive::script(opt))
     } else {
         if opt.interactive {
             interactive::main(opt)
         } else {
             task::block_on(non_interactive::main(opt))
         }
-    }
+    };
+    telemetry::record(&result);
+    result
 }
@@ -0,0 +1,157 @@
+//! Rendering of troff manual pages from the clap definitions
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{App, AppSettings, ArgSettings, Clap, IntoApp, ValueHint};
+
+use crate::options::RawOptions;
+
+
+#[derive(Clap, Clone, Debug)]
+pub struct GenMan {
+    /// Directory to write `edgedb*.1` pages to
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub out_dir: PathBuf,
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for line in text.lines() {
+        let line = line.trim_end()
+            .replace('\\', "\\e")
+            .replace('-', "\\-");
+        // lines starting with control characters are treated as requests
+        if line.starts_with('.') || line.starts_with('\'') {
+            result.push_str("\\&");
+        }
+        if line.is_empty() {
+            result.push_str(".PP");
+        } else {
+            result.push_str(&line);
+        }
+        result.push('\n');
+    }
+    result
+}
+
+fn is_hidden(app: &App) -> bool {
+    app.is_set(AppSettings::Hidden) || app.get_name().starts_with('_')
+}
+
+fn render(app: &App, command: &[&str]) -> String {
+    let page = command.join("-");
+    let mut out = String::new();
+    writeln!(out, ".TH {} 1 \"\" \"edgedb {}\" \"EdgeDB Manual\"",
+        page.to_uppercase().replace('-', "\\-"),
+        env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(out, ".SH NAME").unwrap();
+    writeln!(out, "{} \\- {}", page.replace('-', "\\-"),
+        escape(app.get_about().unwrap_or("")).trim_end()).unwrap();
+
+    let subcommands = app.get_subcommands()
+        .filter(|sub| !is_hidden(sub))
+        .collect::<Vec<_>>();
+    let (options, positional): (Vec<_>, Vec<_>) = app.get_arguments()
+        .filter(|arg| !arg.is_set(ArgSettings::Hidden))
+        .partition(|arg| arg.get_short().is_some()
+                         || arg.get_long().is_some());
+    writeln!(out, ".SH SYNOPSIS").unwrap();
+    write!(out, ".B {}\n[OPTIONS]", command.join(" ")).unwrap();
+    for arg in &positional {
+        write!(out, " \\fI{}\\fR", arg.get_name().to_uppercase()).unwrap();
+    }
+    if !subcommands.is_empty() {
+        write!(out, " [SUBCOMMAND]").unwrap();
+    }
+    writeln!(out).unwrap();
+
+    if let Some(about) = app.get_long_about().or(app.get_about()) {
+        writeln!(out, ".SH DESCRIPTION").unwrap();
+        out.push_str(&escape(about));
+    }
+    if !options.is_empty() {
+        writeln!(out, ".SH OPTIONS").unwrap();
+        for arg in &options {
+            let mut flags = Vec::new();
+            if let Some(short) = arg.get_short() {
+                flags.push(format!("\\fB\\-{}\\fR", short));
+            }
+            if let Some(long) = arg.get_long() {
+                flags.push(format!("\\fB\\-\\-{}\\fR", escape(long).trim()));
+            }
+            write!(out, ".TP\n{}", flags.join(", ")).unwrap();
+            if arg.is_set(ArgSettings::TakesValue) {
+                write!(out, " \\fI{}\\fR", arg.get_name().to_uppercase())
+                    .unwrap();
+            }
+            writeln!(out).unwrap();
+            let about = arg.get_long_about().or(arg.get_about());
+            out.push_str(&escape(about.unwrap_or("")));
+        }
+    }
+    if !positional.is_empty() {
+        writeln!(out, ".SH ARGUMENTS").unwrap();
+        for arg in &positional {
+            writeln!(out, ".TP\n\\fI{}\\fR", arg.get_name().to_uppercase())
+                .unwrap();
+            out.push_str(&escape(arg.get_about().unwrap_or("")));
+        }
+    }
+    if !subcommands.is_empty() {
+        writeln!(out, ".SH SUBCOMMANDS").unwrap();
+        for sub in &subcommands {
+            writeln!(out, ".TP\n\\fB{}\\-{}\\fR(1)",
+                page.replace('-', "\\-"),
+                sub.get_name().replace('-', "\\-")).unwrap();
+            out.push_str(&escape(sub.get_about().unwrap_or("")));
+        }
+    }
+    if command.len() > 1 {
+        writeln!(out, ".SH SEE ALSO").unwrap();
+        writeln!(out, "\\fB{}\\fR(1)",
+            command[..command.len()-1].join("-").replace('-', "\\-"))
+            .unwrap();
+    }
+    out
+}
+
+fn write_pages(app: &App, command: &mut Vec<String>, dir: &Path)
+    -> anyhow::Result<()>
+{
+    let words = command.iter().map(|w| &w[..]).collect::<Vec<_>>();
+    let path = dir.join(format!("{}.1", words.join("-")));
+    fs::write(&path, render(app, &words))
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    for sub in app.get_subcommands().filter(|sub| !is_hidden(sub)) {
+        command.push(sub.get_name().to_string());
+        write_pages(sub, command, dir)?;
+        command.pop();
+    }
+    Ok(())
+}
+
+/// Writes pages for `edgedb` and all of its subcommands into the directory
+pub fn write_all(dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("cannot create {}", dir.display()))?;
+    let app = RawOptions::into_app();
+    write_pages(&app, &mut vec!["edgedb".into()], dir)
+}
+
+pub fn gen_man(options: &GenMan) -> anyhow::Result<()> {
+    write_all(&options.out_dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("a-b \\ c"), "a\\-b \\e c\n");
+        assert_eq!(escape(".dot\n\nnext"), "\\&.dot\n.PP\nnext\n");
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context as _;
+use edgedb_client::client::Connection;
+use edgedb_protocol::server_message::ErrorResponse;
+use edgedb_protocol::value::Value;
+use edgeql_parser::schema_file::validate;
+use edgeql_parser::tokenizer::TokenStream;
+
+use crate::commands::ExitCode;
+use crate::commands::parser::Check;
+use crate::migrations::context::Context;
+use crate::migrations::create::{execute, execute_start_migration};
+use crate::migrations::create::SourceName;
+use crate::migrations::grammar::parse_migration;
+use crate::migrations::lint::list_files;
+use crate::migrations::migrate::skip_revisions;
+use crate::migrations::migration;
+use crate::migrations::print_error::print_migration_error;
+use crate::migrations::source_map::Builder;
+
+
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    pub offset: usize,
+    pub message: String,
+}
+
+/// Returns 1-based line and column of the byte `offset` in `text`
+pub fn location(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map(|p| p + 1).unwrap_or(0);
+    (before.matches('\n').count() + 1,
+     before[line_start..].chars().count() + 1)
+}
+
+/// Checks that the text tokenizes and brackets are balanced
+pub fn check_tokens(text: &str) -> Result<(), SyntaxError> {
+    let mut open = Vec::new();
+    let mut last_end = 0;
+    for item in &mut TokenStream::new(text) {
+        let tok = match item {
+            Ok(tok) => tok,
+            Err(e) => return Err(SyntaxError {
+                offset: last_end,
+                message: e.to_string(),
+            }),
+        };
+        let offset = tok.start.offset as usize;
+        last_end = tok.end.offset as usize;
+        let expected = match tok.token.value {
+            "(" | "[" | "{" => {
+                open.push((tok.token.value, offset));
+                continue;
+            }
+            ")" => "(",
+            "]" => "[",
+            "}" => "{",
+            _ => continue,
+        };
+        match open.pop() {
+            Some((value, _)) if value == expected => {}
+            Some((value, start)) => {
+                let (line, column) = location(text, start);
+                return Err(SyntaxError {
+                    offset,
+                    message: format!("`{}` doesn't match `{}` at {}:{}",
+                        tok.token.value, value, line, column),
+                });
+            }
+            None => return Err(SyntaxError {
+                offset,
+                message: format!("unexpected `{}`", tok.token.value),
+            }),
+        }
+    }
+    if let Some((value, start)) = open.pop() {
+        return Err(SyntaxError {
+            offset: start,
+            message: format!("`{}` is never closed", value),
+        });
+    }
+    Ok(())
+}
+
+fn report(path: &Path, text: &str, err: SyntaxError) -> anyhow::Error {
+    let (line, column) = location(text, err.offset);
+    eprintln!("{}:{}:{}: error: {}", path.display(), line, column,
+              err.message);
+    ExitCode::new(1).into()
+}
+
+fn read(path: &Path) -> anyhow::Result<String> {
+    fs::read_to_string(path)
+        .with_context(|| format!("cannot read {}", path.display()))
+}
+
+fn check_schema_file(path: &Path) -> anyhow::Result<()> {
+    let text = read(path)?;
+    check_tokens(&text).map_err(|e| report(path, &text, e))?;
+    if let Err(e) = validate(&text) {
+        eprintln!("{}: error: {}", path.display(), e);
+        return Err(ExitCode::new(1))?;
+    }
+    Ok(())
+}
+
+fn check_migration_file(path: &Path) -> anyhow::Result<()> {
+    let text = read(path)?;
+    check_tokens(&text).map_err(|e| report(path, &text, e))?;
+    if let Err(e) = parse_migration(&text) {
+        eprintln!("{}: error: {:#}", path.display(), e);
+        return Err(ExitCode::new(1))?;
+    }
+    Ok(())
+}
+
+/// Parses schema and migration files, stopping at the first error
+pub async fn check(options: &Check) -> anyhow::Result<()> {
+    let ctx = Context::from_config(&options.cfg)?;
+    let mut files = 0;
+    for dir in &ctx.sdl_dirs {
+        for path in list_files(dir, ".esdl")? {
+            check_schema_file(&path)?;
+            files += 1;
+        }
+    }
+    let migrations_dir = ctx.schema_dir.join("migrations");
+    for path in list_files(&migrations_dir, ".edgeql")? {
+        check_migration_file(&path)?;
+        files += 1;
+    }
+    // hashes and the order of revisions
+    if let Err(e) = migration::read_all(&ctx, true).await {
+        eprintln!("error: {:#}", e);
+        return Err(ExitCode::new(1))?;
+    }
+    if !options.quiet {
+        eprintln!("Checked {} files", files);
+    }
+    Ok(())
+}
+
+async fn compile(ctx: &Context, cli: &mut Connection)
+    -> anyhow::Result<()>
+{
+    let mut migrations = migration::read_all(ctx, true).await?;
+    let db_migration: Option<String> = cli.query_row_opt(r###"
+            WITH Last := (SELECT schema::Migration
+                          FILTER NOT EXISTS .<parents[IS schema::Migration])
+            SELECT name := Last.name
+        "###, &Value::empty_tuple()).await?;
+    if let Some(db_migration) = &db_migration {
+        skip_revisions(&mut migrations, db_migration)?;
+    }
+    for (_, migration) in migrations {
+        let text = read(migration.path.as_ref())?;
+        if let Err(e) = execute(cli, &text).await {
+            return match e.downcast::<ErrorResponse>() {
+                Ok(e) => {
+                    let (_, source_map) = Builder::new()
+                        .add_lines(SourceName::File(migration.path), &text)
+                        .done();
+                    print_migration_error(&e, &source_map)?;
+                    Err(ExitCode::new(1).into())
+                }
+                Err(e) => Err(e),
+            };
+        }
+    }
+    execute_start_migration(ctx, cli).await
+        .map_err(|e| {
+            eprintln!("error: {:#}", e);
+            ExitCode::new(1).into()
+        })
+}
+
+/// Applies pending migrations and the schema in a transaction that is
+/// rolled back, so the server reports errors without changing the database
+pub async fn check_compile(cli: &mut Connection, options: &Check)
+    -> anyhow::Result<()>
+{
+    let ctx = Context::from_config(&options.cfg)?;
+    cli.execute("START TRANSACTION").await?;
+    let result = compile(&ctx, cli).await;
+    cli.execute("ROLLBACK").await?;
+    result?;
+    if !options.quiet {
+        eprintln!("Schema compiles on the server");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_tokens, location};
+
+    #[test]
+    fn locations() {
+        let text = "module default {\n  type User;\n}\n";
+        assert_eq!(location(text, 0), (1, 1));
+        assert_eq!(location(text, 19), (2, 3));
+        assert_eq!(location(text, text.len()), (4, 1));
+    }
+
+    #[test]
+    fn brackets() {
+        assert!(check_tokens("type User { property name -> str; }").is_ok());
+        let err = check_tokens("type User {\n  link x := (.y;\n}")
+            .unwrap_err();
+        assert_eq!(err.offset, 29);
+        assert_eq!(err.message, "`}` doesn't match `(` at 2:13");
+        let err = check_tokens("type User {").unwrap_err();
+        assert_eq!(err.message, "`{` is never closed");
+        assert!(check_tokens("}").is_err());
+    }
+}
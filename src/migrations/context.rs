@@ -1,16 +1,33 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::commands::parser::MigrationConfig;
+use crate::project;
 
 
 pub struct Context {
     pub schema_dir: PathBuf,
+    /// Directories of `*.esdl` files
+    pub sdl_dirs: Vec<PathBuf>,
 }
 
 impl Context {
-    pub fn from_config(cfg: &MigrationConfig) -> Context {
+    pub fn for_dir(schema_dir: &Path) -> Context {
         Context {
-            schema_dir: cfg.schema_dir.clone(),
+            schema_dir: schema_dir.into(),
+            sdl_dirs: vec![schema_dir.into()],
         }
     }
+    pub fn from_config(cfg: &MigrationConfig) -> anyhow::Result<Context> {
+        if let Some(dir) = &cfg.schema_dir {
+            return Ok(Context::for_dir(dir));
+        }
+        if let Some(dir) = project::project_dir_opt(None)? {
+            let config = project::config::read(&dir.join("edgedb.toml"))?;
+            return Ok(Context {
+                schema_dir: config.project.schema_dir,
+                sdl_dirs: config.project.sdl_dirs,
+            });
+        }
+        Ok(Context::for_dir(Path::new("./dbschema")))
+    }
 }
@@ -17,21 +17,25 @@ use edgeql_parser::tokenizer::{TokenStream, Kind as TokenKind};
 use edgeql_parser::schema_file::validate;
 use fn_error_context::context;
 use immutable_chunkmap::set::Set;
+use linked_hash_map::LinkedHashMap;
 use rustyline::error::ReadlineError;
 use serde::Deserialize;
 
 use crate::bug;
+use crate::capabilities::{self, Feature};
 use crate::commands::parser::CreateMigration;
 use crate::commands::{Options, ExitCode};
 use crate::error_display::print_query_error;
 use crate::migrations::context::Context;
+use crate::migrations::dev_mode;
 use crate::migrations::migration;
 use crate::migrations::print_error::print_migration_error;
 use crate::migrations::prompt;
 use crate::migrations::source_map::{Builder, SourceMap};
 use crate::platform::tmp_file_name;
+use crate::project::seed;
 
-const SAFE_CONFIDENCE: f64 = 0.99999;
+pub const SAFE_CONFIDENCE: f64 = 0.99999;
 
 pub enum SourceName {
     Prefix,
@@ -85,7 +89,7 @@ pub struct CurrentMigration {
 #[error("refused to input data required for placeholder")]
 struct Refused;
 
-async fn execute(cli: &mut Connection, text: impl AsRef<str>)
+pub async fn execute(cli: &mut Connection, text: impl AsRef<str>)
     -> anyhow::Result<()>
 {
     let text = text.as_ref();
@@ -94,7 +98,7 @@ async fn execute(cli: &mut Connection, text: impl AsRef<str>)
     Ok(())
 }
 
-async fn query_row<R>(cli: &mut Connection, text: &str)
+pub async fn query_row<R>(cli: &mut Connection, text: &str)
     -> anyhow::Result<R>
     where R: Queryable
 {
@@ -152,14 +156,13 @@ h or ? - print help
     }
 }
 
-#[context("could not read schema in {}", ctx.schema_dir.display())]
-async fn gen_start_migration(ctx: &Context)
-    -> anyhow::Result<(String, SourceMap<SourceName>)>
+#[context("could not read schema in {}", dir.display())]
+async fn add_schema_dir(bld: &mut Builder<SourceName>,
+    dir: &std::path::Path)
+    -> anyhow::Result<()>
 {
-    let mut bld = Builder::new();
-    bld.add_lines(SourceName::Prefix, "START MIGRATION TO {");
-    let mut dir = fs::read_dir(&ctx.schema_dir).await?;
-    while let Some(item) = dir.next().await.transpose()? {
+    let mut items = fs::read_dir(dir).await?;
+    while let Some(item) = items.next().await.transpose()? {
         let fname = item.file_name();
         let lossy_name = fname.to_string_lossy();
         if lossy_name.starts_with(".") || !lossy_name.ends_with(".esdl")
@@ -172,6 +175,21 @@ async fn gen_start_migration(ctx: &Context)
         bld.add_lines(SourceName::File(path.clone()), &chunk);
         bld.add_lines(SourceName::Semicolon(path), ";");
     }
+    Ok(())
+}
+
+async fn gen_start_migration(ctx: &Context, seeds: bool)
+    -> anyhow::Result<(String, SourceMap<SourceName>)>
+{
+    let mut bld = Builder::new();
+    bld.add_lines(SourceName::Prefix, "START MIGRATION TO {");
+    for dir in &ctx.sdl_dirs {
+        add_schema_dir(&mut bld, dir).await?;
+    }
+    if seeds {
+        // keep the type recording applied seeds
+        bld.add_lines(SourceName::Prefix, seed::SCHEMA);
+    }
     bld.add_lines(SourceName::Suffix, "};");
     Ok(bld.done())
 }
@@ -179,7 +197,8 @@ async fn gen_start_migration(ctx: &Context)
 pub async fn execute_start_migration(ctx: &Context, cli: &mut Connection)
     -> anyhow::Result<()>
 {
-    let (text, source_map) = gen_start_migration(&ctx).await?;
+    let seeds = seed::has_seeds(&ctx.schema_dir.join("seeds"))?;
+    let (text, source_map) = gen_start_migration(&ctx, seeds).await?;
     match execute(cli, text).await {
         Ok(_) => Ok(()),
         Err(e) => match e.downcast::<ErrorResponse>() {
@@ -457,8 +476,13 @@ pub async fn create(cli: &mut Connection, _options: &Options,
     create: &CreateMigration)
     -> Result<(), anyhow::Error>
 {
-    let ctx = Context::from_config(&create.cfg);
+    let ctx = Context::from_config(&create.cfg)?;
     let migrations = migration::read_all(&ctx, true).await?;
+    if capabilities::supports(cli, Feature::DevMode).await?
+        && dev_mode::has_dev_migrations(cli).await?
+    {
+        return squash_dev_mode(&ctx, cli, &migrations, create).await;
+    }
     execute_start_migration(&ctx, cli).await?;
     let descr = query_row::<CurrentMigration>(cli,
         "DESCRIBE CURRENT MIGRATION AS JSON",
@@ -489,6 +513,47 @@ pub async fn create(cli: &mut Connection, _options: &Options,
     Ok(())
 }
 
+/// Creates migration file for changes applied in dev mode
+///
+/// The history of the database is rewritten, so the migrations applied in
+/// dev mode are replaced by the migration file created.
+async fn squash_dev_mode(ctx: &Context, cli: &mut Connection,
+    migrations: &LinkedHashMap<String, migration::MigrationFile>,
+    create: &CreateMigration)
+    -> Result<(), anyhow::Error>
+{
+    execute(cli, "START MIGRATION REWRITE").await?;
+    let result: anyhow::Result<()> = async {
+        dev_mode::apply_files(cli, migrations).await?;
+        execute_start_migration(ctx, cli).await?;
+        let key = migrations.len() as u64 + 1;
+        let exec = if create.non_interactive {
+            run_non_interactive(ctx, cli, key, create).await
+        } else {
+            run_interactive(ctx, cli, key, create).await
+        };
+        let abort = execute(cli, "ABORT MIGRATION").await;
+        exec.and(abort)?;
+        // apply the new file in place of the dev mode migrations
+        let created = migration::read_all(ctx, true).await?;
+        let new = created.values().last()
+            .filter(|_| created.len() > migrations.len())
+            .ok_or_else(|| bug::error("migration file is not created"))?;
+        let data = fs::read_to_string(&new.path).await?;
+        execute(cli, data).await
+    }.await;
+    match result {
+        Ok(()) => {
+            execute(cli, "COMMIT MIGRATION REWRITE").await?;
+            Ok(())
+        }
+        Err(e) => {
+            execute(cli, "ABORT MIGRATION REWRITE").await.ok();
+            Err(e)
+        }
+    }
+}
+
 fn add_newline_after_comment(value: &mut String) -> Result<(), anyhow::Error> {
     let last_token = TokenStream::new(value).last()
         .ok_or_else(|| bug::error("input should not be empty"))?
@@ -0,0 +1,162 @@
+use anyhow::Context as _;
+use async_std::fs;
+use edgedb_client::client::Connection;
+use linked_hash_map::LinkedHashMap;
+
+use crate::capabilities::{self, Feature};
+use crate::migrations::context::Context;
+use crate::migrations::create::{execute, query_row, execute_start_migration};
+use crate::migrations::create::{CurrentMigration, SAFE_CONFIDENCE};
+use crate::migrations::log::db_revisions;
+use crate::migrations::migration::{self, MigrationFile};
+
+
+/// Returns `true` if some migrations in the database were applied in dev
+/// mode, i.e. they have no migration files
+pub async fn has_dev_migrations(cli: &mut Connection)
+    -> anyhow::Result<bool>
+{
+    query_row::<bool>(cli, r###"
+        SELECT EXISTS (
+            SELECT schema::Migration
+            FILTER .generated_by = schema::MigrationGeneratedBy.DevMode
+        )
+    "###).await
+}
+
+/// Applies migration files, must be run within `START MIGRATION REWRITE`
+pub async fn apply_files(cli: &mut Connection,
+    migrations: &LinkedHashMap<String, MigrationFile>)
+    -> anyhow::Result<()>
+{
+    for (_, migration) in migrations {
+        let data = fs::read_to_string(&migration.path).await
+            .context("error re-reading migration file")?;
+        execute(cli, data).await.with_context(|| {
+            format!("cannot apply {}", migration.path.display())
+        })?;
+    }
+    Ok(())
+}
+
+/// Applies schema from the filesystem directly to the database
+///
+/// No migration files are written. Instead history of the database is
+/// rewritten to contain all the migration files followed by a single
+/// migration marked as generated in dev mode, so that `create-migration`
+/// can replace it with a real migration later. Servers without dev mode
+/// get a plain migration instead. Returns `false` if schema in the database
+/// is already up to date.
+pub async fn migrate(ctx: &Context, cli: &mut Connection, allow_unsafe: bool)
+    -> anyhow::Result<bool>
+{
+    if !capabilities::supports(cli, Feature::DevMode).await? {
+        return apply_schema(ctx, cli, false, allow_unsafe).await;
+    }
+    let migrations = migration::read_all(ctx, true).await?;
+    let old_revisions = db_revisions(cli).await?;
+    execute(cli, "START MIGRATION REWRITE").await?;
+    let result: anyhow::Result<_> = async {
+        apply_files(cli, &migrations).await?;
+        apply_schema(ctx, cli, true, allow_unsafe).await?;
+        Ok(db_revisions(cli).await? != old_revisions)
+    }.await;
+    match result {
+        Ok(true) => {
+            execute(cli, "COMMIT MIGRATION REWRITE").await?;
+            Ok(true)
+        }
+        Ok(false) => {
+            execute(cli, "ABORT MIGRATION REWRITE").await?;
+            Ok(false)
+        }
+        Err(e) => {
+            execute(cli, "ABORT MIGRATION REWRITE").await.ok();
+            Err(e)
+        }
+    }
+}
+
+async fn apply_schema(ctx: &Context, cli: &mut Connection,
+    dev_mode: bool, allow_unsafe: bool)
+    -> anyhow::Result<bool>
+{
+    execute_start_migration(ctx, cli).await?;
+    let result: anyhow::Result<_> = async {
+        if dev_mode {
+            execute(cli,
+                "SET generated_by := schema::MigrationGeneratedBy.DevMode"
+            ).await?;
+        }
+        apply_proposals(cli, allow_unsafe).await
+    }.await;
+    match result {
+        Ok(true) => {
+            execute(cli, "COMMIT MIGRATION").await?;
+            Ok(true)
+        }
+        Ok(false) => {
+            execute(cli, "ABORT MIGRATION").await?;
+            Ok(false)
+        }
+        Err(e) => {
+            execute(cli, "ABORT MIGRATION").await.ok();
+            Err(e)
+        }
+    }
+}
+
+async fn apply_proposals(cli: &mut Connection, allow_unsafe: bool)
+    -> anyhow::Result<bool>
+{
+    loop {
+        let data = query_row::<CurrentMigration>(cli,
+            "DESCRIBE CURRENT MIGRATION AS JSON"
+        ).await?;
+        if data.complete {
+            return Ok(!data.confirmed.is_empty());
+        }
+        let proposal = match data.proposed {
+            Some(proposal) => proposal,
+            None => anyhow::bail!("Server could not figure out \
+                migration automatically. Please run \
+                `edgedb create-migration` to confirm changes"),
+        };
+        if proposal.confidence < SAFE_CONFIDENCE && !allow_unsafe {
+            eprintln!("Schema change requires confirmation:");
+            for statement in &proposal.statements {
+                for line in statement.text.lines() {
+                    eprintln!("    {}", line);
+                }
+            }
+            anyhow::bail!("Confidence is {} (minimum is {}). \
+                Please run `edgedb create-migration` to confirm changes",
+                proposal.confidence, SAFE_CONFIDENCE);
+        }
+        if !proposal.required_user_input.is_empty() {
+            anyhow::bail!("cannot apply schema change without user input, \
+                please run `edgedb create-migration`");
+        }
+        for statement in proposal.statements {
+            execute(cli, &statement.text).await?;
+        }
+    }
+}
+
+/// Removes all the schema objects (and so all the data) in the database
+pub async fn wipe(cli: &mut Connection) -> anyhow::Result<()> {
+    if capabilities::supports(cli, Feature::ResetSchema).await? {
+        return execute(cli, "RESET SCHEMA TO initial").await;
+    }
+    execute(cli, "START MIGRATION TO {}").await?;
+    match apply_proposals(cli, true).await {
+        Ok(_) => {
+            execute(cli, "COMMIT MIGRATION").await?;
+            Ok(())
+        }
+        Err(e) => {
+            execute(cli, "ABORT MIGRATION").await.ok();
+            Err(e)
+        }
+    }
+}
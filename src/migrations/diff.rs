@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use async_std::task;
+use colorful::Colorful;
+use edgedb_client::client::Connection;
+use edgeql_parser::helpers::quote_name;
+use edgeql_parser::schema_file::validate;
+use edgeql_parser::tokenizer::TokenStream;
+use rand::{Rng, SeedableRng};
+
+use crate::commands::{self, ExitCode, Location};
+use crate::commands::parser::{Schema, SchemaCommand, SchemaDiff};
+use crate::connect::Connector;
+use crate::credentials;
+use crate::migrations::create::{execute, query_row, CurrentMigration};
+use crate::migrations::lint::list_files;
+use crate::options::Options;
+use crate::print;
+
+const DATABASE_SUFFIX_LENGTH: usize = 8;
+const DATABASE_SUFFIX_CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+
+enum Source {
+    Files(Vec<PathBuf>),
+    Database(Location),
+}
+
+fn source(spec: &str, options: &Options) -> anyhow::Result<Source> {
+    let path = Path::new(spec);
+    if path.is_dir() {
+        let files = list_files(path, ".esdl")?;
+        if files.is_empty() {
+            anyhow::bail!("no .esdl files in {}", path.display());
+        }
+        Ok(Source::Files(files))
+    } else if spec.ends_with(".esdl") {
+        Ok(Source::Files(vec![path.into()]))
+    } else if spec.contains('/') {
+        Ok(Source::Database(commands::location(spec, options)?))
+    } else {
+        let name = credentials::resolve_name(spec)?;
+        let conn_params = Connector::new(credentials::get_connector(&name));
+        let database = conn_params.get()?.get_database().into();
+        Ok(Source::Database(Location { conn_params, database }))
+    }
+}
+
+async fn read_sdl(source: Source) -> anyhow::Result<String> {
+    match source {
+        Source::Files(files) => {
+            let mut sdl = String::new();
+            for path in files {
+                let text = async_std::fs::read_to_string(&path).await
+                    .with_context(|| format!("cannot read {}",
+                                             path.display()))?;
+                validate(&text).with_context(|| format!(
+                    "could not read schema file {}", path.display()))?;
+                sdl.push_str(&text);
+                sdl.push_str(";\n");
+            }
+            Ok(sdl)
+        }
+        Source::Database(mut location) => {
+            let database = location.database.clone();
+            location.conn_params.modify(|p| { p.database(&database); });
+            let mut cli = location.conn_params.connect().await
+                .with_context(|| format!("cannot connect to database {:?}",
+                                         database))?;
+            query_row::<String>(&mut cli, "DESCRIBE SCHEMA AS SDL").await
+        }
+    }
+}
+
+fn temporary_name() -> String {
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    let suffix: String = (0..DATABASE_SUFFIX_LENGTH).map(|_| {
+        DATABASE_SUFFIX_CHARS[rng.gen_range(0..DATABASE_SUFFIX_CHARS.len())]
+            as char
+    }).collect();
+    format!("schema_diff_{}", suffix)
+}
+
+/// Starts migration to the schema and lets the server fill in the
+/// statements, returns the description of the migration
+async fn plan(cli: &mut Connection, sdl: &str)
+    -> anyhow::Result<CurrentMigration>
+{
+    execute(cli, format!("START MIGRATION TO {{ {} }}", sdl)).await?;
+    let result: anyhow::Result<_> = async {
+        execute(cli, "POPULATE MIGRATION").await?;
+        let descr = query_row::<CurrentMigration>(cli,
+            "DESCRIBE CURRENT MIGRATION AS JSON"
+        ).await?;
+        if !descr.complete {
+            anyhow::bail!("server could not figure out \
+                the migration between the schemas");
+        }
+        Ok(descr)
+    }.await;
+    if result.is_err() {
+        execute(cli, "ABORT MIGRATION").await.ok();
+    }
+    result
+}
+
+/// Returns DDL statements migrating schema `from` to schema `to`
+async fn compare(conn_params: &Connector, from: &str, to: &str)
+    -> anyhow::Result<Vec<String>>
+{
+    let mut admin = conn_params.connect().await?;
+    let name = temporary_name();
+    execute(&mut admin, format!("CREATE DATABASE {}", quote_name(&name)))
+        .await
+        .context("cannot create temporary database for comparison")?;
+    let result: anyhow::Result<_> = async {
+        let mut params = conn_params.clone();
+        params.modify(|p| { p.database(&name); });
+        let mut cli = params.connect().await?;
+        plan(&mut cli, from).await
+            .context("cannot apply the first schema")?;
+        execute(&mut cli, "COMMIT MIGRATION").await?;
+        let descr = plan(&mut cli, to).await
+            .context("cannot compare with the second schema")?;
+        execute(&mut cli, "ABORT MIGRATION").await?;
+        Ok(descr.confirmed)
+    }.await;
+    let drop_db = format!("DROP DATABASE {}", quote_name(&name));
+    if let Err(e) = execute(&mut admin, drop_db).await {
+        log::warn!("Could not drop temporary database {:?}: {:#}", name, e);
+    }
+    result
+}
+
+/// Returns headers of the statement and its direct subcommands with
+/// their nesting level, skipping the deeper blocks
+fn summarize(statement: &str) -> Vec<(usize, String)> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut parens = 0;
+    let mut start = None;
+    let mut end = 0;
+    for item in &mut TokenStream::new(statement) {
+        let tok = match item {
+            Ok(tok) => tok,
+            Err(_) => break,
+        };
+        let value = tok.token.value;
+        match value {
+            "{" | ";" | "}" if parens == 0 => {
+                if let Some(start) = start.take() {
+                    if depth <= 1 {
+                        let header = statement[start..end]
+                            .split_whitespace()
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        result.push((depth, header));
+                    }
+                }
+                match value {
+                    "{" => depth += 1,
+                    "}" => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                continue;
+            }
+            "(" | "[" => parens += 1,
+            ")" | "]" => parens = parens.saturating_sub(1),
+            _ => {}
+        }
+        if start.is_none() {
+            start = Some(tok.start.offset as usize);
+        }
+        end = tok.end.offset as usize;
+    }
+    result
+}
+
+fn print_summary(statements: &[String]) {
+    let colors = print::console::colors_stdout();
+    for statement in statements {
+        for (depth, header) in summarize(statement) {
+            let verb = header.split_whitespace().next().unwrap_or("");
+            let marker = match &verb.to_uppercase()[..] {
+                "CREATE" => "+",
+                "DROP" => "-",
+                _ => "~",
+            };
+            let line = format!("{}{} {}",
+                               "    ".repeat(depth), marker, header);
+            if colors {
+                match marker {
+                    "+" => println!("{}", line.green()),
+                    "-" => println!("{}", line.red()),
+                    _ => println!("{}", line.yellow()),
+                }
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+pub fn diff(cmd: &SchemaDiff, options: &Options) -> anyhow::Result<()> {
+    let from = source(&cmd.from, options)?;
+    let to = source(&cmd.to, options)?;
+    let statements = task::block_on(async {
+        let from = read_sdl(from).await?;
+        let to = read_sdl(to).await?;
+        compare(&options.conn_params, &from, &to).await
+    })?;
+    if statements.is_empty() {
+        eprintln!("Schemas are identical");
+        return Ok(());
+    }
+    if cmd.ddl {
+        for statement in &statements {
+            println!("{}", statement);
+        }
+    } else {
+        print_summary(&statements);
+    }
+    Err(ExitCode::new(1).into())
+}
+
+pub fn schema(cmd: &Schema, options: &Options) -> anyhow::Result<()> {
+    match &cmd.command {
+        SchemaCommand::Diff(c) => diff(c, options),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::summarize;
+
+    #[test]
+    fn summary() {
+        assert_eq!(summarize("CREATE TYPE default::User {\n    \
+                CREATE PROPERTY name -> std::str {\n        \
+                    CREATE CONSTRAINT std::exclusive;\n    \
+                };\n    \
+                CREATE LINK friends := (SELECT default::User {id});\n\
+            };"), vec![
+                (0, "CREATE TYPE default::User".into()),
+                (1, "CREATE PROPERTY name -> std::str".into()),
+                (1, "CREATE LINK friends := (SELECT default::User {id})"
+                    .into()),
+            ]);
+        assert_eq!(summarize("DROP TYPE default::Post;"),
+                   vec![(0, "DROP TYPE default::Post".into())]);
+    }
+}
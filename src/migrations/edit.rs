@@ -0,0 +1,104 @@
+use std::mem;
+
+use anyhow::Context as _;
+use async_std::fs;
+
+use crate::commands::parser::EditMigration;
+use crate::migrations::NULL_MIGRATION;
+use crate::migrations::context::Context;
+use crate::migrations::migration::{self, computed_id, file_num};
+use crate::prompt;
+
+
+/// Replaces migration id and parent id in the `CREATE MIGRATION` header
+fn fix_header(text: &str, header_end: usize,
+    (old_id, old_parent): (&str, &str), (new_id, new_parent): (&str, &str))
+    -> anyhow::Result<String>
+{
+    let header = &text[..header_end];
+    let id_start = header.find(old_id)
+        .ok_or_else(|| anyhow::anyhow!("cannot find migration name"))?;
+    let id_end = id_start + old_id.len();
+    let parent_start = header[id_end..].find(old_parent)
+        .map(|pos| id_end + pos)
+        .ok_or_else(|| anyhow::anyhow!("cannot find parent migration"))?;
+    let parent_end = parent_start + old_parent.len();
+    Ok(format!("{}{}{}{}{}",
+        &text[..id_start], new_id,
+        &text[id_end..parent_start], new_parent,
+        &text[parent_end..]))
+}
+
+pub async fn edit(params: &EditMigration) -> anyhow::Result<()> {
+    let ctx = Context::from_config(&params.cfg)?;
+    let files = migration::read_unchecked(&ctx).await?;
+    if files.is_empty() {
+        anyhow::bail!("no migrations found in {}",
+            ctx.schema_dir.join("migrations").display());
+    }
+    let start = match params.number {
+        Some(num) => files.iter()
+            .position(|(f, _)| file_num(&f.path) == Some(num))
+            .ok_or_else(|| anyhow::anyhow!(
+                "no migration file `{:05}.edgeql` found", num))?,
+        None if params.no_editor => 0,
+        None => files.len() - 1,
+    };
+    if !params.no_editor {
+        prompt::run_editor(files[start].0.path.as_ref())?;
+    }
+
+    // re-read files as the header might have been edited too
+    let mut files = migration::read_unchecked(&ctx).await?;
+    let mut parent = match start {
+        0 => NULL_MIGRATION.to_string(),
+        _ => files[start-1].0.data.id.clone(),
+    };
+    let mut updated = 0;
+    for (file, text) in &mut files[start..] {
+        let old_id = file.data.id.clone();
+        let old_parent = mem::replace(
+            &mut file.data.parent_id, parent.clone());
+        let new_id = computed_id(text, &file.data)
+            .with_context(|| format!("cannot hash {}", file.path.display()))?;
+        if new_id != old_id || parent != old_parent {
+            let new_text = fix_header(text, file.data.text_range.0,
+                    (old_id.as_str(), old_parent.as_str()),
+                    (new_id.as_str(), parent.as_str()))
+                .with_context(|| format!("cannot update {}",
+                                         file.path.display()))?;
+            fs::write(&file.path, new_text).await
+                .with_context(|| format!("cannot write {}",
+                                         file.path.display()))?;
+            eprintln!("Updated {}: {} -> {}",
+                file.path.display(), old_id, new_id);
+            updated += 1;
+        }
+        parent = new_id;
+    }
+    if updated == 0 {
+        eprintln!("Migration names are up to date.");
+    } else {
+        eprintln!("Note: databases that have the old migrations applied \
+            will not match the updated files, \
+            `edgedb verify-migrations` will report them.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::fix_header;
+
+    #[test]
+    fn header() {
+        let text = "CREATE MIGRATION m1aaa\n    ONTO m1bbb\n{\n    \
+            CREATE TYPE m1aaa;\n};\n";
+        let header_end = text.find('{').unwrap() + 1;
+        assert_eq!(
+            fix_header(text, header_end,
+                       ("m1aaa", "m1bbb"), ("m1ccc", "initial")).unwrap(),
+            "CREATE MIGRATION m1ccc\n    ONTO initial\n{\n    \
+            CREATE TYPE m1aaa;\n};\n");
+    }
+}
@@ -0,0 +1,470 @@
+//! Checks of schema and migration files that don't need a server
+//!
+//! Files are tokenized and split into declarations by braces and
+//! semicolons, which is enough to find the constructs the rules are about
+//! without implementing the full SDL grammar.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term::emit;
+use edgeql_parser::tokenizer::{TokenStream, Kind};
+use termcolor::{StandardStream, ColorChoice};
+
+use crate::commands::ExitCode;
+use crate::commands::parser::Lint;
+use crate::migrations::context::Context;
+use crate::project;
+use crate::project::config::LintLevel;
+
+
+/// Words that may precede the kind of the declaration
+const QUALIFIERS: &[&str] = &[
+    "create", "alter", "drop",
+    "required", "optional", "single", "multi",
+    "abstract", "overloaded", "inheritable", "delegated", "scalar",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    OptionalSingleLink,
+    DeprecatedSyntax,
+    UnusedModule,
+    DeletionPolicy,
+}
+
+const RULES: &[Rule] = &[
+    Rule::OptionalSingleLink,
+    Rule::DeprecatedSyntax,
+    Rule::UnusedModule,
+    Rule::DeletionPolicy,
+];
+
+#[derive(Debug)]
+pub struct Issue {
+    /// `None` for syntax errors, which can't be disabled
+    pub rule: Option<Rule>,
+    pub range: Range<usize>,
+    pub message: String,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Tok<'a> {
+    kind: Kind,
+    value: &'a str,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug)]
+struct Decl<'a> {
+    header: Vec<Tok<'a>>,
+    body: Option<Vec<Decl<'a>>>,
+}
+
+/// Declaration split into `qualifiers kind name ...`
+struct Head<'a, 'd> {
+    qualifiers: Vec<String>,
+    kind: String,
+    name: Option<&'d Tok<'a>>,
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::OptionalSingleLink => "optional-single-link",
+            Rule::DeprecatedSyntax => "deprecated-syntax",
+            Rule::UnusedModule => "unused-module",
+            Rule::DeletionPolicy => "deletion-policy",
+        }
+    }
+}
+
+impl<'a> Tok<'a> {
+    fn is_word(&self) -> bool {
+        matches!(self.kind, Kind::Ident | Kind::Keyword)
+    }
+    fn is(&self, word: &str) -> bool {
+        self.is_word() && self.value.eq_ignore_ascii_case(word)
+    }
+}
+
+impl<'a> Decl<'a> {
+    fn head(&self) -> Option<Head<'a, '_>> {
+        let mut tokens = self.header.iter();
+        let mut qualifiers = Vec::new();
+        let kind = loop {
+            let tok = tokens.next().filter(|t| t.is_word())?;
+            let word = tok.value.to_lowercase();
+            if QUALIFIERS.contains(&&word[..]) {
+                qualifiers.push(word);
+            } else {
+                break word;
+            }
+        };
+        Some(Head { qualifiers, kind, name: tokens.next() })
+    }
+    fn is_computed(&self) -> bool {
+        self.header.iter().any(|t| t.value == ":=")
+    }
+    fn range(&self) -> Range<usize> {
+        match (self.header.first(), self.header.last()) {
+            (Some(first), Some(last)) => first.start..last.end,
+            _ => 0..0,
+        }
+    }
+}
+
+impl Head<'_, '_> {
+    fn has(&self, qualifier: &str) -> bool {
+        self.qualifiers.iter().any(|q| q == qualifier)
+    }
+    fn name_range(&self, decl: &Decl) -> Range<usize> {
+        match self.name {
+            Some(tok) => tok.start..tok.end,
+            None => decl.range(),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Tok<'_>>, Issue> {
+    let mut result = Vec::new();
+    for item in &mut TokenStream::new(text) {
+        match item {
+            Ok(item) => result.push(Tok {
+                kind: item.token.kind,
+                value: item.token.value,
+                start: item.start.offset as usize,
+                end: item.end.offset as usize,
+            }),
+            Err(e) => {
+                let pos = result.last().map(|t: &Tok| t.end).unwrap_or(0);
+                return Err(Issue {
+                    rule: None,
+                    range: pos..pos,
+                    message: format!("syntax error: {}", e),
+                    note: None,
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Splits tokens into declarations up to the closing brace of the block
+fn parse_block<'a>(tokens: &mut impl Iterator<Item=Tok<'a>>)
+    -> Vec<Decl<'a>>
+{
+    let mut decls = Vec::new();
+    let mut header = Vec::new();
+    // braces within parenthesis are shapes, not declaration bodies
+    let mut depth = 0usize;
+    while let Some(tok) = tokens.next() {
+        match tok.value {
+            "(" | "[" => depth += 1,
+            ")" | "]" => depth = depth.saturating_sub(1),
+            "{" if depth == 0 => {
+                let body = parse_block(tokens);
+                decls.push(Decl {
+                    header: std::mem::take(&mut header),
+                    body: Some(body),
+                });
+                continue;
+            }
+            "}" if depth == 0 => break,
+            ";" if depth == 0 => {
+                if !header.is_empty() {
+                    decls.push(Decl {
+                        header: std::mem::take(&mut header),
+                        body: None,
+                    });
+                }
+                continue;
+            }
+            _ => {}
+        }
+        header.push(tok);
+    }
+    if !header.is_empty() {
+        decls.push(Decl { header, body: None });
+    }
+    decls
+}
+
+fn check_deprecated(decl: &Decl, issues: &mut Vec<Issue>) {
+    let head = match decl.head() {
+        Some(head) => head,
+        None => return,
+    };
+    let replacement = match &head.kind[..] {
+        "view" => "alias",
+        "attribute" => "annotation",
+        _ => return,
+    };
+    let kind_tok = &decl.header[head.qualifiers.len()];
+    if head.name.map(|t| t.is_word()).unwrap_or(false) {
+        issues.push(Issue {
+            rule: Some(Rule::DeprecatedSyntax),
+            range: kind_tok.start..kind_tok.end,
+            message: format!("`{}` is deprecated", head.kind),
+            note: Some(format!("use `{}` instead", replacement)),
+        });
+    }
+}
+
+fn check_link(decl: &Decl, head: &Head, issues: &mut Vec<Issue>) {
+    if head.has("alter") || head.has("drop") || decl.is_computed() {
+        return;
+    }
+    let name = head.name.map(|t| t.value).unwrap_or("");
+    let required = head.has("required");
+    if head.has("single") && !required {
+        issues.push(Issue {
+            rule: Some(Rule::OptionalSingleLink),
+            range: head.name_range(decl),
+            message: format!("link `{}` is `single` but not `required`",
+                             name),
+            note: Some("add `required` if every object must have the \
+                        target, links are single by default".into()),
+        });
+    }
+    let body = decl.body.as_deref().unwrap_or(&[]);
+    for item in body {
+        let words = &item.header;
+        let is_policy = words.len() > 3 && words[0].is("on")
+            && words[1].is("target") && words[2].is("delete");
+        if !is_policy {
+            continue;
+        }
+        let policy = &words[3..];
+        let (message, note) = if policy[0].is("allow") && required {
+            ("`allow` policy on a required link",
+             "deleting the target fails as the link can't be empty, \
+              use `restrict` to make this explicit or `delete source`")
+        } else if policy[0].is("delete") && head.has("multi") {
+            ("`delete source` policy on a multi link",
+             "deleting any of the targets deletes the whole source object")
+        } else {
+            continue;
+        };
+        issues.push(Issue {
+            rule: Some(Rule::DeletionPolicy),
+            range: item.range(),
+            message: message.into(),
+            note: Some(note.into()),
+        });
+    }
+}
+
+fn is_empty(body: &Option<Vec<Decl>>) -> bool {
+    body.as_ref().map(|b| b.is_empty()).unwrap_or(false)
+}
+
+fn check_decls(decls: &[Decl], schema: bool, issues: &mut Vec<Issue>) {
+    for decl in decls {
+        check_deprecated(decl, issues);
+        if let (true, Some(head)) = (schema, decl.head()) {
+            match &head.kind[..] {
+                "link" => check_link(decl, &head, issues),
+                "module" if is_empty(&decl.body) => {
+                    issues.push(Issue {
+                        rule: Some(Rule::UnusedModule),
+                        range: head.name_range(decl),
+                        message: format!("module `{}` declares nothing",
+                            head.name.map(|t| t.value).unwrap_or("")),
+                        note: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+        if let Some(body) = &decl.body {
+            check_decls(body, schema, issues);
+        }
+    }
+}
+
+/// Checks schema (`*.esdl`) or, if `schema` is false, migration text
+///
+/// Only `deprecated-syntax` applies to migrations, other rules are about
+/// the resulting schema, which is checked in `*.esdl` files.
+pub fn check_text(text: &str, schema: bool) -> Vec<Issue> {
+    let tokens = match tokenize(text) {
+        Ok(tokens) => tokens,
+        Err(issue) => return vec![issue],
+    };
+    let mut issues = Vec::new();
+    let decls = parse_block(&mut tokens.into_iter());
+    check_decls(&decls, schema, &mut issues);
+    issues
+}
+
+/// Files with the `ext`ension in the directory, ordered by name
+pub fn list_files(dir: &Path, ext: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let items = match fs::read_dir(dir) {
+        Ok(items) => items,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => Err(e)
+            .with_context(|| format!("cannot read {}", dir.display()))?,
+    };
+    let mut result = Vec::new();
+    for item in items {
+        let item = item?;
+        let name = item.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(".") || !name.ends_with(ext)
+            || !item.file_type()?.is_file()
+        {
+            continue;
+        }
+        result.push(item.path());
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn levels() -> anyhow::Result<BTreeMap<String, LintLevel>> {
+    let levels = match project::project_dir_opt(None)? {
+        Some(dir) => project::config::read(&dir.join("edgedb.toml"))?.lint,
+        None => BTreeMap::new(),
+    };
+    for name in levels.keys() {
+        if !RULES.iter().any(|r| r.name() == name) {
+            log::warn!("Unknown lint rule `{}` in edgedb.toml",
+                       name.escape_default());
+        }
+    }
+    Ok(levels)
+}
+
+pub fn lint(options: &Lint) -> anyhow::Result<()> {
+    let ctx = Context::from_config(&options.cfg)?;
+    let levels = levels()?;
+    let mut files = Vec::new();
+    for dir in &ctx.sdl_dirs {
+        let schema = list_files(dir, ".esdl")?;
+        files.extend(schema.into_iter().map(|p| (p, true)));
+    }
+    let migrations_dir = ctx.schema_dir.join("migrations");
+    let migrations = list_files(&migrations_dir, ".edgeql")?;
+    files.extend(migrations.into_iter().map(|p| (p, false)));
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    let mut out = StandardStream::stderr(ColorChoice::Auto);
+    for (path, schema) in &files {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let display = path.display();
+        let file = SimpleFile::new(&display, &text);
+        for issue in check_text(&text, *schema) {
+            let level = match issue.rule {
+                Some(rule) => levels.get(rule.name()).copied()
+                    .unwrap_or(LintLevel::Warn),
+                None => LintLevel::Error,
+            };
+            let diag = match level {
+                LintLevel::Allow => continue,
+                LintLevel::Warn => {
+                    warnings += 1;
+                    Diagnostic::warning()
+                }
+                LintLevel::Error => {
+                    errors += 1;
+                    Diagnostic::error()
+                }
+            };
+            let mut diag = diag
+                .with_message(&issue.message)
+                .with_labels(vec![Label::primary((), issue.range.clone())])
+                .with_notes(issue.note.into_iter().collect());
+            if let Some(rule) = issue.rule {
+                diag = diag.with_code(rule.name());
+            }
+            emit(&mut out, &Default::default(), &file, &diag)?;
+        }
+    }
+    if errors == 0 && warnings == 0 {
+        eprintln!("Checked {} files, no issues found", files.len());
+        return Ok(());
+    }
+    eprintln!("Checked {} files: {} error(s), {} warning(s)",
+              files.len(), errors, warnings);
+    if errors > 0 {
+        return Err(ExitCode::new(1))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_text, Rule};
+
+    fn rules(text: &str, schema: bool) -> Vec<Option<Rule>> {
+        check_text(text, schema).into_iter().map(|i| i.rule).collect()
+    }
+
+    #[test]
+    fn schema() {
+        assert_eq!(rules(r###"
+            module default {
+                type User {
+                    required property name -> str;
+                    single link avatar -> Image;
+                    required link team -> Team {
+                        on target delete allow;
+                    }
+                    multi link groups -> Group {
+                        on target delete delete source;
+                    }
+                    link manager := (SELECT User { name } LIMIT 1);
+                }
+            }
+            module unused {}
+        "###, true), [
+            Some(Rule::OptionalSingleLink),
+            Some(Rule::DeletionPolicy),
+            Some(Rule::DeletionPolicy),
+            Some(Rule::UnusedModule),
+        ]);
+    }
+
+    #[test]
+    fn deprecated() {
+        assert_eq!(rules(r###"
+            module default {
+                view Admins := (SELECT User FILTER .admin);
+                type User {
+                    attribute title := 'user';
+                    property view -> str;
+                }
+            }
+        "###, true), [
+            Some(Rule::DeprecatedSyntax),
+            Some(Rule::DeprecatedSyntax),
+        ]);
+    }
+
+    #[test]
+    fn migration() {
+        assert_eq!(rules(r###"
+            CREATE MIGRATION m1 ONTO initial {
+                CREATE MODULE other IF NOT EXISTS;
+                CREATE TYPE default::User {
+                    CREATE SINGLE LINK avatar -> default::Image;
+                };
+                CREATE VIEW default::Admins := (SELECT default::User);
+            };
+        "###, false), [Some(Rule::DeprecatedSyntax)]);
+        assert_eq!(rules("SELECT 'unterminated", true), [None]);
+    }
+}
@@ -59,9 +59,9 @@ fn topology_sort(migrations: Vec<Migration>) -> Vec<Migration> {
     return output
 }
 
-pub async fn log_db(cli: &mut Connection, _common: &Options,
-    options: &MigrationLog)
-    -> Result<(), anyhow::Error>
+/// Returns names of the migrations applied to the database, oldest first
+pub async fn db_revisions(cli: &mut Connection)
+    -> Result<Vec<String>, anyhow::Error>
 {
     let mut items = cli.query::<Migration>(r###"
             SELECT schema::Migration {name, parent_names := .parents.name }
@@ -70,15 +70,22 @@ pub async fn log_db(cli: &mut Connection, _common: &Options,
     while let Some(item) = items.next().await.transpose()? {
         migrations.push(item);
     }
-    let output = topology_sort(migrations);
+    Ok(topology_sort(migrations).into_iter().map(|m| m.name).collect())
+}
+
+pub async fn log_db(cli: &mut Connection, _common: &Options,
+    options: &MigrationLog)
+    -> Result<(), anyhow::Error>
+{
+    let output = db_revisions(cli).await?;
     let limit = options.limit.unwrap_or(output.len());
     if options.newest_first {
         for rev in output.iter().rev().take(limit) {
-            println!("{}", rev.name);
+            println!("{}", rev);
         }
     } else {
         for rev in output.iter().take(limit) {
-            println!("{}", rev.name);
+            println!("{}", rev);
         }
     }
     Ok(())
@@ -89,7 +96,7 @@ pub async fn log_fs(_common: &Options, options: &MigrationLog)
 {
     assert!(options.from_fs);
 
-    let ctx = Context::from_config(&options.cfg);
+    let ctx = Context::from_config(&options.cfg)?;
     let migrations = migration::read_all(&ctx, true).await?;
     let limit = options.limit.unwrap_or(migrations.len());
     if options.newest_first {
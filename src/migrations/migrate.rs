@@ -6,14 +6,17 @@ use edgedb_client::client::Connection;
 use edgedb_protocol::value::Value;
 use linked_hash_map::LinkedHashMap;
 
+use crate::capabilities::{self, Feature};
 use crate::commands::Options;
 use crate::commands::ExitCode;
 use crate::commands::parser::Migrate;
 use crate::migrations::context::Context;
+use crate::migrations::dev_mode;
 use crate::migrations::migration::{self, MigrationFile};
+use crate::project::seed;
 
 
-fn skip_revisions(migrations: &mut LinkedHashMap<String, MigrationFile>,
+pub fn skip_revisions(migrations: &mut LinkedHashMap<String, MigrationFile>,
     db_migration: &str)
     -> anyhow::Result<()>
 {
@@ -53,7 +56,21 @@ pub async fn migrate(cli: &mut Connection, _options: &Options,
     migrate: &Migrate)
     -> Result<(), anyhow::Error>
 {
-    let ctx = Context::from_config(&migrate.cfg);
+    let ctx = Context::from_config(&migrate.cfg)?;
+    if migrate.dev_mode {
+        capabilities::require(cli, Feature::DevMode).await?;
+        let changed = dev_mode::migrate(&ctx, cli, false).await?;
+        if !migrate.quiet {
+            if changed {
+                eprintln!("Schema is applied in dev mode. \
+                    Run `edgedb create-migration` to turn changes into \
+                    a migration file.");
+            } else {
+                eprintln!("Everything is up to date.");
+            }
+        }
+        return Ok(());
+    }
 
     let mut migrations = migration::read_all(&ctx, true).await?;
     let db_migration: Option<String> = cli.query_row_opt(r###"
@@ -106,6 +123,15 @@ pub async fn migrate(cli: &mut Connection, _options: &Options,
     };
 
     if let Some(db_migration) = &db_migration {
+        if migrations.get(db_migration).is_none()
+            && capabilities::supports(cli, Feature::DevMode).await?
+            && dev_mode::has_dev_migrations(cli).await?
+        {
+            anyhow::bail!("Database has schema changes applied in dev mode. \
+                Run `edgedb create-migration` to create migration files \
+                for them, or `edgedb migrate --dev-mode` to continue \
+                in dev mode.");
+        }
         skip_revisions(&mut migrations, db_migration)?;
     };
     if let Some(target_rev) = &target_rev {
@@ -122,20 +148,24 @@ pub async fn migrate(cli: &mut Connection, _options: &Options,
             eprintln!("Everything is up to date. Revision {}",
                 db_migration.as_ref().map(|x| &x[..]).unwrap_or("initial"));
         }
-        return Ok(());
-    }
-    // TODO(tailhook) use special transaction facility
-    cli.execute("START TRANSACTION").await?;
-    for (_, migration) in migrations {
-        let data = fs::read_to_string(&migration.path).await
-            .context("error re-reading migration file")?;
-        cli.execute(data).await?;
-        if !migrate.quiet {
-            eprintln!("Applied {} ({})",
-                migration.data.id,
-                Path::new(migration.path.file_name().unwrap()).display());
+    } else {
+        // TODO(tailhook) use special transaction facility
+        cli.execute("START TRANSACTION").await?;
+        for (_, migration) in migrations {
+            let data = fs::read_to_string(&migration.path).await
+                .context("error re-reading migration file")?;
+            cli.execute(data).await?;
+            if !migrate.quiet {
+                eprintln!("Applied {} ({})",
+                    migration.data.id,
+                    Path::new(migration.path.file_name().unwrap()).display());
+            }
         }
+        cli.execute("COMMIT").await?;
+    }
+    if target_rev.is_none() {
+        // seeds are written for the latest schema
+        seed::apply(cli, &ctx.schema_dir.join("seeds")).await?;
     }
-    cli.execute("COMMIT").await?;
     return Ok(())
 }
@@ -1,6 +1,7 @@
 use std::collections::hash_map::{HashMap, Entry};
 use std::ffi::OsStr;
 
+use anyhow::Context as _;
 use async_std::io;
 use async_std::fs;
 use async_std::path::{Path, PathBuf};
@@ -42,12 +43,19 @@ pub fn hashing_error(_source: &str, e: hash::Error) -> anyhow::Error {
     }
 }
 
+/// Computes migration id from the text of the migration and its parent
+pub fn computed_id(text: &str, migration: &Migration)
+    -> anyhow::Result<String>
+{
+    let mut hasher = Hasher::start_migration(&migration.parent_id);
+    let txt = &text[migration.text_range.0..migration.text_range.1];
+    hasher.add_source(txt).map_err(|e| hashing_error(txt, e))?;
+    Ok(hasher.make_migration_id())
+}
+
 fn validate_text(text: &str, migration: &Migration) -> anyhow::Result<()> {
     if migration.id.starts_with("m1") {
-        let mut hasher = Hasher::start_migration(&migration.parent_id);
-        let txt = &text[migration.text_range.0..migration.text_range.1];
-        hasher.add_source(txt).map_err(|e| hashing_error(txt, e))?;
-        let id = hasher.make_migration_id();
+        let id = computed_id(text, migration)?;
         if id != migration.id {
             anyhow::bail!("migration name should be `{computed}` \
                 but `{file}` is used instead.\n\
@@ -77,22 +85,20 @@ async fn read_file(path: &Path, validate_hashes:bool)
     return Ok(data)
 }
 
-fn file_num(path: &Path) -> Option<u64> {
+pub fn file_num(path: &Path) -> Option<u64> {
     path.file_stem().and_then(|x| x.to_str()).and_then(|x| x.parse().ok())
 }
 
-#[context("could not read migrations in {}", dir.display())]
-async fn _read_all(dir: &Path, validate_hashes: bool)
-    -> anyhow::Result<LinkedHashMap<String, MigrationFile>>
-{
+/// Returns paths of all `*.edgeql` files in the directory (unordered)
+async fn list_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
     let mut dir = match fs::read_dir(dir).await {
         Ok(dir) => dir,
         Err(e) if e.kind() == io::ErrorKind::NotFound => {
-            return Ok(LinkedHashMap::new());
+            return Ok(Vec::new());
         }
         Err(e) => Err(e)?,
     };
-    let mut all = HashMap::new();
+    let mut result = Vec::new();
     while let Some(item) = dir.next().await.transpose()? {
         let fname = item.file_name();
         let lossy_name = fname.to_string_lossy();
@@ -101,7 +107,24 @@ async fn _read_all(dir: &Path, validate_hashes: bool)
         {
             continue;
         }
-        let path = item.path();
+        result.push(item.path());
+    }
+    Ok(result)
+}
+
+fn sort_key(path: &Path) -> SortKey<'_> {
+    match file_num(path) {
+        Some(n) => SortKey::Numeric(n),
+        None => SortKey::Text(path.file_stem().unwrap_or(path.as_os_str())),
+    }
+}
+
+#[context("could not read migrations in {}", dir.display())]
+async fn _read_all(dir: &Path, validate_hashes: bool)
+    -> anyhow::Result<LinkedHashMap<String, MigrationFile>>
+{
+    let mut all = HashMap::new();
+    for path in list_files(dir).await? {
         let data = read_file(&path, validate_hashes).await?;
         match all.entry(data.parent_id.clone()) {
             Entry::Vacant(v) => {
@@ -164,6 +187,30 @@ fn sort_revisions(mut all: HashMap<String, MigrationFile>)
     Ok(res)
 }
 
+/// Reads migration files ordered by file name, along with their text
+///
+/// Unlike `read_all` neither hashes nor parent revisions are validated, so
+/// this can be used to report all the problems at once.
+#[context("could not read migrations in {}", ctx.schema_dir.display())]
+pub async fn read_unchecked(ctx: &Context)
+    -> anyhow::Result<Vec<(MigrationFile, String)>>
+{
+    let dir = ctx.schema_dir.join("migrations");
+    let mut paths = list_files(dir.as_ref()).await?;
+    paths.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+    let mut result = Vec::with_capacity(paths.len());
+    for path in paths {
+        let text = fs::read_to_string(&path).await
+            .with_context(|| format!("could not read migration file {}",
+                                     path.display()))?;
+        let data = parse_migration(&text)
+            .with_context(|| format!("could not read migration file {}",
+                                     path.display()))?;
+        result.push((MigrationFile { path, data }, text));
+    }
+    Ok(result)
+}
+
 pub async fn read_all(ctx: &Context, validate_hashes: bool)
     -> anyhow::Result<LinkedHashMap<String, MigrationFile>>
 {
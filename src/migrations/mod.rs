@@ -1,6 +1,11 @@
+mod check;
 mod context;
 mod create;
+mod dev_mode;
+mod diff;
+mod edit;
 mod grammar;
+mod lint;
 mod log;
 mod migrate;
 mod migration;
@@ -8,10 +13,34 @@ mod print_error;
 mod source_map;
 mod status;
 mod prompt;
+mod verify;
+mod watch;
+
+use std::path::Path;
+
+use crate::commands::parser::MigrationConfig;
 
 const NULL_MIGRATION: &str = "initial";
 
+pub use check::{check, check_compile, check_tokens, location};
 pub use create::create;
+pub use dev_mode::wipe;
+pub use diff::schema;
+pub use edit::edit;
+pub use lint::{lint, check_text};
 pub use migrate::migrate;
 pub use status::status;
+pub use verify::verify;
+pub use watch::watch;
 pub use self::log::{log, log_fs};
+
+/// Returns names of the migrations in the schema directory
+pub async fn revisions(schema_dir: Option<&Path>)
+    -> anyhow::Result<Vec<String>>
+{
+    let ctx = context::Context::from_config(&MigrationConfig {
+        schema_dir: schema_dir.map(|d| d.into()),
+    })?;
+    let migrations = migration::read_all(&ctx, false).await?;
+    Ok(migrations.keys().cloned().collect())
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+
+use edgedb_client::client::Connection;
+
+use crate::commands::{Options, ExitCode};
+use crate::commands::parser::VerifyMigrations;
+use crate::migrations::NULL_MIGRATION;
+use crate::migrations::context::Context;
+use crate::migrations::log::db_revisions;
+use crate::migrations::migration::{self, computed_id};
+
+
+/// Migration file as found on the filesystem
+#[derive(Debug)]
+struct FileInfo {
+    name: String,
+    id: String,
+    parent_id: String,
+    /// Id computed from the text, `None` if text can't be hashed
+    computed_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Problem {
+    /// Text of the file doesn't match the id in the file
+    Edited { file: String, computed: String },
+    /// File doesn't refer to the previous file as its parent
+    BrokenChain { file: String, expected: String },
+    /// File has an id that is applied at a different position
+    Reordered { file: String, applied: String },
+    /// File has an id that is not the one applied at this position
+    Mismatch { file: String, applied: String },
+    /// Migration is applied to the database but has no file
+    Missing { applied: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Problem::*;
+        match self {
+            Edited { file, computed } => {
+                write!(f, "{} was modified: its contents hash to {}",
+                    file, computed)
+            }
+            BrokenChain { file, expected } => {
+                write!(f, "{} should have {} as the parent migration",
+                    file, expected)
+            }
+            Reordered { file, applied } => {
+                write!(f, "{} is out of order: \
+                    migration {} was applied at this position",
+                    file, applied)
+            }
+            Mismatch { file, applied } => {
+                write!(f, "{} differs from migration {} \
+                    applied to the database", file, applied)
+            }
+            Missing { applied } => {
+                write!(f, "migration {} is applied to the database \
+                    but there is no file for it", applied)
+            }
+        }
+    }
+}
+
+
+fn check(files: &[FileInfo], applied: &[String]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut parent = NULL_MIGRATION;
+    for (idx, file) in files.iter().enumerate() {
+        if let Some(computed) = &file.computed_id {
+            if computed != &file.id {
+                problems.push(Problem::Edited {
+                    file: file.name.clone(),
+                    computed: computed.clone(),
+                });
+            }
+        }
+        if file.parent_id != parent {
+            problems.push(Problem::BrokenChain {
+                file: file.name.clone(),
+                expected: parent.to_string(),
+            });
+        }
+        parent = file.id.as_str();
+        match applied.get(idx) {
+            Some(db_id) if db_id == &file.id => {}
+            Some(db_id) if applied.contains(&file.id) => {
+                problems.push(Problem::Reordered {
+                    file: file.name.clone(),
+                    applied: db_id.clone(),
+                });
+            }
+            Some(db_id) => {
+                problems.push(Problem::Mismatch {
+                    file: file.name.clone(),
+                    applied: db_id.clone(),
+                });
+            }
+            None => {}  // not applied yet
+        }
+    }
+    for db_id in applied.iter().skip(files.len()) {
+        if !files.iter().any(|f| &f.id == db_id) {
+            problems.push(Problem::Missing { applied: db_id.clone() });
+        }
+    }
+    problems
+}
+
+pub async fn verify(cli: &mut Connection, _options: &Options,
+    params: &VerifyMigrations)
+    -> Result<(), anyhow::Error>
+{
+    let ctx = Context::from_config(&params.cfg)?;
+    let mut files = Vec::new();
+    for (file, text) in migration::read_unchecked(&ctx).await? {
+        let computed_id = match computed_id(&text, &file.data) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log::warn!("Cannot hash {}: {:#}", file.path.display(), e);
+                None
+            }
+        };
+        files.push(FileInfo {
+            name: file.path.display().to_string(),
+            id: file.data.id,
+            parent_id: file.data.parent_id,
+            computed_id,
+        });
+    }
+    let applied = db_revisions(cli).await?;
+    let problems = check(&files, &applied);
+    if problems.is_empty() {
+        if !params.quiet {
+            eprintln!("Migrations are intact: {} files, {} of them applied \
+                to the database.",
+                files.len(), applied.len().min(files.len()));
+        }
+        return Ok(());
+    }
+    if !params.quiet {
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        eprintln!("Found {} problems in migrations. Revert the changes \
+            to the files listed above.", problems.len());
+    }
+    Err(ExitCode::new(3).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, FileInfo, Problem};
+
+    fn file(num: u32, id: &str, parent: &str, computed: &str) -> FileInfo {
+        FileInfo {
+            name: format!("{:05}.edgeql", num),
+            id: id.into(),
+            parent_id: parent.into(),
+            computed_id: Some(computed.into()),
+        }
+    }
+
+    fn ids(items: &[&str]) -> Vec<String> {
+        items.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn intact() {
+        let files = [
+            file(1, "m11", "initial", "m11"),
+            file(2, "m12", "m11", "m12"),
+        ];
+        assert_eq!(check(&files, &ids(&["m11", "m12"])), vec![]);
+        // the last one is not applied yet
+        assert_eq!(check(&files, &ids(&["m11"])), vec![]);
+    }
+
+    #[test]
+    fn edited() {
+        let files = [
+            file(1, "m11", "initial", "m11"),
+            file(2, "m12", "m11", "m1x"),
+        ];
+        assert_eq!(check(&files, &ids(&["m11", "m12"])), vec![
+            Problem::Edited {
+                file: "00002.edgeql".into(),
+                computed: "m1x".into(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn reordered() {
+        let files = [
+            file(1, "m12", "m11", "m12"),
+            file(2, "m11", "initial", "m11"),
+        ];
+        assert_eq!(check(&files, &ids(&["m11", "m12"])), vec![
+            Problem::BrokenChain {
+                file: "00001.edgeql".into(),
+                expected: "initial".into(),
+            },
+            Problem::Reordered {
+                file: "00001.edgeql".into(),
+                applied: "m11".into(),
+            },
+            Problem::BrokenChain {
+                file: "00002.edgeql".into(),
+                expected: "m12".into(),
+            },
+            Problem::Reordered {
+                file: "00002.edgeql".into(),
+                applied: "m12".into(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn missing() {
+        let files = [
+            file(1, "m11", "initial", "m11"),
+        ];
+        assert_eq!(check(&files, &ids(&["m10", "m13"])), vec![
+            Problem::Mismatch {
+                file: "00001.edgeql".into(),
+                applied: "m10".into(),
+            },
+            Problem::Missing { applied: "m13".into() },
+        ]);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use async_std::fs;
+use async_std::stream::StreamExt;
+use async_std::task;
+use colorful::Colorful;
+use edgedb_client::client::Connection;
+
+use crate::commands::Options;
+use crate::commands::parser::Watch;
+use crate::interrupt::{Interrupt, Interrupted};
+use crate::migrations::context::Context;
+use crate::migrations::dev_mode;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+
+async fn snapshot(ctx: &Context) -> anyhow::Result<Snapshot> {
+    let mut result = BTreeMap::new();
+    for sdl_dir in &ctx.sdl_dirs {
+        let mut dir = fs::read_dir(sdl_dir).await?;
+        while let Some(item) = dir.next().await.transpose()? {
+            let fname = item.file_name();
+            let lossy_name = fname.to_string_lossy();
+            if lossy_name.starts_with(".") || !lossy_name.ends_with(".esdl")
+            {
+                continue;
+            }
+            let meta = item.metadata().await?;
+            if !meta.is_file() {
+                continue;
+            }
+            result.insert(item.path().into(), meta.modified().ok());
+        }
+    }
+    Ok(result)
+}
+
+fn sdl_dirs(ctx: &Context) -> String {
+    ctx.sdl_dirs.iter()
+        .map(|d| d.display().to_string())
+        .collect::<Vec<_>>().join(", ")
+}
+
+/// Waits until files are not changed for `debounce` time
+async fn settle(ctx: &Context, mut current: Snapshot, debounce: Duration)
+    -> anyhow::Result<Snapshot>
+{
+    loop {
+        task::sleep(debounce).await;
+        let next = snapshot(ctx).await?;
+        if next == current {
+            return Ok(next);
+        }
+        current = next;
+    }
+}
+
+/// Waits until files are changed and settled
+async fn changes(ctx: &Context, current: &Snapshot, debounce: Duration)
+    -> anyhow::Result<Snapshot>
+{
+    loop {
+        task::sleep(POLL_INTERVAL).await;
+        let next = match snapshot(ctx).await {
+            Ok(next) => next,
+            Err(e) => {
+                eprintln!("Error reading {}: {:#}", sdl_dirs(ctx), e);
+                continue;
+            }
+        };
+        if &next != current {
+            return settle(ctx, next, debounce).await;
+        }
+    }
+}
+
+async fn apply(ctx: &Context, cli: &mut Connection, watch: &Watch) {
+    match dev_mode::migrate(ctx, cli, watch.allow_unsafe).await {
+        Ok(true) => {
+            if !watch.quiet {
+                eprintln!("{}", "Schema changes applied.".green());
+            }
+        }
+        Ok(false) => {
+            if !watch.quiet {
+                eprintln!("No schema changes detected.");
+            }
+        }
+        Err(e) => {
+            eprintln!("{} {:#}", "Error:".red().bold(), e);
+        }
+    }
+}
+
+pub async fn watch(cli: &mut Connection, _options: &Options, watch: &Watch)
+    -> anyhow::Result<()>
+{
+    let ctx = Context::from_config(&watch.cfg)?;
+    let interrupt = Interrupt::new()?;
+    let mut current = snapshot(&ctx).await?;
+    if !watch.quiet {
+        eprintln!("Watching {} for changes. Press Ctrl+C to stop.",
+            sdl_dirs(&ctx));
+    }
+    apply(&ctx, cli, watch).await;
+    loop {
+        // migration being applied is not interrupted, so we only stop
+        // while waiting for changes
+        match interrupt.run(changes(&ctx, &current, watch.debounce)).await {
+            Ok(next) => current = next,
+            Err(e) if e.is::<Interrupted>() => {
+                if !watch.quiet {
+                    eprintln!("Stopped watching.");
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+        apply(&ctx, cli, watch).await;
+    }
+}
@@ -1,23 +1,318 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::Path;
 use std::str;
+use std::time::Instant;
 
 use anyhow::{self, Context};
 use async_std::prelude::StreamExt;
-use async_std::io::{stdin, stdout};
+use async_std::fs;
+use async_std::task;
+use async_std::io::{stdin, stdout, Read as AsyncRead};
 use async_std::io::prelude::WriteExt;
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
+use futures_util::future::join_all;
 use edgeql_parser::preparser;
+use edgedb_protocol::client_message::ClientMessage;
+use edgedb_protocol::client_message::{Prepare, IoFormat, Cardinality};
+use edgedb_protocol::client_message::{DescribeStatement, DescribeAspect};
+use edgedb_protocol::descriptors::InputTypedesc;
+use edgedb_protocol::server_message::{ServerMessage, ErrorResponse};
 use edgedb_protocol::value::Value;
 
-use crate::options::Options;
+use crate::commands::ExitCode;
+use crate::connect;
+use crate::error_display;
+use crate::format;
+use crate::http::Endpoint;
+use crate::options::{Options, Script};
 use crate::print::{self, PrintError};
+use crate::read_only;
+use crate::result_diff;
+use crate::retry;
 use edgedb_client::reader::ReadError;
 use crate::statement::{ReadStatement, EndOfFile};
+use crate::tee;
 use edgedb_client::client::Connection;
 use edgedb_client::errors::NoResultExpected;
 use crate::outputs::tab_separated;
+use crate::variables::make_arguments;
 
 
+/// Destination of the query output
+enum Sink<'a> {
+    /// Query isn't retried after anything is written to stdout
+    Stdout { written: bool },
+    Buffer(&'a mut Buffered),
+}
+
+/// Output of a statement kept until outputs of the previous statements are
+/// printed
+#[derive(Default)]
+struct Buffered {
+    data: String,
+    /// Status of the command returning no data, printed to stderr
+    completion: Option<Bytes>,
+}
+
+/// Shared state of the workers executing script concurrently
+struct Pool<'a> {
+    queue: RefCell<VecDeque<(usize, &'a str)>>,
+    output: RefCell<OrderedOutput>,
+    stop_on_error: bool,
+    errors: Cell<usize>,
+    first_error: RefCell<Option<anyhow::Error>>,
+}
+
+/// Prints outputs of statements in the order of statements
+struct OrderedOutput {
+    ordered: bool,
+    next: usize,
+    ready: BTreeMap<usize, Buffered>,
+}
+
+impl Sink<'_> {
+    async fn write(&mut self, data: &str) -> Result<(), anyhow::Error> {
+        match self {
+            Sink::Stdout { written } => {
+                *written = true;
+                tee::write(data);
+                stdout().write_all(data.as_bytes()).await
+                    .map_err(retry::OutputError)?;
+            }
+            Sink::Buffer(buf) => buf.data.push_str(data),
+        }
+        Ok(())
+    }
+    fn completion(&mut self, message: &Bytes) {
+        match self {
+            Sink::Stdout { .. } => print::completion(message),
+            Sink::Buffer(buf) => buf.completion = Some(message.clone()),
+        }
+    }
+    /// Position to discard output from if the query is retried
+    fn position(&self) -> usize {
+        match self {
+            Sink::Stdout { .. } => 0,
+            Sink::Buffer(buf) => buf.data.len(),
+        }
+    }
+    /// Discards output of the failed attempt, returns `false` if it's
+    /// already printed
+    fn discard(&mut self, position: usize) -> bool {
+        match self {
+            Sink::Stdout { written } => !*written,
+            Sink::Buffer(buf) => {
+                buf.data.truncate(position);
+                buf.completion = None;
+                true
+            }
+        }
+    }
+}
+
+impl OrderedOutput {
+    fn new(ordered: bool) -> OrderedOutput {
+        OrderedOutput { ordered, next: 0, ready: BTreeMap::new() }
+    }
+    fn push(&mut self, index: usize, output: Buffered) {
+        if !self.ordered {
+            Self::print(&output);
+            return;
+        }
+        self.ready.insert(index, output);
+        while let Some(output) = self.ready.remove(&self.next) {
+            Self::print(&output);
+            self.next += 1;
+        }
+    }
+    fn print(output: &Buffered) {
+        use std::io::Write;
+
+        tee::write(&output.data);
+        std::io::stdout().write_all(output.data.as_bytes())
+            .map_err(|e| log::warn!("Cannot write output: {}", e))
+            .ok();
+        if let Some(completion) = &output.completion {
+            print::completion(completion);
+        }
+    }
+}
+
+impl Pool<'_> {
+    async fn worker(&self, conn: &mut Connection, options: &Options) {
+        loop {
+            if self.first_error.borrow().is_some() {
+                break;
+            }
+            let item = self.queue.borrow_mut().pop_front();
+            let (index, stmt) = match item {
+                Some(item) => item,
+                None => break,
+            };
+            let start = Instant::now();
+            let mut buf = Buffered::default();
+            let result = query_retry(conn, stmt, options,
+                    &Value::empty_tuple(), &mut Sink::Buffer(&mut buf)).await
+                .map_err(|e| verbose_error(e, stmt, options));
+            print_timing(options, start);
+            self.output.borrow_mut().push(index, buf);
+            match result {
+                Ok(()) => {}
+                Err(e) if self.stop_on_error => {
+                    self.first_error.borrow_mut().get_or_insert(e);
+                }
+                Err(e) => {
+                    print_statement_error(&e);
+                    self.errors.set(self.errors.get() + 1);
+                }
+            }
+        }
+    }
+}
+
+pub async fn script(options: Options) -> Result<(), anyhow::Error> {
+    let script = options.script.as_ref().expect("script is set");
+    if script.concurrency > 1 {
+        return run_concurrently(&options, script).await;
+    }
+    let mut conn = options.conn_params.connect().await?;
+    let result = if script.file == Path::new("-") {
+        run_script(&mut conn, &mut stdin(), &options, script).await
+    } else {
+        let mut file = fs::File::open(&script.file).await
+            .with_context(|| format!("cannot open script file {}",
+                                     script.file.display()))?;
+        run_script(&mut conn, &mut file, &options, script).await
+    };
+    if script.single_transaction {
+        match result {
+            Ok(()) => {
+                conn.execute("COMMIT").await?;
+            }
+            Err(e) => {
+                conn.execute("ROLLBACK").await
+                    .map_err(|e| log::warn!("Rollback failed: {:#}", e))
+                    .ok();
+                return Err(e);
+            }
+        }
+    } else {
+        result?;
+    }
+    Ok(())
+}
+
+async fn read_statements<T>(input: &mut T)
+    -> Result<Vec<String>, anyhow::Error>
+    where T: AsyncRead + Unpin,
+{
+    let mut inbuf = BytesMut::with_capacity(8192);
+    let mut result = Vec::new();
+    loop {
+        let stmt = match ReadStatement::new(&mut inbuf, input).await {
+            Ok(chunk) => chunk,
+            Err(e) if e.is::<EndOfFile>() => break,
+            Err(e) => return Err(e),
+        };
+        let stmt = str::from_utf8(&stmt[..])
+            .context("can't decode statement")?;
+        if !preparser::is_empty(stmt) {
+            result.push(stmt.to_string());
+        }
+    }
+    Ok(result)
+}
+
+/// Executes independent statements of the script over a connection pool
+async fn run_concurrently(options: &Options, script: &Script)
+    -> Result<(), anyhow::Error>
+{
+    let statements = if script.file == Path::new("-") {
+        read_statements(&mut stdin()).await?
+    } else {
+        let mut file = fs::File::open(&script.file).await
+            .with_context(|| format!("cannot open script file {}",
+                                     script.file.display()))?;
+        read_statements(&mut file).await?
+    };
+    let mut connections = Vec::with_capacity(script.concurrency);
+    for _ in 0..script.concurrency.min(statements.len()) {
+        connections.push(options.conn_params.connect().await?);
+    }
+    let pool = Pool {
+        queue: RefCell::new(statements.iter()
+            .map(|s| &s[..]).enumerate().collect()),
+        output: RefCell::new(OrderedOutput::new(!script.unordered)),
+        stop_on_error: script.stop_on_error,
+        errors: Cell::new(0),
+        first_error: RefCell::new(None),
+    };
+    join_all(connections.iter_mut()
+        .map(|conn| pool.worker(conn, options))).await;
+    if let Some(e) = pool.first_error.into_inner() {
+        return Err(e);
+    }
+    let errors = pool.errors.get();
+    if errors > 0 {
+        eprintln!("Script finished with {} failed statement(s)", errors);
+        return Err(ExitCode::new(1))?;
+    }
+    Ok(())
+}
+
+fn print_statement_error(err: &anyhow::Error) {
+    if err.is::<ExitCode>() {
+        // already printed by `--verbose-errors`
+    } else if error_display::json_errors() {
+        error_display::print_json_error(err);
+    } else {
+        eprintln!("edgedb error: {:#}", err);
+    }
+}
+
+async fn run_script<T>(conn: &mut Connection, input: &mut T,
+    options: &Options, script: &Script)
+    -> Result<(), anyhow::Error>
+    where T: AsyncRead + Unpin,
+{
+    let mut inbuf = BytesMut::with_capacity(8192);
+    let mut errors = 0;
+    if script.single_transaction {
+        if options.read_only {
+            conn.execute(read_only::START).await?;
+        } else {
+            conn.execute("START TRANSACTION").await?;
+        }
+    }
+    loop {
+        let stmt = match ReadStatement::new(&mut inbuf, input).await {
+            Ok(chunk) => chunk,
+            Err(e) if e.is::<EndOfFile>() => break,
+            Err(e) => return Err(e),
+        };
+        let stmt = str::from_utf8(&stmt[..])
+            .context("can't decode statement")?;
+        if preparser::is_empty(stmt) {
+            continue;
+        }
+        match query(conn, &stmt, options).await {
+            Ok(()) => {}
+            Err(e) if script.stop_on_error => return Err(e),
+            Err(e) => {
+                print_statement_error(&e);
+                errors += 1;
+            }
+        }
+    }
+    if errors > 0 {
+        eprintln!("Script finished with {} failed statement(s)", errors);
+        return Err(ExitCode::new(1))?;
+    }
+    Ok(())
+}
+
 pub async fn main(options: Options)
     -> Result<(), anyhow::Error>
 {
@@ -42,23 +337,363 @@ pub async fn main(options: Options)
 
 pub async fn query(conn: &mut Connection, stmt: &str, options: &Options)
     -> Result<(), anyhow::Error>
+{
+    tee::write(&format!("> {}\n", stmt));
+    let start = Instant::now();
+    let result = query_args(conn, stmt, options, &Value::empty_tuple()).await
+        .map_err(|e| verbose_error(e, stmt, options));
+    print_timing(options, start);
+    result
+}
+
+fn print_timing(options: &Options, start: Instant) {
+    if options.timing {
+        eprintln!("Time: {}", format::duration(start.elapsed()));
+    }
+}
+
+/// Prints server error with the query context if `--verbose-errors` is set
+///
+/// Returns `ExitCode` if error is already printed, or the original error
+fn verbose_error(err: anyhow::Error, stmt: &str, options: &Options)
+    -> anyhow::Error
+{
+    if !options.verbose_errors || error_display::json_errors() {
+        return err;
+    }
+    let response = err.chain().find_map(|e| e.downcast_ref::<ErrorResponse>());
+    let printed = match response {
+        Some(response) => {
+            error_display::print_query_error(response, stmt, true)
+                .map_err(|e| log::warn!("Cannot print error: {:#}", e))
+                .is_ok()
+        }
+        None => false,
+    };
+    if printed {
+        ExitCode::new(1).into()
+    } else {
+        err
+    }
+}
+
+async fn describe_input(conn: &mut Connection, stmt: &str)
+    -> Result<InputTypedesc, anyhow::Error>
+{
+    let statement_name = Bytes::from_static(b"");
+    let mut seq = conn.start_sequence().await?;
+    let messages = [
+        ClientMessage::Prepare(Prepare {
+            headers: HashMap::new(),
+            io_format: IoFormat::Binary,
+            expected_cardinality: Cardinality::Many,
+            statement_name: statement_name.clone(),
+            command_text: String::from(stmt),
+        }),
+        ClientMessage::Flush,
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
+    loop {
+        let msg = seq.message().await?;
+        connect::trace_received(&msg);
+        match msg {
+            ServerMessage::PrepareComplete(..) => break,
+            ServerMessage::ErrorResponse(err) => {
+                seq.err_sync().await?;
+                return Err(err.into());
+            }
+            _ => {
+                eprintln!("WARNING: unsolicited message {:?}", msg);
+            }
+        }
+    }
+    let messages = [
+        ClientMessage::DescribeStatement(DescribeStatement {
+            headers: HashMap::new(),
+            aspect: DescribeAspect::DataDescription,
+            statement_name,
+        }),
+        ClientMessage::Flush,
+    ];
+    connect::trace_sent(&messages);
+    seq.send_messages(&messages).await?;
+    let data_description = loop {
+        let msg = seq.message().await?;
+        connect::trace_received(&msg);
+        match msg {
+            ServerMessage::CommandDataDescription(data_desc) => {
+                break data_desc;
+            }
+            ServerMessage::ErrorResponse(err) => {
+                seq.err_sync().await?;
+                return Err(err.into());
+            }
+            _ => {
+                eprintln!("WARNING: unsolicited message {:?}", msg);
+            }
+        }
+    };
+    seq.end_clean();
+    Ok(data_description.input()?)
+}
+
+fn read_parameter(name: &str, type_name: &str, sensitive: bool)
+    -> Result<String, anyhow::Error>
+{
+    let prompt = format!("Parameter <{}>${}: ", type_name, name);
+    if sensitive {
+        return Ok(rpassword::read_password_from_tty(Some(&prompt))
+            .context("error reading parameter")?);
+    }
+    if !atty::is(atty::Stream::Stdin) {
+        anyhow::bail!("no value for parameter ${name}, \
+            use `--param {name}=VALUE`", name=name);
+    }
+    eprint!("{}", prompt);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)
+        .context("error reading parameter")?;
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Executes a query passing parameters from the command-line
+///
+/// Parameters that are not specified in `params` are asked on the terminal,
+/// ones listed in `sensitive` are read with the input hidden. Names of the
+/// parameters of the query are added to `used`, so that parameters unused
+/// by all of the queries can be reported by [`check_unused`].
+pub async fn query_with_params(conn: &mut Connection, stmt: &str,
+    options: &Options, params: &[(String, String)], sensitive: &[String],
+    used: &mut BTreeSet<String>)
+    -> Result<(), anyhow::Error>
+{
+    tee::write(&format!("> {}\n", stmt));
+    let start = Instant::now();
+    let result = _query_with_params(conn, stmt, options,
+                                    params, sensitive, used)
+        .await
+        .map_err(|e| verbose_error(e, stmt, options));
+    print_timing(options, start);
+    result
+}
+
+async fn _query_with_params(conn: &mut Connection, stmt: &str,
+    options: &Options, params: &[(String, String)], sensitive: &[String],
+    used: &mut BTreeSet<String>)
+    -> Result<(), anyhow::Error>
+{
+    let arguments = arguments(conn, stmt, params, sensitive, used).await?;
+    query_args(conn, stmt, options, &arguments).await
+}
+
+/// Executes a query and prints differences of its result from the JSON
+/// saved in `path`, see `--diff-against`
+pub async fn diff_with_params(conn: &mut Connection, stmt: &str,
+    params: &[(String, String)], sensitive: &[String], path: &Path)
+    -> Result<(), anyhow::Error>
+{
+    tee::write(&format!("> {}\n", stmt));
+    let mut used = BTreeSet::new();
+    let arguments = arguments(conn, stmt, params, sensitive, &mut used)
+        .await?;
+    check_unused(params, &used)?;
+    let changes = result_diff::diff(conn, stmt, &arguments, path).await?;
+    if changes.is_empty() {
+        eprintln!("Result is the same as in {}", path.display());
+        return Ok(());
+    }
+    let text = changes.to_string();
+    tee::write(&text);
+    print!("{}", text);
+    Err(ExitCode::new(1).into())
+}
+
+/// Reports parameters passed in `params` which none of the queries has
+pub fn check_unused(params: &[(String, String)], used: &BTreeSet<String>)
+    -> anyhow::Result<()>
+{
+    for (name, _) in params {
+        if !used.contains(name) {
+            anyhow::bail!("no query has parameter ${}", name);
+        }
+    }
+    Ok(())
+}
+
+async fn arguments(conn: &mut Connection, stmt: &str,
+    params: &[(String, String)], sensitive: &[String],
+    used: &mut BTreeSet<String>)
+    -> Result<Value, anyhow::Error>
+{
+    if !stmt.contains('$') {
+        return Ok(Value::empty_tuple());
+    }
+    let indesc = describe_input(conn, stmt).await?;
+    make_arguments(&indesc, |name, type_name| {
+        used.insert(name.to_string());
+        match params.iter().rev().find(|(n, _)| n == name) {
+            Some((_, value)) => Ok(value.clone()),
+            None => read_parameter(name, type_name,
+                sensitive.iter().any(|s| s == name)),
+        }
+    })
+}
+
+/// Executes query using the EdgeQL over HTTP endpoint
+///
+/// The endpoint returns JSON, so `--output-format=default` prints the same
+/// as `--json`, and `--tab-separated` is not supported.
+pub async fn query_http(endpoint: &Endpoint, stmt: &str, options: &Options,
+    params: &[(String, String)], sensitive: &[String])
+    -> Result<(), anyhow::Error>
+{
+    use crate::repl::OutputMode::*;
+
+    tee::write(&format!("> {}\n", stmt));
+    options.restrictions.check(stmt)?;
+    let start = Instant::now();
+    let mut variables = serde_json::Map::new();
+    for (name, value) in params {
+        variables.insert(name.clone(), value.clone().into());
+    }
+    for name in sensitive {
+        if !variables.contains_key(name) {
+            let value = read_parameter(name, "str", true)?;
+            variables.insert(name.clone(), value.into());
+        }
+    }
+    let items = endpoint.query(stmt, variables).await?;
+    print_timing(options, start);
+
+    let mut cfg = print::Config::new();
+    if let Some((w, _h)) = term_size::dimensions_stdout() {
+        cfg.max_width(w);
+    }
+    cfg.colors(print::console::colors_stdout());
+    match options.output_mode {
+        TabSeparated => {
+            anyhow::bail!("tab-separated output is not supported \
+                with `--connection=http`");
+        }
+        Default | Json => {
+            let data = print::json_to_string(&items, &cfg)?;
+            tee::write(&format!("{}\n", data));
+            println!("{}", data);
+        }
+        JsonElements => {
+            for item in &items {
+                let data = print::json_item_to_string(item, &cfg)?;
+                tee::write(&format!("{}\n", data));
+                println!("{}", data);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn query_args(conn: &mut Connection, stmt: &str, options: &Options,
+    arguments: &Value)
+    -> Result<(), anyhow::Error>
+{
+    query_retry(conn, stmt, options, arguments,
+                &mut Sink::Stdout { written: false }).await
+}
+
+/// Executes the query, retrying it on transient errors if nothing was
+/// printed yet
+///
+/// Errors writing the output are never retried.
+///
+/// Statements of a script running in a single transaction are never
+/// retried, because the whole transaction is aborted on error.
+async fn query_retry(conn: &mut Connection, stmt: &str, options: &Options,
+    arguments: &Value, out: &mut Sink<'_>)
+    -> Result<(), anyhow::Error>
+{
+    let in_transaction = options.script.as_ref()
+        .map(|s| s.single_transaction)
+        .unwrap_or(false);
+    let read_only = retry::is_read_only(stmt);
+    let mut attempt = 0;
+    loop {
+        let position = out.position();
+        let err = match query_session(conn, stmt, options, arguments, out)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        if in_transaction || attempt >= options.retry.attempts
+            || !retry::is_retryable(&err, read_only)
+            || !out.discard(position)
+        {
+            log::trace!(target: "edgedb::protocol",
+                "Not retrying (attempt {}, read-only: {}, \
+                 in transaction: {})", attempt, read_only, in_transaction);
+            return Err(err);
+        }
+        let delay = options.retry.delay(attempt);
+        log::info!("Retrying query in {:?} (attempt {} of {}) after: {:#}",
+            delay, attempt + 1, options.retry.attempts, err);
+        task::sleep(delay).await;
+        if !conn.is_consistent() {
+            log::trace!(target: "edgedb::protocol",
+                "Connection is broken, reconnecting before retry");
+            *conn = options.conn_params.connect().await?;
+        }
+        attempt += 1;
+    }
+}
+
+/// Checks restrictions of the session (`--no-ddl` and others) and starts
+/// read-only transaction for the statement if `--read-only` is set
+///
+/// Returns true if the transaction should be finished with
+/// `read_only::finish()`.
+pub async fn begin_statement(conn: &mut Connection, stmt: &str,
+    options: &Options)
+    -> anyhow::Result<bool>
+{
+    options.restrictions.check(stmt)?;
+    if !options.read_only {
+        return Ok(false);
+    }
+    read_only::check(stmt)?;
+    read_only::begin(conn, stmt).await
+}
+
+/// Checks restrictions of the session and executes the query, in a
+/// read-only transaction if `--read-only` is set
+async fn query_session(conn: &mut Connection, stmt: &str, options: &Options,
+    arguments: &Value, out: &mut Sink<'_>)
+    -> Result<(), anyhow::Error>
+{
+    let started = begin_statement(conn, stmt, options).await?;
+    let result = query_to(conn, stmt, options, arguments, out).await;
+    read_only::finish(conn, started, result).await
+}
+
+async fn query_to(conn: &mut Connection, stmt: &str, options: &Options,
+    arguments: &Value, out: &mut Sink<'_>)
+    -> Result<(), anyhow::Error>
 {
     use crate::repl::OutputMode::*;
     let mut cfg = print::Config::new();
     if let Some((w, _h)) = term_size::dimensions_stdout() {
         cfg.max_width(w);
     }
-    cfg.colors(atty::is(atty::Stream::Stdout));
+    cfg.colors(print::console::colors_stdout());
 
     match options.output_mode {
         TabSeparated => {
             let mut items = match
-                conn.query_dynamic(stmt, &Value::empty_tuple()).await
+                conn.query_dynamic(stmt, arguments).await
             {
                 Ok(items) => items,
                 Err(e) => match e.downcast::<NoResultExpected>() {
                     Ok(e) => {
-                        print::completion(&e.completion_message);
+                        out.completion(&e.completion_message);
                         return Ok(());
                     }
                     Err(e) => Err(e)?,
@@ -68,23 +703,33 @@ pub async fn query(conn: &mut Connection, stmt: &str, options: &Options)
                 let mut text = tab_separated::format_row(&row)?;
                 // trying to make writes atomic if possible
                 text += "\n";
-                stdout().write_all(text.as_bytes()).await?;
+                out.write(&text).await?;
             }
         }
         Default => {
             let items = match
-                conn.query_dynamic(stmt, &Value::empty_tuple()).await
+                conn.query_dynamic(stmt, arguments).await
             {
                 Ok(items) => items,
                 Err(e) => match e.downcast::<NoResultExpected>() {
                     Ok(e) => {
-                        print::completion(&e.completion_message);
+                        out.completion(&e.completion_message);
                         return Ok(());
                     }
                     Err(e) => Err(e)?,
                 },
             };
-            match print::native_to_stdout(items, &cfg).await {
+            let result = match out {
+                Sink::Stdout { written } => {
+                    // rows are printed as soon as they are received
+                    *written = true;
+                    print::native_to_stdout(items, &cfg).await.map(|_| ())
+                }
+                Sink::Buffer(buf) => print::native_to_string(items, &cfg)
+                    .await
+                    .map(|text| buf.data.push_str(&text)),
+            };
+            match result {
                 Ok(()) => {}
                 Err(e) => {
                     match e {
@@ -104,12 +749,12 @@ pub async fn query(conn: &mut Connection, stmt: &str, options: &Options)
         }
         JsonElements => {
             let mut items = match
-                conn.query_json_els(stmt, &Value::empty_tuple()).await
+                conn.query_json_els(stmt, arguments).await
             {
                 Ok(items) => items,
                 Err(e) => match e.downcast::<NoResultExpected>() {
                     Ok(e) => {
-                        print::completion(&e.completion_message);
+                        out.completion(&e.completion_message);
                         return Ok(());
                     }
                     Err(e) => Err(e)?,
@@ -121,17 +766,17 @@ pub async fn query(conn: &mut Connection, stmt: &str, options: &Options)
                 // trying to make writes atomic if possible
                 let mut data = print::json_item_to_string(&value, &cfg)?;
                 data += "\n";
-                stdout().write_all(data.as_bytes()).await?;
+                out.write(&data).await?;
             }
         }
         Json => {
             let mut items = match
-                conn.query_json(stmt, &Value::empty_tuple()).await
+                conn.query_json(stmt, arguments).await
             {
                 Ok(items) => items,
                 Err(e) => match e.downcast::<NoResultExpected>() {
                     Ok(e) => {
-                        print::completion(&e.completion_message);
+                        out.completion(&e.completion_message);
                         return Ok(());
                     }
                     Err(e) => Err(e)?,
@@ -146,7 +791,7 @@ pub async fn query(conn: &mut Connection, stmt: &str, options: &Options)
                 // trying to make writes atomic if possible
                 let mut data = print::json_to_string(items, &cfg)?;
                 data += "\n";
-                stdout().write_all(data.as_bytes()).await?;
+                out.write(&data).await?;
             }
         }
     }
@@ -1,23 +1,48 @@
 use std::env;
 use std::time::Duration;
 use std::fs;
+use std::path::PathBuf;
 
 use anyhow::Context;
 use atty;
 use clap::{Clap, AppSettings, ValueHint};
 use edgedb_client::Builder;
 
-use crate::commands::parser::Common;
-use crate::connect::Connector;
-use crate::credentials::get_connector;
+use crate::bench;
+use crate::cloud;
+use crate::commands::parser::{Common, Watch, InspectDump};
+use crate::commands::parser::{Check, CloneDatabase, EditMigration, Lint};
+use crate::commands::parser::Schema;
+use crate::config;
+use crate::connect::{Connector, set_global_command};
+use crate::crash_reports;
+use crate::credentials::{self, get_connector};
+use crate::doctor;
+use crate::error_display;
 use crate::hint::HintExt;
+use crate::graphql;
+use crate::info;
+use crate::man;
+use crate::ping;
 use crate::project;
 use crate::repl::OutputMode;
+use crate::restrict::Restrictions;
+use crate::retry;
+use crate::secrets;
 use crate::self_install;
 use crate::self_upgrade;
 use crate::server;
+use crate::server_proxy;
+use crate::shell_completion;
+use crate::shell_env;
+use crate::slowlog;
+use crate::snippets;
+use crate::telemetry;
+use crate::ui;
 
 
+const DEFAULT_IMPLICIT_LIMIT: usize = 100;
+
 static CONNECTION_ARG_HINT: &str = "\
     Run `edgedb project init` or use any of `-H`, `-P`, `-I` arguments \
     to specify connection parameters. See `--help` for details";
@@ -78,12 +103,19 @@ pub struct RawOptions {
            parse(try_from_str=humantime::parse_duration))]
     pub connect_timeout: Option<Duration>,
 
-    /// Local instance name created with `edgedb server init` to connect to
-    /// (overrides host and port)
+    /// Local instance name created with `edgedb server init` or cloud
+    /// instance name in form `org/instance` to connect to (overrides host
+    /// and port)
     #[clap(short='I', long, help_heading=Some("CONNECTION OPTIONS"))]
     #[clap(value_hint=ValueHint::Other)]  // TODO complete instance name
     pub instance: Option<String>,
 
+    /// Connect to the instance of the project in this directory, instead
+    /// of the project found in the current directory or above
+    #[clap(long, help_heading=Some("CONNECTION OPTIONS"))]
+    #[clap(value_hint=ValueHint::DirPath, conflicts_with="instance")]
+    pub project_dir: Option<PathBuf>,
+
     #[clap(long, help_heading=Some("DEBUG OPTIONS"))]
     #[cfg_attr(not(feature="dev_mode"),
         clap(setting=clap::ArgSettings::Hidden))]
@@ -107,14 +139,106 @@ pub struct RawOptions {
     #[clap(short='j', long, overrides_with="tab_separated")]
     pub json: bool,
 
+    /// Output format of the queries. One of: default, json, json-elements,
+    /// tab-separated. JSON formats also print errors as JSON objects
+    #[clap(long, value_name="FORMAT",
+           possible_values=
+               &["default", "json", "json-elements", "tab-separated"][..],
+           conflicts_with_all=&["json", "tab_separated"])]
+    pub output_format: Option<OutputMode>,
+
     /// Execute a query instead of starting REPL (alias to `edgedb query`)
     #[clap(short='c')]
     pub query: Option<String>,
 
+    /// Execute EdgeQL script from a file (`-` for stdin) instead of
+    /// starting REPL
+    #[clap(long, value_hint=ValueHint::FilePath, conflicts_with="query")]
+    pub file: Option<PathBuf>,
+
+    /// Run the whole script of `--file` in a single transaction
+    /// (default `true`)
+    #[clap(long, value_name="BOOL",
+           possible_values=&["true", "false"][..],
+           parse(try_from_str))]
+    pub single_transaction: Option<bool>,
+
+    /// Stop executing the script of `--file` on the first error
+    /// (default `true`)
+    #[clap(long, value_name="BOOL",
+           possible_values=&["true", "false"][..],
+           parse(try_from_str))]
+    pub stop_on_error: Option<bool>,
+
+    /// Execute statements of the script in parallel over N connections
+    /// (requires `--single-transaction=false`)
+    #[clap(long, value_name="N", default_value="1")]
+    pub concurrency: usize,
+
+    /// Print results of the concurrent script as soon as they are ready
+    /// rather than in the order of statements
+    #[clap(long)]
+    pub unordered: bool,
+
     /// Disable version check
     #[clap(long)]
     pub no_version_check: bool,
 
+    /// Keep passwords in credentials files instead of the OS keychain
+    #[clap(long)]
+    pub no_keyring: bool,
+
+    /// Show server tracebacks and query context of the errors
+    #[clap(long)]
+    pub verbose_errors: bool,
+
+    /// Print execution time after each statement
+    #[clap(long)]
+    pub timing: bool,
+
+    /// Execute statements in read-only transactions, so that data and
+    /// schema can't be modified by accident
+    #[clap(long)]
+    pub read_only: bool,
+
+    /// Read the query parameter in the interactive shell with input hidden
+    #[clap(long="sensitive", value_name="NAME", number_of_values=1)]
+    pub sensitive: Vec<String>,
+
+    /// Reject DDL statements (including migrations) before sending them
+    #[clap(long)]
+    pub no_ddl: bool,
+
+    /// Reject `CONFIGURE` statements and setting of aliases and modules
+    #[clap(long)]
+    pub no_config: bool,
+
+    /// Reject statements controlling transactions
+    #[clap(long)]
+    pub no_transactions: bool,
+
+    /// Append a plain-text copy of the statements, results and errors
+    /// of the session to FILE
+    #[clap(long, value_name="FILE", value_hint=ValueHint::FilePath)]
+    pub tee: Option<PathBuf>,
+
+    /// Log level of the tool and the client library: error, warn, info,
+    /// debug or trace. Overrides `RUST_LOG` for these modules. Passwords are
+    /// never logged
+    #[clap(long, value_name="LEVEL",
+           possible_values=&["error", "warn", "info", "debug", "trace"][..])]
+    pub log_level: Option<log::LevelFilter>,
+
+    /// Number of retries of queries failed with transient errors, such as
+    /// transaction serialization failures (0 to disable)
+    #[clap(long, value_name="N")]
+    pub retry_attempts: Option<u32>,
+
+    /// Delay before the first retry, doubled on each next one
+    #[clap(long, value_name="DURATION",
+           parse(try_from_str=humantime::parse_duration))]
+    pub retry_backoff: Option<Duration>,
+
     #[clap(subcommand)]
     pub subcommand: Option<Command>,
 }
@@ -124,23 +248,94 @@ pub enum Command {
     /// Change role parameters
     AlterRole(RoleParams),
     /// Create a new role
+    CreateRole(RoleParams),
+    /// Create a new superuser role
     CreateSuperuserRole(RoleParams),
     /// Delete a role
     DropRole(RoleName),
+    /// Show information about the CLI installation and environment
+    Info(info::Info),
+    /// Check the environment for common problems and suggest fixes
+    ///
+    /// Run it before reporting a bug, and include its output in the report.
+    Doctor(doctor::Doctor),
+    /// Manage sending of anonymous usage statistics (disabled by default)
+    Telemetry(telemetry::TelemetryCommand),
+    /// Manage reports saved on crashes of the CLI
+    CrashReports(crash_reports::CrashReportsCommand),
+    /// Execute GraphQL query using the HTTP endpoint of the database
+    Graphql(graphql::Graphql),
     /// Execute EdgeQL query
     Query(Query),
+    /// Check that the instance accepts connections and report latency
+    Ping(ping::Ping),
+    /// Run a query repeatedly and report latency percentiles and throughput
+    Bench(bench::Bench),
+    /// Print `EDGEDB_DSN` of the instance as a shell command to `eval`
+    Env(shell_env::Env),
     /// Manage local server installations
     Server(server::options::ServerCommand),
+    /// Run language server for editors (completion, diagnostics, hover)
+    ServerProxy(server_proxy::ServerProxy),
     /// Manage project installation
     Project(project::options::ProjectCommand),
+    /// Log in to EdgeDB Cloud and manage cloud instances
+    Cloud(cloud::options::CloudCommand),
+    /// Watch schema files and apply changes to the database
+    Watch(Watch),
+    /// Edit a migration file and fix names of it and the later migrations
+    ///
+    /// Opens the file in `$EDITOR`, then recomputes migration names, which
+    /// are hashes of the contents, and updates the `CREATE MIGRATION`
+    /// headers of this and all the following migration files.
+    EditMigration(EditMigration),
+    /// Check syntax of schema and migration files without applying them
+    ///
+    /// Exits with non-zero code on the first error, which makes it usable
+    /// in CI.
+    Check(Check),
+    /// Check schema and migration files for likely mistakes
+    ///
+    /// Rules can be set to `allow`, `warn` or `error` in the `[lint]`
+    /// table of `edgedb.toml`, e.g. `deletion-policy = "error"`.
+    Lint(Lint),
+    /// Compare schemas of databases and schema files
+    ///
+    /// Comparison is done by the migration planner of the server in
+    /// a temporary database, created on the instance specified by the
+    /// connection options and dropped afterwards.
+    Schema(Schema),
+    /// Show what a dump file contains without restoring it
+    InspectDump(InspectDump),
+    /// Copy a database to another database or instance
+    ///
+    /// Dump of the source database is restored into the target as it's
+    /// received, without writing it to disk.
+    Clone(CloneDatabase),
+    /// Review statements recorded by `\set log-slow-queries` of the REPL
+    Slowlog(slowlog::SlowlogCommand),
+    /// Manage queries saved by `\save` of the REPL
+    Snippet(snippets::SnippetCommand),
+    /// Open the web UI of the instance in the browser
+    Ui(ui::Ui),
     /// Install server
     #[clap(setting=AppSettings::Hidden, name="_self_install")]
     _SelfInstall(self_install::SelfInstall),
     /// Generate shell completions
     #[clap(setting=AppSettings::Hidden, name="_gen_completions")]
     _GenCompletions(self_install::GenCompletions),
+    /// Generate manual pages
+    #[clap(setting=AppSettings::Hidden, name="gen-man")]
+    GenMan(man::GenMan),
+    /// Print dynamic completions for the command line
+    #[clap(setting=AppSettings::Hidden, name="_complete")]
+    _Complete(shell_completion::Complete),
     /// Upgrade this edgedb binary
     SelfUpgrade(self_upgrade::SelfUpgrade),
+    /// Uninstall this edgedb binary and undo changes to shell profiles
+    SelfUninstall(self_install::SelfUninstall),
+    #[clap(external_subcommand)]
+    External(Vec<String>),
     #[clap(flatten)]
     Common(Common),
 }
@@ -148,9 +343,55 @@ pub enum Command {
 #[derive(Clap, Clone, Debug)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct Query {
+    /// Set query parameter, e.g. `--param name=value` for `<str>$name`
+    #[clap(long="param", value_name="NAME=VALUE", number_of_values=1,
+           parse(try_from_str=parse_param))]
+    pub params: Vec<(String, String)>,
+    /// Read the parameter from the terminal with input hidden
+    /// (unless set by `--param`)
+    #[clap(long="sensitive", value_name="NAME", number_of_values=1)]
+    pub sensitive: Vec<String>,
+    /// Set global for the session before running queries, where value is
+    /// an EdgeQL expression, e.g. `--set-global "name=<str>'alice'"`
+    #[clap(long="set-global", value_name="NAME=VALUE", number_of_values=1,
+           parse(try_from_str=parse_global))]
+    pub globals: Vec<(String, String)>,
+    /// Protocol used to execute queries: `binary` or `http`. The latter
+    /// sends queries to the EdgeQL over HTTP endpoint, which is useful when
+    /// only HTTP(S) ports are reachable. Parameters are sent as strings
+    #[clap(long, possible_values=&["binary", "http"][..],
+           default_value="binary")]
+    pub connection: ConnectionKind,
+    /// Base URL of the server HTTP port for `--connection=http`
+    /// (discovered from connection options by default)
+    #[clap(long, value_name="URL")]
+    pub http_url: Option<String>,
+    /// Print type descriptors and a hex dump of each data element received
+    /// along with the decoded values (for debugging codecs)
+    #[clap(long)]
+    pub debug_protocol: bool,
+    /// Run queries with access policies disabled
+    /// (`apply_access_policies := false`), requires a superuser
+    #[clap(long)]
+    pub bypass_access_policies: bool,
+    /// Append a plain-text copy of the queries, results and errors to FILE
+    #[clap(long, value_name="FILE", value_hint=ValueHint::FilePath)]
+    pub tee: Option<PathBuf>,
+    /// Compare the result with the output of a previous run saved using
+    /// `--json` or `--output-format=json-elements`, and print objects
+    /// added, removed and changed (matched by `id`). Exits with status 1
+    /// if the results differ
+    #[clap(long, value_name="FILE", value_hint=ValueHint::FilePath)]
+    pub diff_against: Option<PathBuf>,
     pub queries: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionKind {
+    Binary,
+    Http,
+}
+
 #[derive(Clap, Clone, Debug)]
 #[clap(setting=AppSettings::DisableVersionFlag)]
 pub struct RoleParams {
@@ -170,16 +411,45 @@ pub struct RoleName {
     pub role: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub file: PathBuf,
+    pub single_transaction: bool,
+    pub stop_on_error: bool,
+    pub concurrency: usize,
+    pub unordered: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Options {
     pub conn_params: Connector,
     pub subcommand: Option<Command>,
+    pub script: Option<Script>,
     pub interactive: bool,
     pub debug_print_frames: bool,
     pub debug_print_descriptors: bool,
     pub debug_print_codecs: bool,
+    pub log_level: Option<log::LevelFilter>,
+    pub retry: retry::Config,
     pub output_mode: OutputMode,
     pub no_version_check: bool,
+    pub verbose_errors: bool,
+    pub timing: bool,
+    /// Ask for password again if authentication fails (interactive mode)
+    pub password_prompt: bool,
+    /// Connection options passed to external subcommands
+    pub plugin_env: Vec<(&'static str, String)>,
+    /// Local instance connected to (if any)
+    pub instance_name: Option<String>,
+    /// Initial implicit limit of the REPL (can be set in `edgedb.toml`)
+    pub implicit_limit: Option<usize>,
+    /// Session log file (`--tee`)
+    pub tee: Option<PathBuf>,
+    pub read_only: bool,
+    /// Parameters read with input hidden in the REPL (`--sensitive`)
+    pub sensitive: Vec<String>,
+    /// Statements forbidden by `--no-ddl` and similar options
+    pub restrictions: Restrictions,
 }
 
 impl Options {
@@ -187,12 +457,29 @@ impl Options {
         let tmp = RawOptions::parse();
         // TODO(pc) add option to force interactive mode not on a tty (tests)
         let interactive = tmp.query.is_none()
+            && tmp.file.is_none()
             && tmp.subcommand.is_none()
             && atty::is(atty::Stream::Stdin);
-        let mut conn_params = Connector::new(conn_params(&tmp));
+        if tmp.no_keyring {
+            secrets::disable();
+        }
+        let project_config = project_config(&tmp).unwrap_or_else(|e| {
+            log::warn!("{:#}", e);
+            None
+        });
+        let instance = if tmp.dsn.is_some() {
+            Ok(None)
+        } else {
+            instance_name(&tmp)
+        };
+        // errors are reported on connect by the connector below
+        let instance_name = instance.as_ref().ok().cloned().flatten()
+            .filter(|name| !cloud::is_cloud_name(name));
+        let mut conn_params = Connector::new(
+            conn_params(&tmp, project_config.as_ref(), instance));
         let password = if tmp.password_from_stdin {
             let password = rpassword::read_password()
-                .expect("password can be read");
+                .context("error reading password from stdin")?;
             Some(password)
         } else if tmp.no_password {
             None
@@ -208,6 +495,13 @@ impl Options {
                 Err(_) => None,
             }
         };
+        let plugin_env = plugin_env(&tmp, &password);
+        let retry = retry_config(&tmp);
+        if matches!(tmp.output_format,
+                    Some(OutputMode::Json) | Some(OutputMode::JsonElements))
+        {
+            error_display::enable_json_errors();
+        }
         conn_params.modify(|params| {
             password.map(|password| params.password(password));
             tmp.wait_until_available.map(|w| params.wait_until_available(w));
@@ -220,21 +514,91 @@ impl Options {
                     "Option `-c` conflicts with specifying subcommand");
             } else {
                 Some(Command::Query(Query {
+                    params: Vec::new(),
+                    sensitive: Vec::new(),
+                    globals: Vec::new(),
+                    connection: ConnectionKind::Binary,
+                    http_url: None,
+                    debug_protocol: false,
+                    bypass_access_policies: false,
+                    tee: None,
+                    diff_against: None,
                     queries: vec![query],
                 }))
             }
         } else {
             tmp.subcommand
         };
+        if let Some(Command::Query(q)) = &subcommand {
+            for (name, value) in &q.globals {
+                // fail early rather than on each connect
+                set_global_command(name, value)?;
+                conn_params.set_global(name, value);
+            }
+        }
+        let bypass_access_policies = match &subcommand {
+            Some(Command::Query(q)) => q.bypass_access_policies,
+            Some(Command::Common(Common::Dump(d))) => {
+                d.bypass_access_policies
+            }
+            Some(Command::Common(Common::Export(e))) => {
+                e.bypass_access_policies
+            }
+            _ => false,
+        };
+        if bypass_access_policies {
+            conn_params.bypass_access_policies();
+        }
+        let tee = match &subcommand {
+            Some(Command::Query(q)) => q.tee.clone().or(tmp.tee),
+            _ => tmp.tee,
+        };
+        let script = if let Some(file) = tmp.file {
+            if subcommand.is_some() {
+                anyhow::bail!(
+                    "Option `--file` conflicts with specifying subcommand");
+            }
+            let single_transaction = tmp.single_transaction.unwrap_or(true);
+            let stop_on_error = tmp.stop_on_error.unwrap_or(true);
+            if single_transaction && !stop_on_error {
+                anyhow::bail!("Option `--stop-on-error=false` requires \
+                    `--single-transaction=false`");
+            }
+            if tmp.concurrency == 0 {
+                anyhow::bail!("Option `--concurrency` must be positive");
+            }
+            if single_transaction && tmp.concurrency > 1 {
+                anyhow::bail!("Option `--concurrency` requires \
+                    `--single-transaction=false`");
+            }
+            Some(Script {
+                file,
+                single_transaction,
+                stop_on_error,
+                concurrency: tmp.concurrency,
+                unordered: tmp.unordered,
+            })
+        } else if tmp.single_transaction.is_some() {
+            anyhow::bail!("Option `--single-transaction` requires `--file`");
+        } else if tmp.stop_on_error.is_some() {
+            anyhow::bail!("Option `--stop-on-error` requires `--file`");
+        } else {
+            None
+        };
 
         Ok(Options {
             conn_params,
             interactive,
             subcommand,
+            script,
             debug_print_frames: tmp.debug_print_frames,
             debug_print_descriptors: tmp.debug_print_descriptors,
             debug_print_codecs: tmp.debug_print_codecs,
-            output_mode: if tmp.tab_separated {
+            log_level: tmp.log_level,
+            retry,
+            output_mode: if let Some(mode) = tmp.output_format {
+                mode
+            } else if tmp.tab_separated {
                 OutputMode::TabSeparated
             } else if tmp.json {
                 OutputMode::Json
@@ -244,24 +608,107 @@ impl Options {
                 OutputMode::JsonElements
             },
             no_version_check: tmp.no_version_check,
+            verbose_errors: tmp.verbose_errors,
+            timing: tmp.timing,
+            password_prompt: !tmp.no_password && !tmp.password_from_stdin,
+            plugin_env,
+            instance_name,
+            implicit_limit: match project_config
+                .and_then(|c| c.connection.implicit_limit)
+            {
+                Some(0) => None,
+                Some(limit) => Some(limit),
+                None => Some(DEFAULT_IMPLICIT_LIMIT),
+            },
+            tee,
+            read_only: tmp.read_only,
+            sensitive: tmp.sensitive,
+            restrictions: Restrictions {
+                no_ddl: tmp.no_ddl,
+                no_config: tmp.no_config,
+                no_transactions: tmp.no_transactions,
+            },
         })
     }
 }
 
-fn conn_params(tmp: &RawOptions) -> anyhow::Result<Builder> {
-    let instance = if let Some(dsn) = &tmp.dsn {
-        return Ok(Builder::from_dsn(dsn)?);
-    } else if tmp.instance.is_some() ||
+fn plugin_env(tmp: &RawOptions, password: &Option<String>)
+    -> Vec<(&'static str, String)>
+{
+    let mut env = Vec::new();
+    if let Some(dsn) = &tmp.dsn {
+        env.push(("EDGEDB_DSN", dsn.clone()));
+    }
+    if let Some(instance) = &tmp.instance {
+        env.push(("EDGEDB_INSTANCE", instance.clone()));
+    }
+    if let Some(host) = &tmp.host {
+        env.push(("EDGEDB_HOST", host.clone()));
+    }
+    if let Some(port) = tmp.port {
+        env.push(("EDGEDB_PORT", port.to_string()));
+    }
+    if let Some(user) = &tmp.user {
+        env.push(("EDGEDB_USER", user.clone()));
+    }
+    if let Some(database) = &tmp.database {
+        env.push(("EDGEDB_DATABASE", database.clone()));
+    }
+    if let Some(password) = password {
+        env.push(("EDGEDB_PASSWORD", password.clone()));
+    }
+    return env;
+}
+
+impl std::str::FromStr for ConnectionKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<ConnectionKind, anyhow::Error> {
+        match s {
+            "binary" => Ok(ConnectionKind::Binary),
+            "http" => Ok(ConnectionKind::Http),
+            _ => Err(anyhow::anyhow!("unsupported connection {:?}", s)),
+        }
+    }
+}
+
+pub(crate) fn parse_param(value: &str) -> anyhow::Result<(String, String)> {
+    match value.find('=') {
+        Some(pos) => {
+            let name = value[..pos].trim_start_matches('$');
+            if name.is_empty() {
+                anyhow::bail!("parameter name must not be empty");
+            }
+            Ok((name.into(), value[pos+1..].into()))
+        }
+        None => anyhow::bail!("parameter must be in form `name=value`"),
+    }
+}
+
+fn parse_global(value: &str) -> anyhow::Result<(String, String)> {
+    match value.find('=') {
+        Some(pos) if pos > 0 => {
+            Ok((value[..pos].into(), value[pos+1..].into()))
+        }
+        _ => anyhow::bail!("global must be in form `name=value`"),
+    }
+}
+
+fn instance_name(tmp: &RawOptions) -> anyhow::Result<Option<String>> {
+    let instance = if
+            tmp.instance.is_some() ||
             tmp.host.is_some() || tmp.port.is_some() ||
             env::var("EDGEDB_HOST").is_ok() ||
             env::var("EDGEDB_PORT").is_ok()
     {
-        tmp.instance.clone()
+        tmp.instance.as_deref().map(|name| {
+            if cloud::is_cloud_name(name) {
+                Ok(name.to_string())
+            } else {
+                credentials::resolve_name(name)
+            }
+        }).transpose()?
     } else {
-        let dir = env::current_dir()
-            .context("cannot determine current dir")
-            .hint(CONNECTION_ARG_HINT)?;
-        let config_dir = project::project_dir_opt(Some(&dir))
+        let config_dir = project::project_dir_opt(tmp.project_dir.as_deref())
             .context("error searching for `edgedb.toml`")
             .hint(CONNECTION_ARG_HINT)?
             .ok_or_else(|| {
@@ -275,6 +722,44 @@ fn conn_params(tmp: &RawOptions) -> anyhow::Result<Builder> {
             .context("error reading project settings")?
         )
     };
+    Ok(instance)
+}
+
+/// Retry settings from the command line, then `cli.toml`
+fn retry_config(tmp: &RawOptions) -> retry::Config {
+    let config = config::read().unwrap_or_else(|e| {
+        log::warn!("{:#}", e);
+        config::Config::default()
+    });
+    retry::Config {
+        attempts: tmp.retry_attempts
+            .or(config.query.retry_attempts)
+            .unwrap_or(retry::DEFAULT_ATTEMPTS),
+        backoff: tmp.retry_backoff
+            .or(config.query.retry_backoff)
+            .unwrap_or(retry::DEFAULT_BACKOFF),
+    }
+}
+
+fn project_config(tmp: &RawOptions)
+    -> anyhow::Result<Option<project::config::Config>>
+{
+    match project::project_dir_opt(tmp.project_dir.as_deref())? {
+        Some(dir) => {
+            Ok(Some(project::config::read(&dir.join("edgedb.toml"))?))
+        }
+        None => Ok(None),
+    }
+}
+
+fn conn_params(tmp: &RawOptions, project: Option<&project::config::Config>,
+    instance: anyhow::Result<Option<String>>)
+    -> anyhow::Result<Builder>
+{
+    if let Some(dsn) = &tmp.dsn {
+        return Ok(Builder::from_dsn(dsn)?);
+    }
+    let instance = instance?;
 
     let admin = tmp.admin;
     let user = tmp.user.clone().or_else(|| env::var("EDGEDB_USER").ok());
@@ -287,7 +772,16 @@ fn conn_params(tmp: &RawOptions) -> anyhow::Result<Builder> {
 
     let mut conn_params = Builder::new();
     if let Some(name) = &instance {
-        conn_params = get_connector(name)?;
+        conn_params = if cloud::is_cloud_name(name) {
+            cloud::get_connector(name)?
+        } else {
+            get_connector(name)?
+        };
+        // project defaults only apply to the instance of the project
+        let database = database.or_else(|| {
+            project.filter(|_| tmp.instance.is_none())
+                .and_then(|p| p.connection.database.clone())
+        });
         user.map(|user| conn_params.user(user));
         database.map(|database| conn_params.database(database));
     } else {
@@ -0,0 +1,129 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use async_std::net::ToSocketAddrs;
+use async_std::task;
+use clap::{Clap, AppSettings};
+use edgedb_protocol::value::Value;
+
+use crate::async_util::timeout;
+use crate::commands::ExitCode;
+use crate::connect::is_authentication_error;
+use crate::options::Options;
+
+
+/// Check that the instance accepts connections and queries
+///
+/// Exit codes: 0 -- success, 1 -- other error, 2 -- host name cannot be
+/// resolved, 3 -- TCP connection failed (refused or timed out),
+/// 4 -- TLS handshake failed, 5 -- authentication failed.
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Ping {
+    /// Fail if no response is received within this time
+    #[clap(long, default_value="10s",
+           parse(try_from_str=humantime::parse_duration))]
+    pub timeout: Duration,
+    /// Only report failures
+    #[clap(long, short='q')]
+    pub quiet: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    Other = 1,
+    Dns = 2,
+    Tcp = 3,
+    Tls = 4,
+    Authentication = 5,
+}
+
+
+fn classify(err: &anyhow::Error) -> Failure {
+    if is_authentication_error(err) {
+        return Failure::Authentication;
+    }
+    for item in err.chain() {
+        if item.is::<native_tls::Error>() {
+            return Failure::Tls;
+        }
+        if let Some(e) = item.downcast_ref::<io::Error>() {
+            use io::ErrorKind::*;
+            match e.kind() {
+                ConnectionRefused | ConnectionReset | ConnectionAborted
+                | NotConnected | AddrNotAvailable | TimedOut | NotFound
+                => return Failure::Tcp,
+                _ => {}
+            }
+        }
+    }
+    Failure::Other
+}
+
+async fn resolve(addr: &str) -> anyhow::Result<()> {
+    let mut addrs = addr.to_socket_addrs().await?;
+    if addrs.next().is_none() {
+        anyhow::bail!("no addresses found");
+    }
+    Ok(())
+}
+
+async fn ping(options: &Options, limit: Duration)
+    -> Result<(Duration, String), Failure>
+{
+    let params = options.conn_params.get().map_err(|e| {
+        eprintln!("edgedb error: {:#}", e);
+        Failure::Other
+    })?;
+    let addr = params.get_addr().to_string();
+    // unix sockets have no host name to resolve
+    if !addr.contains('/') {
+        resolve(&addr).await.map_err(|e| {
+            eprintln!("edgedb error: cannot resolve {}: {:#}", addr, e);
+            Failure::Dns
+        })?;
+    }
+    let start = Instant::now();
+    let version = async {
+        let mut cli = params.connect().await?;
+        cli.query_row::<i64>("SELECT 1", &Value::empty_tuple()).await?;
+        cli.get_version().await
+    };
+    let version = timeout(limit, version).await
+        .map_err(|e| {
+            eprintln!("edgedb error: cannot connect to {}: {:#}", addr, e);
+            classify(&e)
+        })?;
+    Ok((start.elapsed(), version))
+}
+
+pub fn main(cmd: &Ping, options: &Options) -> anyhow::Result<()> {
+    match task::block_on(ping(options, cmd.timeout)) {
+        Ok((latency, version)) => {
+            if !cmd.quiet {
+                println!("Ok, {:.1}ms, server version {}",
+                    latency.as_secs_f64() * 1000.0, version);
+            }
+            Ok(())
+        }
+        Err(failure) => Err(ExitCode::new(failure as i32))?,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use super::{classify, Failure};
+
+    #[test]
+    fn failure_kinds() {
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(classify(&anyhow::Error::new(refused)), Failure::Tcp);
+        let timed_out = anyhow::Error::new(
+            io::Error::from(io::ErrorKind::TimedOut))
+            .context("cannot connect");
+        assert_eq!(classify(&timed_out), Failure::Tcp);
+        assert_eq!(classify(&anyhow::anyhow!("protocol error")),
+                   Failure::Other);
+    }
+}
@@ -1,3 +1,4 @@
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::ffi::OsString;
 
@@ -27,6 +28,10 @@ pub fn config_dir() -> anyhow::Result<PathBuf> {
     Ok(home_dir()?.join(".edgedb").join("config"))
 }
 
+pub fn cache_dir() -> anyhow::Result<PathBuf> {
+    Ok(home_dir()?.join(".edgedb").join("cache"))
+}
+
 pub fn tmp_file_name(path: &Path) -> OsString {
     if let Some(file_name) = path.file_name() {
         let mut buf = OsString::with_capacity(6 + file_name.len());
@@ -89,3 +94,18 @@ pub fn symlink_dir(original: impl AsRef<Path>, path: impl AsRef<Path>)
     std::os::windows::fs::symlink_dir(original, path)?;
     Ok(())
 }
+
+/// Total size of files in the directory (symlinks are not followed)
+pub fn dir_size(path: &Path) -> anyhow::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = fs::symlink_metadata(entry.path())?;
+        if meta.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += meta.len();
+        }
+    }
+    Ok(size)
+}
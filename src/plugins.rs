@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::IntoApp;
+
+use crate::options::{Options, RawOptions};
+use crate::process;
+
+const PREFIX: &str = "edgedb-";
+
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.extension().map(|e| e == "exe").unwrap_or(false) && path.is_file()
+}
+
+fn plugin_name(path: &Path) -> Option<String> {
+    let name = if cfg!(windows) {
+        path.file_stem()?.to_str()?
+    } else {
+        path.file_name()?.to_str()?
+    };
+    let name = name.strip_prefix(PREFIX)?;
+    if name.is_empty() || name.starts_with('_') {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Finds all `edgedb-<name>` executables in the `PATH`
+///
+/// If the same plugin is found in multiple directories, the first one wins,
+/// like the shell does.
+pub fn find_all() -> BTreeMap<String, PathBuf> {
+    let mut result = BTreeMap::new();
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None => return result,
+    };
+    for dir in env::split_paths(&path) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if let Some(name) = plugin_name(&path) {
+                if !result.contains_key(&name) && is_executable(&path) {
+                    result.insert(name, path);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Prints top-level help with the list of plugins appended
+///
+/// Returns `false` if command-line is not a top-level help request.
+pub fn print_help() -> anyhow::Result<bool> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let long = match &args.iter().map(|a| &a[..]).collect::<Vec<_>>()[..] {
+        ["--help"] | ["help"] => true,
+        ["-h"] => false,
+        _ => return Ok(false),
+    };
+    let mut app = RawOptions::into_app();
+    if long {
+        app.print_long_help()?;
+    } else {
+        app.print_help()?;
+    }
+    let plugins = find_all();
+    if !plugins.is_empty() {
+        println!();
+        println!("EXTERNAL SUBCOMMANDS:");
+        for (name, path) in &plugins {
+            println!("    {:<24}{}", name, path.display());
+        }
+    }
+    Ok(true)
+}
+
+pub fn run(args: &[String], options: &Options) -> anyhow::Result<()> {
+    let (name, rest) = args.split_first()
+        .ok_or_else(|| anyhow::anyhow!("no subcommand specified"))?;
+    let path = match find_all().remove(name) {
+        Some(path) => path,
+        None => {
+            anyhow::bail!("unrecognized subcommand {:?}. \
+                No `{}{}` found in PATH", name, PREFIX, name);
+        }
+    };
+    let mut cmd = Command::new(&path);
+    cmd.args(rest);
+    for (var, value) in &options.plugin_env {
+        cmd.env(var, value);
+    }
+    process::exit_from(&mut cmd)
+}
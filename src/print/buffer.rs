@@ -84,6 +84,7 @@ impl<T: Output> Printer<T> {
     pub(in crate::print) fn flush_buf(&mut self) -> Result<T::Error> {
         debug_assert_eq!(self.committed, self.buffer.len());
         self.stream.write(&self.buffer).map_err(Exception::Error)?;
+        self.flushed += self.buffer.len();
         self.buffer.clear();
         self.committed = 0;
         Ok(())
@@ -181,7 +182,7 @@ impl<T: Output> Printer<T> {
 
     pub(in crate::print) fn ellipsis(&mut self) -> Result<T::Error> {
         self.delimit()?;
-        if self.flow {
+        if self.flow || self.size_exceeded {
             self.write("...".clear())?;
         } else {
             self.write("...".clear())?;
@@ -192,6 +193,32 @@ impl<T: Output> Printer<T> {
         }
         Ok(())
     }
+    /// Returns true if output is larger than `max_output`, so the rest of
+    /// the items should be replaced by an ellipsis
+    pub(in crate::print) fn check_size(&mut self) -> bool {
+        if let Some(max) = self.max_output {
+            if self.flushed + self.buffer.len() > max {
+                self.size_exceeded = true;
+            }
+        }
+        self.size_exceeded
+    }
+    /// Note on the limits hit while printing, if any
+    pub(in crate::print) fn hidden_note(&self) -> Option<String> {
+        let mut notes = Vec::new();
+        if self.depth_exceeded {
+            notes.push(format!("values nested deeper than {} levels",
+                self.max_depth.unwrap_or(0)));
+        }
+        if self.size_exceeded {
+            notes.push(format!("output larger than {} bytes",
+                self.max_output.unwrap_or(0)));
+        }
+        if notes.is_empty() {
+            return None;
+        }
+        Some(format!("(hidden: {})", notes.join(", ")))
+    }
     pub(in crate::print) fn field(&mut self) -> Result<T::Error> {
         self.delim = Field;
         self.write(": ".clear())
@@ -217,6 +244,22 @@ impl<T: Output> Printer<T> {
         Ok(())
     }
     pub(in crate::print) fn block<F>(&mut self,
+        open: CString, f: F, close: CString)
+        -> Result<T::Error>
+        where F: FnMut(&mut Self) -> Result<T::Error>
+    {
+        if self.max_depth.map(|max| self.depth >= max).unwrap_or(false) {
+            self.depth_exceeded = true;
+            self.write(open)?;
+            self.write("...".clear())?;
+            return self.write(close);
+        }
+        self.depth += 1;
+        let result = self.nested_block(open, f, close);
+        self.depth -= 1;
+        result
+    }
+    fn nested_block<F>(&mut self,
         open: CString, mut f: F, close: CString)
         -> Result<T::Error>
         where F: FnMut(&mut Self) -> Result<T::Error>
@@ -0,0 +1,208 @@
+//! Console setup and output that differs between platforms
+//!
+//! Windows 10 consoles interpret ANSI escape sequences only when virtual
+//! terminal processing is enabled for the console, and legacy consoles
+//! (older `cmd.exe`) don't support them at all. So colors are enabled by
+//! default only if the escapes will be interpreted. Text is written to
+//! Windows consoles as UTF-16, so non-ASCII output doesn't depend on the
+//! console code page.
+
+use std::io::{self, Write};
+
+use once_cell::sync::Lazy;
+
+use crate::tee;
+
+
+static ANSI_STDOUT: Lazy<bool> = Lazy::new(|| {
+    atty::is(atty::Stream::Stdout) && imp::enable_ansi(imp::Stream::Stdout)
+});
+static ANSI_STDERR: Lazy<bool> = Lazy::new(|| {
+    atty::is(atty::Stream::Stderr) && imp::enable_ansi(imp::Stream::Stderr)
+});
+
+
+/// Restores console settings changed by `init` when dropped
+#[must_use]
+pub struct Guard(imp::Guard);
+
+/// Prepares console for output, should be called early at startup
+pub fn init() -> Guard {
+    let guard = imp::init();
+    Lazy::force(&ANSI_STDOUT);
+    Lazy::force(&ANSI_STDERR);
+    Guard(guard)
+}
+
+/// Returns true if stdout is a terminal which supports colors
+pub fn colors_stdout() -> bool {
+    *ANSI_STDOUT
+}
+
+/// Returns true if stderr is a terminal which supports colors
+pub fn colors_stderr() -> bool {
+    *ANSI_STDERR
+}
+
+/// Writes text to stdout, converting it for the console if needed
+pub fn write_stdout(data: &str) -> io::Result<()> {
+    tee::write(data);
+    let mut out = io::stdout();
+    let mut out = out.lock();
+    if imp::is_console(imp::Stream::Stdout) {
+        // keep order with anything buffered by `print!`
+        out.flush()?;
+        imp::write_console(imp::Stream::Stdout, data)
+    } else {
+        out.write_all(data.as_bytes())
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::io;
+
+    pub enum Stream {
+        Stdout,
+        Stderr,
+    }
+
+    pub struct Guard;
+
+    pub fn init() -> Guard {
+        Guard
+    }
+
+    pub fn enable_ansi(_stream: Stream) -> bool {
+        true
+    }
+
+    pub fn is_console(_stream: Stream) -> bool {
+        false
+    }
+
+    pub fn write_console(_stream: Stream, _data: &str) -> io::Result<()> {
+        unreachable!("only windows consoles need conversion");
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::env;
+    use std::io;
+    use std::ptr::null_mut;
+
+    use winapi::shared::minwindef::{DWORD, UINT};
+    use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+    use winapi::um::consoleapi::WriteConsoleW;
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::{STD_OUTPUT_HANDLE, STD_ERROR_HANDLE};
+    use winapi::um::wincon::{GetConsoleOutputCP, SetConsoleOutputCP};
+    use winapi::um::wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+    use winapi::um::winnls::CP_UTF8;
+    use winapi::um::winnt::HANDLE;
+
+    pub enum Stream {
+        Stdout,
+        Stderr,
+    }
+
+    fn handle(stream: Stream) -> Option<HANDLE> {
+        let kind = match stream {
+            Stream::Stdout => STD_OUTPUT_HANDLE,
+            Stream::Stderr => STD_ERROR_HANDLE,
+        };
+        let handle = unsafe { GetStdHandle(kind) };
+        if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+            None
+        } else {
+            Some(handle)
+        }
+    }
+
+    fn console_mode(handle: HANDLE) -> Option<DWORD> {
+        let mut mode: DWORD = 0;
+        if unsafe { GetConsoleMode(handle, &mut mode) } == 0 {
+            // not a console (redirected to a file or a pipe)
+            None
+        } else {
+            Some(mode)
+        }
+    }
+
+    /// Code page of the console before `init`, it's shared by all
+    /// processes attached to the console, so it's restored on exit
+    pub struct Guard {
+        output_cp: UINT,
+    }
+
+    pub fn init() -> Guard {
+        // Output which isn't written by `write_console` (e.g. by libraries
+        // or child processes) is interpreted in the output code page.
+        // Failure means there is no console, which is fine.
+        let output_cp = unsafe { GetConsoleOutputCP() };
+        if output_cp != 0 && output_cp != CP_UTF8 {
+            unsafe { SetConsoleOutputCP(CP_UTF8) };
+        }
+        Guard { output_cp }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            if self.output_cp != 0 && self.output_cp != CP_UTF8 {
+                unsafe { SetConsoleOutputCP(self.output_cp) };
+            }
+        }
+    }
+
+    pub fn enable_ansi(stream: Stream) -> bool {
+        let handle = match handle(stream) {
+            Some(handle) => handle,
+            None => return false,
+        };
+        let mode = match console_mode(handle) {
+            Some(mode) => mode,
+            // terminal emulators like mintty (git bash) are pipes for us,
+            // but they interpret escapes themselves
+            None => return env::var_os("TERM").is_some(),
+        };
+        if mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0 {
+            return true;
+        }
+        let new_mode = mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+        if unsafe { SetConsoleMode(handle, new_mode) } == 0 {
+            log::debug!("Console doesn't support ANSI escapes: {}",
+                        io::Error::last_os_error());
+            return false;
+        }
+        true
+    }
+
+    pub fn is_console(stream: Stream) -> bool {
+        handle(stream).and_then(console_mode).is_some()
+    }
+
+    pub fn write_console(stream: Stream, data: &str) -> io::Result<()> {
+        let handle = handle(stream)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        let wide = data.encode_utf16().collect::<Vec<u16>>();
+        let mut pos = 0;
+        while pos < wide.len() {
+            let mut written: DWORD = 0;
+            let chunk = &wide[pos..];
+            let ok = unsafe {
+                WriteConsoleW(handle, chunk.as_ptr() as *const _,
+                    chunk.len() as DWORD, &mut written, null_mut())
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            pos += written as usize;
+        }
+        Ok(())
+    }
+}
@@ -3,7 +3,9 @@ use crate::print::Printer;
 
 use colorful::{Colorful, core::color_string::CString};
 
-use crate::print::buffer::Result;
+use crate::print::buffer::{Result, Exception};
+use crate::print::image::{self, Protocol};
+use crate::print::timezone::DisplayTz;
 
 
 pub(in crate::print) trait ColorfulExt {
@@ -39,12 +41,17 @@ pub trait Formatter {
         where F: FnMut(&mut Self) -> Result<Self::Error>;
     fn comma(&mut self) -> Result<Self::Error>;
     fn ellipsis(&mut self) -> Result<Self::Error>;
+    fn check_size(&mut self) -> bool;
     fn object_field(&mut self, f: CString) -> Result<Self::Error>;
     fn tuple_field(&mut self, f: &str) -> Result<Self::Error>;
+    fn image(&mut self, protocol: Protocol, data: &[u8])
+        -> Result<Self::Error>;
 
     fn implicit_properties(&self) -> bool;
     fn expand_strings(&self) -> bool;
     fn max_items(&self) -> Option<usize>;
+    fn images(&self) -> Option<Protocol>;
+    fn display_tz(&self) -> DisplayTz;
 }
 
 impl<T: Output> Formatter for Printer<T> {
@@ -82,6 +89,9 @@ impl<T: Output> Formatter for Printer<T> {
     fn ellipsis(&mut self) -> Result<Self::Error> {
         Printer::ellipsis(self)
     }
+    fn check_size(&mut self) -> bool {
+        Printer::check_size(self)
+    }
     fn object<F>(&mut self, type_name: Option<&str>, f: F)
         -> Result<Self::Error>
         where F: FnMut(&mut Self) -> Result<Self::Error>
@@ -139,6 +149,20 @@ impl<T: Output> Formatter for Printer<T> {
         self.block("[".clear(), f, "]".clear())?;
         Ok(())
     }
+    fn image(&mut self, protocol: Protocol, data: &[u8])
+        -> Result<Self::Error>
+    {
+        // image takes multiple lines, so it can't be a part of the flow
+        if self.flow {
+            return Err(Exception::DisableFlow);
+        }
+        self.delimit()?;
+        // escape sequence has no width on its own, so it's not written
+        // through `write()`
+        self.buffer.push_str(&protocol.escape(data));
+        self.column += image::WIDTH;
+        Ok(())
+    }
 
     fn implicit_properties(&self) -> bool {
         self.implicit_properties
@@ -151,4 +175,12 @@ impl<T: Output> Formatter for Printer<T> {
     fn max_items(&self) -> Option<usize> {
         self.max_items
     }
+
+    fn images(&self) -> Option<Protocol> {
+        self.images
+    }
+
+    fn display_tz(&self) -> DisplayTz {
+        self.display_tz
+    }
 }
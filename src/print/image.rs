@@ -0,0 +1,109 @@
+//! Inline thumbnails of images stored in `bytes` values
+//!
+//! Enabled by `\set render-images on` in terminals supporting kitty or
+//! iTerm2 graphics protocols. Kitty only accepts PNG, so JPEG images are
+//! printed as bytes there.
+use std::env;
+
+
+/// Size of the thumbnail in terminal cells
+pub const WIDTH: usize = 16;
+const HEIGHT: usize = 8;
+/// Maximum size of the payload of a single kitty escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    ITerm,
+}
+
+fn is_png(data: &[u8]) -> bool {
+    data.starts_with(b"\x89PNG\r\n\x1a\n")
+}
+
+fn is_jpeg(data: &[u8]) -> bool {
+    data.starts_with(b"\xff\xd8\xff")
+}
+
+/// Returns graphics protocol of the terminal if stdout is a terminal
+pub fn detect() -> Option<Protocol> {
+    if !atty::is(atty::Stream::Stdout) {
+        return None;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+        Some(Protocol::Kitty)
+    } else if program == "iTerm.app" {
+        Some(Protocol::ITerm)
+    } else {
+        None
+    }
+}
+
+impl Protocol {
+    pub fn supports(&self, data: &[u8]) -> bool {
+        match self {
+            Protocol::Kitty => is_png(data),
+            Protocol::ITerm => is_png(data) || is_jpeg(data),
+        }
+    }
+    /// Escape sequence displaying the image at the cursor position
+    ///
+    /// The cursor is left on the last row of the image, right after it.
+    pub fn escape(&self, data: &[u8]) -> String {
+        let encoded = base64::encode(data);
+        match self {
+            Protocol::Kitty => {
+                let mut buf = String::with_capacity(encoded.len() + 64);
+                let chunks = encoded.as_bytes().chunks(KITTY_CHUNK);
+                let num = chunks.len();
+                for (idx, chunk) in chunks.enumerate() {
+                    let more = if idx + 1 < num { 1 } else { 0 };
+                    if idx == 0 {
+                        buf.push_str(&format!(
+                            "\x1b_Ga=T,f=100,c={},r={},m={};",
+                            WIDTH, HEIGHT, more));
+                    } else {
+                        buf.push_str(&format!("\x1b_Gm={};", more));
+                    }
+                    // base64 is always ascii
+                    buf.push_str(std::str::from_utf8(chunk).unwrap());
+                    buf.push_str("\x1b\\");
+                }
+                buf
+            }
+            Protocol::ITerm => {
+                format!("\x1b]1337;File=inline=1;size={};width={};\
+                    height={};preserveAspectRatio=1:{}\x07",
+                    data.len(), WIDTH, HEIGHT, encoded)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Protocol;
+
+    #[test]
+    fn supports() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF";
+        assert!(Protocol::Kitty.supports(png));
+        assert!(!Protocol::Kitty.supports(jpeg));
+        assert!(Protocol::ITerm.supports(png));
+        assert!(Protocol::ITerm.supports(jpeg));
+        assert!(!Protocol::ITerm.supports(b"hello"));
+    }
+
+    #[test]
+    fn kitty_chunks() {
+        let data = vec![0u8; 6000];
+        let text = Protocol::Kitty.escape(&data);
+        assert_eq!(text.matches("\x1b_G").count(), 2);
+        assert!(text.starts_with("\x1b_Ga=T,f=100,c=16,r=8,m=1;"));
+        assert!(text.contains("\x1b_Gm=0;"));
+    }
+}
@@ -2,17 +2,25 @@ use std::error::Error;
 use std::fmt;
 use std::io;
 use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use async_std::stream::{Stream, StreamExt};
 use bytes::Bytes;
 use colorful::Colorful;
 use snafu::{Snafu, ResultExt, AsErrorSource};
 
+use crate::tee;
+
 mod native;
 mod json;
 mod buffer;
 mod stream;
 mod formatter;
+pub mod console;
+pub mod image;
+pub mod timezone;
+pub mod plan;
 pub mod style;
 #[cfg(test)] mod tests;
 
@@ -39,8 +47,19 @@ pub struct Config {
     pub max_width: Option<usize>,
     pub implicit_properties: bool,
     pub max_items: Option<usize>,
+    /// Render images stored in `bytes` values inline
+    pub images: Option<image::Protocol>,
+    pub display_tz: timezone::DisplayTz,
+    /// Nested collections deeper than this are printed as `...`
+    pub max_depth: Option<usize>,
+    /// Approximate limit of the output size in bytes
+    pub max_output: Option<usize>,
 }
 
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// Default output size limit of the interactive shell
+pub const DEFAULT_MAX_OUTPUT: usize = 10 << 20;
+
 
 pub(in crate::print) struct Printer<T> {
     // config
@@ -50,6 +69,10 @@ pub(in crate::print) struct Printer<T> {
     max_width: usize,
     implicit_properties: bool,
     max_items: Option<usize>,
+    images: Option<image::Protocol>,
+    display_tz: timezone::DisplayTz,
+    max_depth: Option<usize>,
+    max_output: Option<usize>,
     trailing_comma: bool,
 
     // state
@@ -62,10 +85,35 @@ pub(in crate::print) struct Printer<T> {
     committed_column: usize,
     column: usize,
     cur_indent: usize,
+    depth: usize,
+    flushed: usize,
+    depth_exceeded: bool,
+    size_exceeded: bool,
 }
 
 struct Stdout {}
 
+/// Stream wrapper counting rows fetched from the underlying stream
+struct Counted<S> {
+    inner: S,
+    count: usize,
+}
+
+impl<S, I, E> Stream for Counted<S>
+    where S: Stream<Item=Result<I, E>> + Unpin,
+{
+    type Item = S::Item;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+        -> Poll<Option<Self::Item>>
+    {
+        let result = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(Some(Ok(_))) = result {
+            self.count += 1;
+        }
+        result
+    }
+}
+
 impl Config {
     pub fn new() -> Config {
         Config {
@@ -75,6 +123,10 @@ impl Config {
             max_width: None,
             implicit_properties: false,
             max_items: None,
+            images: None,
+            display_tz: timezone::DisplayTz::Utc,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_output: None,
         }
     }
     #[allow(dead_code)]
@@ -86,6 +138,10 @@ impl Config {
         self.max_items = Some(value);
         self
     }
+    pub fn max_output(&mut self, value: usize) -> &mut Config {
+        self.max_output = Some(value);
+        self
+    }
     pub fn colors(&mut self, value: bool) -> &mut Config {
         self.colors = Some(value);
         self
@@ -98,7 +154,8 @@ impl Config {
 }
 
 pub fn completion(res: &Bytes) {
-    if atty::is(atty::Stream::Stderr) {
+    tee::write(&format!("OK: {}\n", String::from_utf8_lossy(&res[..])));
+    if console::colors_stderr() {
         eprintln!("{}",
             format!("OK: {}", String::from_utf8_lossy(&res[..]))
                 .dark_gray().bold());
@@ -120,6 +177,12 @@ async fn format_rows_buf<S, I, E, O>(prn: &mut Printer<O>, rows: &mut S,
     debug_assert!(branch);
     while let Some(v) = rows.next().await.transpose().wrap_err(StreamErr)? {
         row_buf.push(v);
+        if prn.check_size() {
+            prn.ellipsis().wrap_err(PrintErr)?;
+            while let Some(_) = rows.next().await
+                .transpose().wrap_err(StreamErr)? {}
+            break;
+        }
         if let Some(limit) = prn.max_items {
             if row_buf.len() > limit {
                 prn.ellipsis().wrap_err(PrintErr)?;
@@ -153,6 +216,10 @@ async fn format_rows<S, I, E, O>(prn: &mut Printer<O>,
     let mut counter: usize = 0;
     for v in buffered_rows {
         counter += 1;
+        if prn.check_size() {
+            prn.ellipsis().wrap_err(PrintErr)?;
+            break;
+        }
         if let Some(limit) = prn.max_items {
             if counter > limit {
                 prn.ellipsis().wrap_err(PrintErr)?;
@@ -164,6 +231,12 @@ async fn format_rows<S, I, E, O>(prn: &mut Printer<O>,
     }
     while let Some(v) = rows.next().await.transpose().wrap_err(StreamErr)? {
         counter += 1;
+        if prn.check_size() {
+            prn.ellipsis().wrap_err(PrintErr)?;
+            while let Some(_) = rows.next().await
+                .transpose().wrap_err(StreamErr)? {}
+            break;
+        }
         if let Some(limit) = prn.max_items {
             if counter > limit {
                 prn.ellipsis().wrap_err(PrintErr)?;
@@ -180,8 +253,9 @@ async fn format_rows<S, I, E, O>(prn: &mut Printer<O>,
     Ok(())
 }
 
+/// Prints rows to stdout, returns number of rows fetched
 pub async fn native_to_stdout<S, I, E>(rows: S, config: &Config)
-    -> Result<(), PrintError<E, io::Error>>
+    -> Result<usize, PrintError<E, io::Error>>
     where S: Stream<Item=Result<I, E>> + Send + Unpin,
           I: FormatExt,
           E: fmt::Debug + Error + 'static,
@@ -189,14 +263,44 @@ pub async fn native_to_stdout<S, I, E>(rows: S, config: &Config)
     let w = config.max_width.unwrap_or_else(|| {
         term_size::dimensions_stdout().map(|(w, _h)| w).unwrap_or(80)
     });
-    let colors = config.colors
-            .unwrap_or_else(|| atty::is(atty::Stream::Stdout));
-    _native_format(rows, config, w, colors, Stdout {}).await
+    let colors = config.colors.unwrap_or_else(console::colors_stdout);
+    let (count, hidden) = _native_format(rows, config, w, colors, Stdout {})
+        .await?;
+    if let Some(note) = hidden {
+        print_hidden(&note);
+    }
+    Ok(count)
 }
 
-async fn _native_format<S, I, E, O>(mut rows: S, config: &Config,
+/// Formats rows into a string instead of printing them
+///
+/// Used to buffer output of a query which is printed later.
+pub async fn native_to_string<S, I, E>(rows: S, config: &Config)
+    -> Result<String, PrintError<E, io::Error>>
+    where S: Stream<Item=Result<I, E>> + Send + Unpin,
+          I: FormatExt,
+          E: fmt::Debug + Error + 'static,
+{
+    let w = config.max_width.unwrap_or(80);
+    let colors = config.colors.unwrap_or_else(console::colors_stdout);
+    let mut buf = String::with_capacity(8192);
+    match _native_format(rows, config, w, colors, &mut buf).await {
+        Ok((_, hidden)) => {
+            if let Some(note) = hidden {
+                print_hidden(&note);
+            }
+            Ok(buf)
+        }
+        Err(PrintError::StreamErr { source }) => {
+            Err(PrintError::StreamErr { source })
+        }
+        Err(PrintError::PrintErr { source }) => match source {},
+    }
+}
+
+async fn _native_format<S, I, E, O>(rows: S, config: &Config,
     max_width: usize, colors: bool, output: O)
-    -> Result<(), PrintError<E, O::Error>>
+    -> Result<(usize, Option<String>), PrintError<E, O::Error>>
     where S: Stream<Item=Result<I, E>> + Send + Unpin,
           I: FormatExt,
           E: fmt::Debug + Error + 'static,
@@ -210,6 +314,10 @@ async fn _native_format<S, I, E, O>(mut rows: S, config: &Config,
         max_width,
         implicit_properties: config.implicit_properties,
         max_items: config.max_items,
+        images: config.images,
+        display_tz: config.display_tz,
+        max_depth: config.max_depth,
+        max_output: config.max_output,
         trailing_comma: true,
 
         buffer: String::with_capacity(8192),
@@ -221,7 +329,12 @@ async fn _native_format<S, I, E, O>(mut rows: S, config: &Config,
         committed_column: 0,
         column: 0,
         cur_indent: 0,
+        depth: 0,
+        flushed: 0,
+        depth_exceeded: false,
+        size_exceeded: false,
     };
+    let mut rows = Counted { inner: rows, count: 0 };
     let mut row_buf = Vec::new();
     let mut eos = false;
     match format_rows_buf(&mut prn, &mut rows, &mut row_buf, &mut eos).await {
@@ -234,7 +347,17 @@ async fn _native_format<S, I, E, O>(mut rows: S, config: &Config,
         Err(Exception::Error(e)) => return Err(e),
     };
     prn.end().unwrap_exc().context(PrintErr)?;
-    Ok(())
+    Ok((rows.count, prn.hidden_note()))
+}
+
+/// Prints a note on the values hidden because of limits to stderr, so it
+/// doesn't get mixed into the data
+fn print_hidden(note: &str) {
+    if console::colors_stderr() {
+        eprintln!("{}", note.dark_gray());
+    } else {
+        eprintln!("{}", note);
+    }
 }
 
 fn format_rows_str<I: FormatExt>(prn: &mut Printer<&mut String>, items: &[I],
@@ -266,6 +389,11 @@ pub fn json_to_string<I: FormatExt>(items: &[I], config: &Config)
         max_width: config.max_width.unwrap_or(80),
         implicit_properties: config.implicit_properties,
         max_items: config.max_items,
+        images: None,
+        display_tz: config.display_tz,
+        // JSON must stay valid, so it's never truncated
+        max_depth: None,
+        max_output: None,
         trailing_comma: false,
 
         buffer: String::with_capacity(8192),
@@ -277,6 +405,10 @@ pub fn json_to_string<I: FormatExt>(items: &[I], config: &Config)
         committed_column: 0,
         column: 0,
         cur_indent: 0,
+        depth: 0,
+        flushed: 0,
+        depth_exceeded: false,
+        size_exceeded: false,
     };
     match format_rows_str(&mut prn, &items, "[", "]", false) {
         Ok(()) => {},
@@ -300,6 +432,11 @@ pub fn json_item_to_string<I: FormatExt>(item: &I, config: &Config)
         max_width: config.max_width.unwrap_or(80),
         implicit_properties: config.implicit_properties,
         max_items: config.max_items,
+        images: None,
+        display_tz: config.display_tz,
+        // JSON must stay valid, so it's never truncated
+        max_depth: None,
+        max_output: None,
         trailing_comma: false,
 
         buffer: String::with_capacity(8192),
@@ -311,6 +448,10 @@ pub fn json_item_to_string<I: FormatExt>(item: &I, config: &Config)
         committed_column: 0,
         column: 0,
         cur_indent: 0,
+        depth: 0,
+        flushed: 0,
+        depth_exceeded: false,
+        size_exceeded: false,
     };
     prn.end().unwrap_exc()?;
     match item.format(&mut prn) {
@@ -90,6 +90,23 @@ fn format_decimal(value: BigDecimal) -> String {
     }
 }
 
+fn format_items<F: Formatter>(prn: &mut F, items: &[Value])
+    -> Result<F::Error>
+{
+    let limit = prn.max_items().unwrap_or(items.len());
+    for item in &items[..min(limit, items.len())] {
+        if prn.check_size() {
+            return prn.ellipsis();
+        }
+        item.format(prn)?;
+        prn.comma()?;
+    }
+    if items.len() > limit {
+        prn.ellipsis()?;
+    }
+    Ok(())
+}
+
 impl FormatExt for Value {
     fn format<F: Formatter>(&self, prn: &mut F) -> Result<F::Error> {
         use Value as V;
@@ -99,7 +116,10 @@ impl FormatExt for Value {
             V::Str(s) => {
                 prn.const_scalar(format_string(s, prn.expand_strings()))
             }
-            V::Bytes(b) => prn.const_scalar(format_bytes(b)),
+            V::Bytes(b) => match prn.images() {
+                Some(proto) if proto.supports(b) => prn.image(proto, b),
+                _ => prn.const_scalar(format_bytes(b)),
+            },
             V::Int16(v) => prn.const_scalar(v),
             V::Int32(v) => prn.const_scalar(v),
             V::Int64(v) => prn.const_scalar(v),
@@ -108,7 +128,10 @@ impl FormatExt for Value {
             V::BigInt(v) => prn.const_scalar(format_bigint(v.into())),
             V::Decimal(v) => prn.const_scalar(format_decimal(v.into())),
             V::Bool(v) => prn.const_scalar(v),
-            V::Datetime(t) => prn.typed("datetime", format!("{:?}", t)),
+            V::Datetime(t) => {
+                let text = prn.display_tz().format(t);
+                prn.typed("datetime", text)
+            }
             V::LocalDatetime(t)
             => prn.typed("cal::local_datetime", format!("{:?}", t)),
             V::LocalDate(d)
@@ -118,24 +141,8 @@ impl FormatExt for Value {
             V::Duration(d) => prn.typed("duration", d.to_string()),
             V::Json(d) => prn.const_scalar(format!("{:?}", d)),
             V::Set(items) => {
-                prn.set(|prn| {
-                    if let Some(limit) = prn.max_items() {
-                        for item in &items[..min(limit, items.len())] {
-                            item.format(prn)?;
-                            prn.comma()?;
-                        }
-                        if items.len() > limit {
-                            prn.ellipsis()?;
-                        }
-                    } else {
-                        for item in items {
-                            item.format(prn)?;
-                            prn.comma()?;
-                        }
-                    }
-                    Ok(())
-                })
-            },
+                prn.set(|prn| format_items(prn, items))
+            }
             V::Object { shape, fields } => {
                 // TODO(tailhook) optimize it on no-implicit-types
                 //                or just cache typeid index on shape
@@ -199,23 +206,7 @@ impl FormatExt for Value {
                 })
             }
             V::Array(items) => {
-                prn.array(|prn| {
-                    if let Some(limit) = prn.max_items() {
-                        for item in &items[..min(limit, items.len())] {
-                            item.format(prn)?;
-                            prn.comma()?;
-                        }
-                        if items.len() > limit {
-                            prn.ellipsis()?;
-                        }
-                    } else {
-                        for item in items {
-                            item.format(prn)?;
-                            prn.comma()?;
-                        }
-                    }
-                    Ok(())
-                })
+                prn.array(|prn| format_items(prn, items))
             }
             V::Enum(v) => prn.const_scalar(&**v),
         }
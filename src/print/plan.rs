@@ -0,0 +1,149 @@
+//! Rendering of query plans returned by `ANALYZE`
+//!
+//! The plan is a tree of JSON objects. Both the EdgeDB format (`plan_type`,
+//! `subplans`) and the raw PostgreSQL format (`Node Type`, `Plans`) are
+//! recognized, so that the renderer works regardless of the level of
+//! detail returned by the server.
+
+use std::fmt::Write;
+
+use colorful::Colorful;
+use serde_json::Value;
+
+const TITLE_KEYS: &[&str] = &["plan_type", "Node Type", "type"];
+const CHILDREN_KEYS: &[&str] = &["subplans", "children", "Plans", "plans"];
+const RELATION_KEYS: &[&str] = &["relation_name", "Relation Name"];
+const COST_KEYS: &[&str] = &["total_cost", "Total Cost"];
+const TIME_KEYS: &[&str] = &["actual_total_time", "Actual Total Time"];
+const ROWS_KEYS: &[&str] = &["actual_rows", "Actual Rows"];
+const LOOPS_KEYS: &[&str] = &["actual_loops", "Actual Loops"];
+
+
+fn get<'x>(node: &'x Value, keys: &[&str]) -> Option<&'x Value> {
+    keys.iter().find_map(|key| node.get(key))
+}
+
+fn number(node: &Value, keys: &[&str]) -> Option<f64> {
+    get(node, keys).and_then(|v| v.as_f64())
+}
+
+fn title(node: &Value) -> String {
+    let mut title = get(node, TITLE_KEYS)
+        .and_then(|v| v.as_str())
+        .unwrap_or("Plan")
+        .to_string();
+    if let Some(rel) = get(node, RELATION_KEYS).and_then(|v| v.as_str()) {
+        write!(title, " on {}", rel).unwrap();
+    }
+    // EdgeDB plans have stages of a node in the pipeline
+    if let Some(stages) = node.get("pipeline").and_then(|v| v.as_array()) {
+        let names = stages.iter()
+            .filter_map(|s| get(s, TITLE_KEYS).and_then(|v| v.as_str()))
+            .collect::<Vec<_>>();
+        if !names.is_empty() {
+            write!(title, " [{}]", names.join(" → ")).unwrap();
+        }
+    }
+    title
+}
+
+fn stats(node: &Value) -> String {
+    // pipeline keeps stats in the first (outermost) stage
+    let node = node.get("pipeline")
+        .and_then(|v| v.as_array())
+        .and_then(|stages| stages.first())
+        .unwrap_or(node);
+    let mut parts = Vec::new();
+    if let Some(cost) = number(node, COST_KEYS) {
+        parts.push(format!("cost={:.2}", cost));
+    }
+    if let Some(time) = number(node, TIME_KEYS) {
+        parts.push(format!("time={:.3}ms", time));
+    }
+    if let Some(rows) = number(node, ROWS_KEYS) {
+        parts.push(format!("rows={}", rows));
+    }
+    if let Some(loops) = number(node, LOOPS_KEYS) {
+        if loops > 1.0 {
+            parts.push(format!("loops={}", loops));
+        }
+    }
+    parts.join(" ")
+}
+
+fn render_node(buf: &mut String, node: &Value, prefix: &str, last: bool,
+    root: bool, colors: bool)
+{
+    let (branch, next_prefix) = if root {
+        ("", String::new())
+    } else if last {
+        ("└─ ", format!("{}   ", prefix))
+    } else {
+        ("├─ ", format!("{}│  ", prefix))
+    };
+    let title = title(node);
+    let stats = stats(node);
+    if colors {
+        write!(buf, "{}{}{}", prefix, branch, title.bold()).unwrap();
+        if !stats.is_empty() {
+            write!(buf, " {}", stats.light_gray()).unwrap();
+        }
+    } else {
+        write!(buf, "{}{}{}", prefix, branch, title).unwrap();
+        if !stats.is_empty() {
+            write!(buf, " ({})", stats).unwrap();
+        }
+    }
+    buf.push('\n');
+    let children = get(node, CHILDREN_KEYS)
+        .and_then(|v| v.as_array())
+        .map(|v| &v[..])
+        .unwrap_or(&[]);
+    for (idx, child) in children.iter().enumerate() {
+        render_node(buf, child, &next_prefix, idx + 1 == children.len(),
+            false, colors);
+    }
+}
+
+/// Renders plan tree as indented text
+pub fn render(plan: &Value, colors: bool) -> String {
+    // PostgreSQL wraps the tree into a list of `{"Plan": ...}`
+    let plan = match plan {
+        Value::Array(items) if items.len() == 1 => &items[0],
+        _ => plan,
+    };
+    let plan = plan.get("fine_grained")
+        .or_else(|| plan.get("Plan"))
+        .unwrap_or(plan);
+    let mut buf = String::new();
+    render_node(&mut buf, plan, "", true, true, colors);
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::render;
+
+    #[test]
+    fn postgres_plan() {
+        let plan = serde_json::json!([{"Plan": {
+            "Node Type": "Hash Join",
+            "Total Cost": 12.5,
+            "Actual Total Time": 0.125,
+            "Actual Rows": 3,
+            "Plans": [
+                {"Node Type": "Seq Scan", "Relation Name": "User",
+                 "Total Cost": 1.0},
+                {"Node Type": "Hash", "Plans": [
+                    {"Node Type": "Index Scan", "Relation Name": "Post"},
+                ]},
+            ],
+        }}]);
+        assert_eq!(render(&plan, false), "\
+Hash Join (cost=12.50 time=0.125ms rows=3)
+├─ Seq Scan on User (cost=1.00)
+└─ Hash
+   └─ Index Scan on Post
+");
+    }
+}
@@ -1,7 +1,8 @@
-use std::io::{self, Write};
+use std::io;
 use std::convert::Infallible;
 
 use super::Stdout;
+use super::console;
 
 pub(in crate::print) trait Output {
     type Error;
@@ -20,7 +21,6 @@ impl<'a> Output for &'a mut String {
 impl Output for Stdout {
     type Error = io::Error;
     fn write(&mut self, data: &str) -> Result<(), io::Error> {
-        io::stdout().lock().write_all(data.as_bytes())?;
-        Ok(())
+        console::write_stdout(data)
     }
 }
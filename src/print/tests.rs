@@ -12,6 +12,7 @@ use edgedb_protocol::model::Datetime;
 use edgedb_protocol::codec::{ObjectShape, ShapeElement};
 use crate::print::{self, _native_format, Config};
 use crate::print::native::FormatExt;
+use crate::print::timezone::DisplayTz;
 
 struct UnfusedStream<'a, I>(Option<&'a [I]>);
 
@@ -39,13 +40,20 @@ impl<I: Clone> Stream for UnfusedStream<'_, I> {
 
 fn test_format_cfg<I: FormatExt + Clone + Send + Sync>(items: &[I], config: &Config)
     -> Result<String, Infallible>
+{
+    Ok(test_format_hidden(items, config).0)
+}
+
+fn test_format_hidden<I: FormatExt + Clone + Send + Sync>(items: &[I],
+    config: &Config)
+    -> (String, Option<String>)
 {
     let mut out = String::new();
-    task::block_on(
+    let (_, hidden) = task::block_on(
         _native_format(UnfusedStream::new(items),
             config, config.max_width.unwrap_or(80), false, &mut out)
     ).unwrap();
-    Ok(out)
+    (out, hidden)
 }
 
 fn test_format<I: FormatExt + Clone + Send + Sync>(items: &[I])
@@ -58,6 +66,10 @@ fn test_format<I: FormatExt + Clone + Send + Sync>(items: &[I])
         max_width: Some(80),
         implicit_properties: false,
         max_items: None,
+        images: None,
+        display_tz: DisplayTz::Utc,
+        max_depth: Some(print::DEFAULT_MAX_DEPTH),
+        max_output: None,
     })
 }
 
@@ -115,6 +127,19 @@ fn datetime() {
       <datetime>\'2050-11-04T16:22:18.347258Z\'}");
 }
 
+#[test]
+fn datetime_tz() {
+    let mut config = Config::new();
+    config.display_tz = "Europe/Prague".parse().unwrap();
+    assert_eq!(test_format_cfg(&[
+        Value::Datetime(Datetime::from_micros(-1000000000000000)),
+        Value::Datetime(Datetime::from_micros(1604506938347258)),
+    ], &config).unwrap(),
+    "{<datetime>\'1968-04-23T23:13:20+01:00\', \
+      <datetime>\'2050-11-04T17:22:18.347258+01:00\'}");
+    assert!("Mars/Olympus".parse::<DisplayTz>().is_err());
+}
+
 #[test]
 fn decimal() {
     assert_eq!(test_format(&[
@@ -169,6 +194,36 @@ fn array_ellipsis() {
     ], Config::new().max_items(2)).unwrap(), "{[10]}");
 }
 
+#[test]
+fn max_depth() {
+    let mut config = Config::new();
+    config.max_depth = Some(2);
+    assert_eq!(test_format_hidden(&[
+        Value::Array(vec![
+            Value::Array(vec![
+                Value::Array(vec![Value::Int64(10)]),
+            ]),
+        ]),
+    ], &config), ("{[[[...]]]}".into(),
+        Some("(hidden: values nested deeper than 2 levels)".into())));
+    config.max_depth = Some(3);
+    assert_eq!(test_format_cfg(&[
+        Value::Array(vec![
+            Value::Array(vec![
+                Value::Array(vec![Value::Int64(10)]),
+            ]),
+        ]),
+    ], &config).unwrap(), "{[[[10]]]}");
+}
+
+#[test]
+fn max_output() {
+    assert_eq!(test_format_hidden(&[
+        Value::Set(vec![Value::Int64(1000000); 20]),
+    ], Config::new().max_output(10)), ("{{1000000, 1000000, ...}}".into(),
+        Some("(hidden: output larger than 10 bytes)".into())));
+}
+
 #[test]
 fn set_ellipsis() {
     assert_eq!(test_format(&[
@@ -0,0 +1,75 @@
+//! Time zone used to display `datetime` values, set by `\set display-tz`
+//!
+//! Only the native output format is affected, JSON output is produced by
+//! the server and always has canonical UTC timestamps.
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, Utc};
+use edgedb_protocol::model::Datetime;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayTz {
+    Utc,
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+/// Validator of the `\set display-tz` argument
+pub fn validate(value: &str) -> Result<(), String> {
+    value.parse::<DisplayTz>().map(|_| ())
+}
+
+impl Default for DisplayTz {
+    fn default() -> DisplayTz {
+        DisplayTz::Utc
+    }
+}
+
+impl FromStr for DisplayTz {
+    type Err = String;
+    fn from_str(value: &str) -> Result<DisplayTz, String> {
+        match value {
+            "utc" | "UTC" => Ok(DisplayTz::Utc),
+            "local" => Ok(DisplayTz::Local),
+            _ => value.parse().map(DisplayTz::Named)
+                .map_err(|_| format!("unknown time zone {:?}, \
+                    use `local`, `utc` or a name like `Europe/London`",
+                    value)),
+        }
+    }
+}
+
+impl fmt::Display for DisplayTz {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisplayTz::Utc => "utc".fmt(f),
+            DisplayTz::Local => "local".fmt(f),
+            DisplayTz::Named(tz) => tz.name().fmt(f),
+        }
+    }
+}
+
+impl DisplayTz {
+    /// Formats datetime in this time zone, with the offset specified
+    pub fn format(&self, value: &Datetime) -> String {
+        const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f%:z";
+
+        let utc_text = format!("{:?}", value);
+        // dates out of range of chrono are kept in UTC
+        let utc = match utc_text.parse::<DateTime<Utc>>() {
+            Ok(utc) => utc,
+            Err(_) => return utc_text,
+        };
+        match self {
+            DisplayTz::Utc => utc_text,
+            DisplayTz::Local => {
+                utc.with_timezone(&Local).format(FORMAT).to_string()
+            }
+            DisplayTz::Named(tz) => {
+                utc.with_timezone(tz).format(FORMAT).to_string()
+            }
+        }
+    }
+}
@@ -131,6 +131,37 @@ impl ProcessGuard {
             child: cmd.spawn()?,
         })
     }
+    /// Waits until current process is interrupted by SIGINT or SIGTERM
+    /// (the guarded process is stopped on drop then)
+    ///
+    /// Returns an error if the guarded process exits by itself.
+    #[cfg(unix)]
+    pub fn wait_interrupted(&mut self) -> anyhow::Result<()> {
+        use signal::Signal::*;
+
+        let trap = signal::trap::Trap::trap(&[SIGINT, SIGTERM, SIGCHLD]);
+        if let Some(status) = self.child.try_wait()? {
+            anyhow::bail!("process exited unexpectedly: {}", status);
+        }
+        for sig in trap {
+            match sig {
+                SIGINT|SIGTERM => {
+                    log::info!("Interrupted by {:?}. Stopping process", sig);
+                    return Ok(());
+                }
+                _ => {}
+            }
+            if let Some(status) = self.child.try_wait()? {
+                anyhow::bail!("process exited unexpectedly: {}", status);
+            }
+        }
+        unreachable!();
+    }
+    #[cfg(not(unix))]
+    pub fn wait_interrupted(&mut self) -> anyhow::Result<()> {
+        let status = self.child.wait()?;
+        anyhow::bail!("process exited unexpectedly: {}", status);
+    }
 }
 
 impl Drop for ProcessGuard {
@@ -0,0 +1,86 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::commands::ExitCode;
+use crate::project::options::Cleanup;
+use crate::project::{all_stashes, Stash};
+use crate::question;
+use crate::server::destroy;
+use crate::server::options::Destroy;
+
+
+/// Returns stash entries of the projects whose directory no longer exists
+pub fn orphaned() -> anyhow::Result<Vec<Stash>> {
+    let mut stashes = all_stashes()?;
+    stashes.retain(|s| s.is_orphaned());
+    Ok(stashes)
+}
+
+fn destroy_instance(name: &str) -> anyhow::Result<()> {
+    let users = destroy::find_project_dirs(name)?;
+    if !users.is_empty() {
+        eprintln!("Instance {:?} is still used by other projects, \
+            keeping it", name);
+        return Ok(());
+    }
+    destroy::do_destroy(&Destroy {
+        name: Some(name.to_string()),
+        verbose: false,
+        force: true,
+        all_temporary: false,
+    })?;
+    eprintln!("Instance {:?} is destroyed", name);
+    Ok(())
+}
+
+pub fn cleanup(options: &Cleanup) -> anyhow::Result<()> {
+    let stale = orphaned()?;
+    if stale.is_empty() {
+        eprintln!("No stale projects found.");
+        return Ok(());
+    }
+    eprintln!("Projects whose directories no longer exist:");
+    for stash in &stale {
+        eprintln!("  {} (instance {})",
+            stash.project_path.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| stash.dir.display().to_string()),
+            stash.instance.as_deref().unwrap_or("unknown"));
+    }
+    if !options.non_interactive {
+        let q = if options.destroy_server_instances {
+            question::Confirm::new_dangerous(
+                "Unlink these projects and destroy their instances?")
+        } else {
+            question::Confirm::new("Unlink these projects?")
+        };
+        if !q.ask()? {
+            eprintln!("Canceled");
+            return Err(ExitCode::new(1))?;
+        }
+    }
+    for stash in &stale {
+        fs::remove_dir_all(&stash.dir)?;
+    }
+    eprintln!("Unlinked {} projects", stale.len());
+    if options.destroy_server_instances {
+        let mut failed = false;
+        let names = stale.iter()
+            .filter_map(|s| s.instance.as_deref())
+            .collect::<BTreeSet<_>>();
+        for name in names {
+            if let Err(e) = destroy_instance(name) {
+                eprintln!("edgedb error: cannot destroy {:?}: {:#}",
+                          name, e);
+                failed = true;
+            }
+        }
+        if failed {
+            return Err(ExitCode::new(1))?;
+        }
+    } else {
+        eprintln!("Their instances are kept, \
+            use `edgedb server destroy` to remove them.");
+    }
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use fn_error_context::context;
 
@@ -11,6 +11,12 @@ use crate::server::distribution::MajorVersion;
 #[serde(rename_all="kebab-case")]
 struct SrcConfig {
     edgedb: SrcEdgedb,
+    #[serde(default)]
+    project: SrcProject,
+    #[serde(default)]
+    connection: SrcConnection,
+    #[serde(default)]
+    lint: BTreeMap<String, LintLevel>,
     #[serde(flatten)]
     extra: BTreeMap<String, toml::Value>,
 }
@@ -24,17 +30,68 @@ struct SrcEdgedb {
     extra: BTreeMap<String, toml::Value>,
 }
 
-#[derive(Debug)]
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all="kebab-case")]
+struct SrcProject {
+    #[serde(default)]
+    schema_dir: Option<PathBuf>,
+    #[serde(default)]
+    sdl_dirs: Option<Vec<PathBuf>>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, toml::Value>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all="kebab-case")]
+struct SrcConnection {
+    #[serde(default)]
+    database: Option<String>,
+    #[serde(default)]
+    implicit_limit: Option<usize>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Config {
     pub edgedb: Edgedb,
+    pub project: Project,
+    pub connection: Connection,
+    /// Levels of `edgedb lint` rules by rule name
+    pub lint: BTreeMap<String, LintLevel>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all="kebab-case")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Edgedb {
     pub server_version: Option<MajorVersion>,
 }
 
-fn warn_extra(extra: &BTreeMap<String, toml::Value>, prefix: &str) {
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// Directory containing `migrations` (absolute path)
+    pub schema_dir: PathBuf,
+    /// Directories containing `*.esdl` files (absolute paths), a single
+    /// `schema_dir` by default
+    pub sdl_dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// Database to connect to, unless specified on the command line
+    pub database: Option<String>,
+    /// Implicit limit of the REPL, `0` means no limit
+    pub implicit_limit: Option<usize>,
+}
+
+pub fn warn_extra(extra: &BTreeMap<String, toml::Value>, prefix: &str) {
     for key in extra.keys() {
         log::warn!("Unknown config option `{}{}`",
                    prefix, key.escape_default());
@@ -48,9 +105,31 @@ pub fn read(path: &Path) -> anyhow::Result<Config> {
     let val: SrcConfig = serde_path_to_error::deserialize(&mut toml)?;
     warn_extra(&val.extra, "");
     warn_extra(&val.edgedb.extra, "edgedb.");
+    warn_extra(&val.project.extra, "project.");
+    warn_extra(&val.connection.extra, "connection.");
+    // relative paths are relative to the directory of `edgedb.toml`
+    let base = path.parent().unwrap_or(Path::new("."));
+    let schema_dir = base.join(val.project.schema_dir
+        .unwrap_or_else(|| "dbschema".into()));
+    let sdl_dirs = match val.project.sdl_dirs {
+        Some(dirs) if dirs.is_empty() => {
+            anyhow::bail!("`project.sdl-dirs` must not be empty");
+        }
+        Some(dirs) => dirs.iter().map(|d| base.join(d)).collect(),
+        None => vec![schema_dir.clone()],
+    };
     return Ok(Config {
         edgedb: Edgedb {
             server_version: val.edgedb.server_version,
-        }
+        },
+        project: Project {
+            schema_dir,
+            sdl_dirs,
+        },
+        connection: Connection {
+            database: val.connection.database,
+            implicit_limit: val.connection.implicit_limit,
+        },
+        lint: val.lint,
     })
 }
@@ -17,13 +17,16 @@ use crate::connect::Connector;
 use crate::migrations;
 use crate::platform::{tmp_file_path, home_dir, path_bytes, symlink_dir};
 use crate::process::ProcessGuard;
+use crate::project::cleanup;
 use crate::project::config;
 use crate::project::options::Init;
+use crate::project::seed;
+use crate::project::templates;
 use crate::question;
 use crate::server::control::get_instance;
 use crate::server::detect::{self, VersionQuery};
 use crate::server::distribution::{DistributionRef, MajorVersion};
-use crate::server::init::{self, try_bootstrap, allocate_port};
+use crate::server::init::{self, try_bootstrap, allocate_port, reserve_port};
 use crate::server::install::{self, optional_docker_check, exit_codes};
 use crate::server::is_valid_name;
 use crate::server::methods::{InstallMethod, InstallationMethods, Methods};
@@ -33,6 +36,7 @@ use crate::server::version::Version;
 use crate::table;
 
 const CHARS: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+const NESTED_SEARCH_DEPTH: usize = 3;
 const DEFAULT_ESDL: &str = "\
     module default {\n\
     \n\
@@ -61,28 +65,69 @@ pub fn search_dir(base: &Path) -> anyhow::Result<Option<PathBuf>> {
     Ok(None)
 }
 
+/// Returns directories below `base` that contain `edgedb.toml`
+fn find_nested(base: &Path, depth: usize, result: &mut Vec<PathBuf>) {
+    let dir = match fs::read_dir(base) {
+        Ok(dir) => dir,
+        Err(e) => {
+            log::debug!("Cannot read {:?}: {}", base, e);
+            return;
+        }
+    };
+    for item in dir.flatten() {
+        let hidden = item.file_name().to_str()
+            .map(|n| n.starts_with('.')).unwrap_or(true);
+        let is_dir = item.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if hidden || !is_dir {
+            continue;
+        }
+        let path = item.path();
+        if path.join("edgedb.toml").exists() {
+            result.push(path);
+        } else if depth > 1 {
+            find_nested(&path, depth - 1, result);
+        }
+    }
+}
+
+/// Warns if the project found in a parent directory shadows projects
+/// nested in the current directory (e.g. in a monorepo)
+fn warn_nested(base: &Path, project_dir: &Path) {
+    let mut nested = Vec::new();
+    find_nested(base, NESTED_SEARCH_DEPTH, &mut nested);
+    if nested.is_empty() {
+        return;
+    }
+    nested.sort();
+    log::warn!("Using project at {:?}, while there are projects nested \
+        in the current directory: {}. \
+        Use `--project-dir` to choose one of them.",
+        project_dir,
+        nested.iter().map(|p| format!("{:?}", p))
+            .collect::<Vec<_>>().join(", "));
+}
+
 fn ask_method(available: &InstallationMethods, options: &Init)
     -> anyhow::Result<InstallMethod>
 {
+    if let Some(meth) = &options.server_install_method {
+        return Ok(meth.clone());
+    }
     if options.non_interactive {
-        if let Some(meth) = &options.server_install_method {
-            return Ok(meth.clone());
+        if available.package.supported {
+            return Ok(InstallMethod::Package);
+        } else if available.docker.supported {
+            return Ok(InstallMethod::Docker);
         } else {
-            if available.package.supported {
-                return Ok(InstallMethod::Package);
-            } else if available.docker.supported {
-                return Ok(InstallMethod::Docker);
-            } else {
-                let mut buf = String::with_capacity(1024);
-                buf.push_str(
-                    "No installation method supported for the platform:");
-                available.package.format_error(&mut buf);
-                available.docker.format_error(&mut buf);
-                buf.push_str("Please consider opening an issue at \
-                    https://github.com/edgedb/edgedb-cli/issues/new\
-                    ?template=install-unsupported.md");
-                anyhow::bail!(buf);
-            }
+            let mut buf = String::with_capacity(1024);
+            buf.push_str(
+                "No installation method supported for the platform:");
+            available.package.format_error(&mut buf);
+            available.docker.format_error(&mut buf);
+            buf.push_str("Please consider opening an issue at \
+                https://github.com/edgedb/edgedb-cli/issues/new\
+                ?template=install-unsupported.md");
+            anyhow::bail!(buf);
         }
     }
     let mut q = question::Numeric::new(
@@ -165,10 +210,18 @@ fn ask_name(methods: &Methods, dir: &Path, options: &Init)
         }
         name
     };
-    if options.non_interactive {
-        if instances.contains(&default_name) {
-            log::warn!("Instance {:?} already exists", default_name);
+    if let Some(name) = &options.server_instance {
+        if !is_valid_name(name) {
+            anyhow::bail!("instance name must be a valid identifier, \
+                           (regex: ^[a-zA-Z_][a-zA-Z_0-9]*$)");
         }
+        let exists = instances.contains(name);
+        if exists {
+            eprintln!("Using existing instance {:?}", name);
+        }
+        return Ok((name.clone(), exists));
+    }
+    if options.non_interactive {
         return Ok((default_name, false))
     }
     let mut q = question::String::new(
@@ -219,7 +272,7 @@ fn ask_version(meth: &dyn Method, options: &Init)
         Some(ver) => VersionQuery::Stable(Some(ver.clone())),
         None => VersionQuery::Stable(None),
     };
-    if options.non_interactive {
+    if options.non_interactive || options.server_version.is_some() {
         return meth.get_version(&ver_query);
     }
     let distribution = meth.get_version(&ver_query)
@@ -275,7 +328,13 @@ pub fn init(init: &Init) -> anyhow::Result<()> {
         None => {
             let base_dir = env::current_dir()
                 .context("failed to get current directory")?;
-            (search_dir(&base_dir)?, base_dir)
+            let dir = search_dir(&base_dir)?;
+            if let Some(dir) = &dir {
+                if dir != &base_dir {
+                    warn_nested(&base_dir, dir);
+                }
+            }
+            (dir, base_dir)
         }
     };
     if let Some(dir) = dir {
@@ -287,6 +346,15 @@ pub fn init(init: &Init) -> anyhow::Result<()> {
             .with_context(|| format!("failed to canonicalize dir {:?}", dir))?;
         init_new(init, &dir)?;
     }
+    match cleanup::orphaned() {
+        Ok(stale) if !stale.is_empty() => {
+            eprintln!("Note: {} projects were removed from disk \
+                but still have instances linked. \
+                Run `edgedb project cleanup` to unlink them.", stale.len());
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("Cannot check for stale projects: {:#}", e),
+    }
     Ok(())
 }
 
@@ -303,6 +371,49 @@ fn write_config(path: &Path, version: &MajorVersion) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Pins server version in the existing config, keeping the rest of it
+fn set_server_version(text: &str, version: &MajorVersion) -> String {
+    let line = format!("server-version = {:?}", version.as_str());
+    let mut result = String::with_capacity(text.len() + line.len());
+    let mut in_edgedb = false;
+    let mut found = false;
+    for item in text.lines() {
+        let trimmed = item.trim();
+        if trimmed.starts_with('[') {
+            if in_edgedb && !found {
+                result.push_str(&line);
+                result.push('\n');
+                found = true;
+            }
+            in_edgedb = trimmed == "[edgedb]";
+        } else if in_edgedb && !found &&
+            trimmed.starts_with("server-version")
+        {
+            result.push_str(&line);
+            result.push('\n');
+            found = true;
+            continue;
+        }
+        result.push_str(item);
+        result.push('\n');
+    }
+    if in_edgedb && !found {
+        result.push_str(&line);
+        result.push('\n');
+    }
+    return result;
+}
+
+#[context("cannot update config `{}`", path.display())]
+fn update_config(path: &Path, version: &MajorVersion) -> anyhow::Result<()> {
+    let text = set_server_version(&fs::read_to_string(path)?, version);
+    let tmp = tmp_file_path(path);
+    fs::remove_file(&tmp).ok();
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
 pub fn init_existing(options: &Init, project_dir: &Path)
     -> anyhow::Result<()>
 {
@@ -317,9 +428,12 @@ pub fn init_existing(options: &Init, project_dir: &Path)
     }
 
     let config_path = project_dir.join("edgedb.toml");
-    let schema_dir = project_dir.join("dbschema");
-    let schema_files = find_schema_files(&schema_dir)?;
     let config = config::read(&config_path)?;
+    let schema_dir = &config.project.schema_dir;
+    let mut schema_files = false;
+    for dir in &config.project.sdl_dirs {
+        schema_files |= find_schema_files(dir)?;
+    }
 
     let ver_query = if let Some(ver) = &options.server_version {
         match ver.num() {
@@ -340,6 +454,7 @@ pub fn init_existing(options: &Init, project_dir: &Path)
 
     let inst = if exists {
         let inst = get_instance(&methods, &name)?;
+        warn_existing(options);
         let inst_ver = inst.get_version()?;
         if !ver_query.matches(inst_ver) {
             eprintln!("WARNING: existing instance has version {}, \
@@ -374,6 +489,8 @@ pub fn init_existing(options: &Init, project_dir: &Path)
             ("Installation method", method.title()),
             ("Version", distr.version().as_ref()),
             ("Instance name", &name),
+            ("Start configuration",
+                &options.server_start_conf.to_string()),
         ]);
         // TODO(tailhook) this condition doesn't work for nightly
         if !installed.iter()
@@ -388,10 +505,8 @@ pub fn init_existing(options: &Init, project_dir: &Path)
             })?;
         }
 
-        write_config(&config_path, distr.major_version())?;
-        if !schema_files {
-            write_default(&schema_dir)?;
-        }
+        update_config(&config_path, distr.major_version())?;
+        write_schema(options, project_dir, &config.project, schema_files)?;
 
         let settings = init::Settings {
             name: name.clone(),
@@ -405,9 +520,13 @@ pub fn init_existing(options: &Init, project_dir: &Path)
                 .join(format!("{}.json", &name)),
             user: "edgedb".into(),
             database: "edgedb".into(),
-            port: allocate_port(&name)?,
-            start_conf: StartConf::Auto,
+            port: match options.server_port {
+                Some(port) => reserve_port(&name, port)?,
+                None => allocate_port(&name)?,
+            },
+            start_conf: options.server_start_conf,
             suppress_messages: true,
+            backend_dsn: None,
         };
 
         println!("Initializing EdgeDB instance...");
@@ -421,7 +540,9 @@ pub fn init_existing(options: &Init, project_dir: &Path)
     write_stash_dir(&stash_dir, project_dir, &name)?;
 
     if err_manual {
-        run_and_migrate(&inst)?;
+        if !options.no_migrations {
+            run_and_migrate(&inst, &schema_dir)?;
+        }
         eprintln!("Bootstrapping complete, \
             but there was an error creating the service. \
             You can run server manually via: \n  \
@@ -429,7 +550,20 @@ pub fn init_existing(options: &Init, project_dir: &Path)
             name.escape_default());
         return Err(ExitCode::new(2))?;
     } else {
-        task::block_on(migrate(&inst, exists && !options.non_interactive))?;
+        if options.no_migrations {
+            eprintln!("Skipping migrations. You can apply migrations \
+                by running:\n  edgedb migrate");
+        } else if !exists && options.server_start_conf == StartConf::Manual {
+            // the instance is not running, start it just for migrations
+            run_and_migrate(&inst, &schema_dir)?;
+            eprintln!("Seeds are not applied. You can apply them after \
+                starting the instance by running:\n  edgedb project seed");
+        } else {
+            task::block_on(
+                migrate(&inst, &schema_dir,
+                        exists && !options.non_interactive))?;
+            apply_seeds(project_dir, &name);
+        }
         print_initialized(&name, &options.project_dir);
     }
 
@@ -457,10 +591,28 @@ fn find_schema_files(path: &Path) -> anyhow::Result<bool> {
     return Ok(false);
 }
 
-#[context("cannot create default schema in `{}`", dir.display())]
-fn write_default(dir: &Path) -> anyhow::Result<()> {
+fn write_schema(options: &Init, project_dir: &Path,
+    project: &config::Project, schema_files: bool)
+    -> anyhow::Result<()>
+{
+    match &options.template {
+        Some(name) if schema_files => {
+            log::warn!("Schema directory is not empty, \
+                template {:?} is not used", name);
+            Ok(())
+        }
+        Some(name) => templates::write(name, project_dir, project),
+        None if schema_files => Ok(()),
+        None => write_default(project),
+    }
+}
+
+#[context("cannot create default schema in `{}`",
+          project.sdl_dirs[0].display())]
+fn write_default(project: &config::Project) -> anyhow::Result<()> {
+    let dir = &project.sdl_dirs[0];
     fs::create_dir_all(&dir)?;
-    fs::create_dir_all(&dir.join("migrations"))?;
+    fs::create_dir_all(&project.schema_dir.join("migrations"))?;
     let default = dir.join("default.esdl");
     let tmp = tmp_file_path(&default);
     fs::remove_file(&tmp).ok();
@@ -568,6 +720,10 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
     let config_path = project_dir.join("edgedb.toml");
     let schema_dir = project_dir.join("dbschema");
     let schema_files = find_schema_files(&schema_dir)?;
+    let project = config::Project {
+        schema_dir: schema_dir.clone(),
+        sdl_dirs: vec![schema_dir.clone()],
+    };
 
     let os = detect::current_os()?;
     let avail_methods = os.get_available_methods()?;
@@ -576,11 +732,10 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
 
     let inst = if exists {
         let inst = get_instance(&methods, &name)?;
+        warn_existing(options);
 
         write_config(&config_path, inst.get_version()?)?;
-        if !schema_files {
-            write_default(&schema_dir)?;
-        }
+        write_schema(options, project_dir, &project, schema_files)?;
 
         inst
     } else {
@@ -601,6 +756,8 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
             ("Installation method", method.title()),
             ("Version", distr.version().as_ref()),
             ("Instance name", &name),
+            ("Start configuration",
+                &options.server_start_conf.to_string()),
         ]);
 
         // TODO(tailhook) this condition doesn't work for nightly
@@ -616,9 +773,7 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
         }
 
         write_config(&config_path, distr.major_version())?;
-        if !schema_files {
-            write_default(&schema_dir)?;
-        }
+        write_schema(options, project_dir, &project, schema_files)?;
         let settings = init::Settings {
             name: name.clone(),
             system: false,
@@ -631,9 +786,13 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
                 .join(format!("{}.json", &name)),
             user: "edgedb".into(),
             database: "edgedb".into(),
-            port: allocate_port(&name)?,
-            start_conf: StartConf::Auto,
+            port: match options.server_port {
+                Some(port) => reserve_port(&name, port)?,
+                None => allocate_port(&name)?,
+            },
+            start_conf: options.server_start_conf,
             suppress_messages: true,
+            backend_dsn: None,
         };
 
         println!("Initializing EdgeDB instance...");
@@ -647,7 +806,9 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
     write_stash_dir(&stash_dir, project_dir, &name)?;
 
     if err_manual {
-        run_and_migrate(&inst)?;
+        if !options.no_migrations {
+            run_and_migrate(&inst, &schema_dir)?;
+        }
         eprintln!("Bootstrapping complete, \
             but there was an error creating the service. \
             You can run server manually via: \n  \
@@ -655,7 +816,20 @@ pub fn init_new(options: &Init, project_dir: &Path) -> anyhow::Result<()> {
             name.escape_default());
         return Err(ExitCode::new(2))?;
     } else {
-        task::block_on(migrate(&inst, exists && !options.non_interactive))?;
+        if options.no_migrations {
+            eprintln!("Skipping migrations. You can apply migrations \
+                by running:\n  edgedb migrate");
+        } else if !exists && options.server_start_conf == StartConf::Manual {
+            // the instance is not running, start it just for migrations
+            run_and_migrate(&inst, &schema_dir)?;
+            eprintln!("Seeds are not applied. You can apply them after \
+                starting the instance by running:\n  edgedb project seed");
+        } else {
+            task::block_on(
+                migrate(&inst, &schema_dir,
+                        exists && !options.non_interactive))?;
+            apply_seeds(project_dir, &name);
+        }
         print_initialized(&name, &options.project_dir);
     }
 
@@ -672,17 +846,42 @@ fn print_initialized(name: &str, dir_option: &Option<PathBuf>) {
     }
 }
 
-fn run_and_migrate(inst: &InstanceRef) -> anyhow::Result<()> {
+fn apply_seeds(project_dir: &Path, name: &str) {
+    match seed::seeds_dir(project_dir) {
+        Ok(dir) if dir.exists() => {}
+        _ => return,
+    }
+    println!("Applying seeds...");
+    if let Err(e) = seed::seed_instance(project_dir, name) {
+        eprintln!("edgedb error: cannot apply seeds: {:#}", e);
+        eprintln!("  Hint: once the instance is running, \
+            apply seeds by running:\n    edgedb project seed");
+    }
+}
+
+fn warn_existing(options: &Init) {
+    if options.server_port.is_some()
+        || options.server_start_conf != StartConf::Auto
+    {
+        eprintln!("WARNING: `--server-port` and `--server-start-conf` \
+            are ignored, because the instance already exists");
+    }
+}
+
+fn run_and_migrate(inst: &InstanceRef, schema_dir: &Path)
+    -> anyhow::Result<()>
+{
     let mut cmd = inst.get_command()?;
     log::info!("Running server manually: {:?}", cmd);
     let child = ProcessGuard::run(&mut cmd)
         .with_context(|| format!("error running server {:?}", cmd))?;
-    task::block_on(migrate(&inst, false))?;
+    task::block_on(migrate(&inst, schema_dir, false))?;
     drop(child);
     Ok(())
 }
 
-async fn migrate(inst: &InstanceRef<'_>, ask_for_running: bool)
+async fn migrate(inst: &InstanceRef<'_>, schema_dir: &Path,
+    ask_for_running: bool)
     -> anyhow::Result<()>
 {
     use crate::commands::Options;
@@ -734,7 +933,7 @@ async fn migrate(inst: &InstanceRef<'_>, ask_for_running: bool)
                         }
                     }
                     Run => {
-                        run_and_migrate(inst)?;
+                        run_and_migrate(inst, schema_dir)?;
                         return Ok(());
                     }
                     Retry => continue,
@@ -757,13 +956,39 @@ async fn migrate(inst: &InstanceRef<'_>, ask_for_running: bool)
             command_line: true,
             styler: None,
             conn_params: Connector::new(Ok(conn_params)),
+            schema_cache: None,
         },
         &Migrate {
             cfg: MigrationConfig {
-                schema_dir: "./dbschema".into(),
+                schema_dir: Some(schema_dir.into()),
             },
             quiet: false,
             to_revision: None,
+            dev_mode: false,
         }).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::set_server_version;
+    use crate::server::distribution::MajorVersion;
+
+    #[test]
+    fn server_version() {
+        let ver = MajorVersion::Nightly;
+        assert_eq!(
+            set_server_version("[edgedb]\nserver-version = \"1-beta2\"\n\n\
+                [project]\nschema-dir = \"db\"\n", &ver),
+            "[edgedb]\nserver-version = \"nightly\"\n\n\
+                [project]\nschema-dir = \"db\"\n");
+        assert_eq!(
+            set_server_version("[project]\nschema-dir = \"db\"\n\
+                [edgedb]\n", &ver),
+            "[project]\nschema-dir = \"db\"\n\
+                [edgedb]\nserver-version = \"nightly\"\n");
+        assert_eq!(
+            set_server_version("[edgedb]\n[connection]\n", &ver),
+            "[edgedb]\nserver-version = \"nightly\"\n[connection]\n");
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+
+use prettytable::{Table, Row, Cell};
+
+use crate::project::options::List;
+use crate::project::{all_stashes, Stash};
+use crate::server::detect;
+use crate::table;
+
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonProject<'a> {
+    project_path: Option<String>,
+    instance: Option<&'a str>,
+    status: &'static str,
+}
+
+
+fn local_instances() -> anyhow::Result<BTreeSet<String>> {
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let mut result = BTreeSet::new();
+    for meth in methods.values() {
+        for inst in meth.all_instances()? {
+            result.insert(inst.name().to_string());
+        }
+    }
+    Ok(result)
+}
+
+fn status(stash: &Stash, instances: &BTreeSet<String>) -> &'static str {
+    if stash.is_orphaned() {
+        "directory removed"
+    } else if stash.instance.as_ref()
+        .map(|name| !instances.contains(name))
+        .unwrap_or(true)
+    {
+        "no instance"
+    } else {
+        "ok"
+    }
+}
+
+pub fn list(options: &List) -> anyhow::Result<()> {
+    let stashes = all_stashes()?;
+    let instances = local_instances()?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&stashes.iter()
+            .map(|stash| JsonProject {
+                project_path: stash.project_path.as_ref()
+                    .map(|p| p.display().to_string()),
+                instance: stash.instance.as_deref(),
+                status: status(stash, &instances),
+            })
+            .collect::<Vec<_>>()
+        )?);
+        return Ok(());
+    }
+    if stashes.is_empty() {
+        eprintln!("No projects initialized");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Project", "Instance", "Status"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for stash in &stashes {
+        table.add_row(Row::new(vec![
+            Cell::new(&stash.project_path.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "?".into())),
+            Cell::new(stash.instance.as_deref().unwrap_or("?")),
+            Cell::new(status(stash, &instances)),
+        ]));
+    }
+    table.printstd();
+    if stashes.iter().any(|s| s.is_orphaned()) {
+        eprintln!("Some project directories were removed, \
+            run `edgedb project cleanup` to unlink them.");
+    }
+    Ok(())
+}
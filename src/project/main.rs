@@ -1,6 +1,9 @@
 use crate::project::options::{ProjectCommand, Command};
 
+use crate::project::cleanup;
 use crate::project::init;
+use crate::project::list;
+use crate::project::seed;
 use crate::project::unlink;
 
 pub fn main(cmd: &ProjectCommand) -> anyhow::Result<()> {
@@ -9,5 +12,8 @@ pub fn main(cmd: &ProjectCommand) -> anyhow::Result<()> {
     match &cmd.subcommand {
         Init(c) => init::init(c),
         Unlink(c) => unlink::unlink(c),
+        Seed(c) => seed::seed(c),
+        List(c) => list::list(c),
+        Cleanup(c) => cleanup::cleanup(c),
     }
 }
@@ -4,18 +4,23 @@ use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
+use crate::platform::bytes_to_path;
+
 pub mod options;
 
 mod main;
+mod cleanup;
 pub mod init;
+mod list;
 mod unlink;
-mod config;
+pub mod config;
+pub mod seed;
+mod templates;
 
 pub use main::main;
 pub use init::{stash_path};
 pub use unlink::unlink;
 
-#[allow(unused)]  // TODO(tailhook) will be used in `project info`
 pub fn project_dir(cli_option: Option<&Path>) -> anyhow::Result<PathBuf> {
     project_dir_opt(cli_option)?
     .ok_or_else(|| {
@@ -53,3 +58,64 @@ pub fn project_dir_opt(cli_options: Option<&Path>)
         }
     }
 }
+
+/// Project initialized on this machine, i.e. an entry in the stash directory
+#[derive(Debug)]
+pub struct Stash {
+    pub dir: PathBuf,
+    pub project_path: Option<PathBuf>,
+    pub instance: Option<String>,
+}
+
+impl Stash {
+    /// Project directory was removed or moved since initialization
+    ///
+    /// Other errors (such as permission denied) don't mean project is gone,
+    /// so such projects are skipped with a warning.
+    pub fn is_orphaned(&self) -> bool {
+        let path = match &self.project_path {
+            Some(path) => path.join("edgedb.toml"),
+            None => return true,
+        };
+        match fs::metadata(&path) {
+            Ok(_) => false,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+            Err(e) => {
+                log::warn!("Cannot check project {:?}: {}", path, e);
+                false
+            }
+        }
+    }
+}
+
+/// Returns all the projects initialized on this machine
+pub fn all_stashes() -> anyhow::Result<Vec<Stash>> {
+    let base = init::stash_base()?;
+    let dir = match fs::read_dir(&base) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("cannot read projects dir {:?}", base)
+            });
+        }
+    };
+    let mut result = Vec::new();
+    for item in dir {
+        let dir = item?.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let project_path = fs::read(dir.join("project-path")).ok()
+            .and_then(|bytes| {
+                bytes_to_path(&bytes).ok().map(|p| p.to_path_buf())
+            });
+        let instance = fs::read_to_string(dir.join("instance-name")).ok()
+            .map(|name| name.trim().to_string());
+        result.push(Stash { dir, project_path, instance });
+    }
+    result.sort_by(|a, b| a.project_path.cmp(&b.project_path));
+    Ok(result)
+}
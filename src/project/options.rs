@@ -1,9 +1,10 @@
 use std::path::PathBuf;
 
 use clap::{Clap, AppSettings, ValueHint};
+use crate::project::templates;
 use crate::server::methods::InstallMethod;
 use crate::server::version::Version;
-use crate::server::options::instance_name_opt;
+use crate::server::options::{instance_name_opt, StartConf};
 
 
 #[derive(Clap, Debug, Clone)]
@@ -20,6 +21,14 @@ pub enum Command {
     /// Remove association with and optionally destroy the
     /// linked EdgeDB intstance.
     Unlink(Unlink),
+    /// Apply seed data from `dbschema/seeds/*.edgeql` files that were not
+    /// applied yet
+    Seed(Seed),
+    /// List all projects initialized on this machine and their instances
+    List(List),
+    /// Unlink projects whose directories were removed and optionally
+    /// destroy their instances
+    Cleanup(Cleanup),
 }
 
 #[derive(Clap, Debug, Clone)]
@@ -37,10 +46,32 @@ pub struct Init {
     #[clap(long, validator(instance_name_opt))]
     pub server_instance: Option<String>,
 
-    /// Specifies a project root directory explicitly.
-    #[clap(long, possible_values=&["package", "docker"][..])]
+    /// Specifies the installation method of the server instance
+    #[clap(long, visible_alias="method",
+           possible_values=&["package", "docker"][..])]
     pub server_install_method: Option<InstallMethod>,
 
+    /// Specifies the port of the created server instance (allocated
+    /// automatically by default)
+    #[clap(long, visible_alias="port")]
+    pub server_port: Option<u16>,
+
+    /// Whether the created server instance is started automatically or
+    /// only by `edgedb server start`
+    #[clap(long, default_value="auto",
+           possible_values=&["auto", "manual"][..])]
+    pub server_start_conf: StartConf,
+
+    /// Create initial schema and example queries from a template: either
+    /// a built-in one (empty, todo, blog) or a git repository URL (its
+    /// `dbschema` and `queries` directories are copied)
+    #[clap(long, validator(templates::validate))]
+    pub template: Option<String>,
+
+    /// Do not apply migrations (and seeds) after the instance is created
+    #[clap(long)]
+    pub no_migrations: bool,
+
     /// Run in non-interactive mode (accepting all defaults)
     #[clap(long)]
     pub non_interactive: bool,
@@ -60,3 +91,32 @@ pub struct Unlink {
     #[clap(long)]
     pub non_interactive: bool,
 }
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Seed {
+    /// Specifies a project root directory explicitly.
+    #[clap(value_hint=ValueHint::DirPath)]
+    pub project_dir: Option<PathBuf>,
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct List {
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Clap, Debug, Clone)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Cleanup {
+    /// Also destroy instances of the removed projects (unless they are
+    /// used by other projects)
+    #[clap(long, short='D')]
+    pub destroy_server_instances: bool,
+
+    /// Do not ask for confirmation
+    #[clap(long)]
+    pub non_interactive: bool,
+}
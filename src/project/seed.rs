@@ -0,0 +1,204 @@
+//! Seed data of the project
+//!
+//! Files `seeds/*.edgeql` of the schema directory are applied in the order
+//! of their names, each one in a separate transaction. Applied files are
+//! recorded in the database itself, as `cli_seeds::Applied` objects, so
+//! every checkout and CI run sees the same state, and a database that is
+//! recreated gets the seeds again. The type is created along with the first
+//! seed if migrations don't create it (see `SCHEMA`).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_std::prelude::StreamExt;
+use async_std::task;
+use edgedb_client::client::Connection;
+use edgedb_derive::Queryable;
+use edgedb_protocol::value::Value;
+use fn_error_context::context;
+
+use crate::credentials;
+use crate::project::config;
+use crate::project::options::Seed;
+use crate::project::{project_dir, stash_path};
+
+
+/// Schema of the type recording applied seeds, it's added to the schema
+/// files when creating migrations of a project that has seeds, so that
+/// migrations don't drop it
+pub const SCHEMA: &str = "module cli_seeds {
+    type Applied {
+        required property name -> str { constraint exclusive; };
+        required property checksum -> str;
+    };
+};";
+
+#[derive(Queryable)]
+struct Applied {
+    name: String,
+    checksum: String,
+}
+
+pub fn seeds_dir(project_dir: &Path) -> anyhow::Result<PathBuf> {
+    let config = config::read(&project_dir.join("edgedb.toml"))?;
+    Ok(config.project.schema_dir.join("seeds"))
+}
+
+/// Returns seed files ordered by name
+#[context("cannot read seeds directory {}", dir.display())]
+fn seed_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut result = Vec::new();
+    for item in fs::read_dir(dir)? {
+        let item = item?;
+        let name = item.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(".") || !name.ends_with(".edgeql")
+            || !item.file_type()?.is_file()
+        {
+            continue;
+        }
+        result.push(item.path());
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Returns `true` if there are seed files in the directory
+pub fn has_seeds(seeds_dir: &Path) -> anyhow::Result<bool> {
+    Ok(!seed_files(seeds_dir)?.is_empty())
+}
+
+/// Returns `true` if the database has the type recording applied seeds
+async fn is_tracked(cli: &mut Connection) -> anyhow::Result<bool> {
+    cli.query_row(r###"
+        SELECT EXISTS (
+            SELECT schema::ObjectType FILTER .name = 'cli_seeds::Applied'
+        )
+    "###, &Value::empty_tuple()).await
+}
+
+/// Returns checksums of the seeds applied to the database by file name
+async fn applied(cli: &mut Connection)
+    -> anyhow::Result<BTreeMap<String, String>>
+{
+    let mut result = BTreeMap::new();
+    if !is_tracked(cli).await? {
+        cli.execute(r###"
+            CREATE MODULE cli_seeds IF NOT EXISTS;
+            CREATE TYPE cli_seeds::Applied {
+                CREATE REQUIRED PROPERTY name -> std::str {
+                    CREATE CONSTRAINT std::exclusive;
+                };
+                CREATE REQUIRED PROPERTY checksum -> std::str;
+            };
+        "###).await.context("cannot create type recording applied seeds")?;
+        return Ok(result);
+    }
+    let mut items = cli.query::<Applied>(
+        "SELECT cli_seeds::Applied { name, checksum }",
+        &Value::empty_tuple(),
+    ).await?;
+    while let Some(item) = items.next().await.transpose()? {
+        result.insert(item.name, item.checksum);
+    }
+    Ok(result)
+}
+
+/// Applies the seed and records it in the same transaction
+async fn apply_file(cli: &mut Connection, name: &str, text: &str,
+    checksum: &str)
+    -> anyhow::Result<()>
+{
+    cli.execute("START TRANSACTION").await?;
+    let result = async {
+        cli.execute(text).await?;
+        cli.query_row::<String>(r###"
+            SELECT <str>(INSERT cli_seeds::Applied {
+                name := <str>$0,
+                checksum := <str>$1,
+            }).id
+        "###, &Value::Tuple(vec![
+            Value::Str(name.into()),
+            Value::Str(checksum.into()),
+        ])).await?;
+        Ok(())
+    }.await;
+    match result {
+        Ok(()) => {
+            cli.execute("COMMIT").await?;
+            Ok(())
+        }
+        Err(e) => {
+            cli.execute("ROLLBACK").await.ok();
+            Err(e)
+        }
+    }
+}
+
+/// Applies seed files of `seeds_dir` that were not applied to the database
+/// yet, returns number of files applied
+pub async fn apply(cli: &mut Connection, seeds_dir: &Path)
+    -> anyhow::Result<usize>
+{
+    let files = seed_files(seeds_dir)?;
+    if files.is_empty() {
+        return Ok(0);
+    }
+    let known = applied(cli).await?;
+    let mut count = 0;
+    for path in files {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let hash = hex::encode(sha1::Sha1::from(&text).digest().bytes());
+        match known.get(&name) {
+            Some(old_hash) if old_hash == &hash => continue,
+            Some(_) => {
+                eprintln!("Seed {} was modified after it was applied, \
+                    skipping", name);
+                continue;
+            }
+            None => {}
+        }
+        apply_file(cli, &name, &text, &hash).await
+            .with_context(|| format!("cannot apply seed {}", name))?;
+        eprintln!("Applied seed {}", name);
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Applies seeds of the project to the instance linked to it
+pub fn seed_instance(project_dir: &Path, instance: &str)
+    -> anyhow::Result<usize>
+{
+    let seeds_dir = seeds_dir(project_dir)?;
+    let connector = credentials::get_connector(instance)?;
+    task::block_on(async {
+        let mut cli = connector.connect().await?;
+        apply(&mut cli, &seeds_dir).await
+    })
+}
+
+pub fn seed(options: &Seed) -> anyhow::Result<()> {
+    let project_dir = project_dir(options.project_dir.as_deref())?;
+    let stash_dir = stash_path(&project_dir)?;
+    let instance = fs::read_to_string(stash_dir.join("instance-name"))
+        .map_err(|e| anyhow::anyhow!("project is not initialized \
+            (cannot read instance name: {}), run `edgedb project init`", e))?;
+    let seeds = seeds_dir(&project_dir)?;
+    if seed_files(&seeds)?.is_empty() {
+        eprintln!("No seed files found in {}", seeds.display());
+        return Ok(());
+    }
+    let applied = seed_instance(&project_dir, instance.trim())?;
+    if applied == 0 {
+        eprintln!("All seeds are already applied.");
+    }
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! Project templates used by `edgedb project init --template`
+//!
+//! A template is either one of the built-in ones below or a git repository.
+//! Files of the template are copied into the project directory, existing
+//! files are never overwritten. Only `dbschema` and `queries` directories of
+//! the repository are copied, `edgedb.toml` is always written by `init`.
+//! The `dbschema` directory is copied to `project.schema-dir` and the
+//! schema files of built-in templates to the first of `project.sdl-dirs`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+use fn_error_context::context;
+
+use crate::platform::tmp_file_path;
+use crate::process;
+use crate::project::config::Project;
+
+/// Directories copied from template repositories
+const REPO_DIRS: &[&str] = &["dbschema", "queries"];
+
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    files: &'static [(&'static str, &'static str)],
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "empty",
+        description: "empty default module",
+        files: &[
+            ("dbschema/default.esdl", "\
+module default {
+
+}
+"),
+        ],
+    },
+    Template {
+        name: "todo",
+        description: "todo list with a single object type",
+        files: &[
+            ("dbschema/default.esdl", "\
+module default {
+    type Todo {
+        required property title -> str;
+        required property completed -> bool {
+            default := false;
+        };
+        property created -> datetime {
+            default := datetime_current();
+        };
+    }
+}
+"),
+            ("queries/todo.edgeql", "\
+# Add an item
+INSERT Todo { title := 'Write a schema' };
+
+# List items that are not done yet
+SELECT Todo { title, created }
+FILTER NOT .completed
+ORDER BY .created;
+
+# Mark an item as done
+UPDATE Todo
+FILTER .title = 'Write a schema'
+SET { completed := true };
+"),
+        ],
+    },
+    Template {
+        name: "blog",
+        description: "users and their posts linked together",
+        files: &[
+            ("dbschema/default.esdl", "\
+module default {
+    type User {
+        required property name -> str {
+            constraint exclusive;
+        };
+        multi link posts := .<author[IS Post];
+    }
+
+    type Post {
+        required property title -> str;
+        property body -> str;
+        required link author -> User;
+        property published -> datetime;
+    }
+}
+"),
+            ("queries/blog.edgeql", "\
+# Add a user and a post
+INSERT Post {
+    title := 'Hello world',
+    body := 'My first post',
+    author := (INSERT User { name := 'alice' }),
+    published := datetime_current(),
+};
+
+# Posts of a user, the latest first
+SELECT User {
+    name,
+    posts: { title, published } ORDER BY .published DESC,
+}
+FILTER .name = 'alice';
+"),
+        ],
+    },
+];
+
+
+fn is_repo_url(name: &str) -> bool {
+    name.contains("://") || name.starts_with("git@") || name.ends_with(".git")
+}
+
+pub fn validate(name: &str) -> Result<(), String> {
+    if is_repo_url(name) || TEMPLATES.iter().any(|t| t.name == name) {
+        Ok(())
+    } else {
+        Err(format!("unknown template {:?}, use a git repository URL \
+            or one of: {}", name,
+            TEMPLATES.iter()
+                .map(|t| format!("{} ({})", t.name, t.description))
+                .collect::<Vec<_>>().join(", ")))
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> anyhow::Result<bool> {
+    if path.exists() {
+        log::warn!("File {:?} exists, not overwriting it", path);
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = tmp_file_path(path);
+    fs::remove_file(&tmp).ok();
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+    Ok(true)
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> anyhow::Result<()> {
+    for item in fs::read_dir(src)? {
+        let item = item?;
+        let path = item.path();
+        let target = dest.join(item.file_name());
+        let file_type = item.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&path, &target)?;
+        } else if file_type.is_file() {
+            write_file(&target, &fs::read(&path)?)?;
+        } else {
+            // symlinks could point to any local file, e.g. in ~/.ssh
+            log::warn!("Skipping {}: not a regular file", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Returns where directory `dir` of the template is written to
+fn target_dir(dir: &str, project_dir: &Path, project: &Project) -> PathBuf {
+    match dir {
+        "dbschema" => project.schema_dir.clone(),
+        _ => project_dir.join(dir),
+    }
+}
+
+#[context("cannot fetch template from {}", url)]
+fn write_repo(url: &str, project_dir: &Path, project: &Project)
+    -> anyhow::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let checkout = tmp.path().join("template");
+    process::run(Command::new("git")
+        .arg("clone").arg("--depth=1").arg("--quiet")
+        .arg("--").arg(url).arg(&checkout))?;
+    let mut found = false;
+    for dir in REPO_DIRS {
+        let src = checkout.join(dir);
+        if fs::symlink_metadata(&src).map(|m| m.is_dir()).unwrap_or(false) {
+            copy_dir(&src, &target_dir(dir, project_dir, project))?;
+            found = true;
+        }
+    }
+    if !found {
+        anyhow::bail!("repository contains none of {} directories",
+            REPO_DIRS.join(", "));
+    }
+    Ok(())
+}
+
+/// Writes files of the template into the project directory
+pub fn write(name: &str, project_dir: &Path, project: &Project)
+    -> anyhow::Result<()>
+{
+    if is_repo_url(name) {
+        println!("Fetching template {}...", name);
+        write_repo(name, project_dir, project)?;
+    } else {
+        let template = TEMPLATES.iter().find(|t| t.name == name)
+            .ok_or_else(|| anyhow::anyhow!("unknown template {:?}", name))?;
+        for (path, data) in template.files {
+            let path = match path.strip_prefix("dbschema/") {
+                Some(file) => project.sdl_dirs[0].join(file),
+                None => project_dir.join(path),
+            };
+            write_file(&path, data.as_bytes())
+                .with_context(|| format!("cannot write {:?}", path))?;
+        }
+    }
+    fs::create_dir_all(project.schema_dir.join("migrations"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{copy_dir, validate, TEMPLATES};
+
+    #[test]
+    fn names() {
+        for template in TEMPLATES {
+            assert!(validate(template.name).is_ok());
+        }
+        assert!(validate("https://github.com/example/template").is_ok());
+        assert!(validate("git@github.com:example/template.git").is_ok());
+        assert!(validate("nonexistent").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        let dest = tmp.path().join("dest");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(tmp.path().join("secret"), "key").unwrap();
+        fs::write(src.join("default.esdl"), "module default {}").unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("secret"),
+                                   src.join("link.esdl")).unwrap();
+        copy_dir(&src, &dest).unwrap();
+        assert!(dest.join("default.esdl").exists());
+        assert!(!dest.join("link.esdl").exists());
+    }
+}
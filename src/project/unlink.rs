@@ -25,7 +25,7 @@ fn search_dir(base: &Path) -> anyhow::Result<PathBuf> {
                 format!("failed to canonicalize dir {:?}", parent)
             })?;
         let stash_dir = stash_path(&canon)?;
-        if stash_dir.exists() || path.join("edgedb.toml").exists() {
+        if stash_dir.exists() || parent.join("edgedb.toml").exists() {
             return Ok(stash_dir)
         }
         path = parent;
@@ -68,9 +68,10 @@ pub fn unlink(options: &Unlink) -> anyhow::Result<()> {
             }
             if options.destroy_server_instance {
                 destroy::do_destroy(&Destroy {
-                    name: inst.to_string(),
+                    name: Some(inst.to_string()),
                     verbose: false,
                     force: true,
+                    all_temporary: false,
                 })?;
             }
             fs::remove_dir_all(&stash_path)?;
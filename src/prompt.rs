@@ -1,14 +1,17 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fs;
 use std::io::{ErrorKind, Write};
 use std::env;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use anyhow::{self, Context as _Context};
 use async_std::channel::{Sender, Receiver, RecvError};
 use async_std::task;
 use dirs::data_local_dir;
-use rustyline::{self, error::ReadlineError, KeyEvent, Modifiers, Cmd};
+use rustyline::{self, error::ReadlineError, KeyEvent, KeyCode, Modifiers};
+use rustyline::Cmd;
 use rustyline::{Editor, Config, Helper, Context};
 use rustyline::config::{EditMode, CompletionType, Builder as ConfigBuilder};
 use rustyline::hint::Hinter;
@@ -22,19 +25,26 @@ use crate::commands::backslash;
 use crate::completion;
 use crate::print::style::Styler;
 use crate::highlight;
-use crate::repl::{TX_MARKER, FAILURE_MARKER};
 
 use colorful::Colorful;
 
 
 pub enum Control {
-    EdgeqlInput { prompt: String, initial: String },
-    ParameterInput { name: String, type_name: String, initial: String },
+    EdgeqlInput { prompt: String, highlighted: String, initial: String },
+    ParameterInput {
+        name: String,
+        type_name: String,
+        initial: String,
+        sensitive: bool,
+    },
     ShowHistory,
     SpawnEditor { entry: Option<isize> },
+    EditFile { path: PathBuf },
     ViMode,
     EmacsMode,
     SetHistoryLimit(usize),
+    /// Databases of the current instance (used for completion)
+    DatabaseNames(Vec<String>),
 }
 
 pub enum Input {
@@ -45,6 +55,11 @@ pub enum Input {
 
 pub struct EdgeqlHelper {
     styler: Styler,
+    databases: Vec<String>,
+    /// Current prompt and its colored version for displaying
+    prompt: (String, String),
+    /// Text being edited, to find out if Ctrl+C is pressed at empty prompt
+    line: RefCell<String>,
 }
 
 impl Helper for EdgeqlHelper {}
@@ -53,6 +68,10 @@ impl Hinter for EdgeqlHelper {
     fn hint(&self, line: &str, pos: usize, _ctx: &Context)
         -> Option<Self::Hint>
     {
+        // hint is requested on every change of the line
+        let mut current = self.line.borrow_mut();
+        current.clear();
+        current.push_str(line);
         return completion::hint(line, pos);
     }
 }
@@ -63,20 +82,10 @@ impl Highlighter for EdgeqlHelper {
         -> Cow<'b, str>
     {
         if info.line_no() > 0 {
-            return format!("{0:.>1$}", " ", prompt.len()).into();
-        } else if prompt.ends_with("> ") {
-            let content = &prompt[..prompt.len()-2];
-            if content.ends_with(TX_MARKER) {
-                return format!("{}{}> ",
-                    &content[..content.len()-TX_MARKER.len()],
-                    TX_MARKER.green()).into();
-            } else if content.ends_with(FAILURE_MARKER) {
-                return format!("{}{}> ",
-                    &content[..content.len()-FAILURE_MARKER.len()],
-                    FAILURE_MARKER.red()).into();
-            } else {
-                return prompt.into();
-            }
+            return format!("{0:.>1$}", " ", prompt.chars().count())
+                .light_gray().to_string().into();
+        } else if prompt == self.prompt.0 {
+            return self.prompt.1.as_str().into();
         } else {
             return prompt.into();
         }
@@ -157,7 +166,7 @@ impl Completer for EdgeqlHelper {
     fn complete(&self, line: &str, pos: usize, _ctx: &Context)
         -> Result<(usize, Vec<Self::Candidate>), ReadlineError>
     {
-        let comp = completion::complete(line, pos);
+        let comp = completion::complete(line, pos, &self.databases);
         if let Some((offset, options)) = comp {
             Ok((offset, options))
         } else {
@@ -198,17 +207,28 @@ pub fn save_history<H: Helper>(ed: &mut Editor<H>, name: &str) {
     }).ok();
 }
 
-pub fn create_editor(config: &ConfigBuilder) -> Editor<EdgeqlHelper> {
+pub fn create_editor(config: &ConfigBuilder, databases: &[String])
+    -> Editor<EdgeqlHelper>
+{
     let mut editor = Editor::<EdgeqlHelper>::with_config(
         config.clone().build());
     editor.bind_sequence(KeyEvent::new('\r', Modifiers::NONE),
         Cmd::AcceptOrInsertLine { accept_in_the_middle: false });
     editor.bind_sequence(KeyEvent::new('\r', Modifiers::ALT), Cmd::AcceptLine);
+    // Arrows move between lines of a multi-line statement first and switch
+    // to history only at the first (last) line
+    editor.bind_sequence(KeyEvent(KeyCode::Up, Modifiers::NONE),
+        Cmd::LineUpOrPreviousHistory(1));
+    editor.bind_sequence(KeyEvent(KeyCode::Down, Modifiers::NONE),
+        Cmd::LineDownOrNextHistory(1));
     load_history(&mut editor, "edgeql").map_err(|e| {
         eprintln!("Can't load history: {:#}", e);
     }).ok();
     editor.set_helper(Some(EdgeqlHelper {
         styler: Styler::dark_256(),
+        databases: databases.to_vec(),
+        prompt: (String::new(), String::new()),
+        line: RefCell::new(String::new()),
     }));
     return editor;
 }
@@ -221,10 +241,14 @@ pub fn var_editor(config: &ConfigBuilder, type_name: &str) -> Editor<()> {
     return editor;
 }
 
-pub fn edgeql_input(prompt: &str, editor: &mut Editor<EdgeqlHelper>,
-    data: &Sender<Input>, initial: &str)
+pub fn edgeql_input(prompt: &str, highlighted: &str,
+    editor: &mut Editor<EdgeqlHelper>, data: &Sender<Input>, initial: &str)
     -> anyhow::Result<()>
 {
+    if let Some(helper) = editor.helper_mut() {
+        helper.prompt = (prompt.into(), highlighted.into());
+        *helper.line.borrow_mut() = initial.into();
+    }
     let text = match
         editor.readline_with_initial(&prompt, (&initial, ""))
     {
@@ -234,7 +258,12 @@ pub fn edgeql_input(prompt: &str, editor: &mut Editor<EdgeqlHelper>,
             return Ok(());
         }
         Err(ReadlineError::Interrupted) => {
-            task::block_on(data.send(Input::Interrupt))?;
+            let empty = editor.helper()
+                .map(|h| h.line.borrow().trim().is_empty())
+                .unwrap_or(false);
+            // Ctrl+C clears the text, and exits at empty prompt
+            let input = if empty { Input::Eof } else { Input::Interrupt };
+            task::block_on(data.send(input))?;
             return Ok(());
         }
         Err(e) => {
@@ -253,27 +282,46 @@ pub fn main(data: Sender<Input>, control: Receiver<Control>)
 {
     let config = Config::builder();
     let config = config.edit_mode(EditMode::Emacs);
+    // Pasted text is inserted as is, so that multi-statement scripts are
+    // executed at once rather than line by line
+    let config = config.bracketed_paste(true);
     let mut config = config.completion_type(CompletionType::List);
-    let mut editor = create_editor(&config);
+    let mut databases = Vec::new();
+    let mut editor = create_editor(&config, &databases);
     'outer: loop {
         match task::block_on(control.recv()) {
             Err(RecvError) => break 'outer,
             Ok(Control::ViMode) => {
                 config = config.edit_mode(EditMode::Vi);
-                editor = create_editor(&config);
+                editor = create_editor(&config, &databases);
             }
             Ok(Control::EmacsMode) => {
                 config = config.edit_mode(EditMode::Emacs);
-                editor = create_editor(&config);
+                editor = create_editor(&config, &databases);
             }
             Ok(Control::SetHistoryLimit(h)) => {
                 config = config.max_history_size(h);
-                editor = create_editor(&config);
+                editor = create_editor(&config, &databases);
             }
-            Ok(Control::EdgeqlInput { prompt, initial }) => {
-                edgeql_input(&prompt, &mut editor, &data, &initial)?;
+            Ok(Control::DatabaseNames(names)) => {
+                databases = names;
+                if let Some(helper) = editor.helper_mut() {
+                    helper.databases = databases.clone();
+                }
+            }
+            Ok(Control::EdgeqlInput { prompt, highlighted, initial }) => {
+                edgeql_input(&prompt, &highlighted,
+                             &mut editor, &data, &initial)?;
             }
-            Ok(Control::ParameterInput { name, type_name, initial })
+            Ok(Control::ParameterInput { name, type_name, sensitive: true,
+                                         .. })
+            => {
+                let prompt = format!("Parameter <{}>${}: ", &type_name, &name);
+                // hidden input is neither editable nor saved in history
+                let text = rpassword::read_password_from_tty(Some(&prompt))?;
+                task::block_on(data.send(Input::Text(text)))?;
+            }
+            Ok(Control::ParameterInput { name, type_name, initial, .. })
             => {
                 let prompt = format!("Parameter <{}>${}: ", &type_name, &name);
                 let mut editor = var_editor(&config, &type_name);
@@ -338,6 +386,24 @@ pub fn main(data: Sender<Input>, control: Receiver<Control>)
                 text.truncate(text.trim_end().len());
                 task::block_on(data.send(Input::Text(text)))?;
             }
+            Ok(Control::EditFile { path }) => {
+                let mut text = match run_editor(&path)
+                    .and_then(|()| Ok(fs::read_to_string(&path)?))
+                {
+                    Ok(text) => text,
+                    Err(e) => {
+                        eprintln!("Error editing {}: {}", path.display(), e);
+                        task::block_on(data.send(Input::Interrupt))?;
+                        continue;
+                    }
+                };
+                text.truncate(text.trim_end().len());
+                if text.is_empty() {
+                    task::block_on(data.send(Input::Interrupt))?;
+                } else {
+                    task::block_on(data.send(Input::Text(text)))?;
+                }
+            }
         }
     }
     save_history(&mut editor, "edgeql");
@@ -381,16 +447,22 @@ fn spawn_editor(data: &str) -> Result<String, anyhow::Error> {
         .tempfile()?;
     temp_file.write_all(data.as_bytes())?;
     let temp_path = temp_file.into_temp_path();
+    run_editor(&temp_path)?;
+    Ok(fs::read_to_string(&temp_path)?)
+}
+
+/// Opens the file in `EDGEDB_EDITOR` or `EDITOR` and waits for it to exit
+pub fn run_editor(path: &Path) -> Result<(), anyhow::Error> {
     let editor = env::var("EDGEDB_EDITOR")
         .or_else(|_| env::var("EDITOR"))
         .unwrap_or_else(|_| String::from("vim"));
     let mut items = editor.split_whitespace();
     let mut cmd = Command::new(items.next().unwrap());
     cmd.args(items);
-    cmd.arg(&temp_path);
+    cmd.arg(path);
     let res = cmd.status()?;
     if res.success() {
-        return Ok(fs::read_to_string(&temp_path)?);
+        Ok(())
     } else {
         Err(anyhow::anyhow!("editor exited with: {}", res))
     }
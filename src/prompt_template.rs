@@ -0,0 +1,250 @@
+//! Prompt of the REPL configured by `\set prompt` or `shell.prompt` in
+//! `cli.toml`
+//!
+//! Placeholders are written in braces: `{database}`, `{instance}`, `{user}`,
+//! `{tx}` (transaction state marker), `{status}` (marker of the failed last
+//! command), `{ro}` (marker of the read-only session) and colors: `{red}`,
+//! `{green}`, `{yellow}`, `{blue}`, `{magenta}`, `{cyan}`, `{gray}`,
+//! `{bold}`, `{reset}`. Use `{{` and `}}` for literal braces.
+
+use std::fmt;
+use std::str::FromStr;
+
+use edgedb_protocol::server_message::TransactionState;
+
+use crate::read_only;
+use crate::repl::{TX_MARKER, FAILURE_MARKER};
+
+
+pub const DEFAULT: &str = "{database}{ro}{tx}> ";
+/// Default prompt after switching to another instance with `\connect`
+pub const SWITCHED_INSTANCE: &str = "{instance}:{database}{ro}{tx}> ";
+const STATUS_MARKER: &str = "[error]";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone)]
+enum Item {
+    Text(String),
+    Instance,
+    Database,
+    User,
+    Transaction,
+    Status,
+    ReadOnly,
+    Color(&'static str),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    items: Vec<Item>,
+}
+
+/// Values substituted into the template
+pub struct Values<'a> {
+    pub instance: Option<&'a str>,
+    pub database: &'a str,
+    pub user: &'a str,
+    pub transaction: Option<TransactionState>,
+    pub failed: bool,
+    pub read_only: bool,
+}
+
+fn placeholder(name: &str) -> Option<Item> {
+    use Item::*;
+    let item = match name {
+        "instance" => Instance,
+        "database" => Database,
+        "user" => User,
+        "tx" => Transaction,
+        "status" => Status,
+        "ro" => ReadOnly,
+        "red" => Color("\x1b[31m"),
+        "green" => Color("\x1b[32m"),
+        "yellow" => Color("\x1b[33m"),
+        "blue" => Color("\x1b[34m"),
+        "magenta" => Color("\x1b[35m"),
+        "cyan" => Color("\x1b[36m"),
+        "gray" => Color("\x1b[90m"),
+        "bold" => Color("\x1b[1m"),
+        "reset" => Color(RESET),
+        _ => return None,
+    };
+    Some(item)
+}
+
+fn push_text(items: &mut Vec<Item>, c: char) {
+    if let Some(Item::Text(text)) = items.last_mut() {
+        text.push(c);
+    } else {
+        items.push(Item::Text(c.to_string()));
+    }
+}
+
+/// Validator of the template for command-line and `\set` arguments
+pub fn validate(value: &str) -> Result<(), String> {
+    value.parse::<Template>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+    fn from_str(source: &str) -> anyhow::Result<Template> {
+        let mut items = Vec::new();
+        let mut iter = source.chars();
+        while let Some(c) = iter.next() {
+            match c {
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match iter.next() {
+                            Some('{') if name.is_empty() => {
+                                push_text(&mut items, '{');
+                                break;
+                            }
+                            Some('}') => {
+                                items.push(placeholder(&name)
+                                    .ok_or_else(|| anyhow::anyhow!(
+                                        "unknown placeholder `{{{}}}`",
+                                        name))?);
+                                break;
+                            }
+                            Some(c) => name.push(c),
+                            None => anyhow::bail!("unclosed `{{`"),
+                        }
+                    }
+                }
+                '}' => {
+                    if iter.next() != Some('}') {
+                        anyhow::bail!("single `}}` must be written as `}}}}`");
+                    }
+                    push_text(&mut items, '}');
+                }
+                c => push_text(&mut items, c),
+            }
+        }
+        Ok(Template { source: source.into(), items })
+    }
+}
+
+impl Default for Template {
+    fn default() -> Template {
+        DEFAULT.parse().expect("default prompt is valid")
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl Template {
+    /// Renders the prompt, with colors or as plain text
+    ///
+    /// Both variants have the same width on the screen, so plain text can
+    /// be used to calculate layout and the colored one to display.
+    pub fn render(&self, values: &Values, colors: bool) -> String {
+        use Item::*;
+        use TransactionState::*;
+
+        let mut buf = String::new();
+        let mut colored = false;
+        let marker = |buf: &mut String, text: &str, color: &str| {
+            if colors {
+                buf.push_str(color);
+                buf.push_str(text);
+                buf.push_str(RESET);
+            } else {
+                buf.push_str(text);
+            }
+        };
+        for item in &self.items {
+            match item {
+                Text(text) => buf.push_str(text),
+                Instance => buf.push_str(values.instance.unwrap_or("")),
+                Database => buf.push_str(values.database),
+                User => buf.push_str(values.user),
+                Transaction => match &values.transaction {
+                    Some(InTransaction) => {
+                        marker(&mut buf, TX_MARKER, "\x1b[32m");
+                    }
+                    Some(InFailedTransaction) => {
+                        marker(&mut buf, FAILURE_MARKER, "\x1b[31m");
+                    }
+                    Some(NotInTransaction) | None => {}
+                },
+                Status => if values.failed {
+                    marker(&mut buf, STATUS_MARKER, "\x1b[31m");
+                },
+                ReadOnly => if values.read_only {
+                    marker(&mut buf, read_only::MARKER, "\x1b[33m");
+                },
+                Color(code) => if colors {
+                    buf.push_str(code);
+                    colored = true;
+                },
+            }
+        }
+        if colored {
+            buf.push_str(RESET);
+        }
+        return buf;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use edgedb_protocol::server_message::TransactionState;
+
+    use super::{Template, Values};
+
+    fn values(transaction: Option<TransactionState>, failed: bool)
+        -> Values<'static>
+    {
+        Values {
+            instance: Some("inst"),
+            database: "edgedb",
+            user: "admin",
+            transaction,
+            failed,
+            read_only: false,
+        }
+    }
+
+    #[test]
+    fn default() {
+        let tpl = Template::default();
+        assert_eq!(tpl.render(&values(None, false), false), "edgedb> ");
+        assert_eq!(
+            tpl.render(
+                &values(Some(TransactionState::InTransaction), false), false),
+            "edgedb[tx]> ");
+        assert_eq!(
+            tpl.render(
+                &values(Some(TransactionState::InTransaction), false), true),
+            "edgedb\x1b[32m[tx]\x1b[0m> ");
+        let read_only = Values { read_only: true, ..values(None, false) };
+        assert_eq!(tpl.render(&read_only, false), "edgedb[ro]> ");
+    }
+
+    #[test]
+    fn placeholders() {
+        let tpl: Template = "{{{user}@{instance}/{database}}}{status} "
+            .parse().unwrap();
+        assert_eq!(tpl.render(&values(None, false), false),
+                   "{admin@inst/edgedb} ");
+        assert_eq!(tpl.render(&values(None, true), false),
+                   "{admin@inst/edgedb}[error] ");
+        let tpl: Template = "{bold}{database}{reset}> ".parse().unwrap();
+        assert_eq!(tpl.render(&values(None, false), false), "edgedb> ");
+        assert_eq!(tpl.render(&values(None, false), true),
+                   "\x1b[1medgedb\x1b[0m> \x1b[0m");
+    }
+
+    #[test]
+    fn errors() {
+        assert!("{databse}> ".parse::<Template>().is_err());
+        assert!("{database> ".parse::<Template>().is_err());
+        assert!("db}> ".parse::<Template>().is_err());
+    }
+}
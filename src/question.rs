@@ -5,6 +5,8 @@ use rustyline::{Editor, Config};
 
 use anyhow::Context;
 
+use crate::i18n::{tr, tr_format};
+
 
 pub struct Numeric<'a, T: Clone + 'a> {
     question: Cow<'a, str>,
@@ -29,7 +31,7 @@ pub fn read_choice() -> anyhow::Result<std::string::String> {
         let line = line.context("reading user input")?;
         return Ok(line.trim().to_lowercase())
     }
-    anyhow::bail!("Unexpected end of input");
+    anyhow::bail!(tr("Unexpected end of input"));
 }
 
 impl<'a, T: Clone + 'a> Numeric<'a, T> {
@@ -37,7 +39,7 @@ impl<'a, T: Clone + 'a> Numeric<'a, T> {
         Numeric {
             question: question.into(),
             options: Vec::new(),
-            suffix: "Type a number to select an option:",
+            suffix: tr("Type a number to select an option:"),
         }
     }
     pub fn option<S: Into<Cow<'a, str>>>(&mut self, name: S, value: T)
@@ -61,13 +63,15 @@ impl<'a, T: Clone + 'a> Numeric<'a, T> {
             let choice = match value.parse::<u32>() {
                 Ok(choice) => choice,
                 Err(e) => {
-                    eprintln!("Error reading choice: {}", e);
-                    println!("Please enter a number");
+                    eprintln!("{}",
+                              tr_format("Error reading choice: {}", &[&e]));
+                    println!("{}", tr("Please enter a number"));
                     continue;
                 }
             };
             if choice == 0 || choice as usize > self.options.len() {
-                println!("Please specify a choice from the list above");
+                println!("{}",
+                         tr("Please specify a choice from the list above"));
                 continue;
             }
             return Ok(self.options[(choice-1) as usize].1.clone());
@@ -91,7 +95,8 @@ impl<'a> String<'a> {
         if self.default.is_empty() {
             println!("{}: ", self.question);
         } else {
-            println!("{} [default: {}]: ", self.question, self.default);
+            println!("{} [{}]: ", self.question,
+                     tr_format("default: {}", &[&self.default]));
         }
         let mut editor = Editor::<()>::with_config(Config::builder().build());
         let initial = self.initial.as_ref().map(|s| &s[..])
@@ -130,7 +135,7 @@ impl<'a> Confirm<'a> {
     pub fn ask(&self) -> anyhow::Result<bool> {
         let mut editor = Editor::<()>::with_config(Config::builder().build());
         if self.is_dangerous {
-            println!("{} (type `Yes`)", self.question);
+            println!("{} {}", self.question, tr("(type `Yes`)"));
         } else {
             println!("{} [{}]", self.question, match self.default {
                 None => "y/n",
@@ -159,7 +164,7 @@ impl<'a> Confirm<'a> {
                     }
                     _ => {
                         initial = val;
-                        eprintln!("Please answer Y or N");
+                        eprintln!("{}", tr("Please answer Y or N"));
                         continue;
                     }
                 }
@@ -0,0 +1,98 @@
+//! Read-only sessions (`--read-only`)
+//!
+//! Each statement is executed in a read-only transaction, so the server
+//! rejects any modification of data or schema. Transactions started
+//! explicitly must be read-only as well.
+
+use edgedb_client::client::Connection;
+use edgedb_protocol::server_message::TransactionState;
+
+use crate::hint::HintExt;
+use crate::restrict::{self, Capability};
+
+pub const START: &str = "START TRANSACTION READ ONLY";
+/// Marker shown in the prompt
+pub const MARKER: &str = "[ro]";
+
+
+/// Checks the statement before executing it in the read-only session
+pub fn check(statement: &str) -> anyhow::Result<()> {
+    let words = restrict::words(statement);
+    let is_start = words.len() >= 2
+        && words[0] == "start" && words[1] == "transaction";
+    let read_only = words.windows(2)
+        .any(|w| w[0] == "read" && w[1] == "only");
+    if is_start && !read_only {
+        return Err(anyhow::anyhow!("session is read-only"))
+            .hint("use `START TRANSACTION READ ONLY`")
+            .map_err(Into::into);
+    }
+    Ok(())
+}
+
+/// Starts read-only transaction for the statement, unless the statement
+/// controls transactions itself or there is a transaction already
+///
+/// Returns true if the transaction should be finished with `finish()`.
+pub async fn begin(conn: &mut Connection, statement: &str)
+    -> anyhow::Result<bool>
+{
+    if restrict::classify(statement) == Some(Capability::Transaction)
+        || !matches!(conn.transaction_state(),
+                     TransactionState::NotInTransaction)
+    {
+        return Ok(false);
+    }
+    conn.execute(START).await?;
+    Ok(true)
+}
+
+/// Finishes the transaction started by `begin()` after the statement
+/// returned `result`
+///
+/// Error of the statement is returned in preference to the error of
+/// finishing the transaction, the latter is attached as a context.
+pub async fn finish<T>(conn: &mut Connection, started: bool,
+    result: anyhow::Result<T>)
+    -> anyhow::Result<T>
+{
+    match (result, end(conn, started).await) {
+        (Ok(value), Ok(())) => Ok(value),
+        (Ok(_), Err(e)) => Err(e),
+        (Err(e), Ok(())) => Err(e),
+        (Err(e), Err(end_err)) => Err(e.context(format!(
+            "cannot finish read-only transaction: {:#}", end_err))),
+    }
+}
+
+async fn end(conn: &mut Connection, started: bool) -> anyhow::Result<()> {
+    if !started || !conn.is_consistent() {
+        // inconsistent connection is dropped along with the transaction
+        return Ok(());
+    }
+    match conn.transaction_state() {
+        TransactionState::InTransaction => {
+            conn.execute("COMMIT").await?;
+        }
+        TransactionState::InFailedTransaction => {
+            conn.execute("ROLLBACK").await?;
+        }
+        TransactionState::NotInTransaction => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::check;
+
+    #[test]
+    fn start_transaction() {
+        assert!(check("SELECT User").is_ok());
+        assert!(check("START TRANSACTION READ ONLY").is_ok());
+        assert!(check("start transaction isolation serializable,\n\
+                       read only;").is_ok());
+        assert!(check("START TRANSACTION").is_err());
+        assert!(check("start  transaction read write").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::time::Duration;
 
 use anyhow::Context;
@@ -8,9 +9,16 @@ use edgedb_client::client::Connection;
 use edgedb_protocol::server_message::TransactionState;
 
 use crate::async_util::timeout;
-use crate::connect::Connector;
+use crate::commands::get_databases;
+use crate::connect::{Connector, set_global_command, reset_global_command};
+use crate::credentials;
+use crate::i18n::tr;
 use crate::prompt;
+use crate::prompt_template::{self, Template};
+use crate::schema_cache::SchemaCache;
+use crate::slowlog;
 use crate::print;
+use crate::tee;
 
 
 pub const TX_MARKER: &str = "[tx]";
@@ -48,6 +56,8 @@ pub struct State {
     pub prompt: PromptRpc,
     pub print: print::Config,
     pub verbose_errors: bool,
+    /// Print number of rows and time after each query
+    pub print_summary: bool,
     pub last_error: Option<anyhow::Error>,
     pub implicit_limit: Option<usize>,
     pub input_mode: InputMode,
@@ -56,14 +66,27 @@ pub struct State {
     pub history_limit: usize,
     pub conn_params: Connector,
     pub database: String,
+    /// Instance connected to, if known
+    pub instance_name: Option<String>,
+    pub prompt_template: Template,
     pub connection: Option<Connection>,
     pub last_version: Option<String>,
+    /// Last query sent, used by `\save`
+    pub last_statement: Option<String>,
     pub initial_text: String,
+    /// Introspection results of the current connection
+    pub schema_cache: SchemaCache,
+    /// Statements running longer are written to the slow query log
+    pub slow_query_threshold: Option<Duration>,
+    /// Statements are executed in read-only transactions (`--read-only`)
+    pub read_only: bool,
+    /// Parameters read with input hidden (`--sensitive`)
+    pub sensitive: Vec<String>,
 }
 
 impl PromptRpc {
     pub async fn variable_input(&mut self,
-        name: &str, type_name: &str, initial: &str)
+        name: &str, type_name: &str, initial: &str, sensitive: bool)
         -> anyhow::Result<prompt::Input>
     {
         self.control.send(
@@ -71,6 +94,7 @@ impl PromptRpc {
                     name: name.to_owned(),
                     type_name: type_name.to_owned(),
                     initial: initial.to_owned(),
+                    sensitive,
                 }
             ).await
             .context("cannot send to input thread")?;
@@ -93,13 +117,33 @@ impl State {
             self.last_version = Some(fetched_version);
         }
         self.database = self.conn_params.get()?.get_database().into();
+        self.update_database_names(&mut conn).await;
+        self.schema_cache.clear();
         self.connection = Some(conn);
         Ok(())
     }
-    pub async fn try_connect(&mut self, database: &str) -> anyhow::Result<()> {
-        let mut params = self.conn_params.clone();
-        params.modify(|p| { p.database(database); });
+    pub async fn try_connect(&mut self,
+        database: Option<&str>, instance: Option<&str>)
+        -> anyhow::Result<()>
+    {
+        let (mut params, instance) = match instance {
+            Some(name) if Some(name) != self.instance_name.as_deref() => {
+                let name = credentials::resolve_name(name)?;
+                let params = credentials::get_connector(&name)?;
+                (Connector::new(Ok(params)), Some(name))
+            }
+            _ => (self.conn_params.clone(), self.instance_name.clone()),
+        };
+        if let Some(database) = database {
+            params.modify(|p| { p.database(database); });
+        }
+        // globals are defined by the schema, so aren't carried over
+        let globals_reset = !params.globals().is_empty();
+        params.clear_globals();
         let mut conn = params.connect().await?;
+        if globals_reset {
+            eprintln!("WARNING: Globals are reset");
+        }
         let fetched_version = conn.get_version().await?;
         if self.last_version.as_ref() != Some(&fetched_version) {
             println!("{} {} (repl v{})",
@@ -108,11 +152,34 @@ impl State {
                 env!("CARGO_PKG_VERSION"));
             self.last_version = Some(fetched_version);
         }
+        self.database = params.get()?.get_database().into();
         self.conn_params = params;
-        self.database = database.into();
+        if instance != self.instance_name &&
+            self.prompt_template.to_string() == prompt_template::DEFAULT
+        {
+            // make it visible that this is not the original instance
+            self.prompt_template = prompt_template::SWITCHED_INSTANCE
+                .parse().expect("valid template");
+        }
+        self.instance_name = instance;
+        self.update_database_names(&mut conn).await;
+        self.schema_cache.clear();
         self.connection = Some(conn);
         Ok(())
     }
+    /// Sends names of the databases to the input thread for completion
+    pub async fn update_database_names(&mut self, conn: &mut Connection) {
+        let names = match get_databases(conn).await {
+            Ok(names) => names,
+            Err(e) => {
+                log::warn!("Cannot fetch database names: {:#}", e);
+                return;
+            }
+        };
+        self.prompt.control.send(prompt::Control::DatabaseNames(names)).await
+            .map_err(|e| log::warn!("Cannot send to input thread: {}", e))
+            .ok();
+    }
     pub async fn soft_reconnect(&mut self) -> anyhow::Result<()> {
         if !self.in_transaction() {
             self.ensure_connection().await?;
@@ -123,7 +190,9 @@ impl State {
         match &self.connection {
             Some(c) if c.is_consistent() => {}
             Some(_) => {
-                eprintln!("Reconnecting...");
+                log::info!("Connection is in inconsistent state, \
+                    reconnecting");
+                eprintln!("{}", tr("Reconnecting..."));
                 self.reconnect().await?;
             }
             None => {
@@ -132,6 +201,41 @@ impl State {
         };
         Ok(())
     }
+    /// Sets the global in the current session, or resets it if `value` is
+    /// `None`. Globals set are restored on reconnect.
+    pub async fn set_global(&mut self, name: &str, value: Option<&str>)
+        -> anyhow::Result<()>
+    {
+        if self.in_transaction() {
+            anyhow::bail!("globals cannot be changed in a transaction");
+        }
+        self.ensure_connection().await?;
+        let command = match value {
+            Some(value) => set_global_command(name, value)?,
+            None => reset_global_command(name),
+        };
+        self.connection.as_mut().expect("connection established")
+            .execute(&command).await?;
+        match value {
+            Some(value) => self.conn_params.set_global(name, value),
+            None => self.conn_params.reset_global(name),
+        };
+        Ok(())
+    }
+    /// Records the statement if it took longer than the threshold
+    pub fn log_slow_query(&self, statement: &str, elapsed: Duration) {
+        match self.slow_query_threshold {
+            Some(threshold) if elapsed >= threshold => {}
+            _ => return,
+        }
+        let entry = slowlog::Entry::new(statement, elapsed,
+            self.instance_name.as_deref(), &self.database);
+        if let Err(e) = slowlog::path(None)
+            .and_then(|path| slowlog::append(&path, &entry))
+        {
+            log::warn!("{:#}", e);
+        }
+    }
     pub async fn terminate(&mut self) {
         if let Some(conn) = self.connection.take() {
             if conn.is_consistent() {
@@ -141,23 +245,40 @@ impl State {
             }
         }
     }
+    /// Cancels the interrupted query and connects again
+    ///
+    /// Protocol has no way to cancel a query in the same connection, so
+    /// connection is terminated (server cancels queries of the closed
+    /// connections), even if it's in the middle of the query.
+    pub async fn cancel_query(&mut self) -> anyhow::Result<()> {
+        if let Some(conn) = self.connection.take() {
+            timeout(Duration::from_secs(1), conn.terminate()).await
+                .map_err(|e| log::debug!("Termination error: {:#}", e))
+                .ok();
+        }
+        self.reconnect().await
+    }
     pub async fn edgeql_input(&mut self, initial: &str)
         -> anyhow::Result<prompt::Input>
     {
-        use TransactionState::*;
-
-        let prompt = format!("{}{}> ",
-            self.database,
-            match self.connection.as_ref().map(|c| c.transaction_state()) {
-                Some(NotInTransaction) => "",
-                Some(InTransaction) => TX_MARKER,
-                Some(InFailedTransaction) => FAILURE_MARKER,
-                None => "",
-            }
-        );
+        let user = self.conn_params.get()
+            .map(|p| p.get_user().to_string())
+            .unwrap_or_default();
+        let values = prompt_template::Values {
+            instance: self.instance_name.as_deref(),
+            database: &self.database,
+            user: &user,
+            transaction: self.connection.as_ref()
+                .map(|c| c.transaction_state()),
+            failed: self.last_error.is_some(),
+            read_only: self.read_only,
+        };
+        let prompt = self.prompt_template.render(&values, false);
+        let highlighted = self.prompt_template.render(&values, true);
         self.prompt.control.send(
                 prompt::Control::EdgeqlInput {
-                    prompt,
+                    prompt: prompt.clone(),
+                    highlighted,
                     initial: initial.to_owned(),
                 }
             ).await
@@ -169,6 +290,10 @@ impl State {
         };
         match result {
             Err(RecvError) | Ok(prompt::Input::Eof) => Ok(prompt::Input::Eof),
+            Ok(prompt::Input::Text(text)) => {
+                tee::write(&format!("{}{}\n", prompt, text));
+                Ok(prompt::Input::Text(text))
+            }
             Ok(x) => Ok(x),
         }
     }
@@ -196,6 +321,16 @@ impl State {
             Ok(x) => Ok(x),
         }
     }
+    pub async fn edit_file(&self, path: PathBuf)
+        -> anyhow::Result<prompt::Input>
+    {
+        self.prompt.control.send(prompt::Control::EditFile { path }).await
+            .context("cannot send to input thread")?;
+        match self.prompt.data.recv().await {
+            Err(RecvError) | Ok(prompt::Input::Eof) => Ok(prompt::Input::Eof),
+            Ok(x) => Ok(x),
+        }
+    }
     pub async fn set_history_limit(&mut self, val: usize)
         -> anyhow::Result<()>
     {
@@ -203,6 +338,11 @@ impl State {
         self.prompt.control.send(prompt::Control::SetHistoryLimit(val)).await
             .context("cannot send to input thread")
     }
+    /// Returns the connection, which must be established by
+    /// `ensure_connection()` or `soft_reconnect()`
+    pub fn connection_mut(&mut self) -> &mut Connection {
+        self.connection.as_mut().expect("connection is established")
+    }
     pub fn in_transaction(&self) -> bool {
         match &self.connection {
             Some(conn) => {
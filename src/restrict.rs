@@ -0,0 +1,157 @@
+//! Restriction of statements allowed to execute (`--no-ddl`, `--no-config`,
+//! `--no-transactions`)
+//!
+//! Mirrors capabilities of the protocol: statements are classified by their
+//! leading keywords on the client, so forbidden statement is rejected
+//! before it's sent to the server. Transactions started by the tool itself
+//! (`--single-transaction`, `--read-only`) are not restricted.
+
+use std::fmt;
+
+use edgeql_parser::tokenizer::{TokenStream, Kind};
+
+use crate::hint::HintExt;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Schema modifications including migrations
+    Ddl,
+    /// `CONFIGURE`, and `SET`/`RESET` of aliases and modules
+    Config,
+    /// `START TRANSACTION`, `COMMIT`, `ROLLBACK` and savepoints
+    Transaction,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Restrictions {
+    pub no_ddl: bool,
+    pub no_config: bool,
+    pub no_transactions: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{capability} is not allowed in this session")]
+pub struct Forbidden {
+    capability: Capability,
+}
+
+impl Capability {
+    fn option(&self) -> &'static str {
+        use Capability::*;
+        match self {
+            Ddl => "--no-ddl",
+            Config => "--no-config",
+            Transaction => "--no-transactions",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Capability::*;
+        let title = match self {
+            Ddl => "DDL",
+            Config => "configuration",
+            Transaction => "transaction control",
+        };
+        f.write_str(title)
+    }
+}
+
+impl Restrictions {
+    pub fn is_empty(&self) -> bool {
+        !self.no_ddl && !self.no_config && !self.no_transactions
+    }
+    fn forbids(&self, capability: Capability) -> bool {
+        use Capability::*;
+        match capability {
+            Ddl => self.no_ddl,
+            Config => self.no_config,
+            Transaction => self.no_transactions,
+        }
+    }
+    /// Returns an error if the statement requires a forbidden capability
+    pub fn check(&self, statement: &str) -> anyhow::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        match classify(statement) {
+            Some(capability) if self.forbids(capability) => {
+                Err(anyhow::Error::from(Forbidden { capability }))
+                    .with_hint(|| format!(
+                        "statement is rejected because of `{}`",
+                        capability.option()))
+                    .map_err(Into::into)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Lowercase keywords and names of the statement (literals are skipped)
+pub fn words(statement: &str) -> Vec<String> {
+    TokenStream::new(statement)
+        .take_while(|token| token.is_ok())
+        .filter_map(|token| token.ok())
+        .filter(|token| matches!(token.token.kind,
+                                 Kind::Keyword | Kind::Ident))
+        .map(|token| token.token.value.to_lowercase())
+        .collect()
+}
+
+/// Returns capability required by the statement (if any besides querying
+/// and modifying data)
+pub fn classify(statement: &str) -> Option<Capability> {
+    use Capability::*;
+
+    let words = words(statement);
+    let second = words.get(1).map(|w| &w[..]);
+    match words.first().map(|w| &w[..])? {
+        "create" | "alter" | "drop" => Some(Ddl),
+        "start" | "commit" if second == Some("migration") => Some(Ddl),
+        "populate" | "abort" if second == Some("migration") => Some(Ddl),
+        "reset" if second == Some("schema") => Some(Ddl),
+        "start" if second == Some("transaction") => Some(Transaction),
+        "commit" | "rollback" | "declare" | "release" => Some(Transaction),
+        "configure" | "set" | "reset" => Some(Config),
+        // DDL can be prefixed by `WITH MODULE`
+        "with" => words.iter().find_map(|w| match &w[..] {
+            "create" | "alter" | "drop" => Some(Ddl),
+            "configure" => Some(Config),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{classify, Capability::*, Restrictions};
+
+    #[test]
+    fn statements() {
+        assert_eq!(classify("SELECT User { name }"), None);
+        assert_eq!(classify("insert User { name := 'create' }"), None);
+        assert_eq!(classify("CREATE TYPE User"), Some(Ddl));
+        assert_eq!(classify("with module x drop type User;"), Some(Ddl));
+        assert_eq!(classify("START MIGRATION TO {}"), Some(Ddl));
+        assert_eq!(classify("COMMIT MIGRATION"), Some(Ddl));
+        assert_eq!(classify("RESET SCHEMA TO initial"), Some(Ddl));
+        assert_eq!(classify("CONFIGURE INSTANCE SET x := 1"), Some(Config));
+        assert_eq!(classify("SET MODULE default"), Some(Config));
+        assert_eq!(classify("reset alias *"), Some(Config));
+        assert_eq!(classify("START TRANSACTION"), Some(Transaction));
+        assert_eq!(classify("commit"), Some(Transaction));
+        assert_eq!(classify("DECLARE SAVEPOINT sp"), Some(Transaction));
+        assert_eq!(classify(""), None);
+    }
+
+    #[test]
+    fn check() {
+        let restrict = Restrictions { no_ddl: true, ..Default::default() };
+        assert!(restrict.check("CREATE TYPE User").is_err());
+        assert!(restrict.check("CONFIGURE SESSION SET x := 1").is_ok());
+        assert!(Restrictions::default().check("DROP TYPE User").is_ok());
+    }
+}
@@ -0,0 +1,232 @@
+//! Comparison of query results with the JSON output saved by a previous
+//! run, see `query --diff-against` and `\diff` of the REPL
+//!
+//! Objects are matched by `id`, other values are compared as a whole, so
+//! they can only be added or removed. Equal values are matched one to one,
+//! so duplicates are reported if their number changes.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use async_std::prelude::StreamExt;
+use edgedb_client::client::Connection;
+use edgedb_protocol::value::Value;
+use edgeql_parser::tokenizer::{TokenStream, Kind};
+use linked_hash_map::LinkedHashMap;
+use serde_json::Value as Json;
+
+use crate::retry;
+
+
+#[derive(Debug, PartialEq)]
+pub struct Changed {
+    pub id: String,
+    /// Field name, old value, new value (`null` if field is missing)
+    pub fields: Vec<(String, Json, Json)>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub added: Vec<Json>,
+    pub removed: Vec<Json>,
+    pub changed: Vec<Changed>,
+}
+
+/// Reads output of `--output-format=json` or `json-elements`
+pub fn read_saved(path: &Path) -> anyhow::Result<Vec<Json>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    let mut values = serde_json::Deserializer::from_str(&text)
+        .into_iter::<Json>()
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("cannot parse {}", path.display()))?;
+    if let [Json::Array(items)] = &mut values[..] {
+        return Ok(std::mem::take(items));
+    }
+    Ok(values)
+}
+
+fn key(item: &Json) -> String {
+    match item.get("id").and_then(|id| id.as_str()) {
+        Some(id) => id.to_string(),
+        None => item.to_string(),
+    }
+}
+
+fn changed_fields(old: &Json, new: &Json) -> Vec<(String, Json, Json)> {
+    let (old, new) = match (old.as_object(), new.as_object()) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return vec![(String::new(), old.clone(), new.clone())],
+    };
+    let mut result = Vec::new();
+    for (name, old_value) in old {
+        let new_value = new.get(name).unwrap_or(&Json::Null);
+        if old_value != new_value {
+            result.push((name.clone(), old_value.clone(), new_value.clone()));
+        }
+    }
+    for (name, new_value) in new {
+        if !old.contains_key(name) && !new_value.is_null() {
+            result.push((name.clone(), Json::Null, new_value.clone()));
+        }
+    }
+    result
+}
+
+pub fn compare(old: &[Json], new: &[Json]) -> Changes {
+    // equal items are kept in the order of the saved result
+    let mut old_items = LinkedHashMap::<_, Vec<&Json>>::new();
+    for item in old {
+        old_items.entry(key(item)).or_insert_with(Vec::new).push(item);
+    }
+    let mut changes = Changes::default();
+    for item in new {
+        let id = key(item);
+        let old_item = match old_items.get_mut(&id) {
+            Some(items) => {
+                let old_item = items.remove(0);
+                if items.is_empty() {
+                    old_items.remove(&id);
+                }
+                Some(old_item)
+            }
+            None => None,
+        };
+        match old_item {
+            Some(old_item) if old_item == item => {}
+            Some(old_item) => changes.changed.push(Changed {
+                fields: changed_fields(old_item, item),
+                id,
+            }),
+            None => changes.added.push(item.clone()),
+        }
+    }
+    changes.removed = old_items.into_iter()
+        .flat_map(|(_, items)| items)
+        .cloned()
+        .collect();
+    changes
+}
+
+/// Returns `true` if the query has parameters, e.g. `<str>$name`
+pub fn has_parameters(stmt: &str) -> bool {
+    TokenStream::new(stmt)
+        .take_while(|token| token.is_ok())
+        .filter_map(|token| token.ok())
+        .any(|token| matches!(token.token.kind, Kind::Argument))
+}
+
+/// Runs the query and compares its result with the one saved in `path`
+///
+/// Only read-only queries are accepted, because the REPL runs the last
+/// query once again for comparison.
+pub async fn diff(conn: &mut Connection, stmt: &str, arguments: &Value,
+    path: &Path)
+    -> anyhow::Result<Changes>
+{
+    if !retry::is_read_only(stmt) {
+        anyhow::bail!("only SELECT queries can be compared \
+            with the saved result");
+    }
+    let saved = read_saved(path)?;
+    let mut items = conn.query_json(stmt, arguments).await?;
+    let mut result = Vec::new();
+    while let Some(row) = items.next().await.transpose()? {
+        let row: Json = serde_json::from_str(&row)
+            .context("cannot decode json result")?;
+        match row {
+            Json::Array(row) => result.extend(row),
+            _ => anyhow::bail!("non-array returned from postgres \
+                in JSON mode"),
+        }
+    }
+    Ok(compare(&saved, &result))
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.added {
+            writeln!(f, "+ {}", item)?;
+        }
+        for item in &self.removed {
+            writeln!(f, "- {}", item)?;
+        }
+        for item in &self.changed {
+            writeln!(f, "~ {}", item.id)?;
+            for (name, old, new) in &item.fields {
+                writeln!(f, "    {}: {} -> {}", name, old, new)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{compare, has_parameters, Changed};
+
+    #[test]
+    fn objects() {
+        let old = vec![
+            json!({"id": "1", "name": "alice", "age": 30}),
+            json!({"id": "2", "name": "bob"}),
+            json!({"id": "3", "name": "carol"}),
+        ];
+        let new = vec![
+            json!({"id": "3", "name": "carol"}),
+            json!({"id": "1", "name": "alice", "age": 31, "email": "a@x"}),
+            json!({"id": "4", "name": "dave"}),
+        ];
+        let changes = compare(&old, &new);
+        assert_eq!(changes.added, vec![json!({"id": "4", "name": "dave"})]);
+        assert_eq!(changes.removed, vec![json!({"id": "2", "name": "bob"})]);
+        assert_eq!(changes.changed, vec![Changed {
+            id: "1".into(),
+            fields: vec![
+                ("age".into(), json!(30), json!(31)),
+                ("email".into(), json!(null), json!("a@x")),
+            ],
+        }]);
+        assert_eq!(changes.to_string(), "\
+            + {\"id\":\"4\",\"name\":\"dave\"}\n\
+            - {\"id\":\"2\",\"name\":\"bob\"}\n\
+            ~ 1\n    \
+                age: 30 -> 31\n    \
+                email: null -> \"a@x\"\n");
+    }
+
+    #[test]
+    fn scalars() {
+        let changes = compare(&[json!(1), json!(2)], &[json!(2), json!(3)]);
+        assert_eq!(changes.added, vec![json!(3)]);
+        assert_eq!(changes.removed, vec![json!(1)]);
+        assert!(changes.changed.is_empty());
+        assert!(compare(&[json!("a")], &[json!("a")]).is_empty());
+    }
+
+    #[test]
+    fn duplicates() {
+        let changes = compare(&[json!(1), json!(1), json!(2)],
+                              &[json!(1), json!(2), json!(2)]);
+        assert_eq!(changes.added, vec![json!(2)]);
+        assert_eq!(changes.removed, vec![json!(1)]);
+        assert!(compare(&[json!(1), json!(1)], &[json!(1), json!(1)])
+                .is_empty());
+    }
+
+    #[test]
+    fn parameters() {
+        assert!(has_parameters("SELECT <str>$name"));
+        assert!(!has_parameters("SELECT '$name'"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Retrying of queries failed because of transient errors
+//!
+//! Follows the rules of the client libraries: transaction conflicts
+//! (serialization failures and deadlocks) are retried for any query, while
+//! network errors are retried only for read-only queries, because the
+//! query might have been already executed by the server.
+use std::time::Duration;
+
+use edgedb_client::reader::ReadError;
+use edgedb_protocol::error_response::ErrorResponse;
+use edgeql_parser::tokenizer::{TokenStream, Kind};
+use rand::Rng;
+
+
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+const TRANSACTION_CONFLICT_ERROR: u32 = 0x_05_03_01_00;
+
+/// Failure to write query output locally, never retried
+#[derive(Debug, thiserror::Error)]
+#[error("cannot write output")]
+pub struct OutputError(#[source] pub std::io::Error);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// Number of retries after the first attempt, 0 disables retrying
+    pub attempts: u32,
+    /// Delay before the first retry, doubled on each next one
+    pub backoff: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            attempts: DEFAULT_ATTEMPTS,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+}
+
+impl Config {
+    /// Delay before the retry number `attempt` (starting from zero)
+    ///
+    /// Exponential growth stops at a minute, unless backoff itself is
+    /// larger.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.backoff
+            .checked_mul(2u32.saturating_pow(attempt.min(16)))
+            .map(|delay| delay.min(MAX_DELAY))
+            .unwrap_or(MAX_DELAY)
+            .max(self.backoff);
+        let jitter = rand::thread_rng()
+            .gen_range(0..=self.backoff.as_millis() as u64);
+        base.checked_add(Duration::from_millis(jitter)).unwrap_or(base)
+    }
+}
+
+/// Returns `true` if statement only reads data, so it's safe to execute it
+/// again even if it could have been already executed
+pub fn is_read_only(statement: &str) -> bool {
+    const MODIFYING: &[&str] = &["insert", "update", "delete"];
+    let mut first = true;
+    for token in TokenStream::new(statement) {
+        let token = match token {
+            Ok(token) => token.token,
+            Err(_) => return false,
+        };
+        if token.kind != Kind::Keyword {
+            if first {
+                return false;
+            }
+            continue;
+        }
+        let value = token.value.to_lowercase();
+        if first && value != "select" && value != "with" {
+            return false;
+        }
+        first = false;
+        if MODIFYING.contains(&&value[..]) {
+            return false;
+        }
+    }
+    !first
+}
+
+/// Returns `true` if the query failed because of a transient error
+pub fn is_retryable(err: &anyhow::Error, read_only: bool) -> bool {
+    if err.chain().any(|e| e.is::<OutputError>()) {
+        return false;
+    }
+    err.chain().any(|e| {
+        if let Some(e) = e.downcast_ref::<ErrorResponse>() {
+            e.code & 0xFFFF_FF00 == TRANSACTION_CONFLICT_ERROR
+        } else if let Some(e) = e.downcast_ref::<ReadError>() {
+            read_only && !matches!(e, ReadError::RequestError { .. })
+        } else {
+            read_only && e.is::<std::io::Error>()
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::time::Duration;
+    use super::{is_read_only, is_retryable, Config, OutputError};
+
+    #[test]
+    fn read_only() {
+        assert!(is_read_only("SELECT 1"));
+        assert!(is_read_only("select User { name } filter .id = <uuid>$0"));
+        assert!(is_read_only("WITH x := 1 SELECT x"));
+        assert!(!is_read_only("INSERT User { name := 'x' }"));
+        assert!(!is_read_only("WITH x := (INSERT User) SELECT x"));
+        assert!(!is_read_only("SELECT (DELETE User)"));
+        assert!(!is_read_only("CREATE TYPE Foo"));
+        assert!(!is_read_only(""));
+    }
+
+    #[test]
+    fn delay() {
+        let config = Config {
+            attempts: 3,
+            backoff: Duration::from_millis(10),
+        };
+        for attempt in 0..3 {
+            let delay = config.delay(attempt);
+            let base = Duration::from_millis(10 << attempt);
+            assert!(delay >= base);
+            assert!(delay <= base + Duration::from_millis(10));
+        }
+        assert!(config.delay(1000) <= Duration::from_millis(60_010));
+        let config = Config {
+            attempts: 3,
+            backoff: Duration::from_secs(u64::MAX / 2),
+        };
+        assert!(config.delay(10) >= config.backoff);
+    }
+
+    #[test]
+    fn output_errors() {
+        let err = anyhow::Error::new(
+            io::Error::from(io::ErrorKind::ConnectionReset));
+        assert!(is_retryable(&err, true));
+        assert!(!is_retryable(&err, false));
+        let err = anyhow::Error::new(
+            OutputError(io::Error::from(io::ErrorKind::BrokenPipe)));
+        assert!(!is_retryable(&err, true));
+    }
+}
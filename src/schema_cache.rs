@@ -0,0 +1,163 @@
+//! Cache of introspection queries run by the REPL commands
+//!
+//! Results are stored along with the version of the schema they were fetched
+//! for, which is the name of the last migration. The version is checked
+//! again at most every `RECHECK_INTERVAL`, so changes made by other clients
+//! are picked up soon, while DDL executed in this session drops the cache
+//! immediately.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_std::prelude::StreamExt;
+use edgedb_client::client::Connection;
+use edgedb_protocol::queryable::Queryable;
+use edgedb_protocol::value::Value;
+
+use crate::commands::Options;
+
+
+const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Status prefixes of the commands that may change the schema
+const DDL_STATUSES: &[&str] = &[
+    "CREATE", "ALTER", "DROP", "ROLLBACK", "COMMIT MIGRATION",
+    "POPULATE MIGRATION", "START MIGRATION", "ABORT MIGRATION",
+];
+
+/// Handle to the cache, clones refer to the same cache
+///
+/// Belongs to a single connection, as the schema is per database.
+#[derive(Clone, Default)]
+pub struct SchemaCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Last migration, `None` inside means there are no migrations
+    version: Option<Option<String>>,
+    checked: Option<Instant>,
+    entries: HashMap<String, Box<dyn Any + Send>>,
+}
+
+fn is_ddl_status(status: &[u8]) -> bool {
+    let status = String::from_utf8_lossy(status).to_uppercase();
+    DDL_STATUSES.iter().any(|prefix| status.starts_with(prefix))
+}
+
+impl SchemaCache {
+    pub fn new() -> SchemaCache {
+        SchemaCache::default()
+    }
+    /// Drops all the entries, e.g. after switching to another database
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().expect("cache is not poisoned");
+        inner.entries.clear();
+        inner.version = None;
+        inner.checked = None;
+    }
+    /// Invalidates the cache if the command with this completion `status`
+    /// could modify the schema
+    pub fn command_complete(&self, status: &[u8]) {
+        if is_ddl_status(status) {
+            log::debug!("Schema cache is reset after {:?}",
+                        String::from_utf8_lossy(status));
+            self.clear();
+        }
+    }
+    async fn check_version(&self, cli: &mut Connection)
+        -> anyhow::Result<()>
+    {
+        {
+            let inner = self.inner.lock().expect("cache is not poisoned");
+            if inner.checked.map(|t| t.elapsed() < RECHECK_INTERVAL)
+                .unwrap_or(false)
+            {
+                return Ok(());
+            }
+        }
+        let mut items = cli.query::<String>(r###"
+            SELECT (
+                SELECT schema::Migration
+                FILTER NOT EXISTS .<parents[IS schema::Migration]
+            ).name
+        "###, &Value::empty_tuple()).await?;
+        let version = items.next().await.transpose()?;
+        while items.next().await.transpose()?.is_some() {}
+        let mut inner = self.inner.lock().expect("cache is not poisoned");
+        if inner.version.as_ref() != Some(&version) {
+            inner.entries.clear();
+            inner.version = Some(version);
+        }
+        inner.checked = Some(Instant::now());
+        Ok(())
+    }
+    fn get<T: Clone + 'static>(&self, key: &str) -> Option<Vec<T>> {
+        let inner = self.inner.lock().expect("cache is not poisoned");
+        inner.entries.get(key)
+            .and_then(|rows| rows.downcast_ref::<Vec<T>>())
+            .cloned()
+    }
+    fn insert<T: Send + 'static>(&self, key: String, rows: Vec<T>) {
+        let mut inner = self.inner.lock().expect("cache is not poisoned");
+        inner.entries.insert(key, Box::new(rows));
+    }
+}
+
+/// Runs the introspection query, returning cached rows when the schema
+/// hasn't changed since they were fetched
+///
+/// Queries are not cached when run from the command line.
+pub async fn query<T>(cli: &mut Connection, options: &Options,
+    query: &str, arguments: &Value)
+    -> anyhow::Result<Vec<T>>
+    where T: Queryable + Clone + Send + 'static,
+{
+    let cache = options.schema_cache.as_ref();
+    let key = format!("{}\0{:?}", query, arguments);
+    if let Some(cache) = cache {
+        cache.check_version(cli).await?;
+        if let Some(rows) = cache.get::<T>(&key) {
+            return Ok(rows);
+        }
+    }
+    let mut items = cli.query::<T>(query, arguments).await?;
+    let mut rows = Vec::new();
+    while let Some(row) = items.next().await.transpose()? {
+        rows.push(row);
+    }
+    if let Some(cache) = cache {
+        cache.insert(key, rows.clone());
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_ddl_status, SchemaCache};
+
+    #[test]
+    fn ddl_statuses() {
+        assert!(is_ddl_status(b"CREATE TYPE"));
+        assert!(is_ddl_status(b"COMMIT MIGRATION"));
+        assert!(is_ddl_status(b"ROLLBACK TO SAVEPOINT"));
+        assert!(!is_ddl_status(b"SELECT"));
+        assert!(!is_ddl_status(b"COMMIT"));
+        assert!(!is_ddl_status(b"CONFIGURE SESSION"));
+    }
+
+    #[test]
+    fn invalidate() {
+        let cache = SchemaCache::new();
+        cache.insert("q".into(), vec![String::from("default")]);
+        assert_eq!(cache.get::<String>("q"), Some(vec!["default".into()]));
+        assert_eq!(cache.get::<i64>("q"), None);
+        cache.command_complete(b"SELECT");
+        assert!(cache.get::<String>("q").is_some());
+        cache.command_complete(b"DROP TYPE");
+        assert!(cache.get::<String>("q").is_none());
+    }
+}
@@ -0,0 +1,131 @@
+//! Storage of instance passwords in the OS keychain
+//!
+//! Passwords are stored under the `edgedb` service with the instance name
+//! (the stem of the credentials file) as the account. If the keychain is
+//! unavailable or disabled with `--no-keyring`, passwords are kept in the
+//! credentials file as before.
+//!
+//! The access token of EdgeDB Cloud is stored under the `edgedb-cloud`
+//! service, it's never written to files.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use keyring::{Keyring, KeyringError};
+
+const SERVICE: &str = "edgedb";
+const CLOUD_SERVICE: &str = "edgedb-cloud";
+const CLOUD_ACCOUNT: &str = "default";
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+
+pub fn disable() {
+    DISABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    !DISABLED.load(Ordering::SeqCst)
+}
+
+fn account(credentials_file: &Path) -> Option<String> {
+    credentials_file.file_stem().map(|s| s.to_string_lossy().into_owned())
+}
+
+fn get(service: &str, account: &str) -> Option<String> {
+    match Keyring::new(service, account).get_password() {
+        Ok(secret) => Some(secret),
+        Err(KeyringError::NoPasswordFound) => None,
+        Err(e) => {
+            // keyring is commonly unavailable (e.g. CI), so don't make
+            // noise unless it's going to be written to
+            log::debug!("Cannot read secret of {:?} from keyring: {}",
+                        account, e);
+            None
+        }
+    }
+}
+
+fn set(service: &str, account: &str, secret: &str) -> bool {
+    let keyring = Keyring::new(service, account);
+    if let Err(e) = keyring.set_password(secret) {
+        log::warn!("Cannot store secret of {:?} in keyring: {}",
+                   account, e);
+        return false;
+    }
+    // some backends accept passwords but lose them (e.g. no session
+    // daemon running), so don't trust the keyring until read back
+    match keyring.get_password() {
+        Ok(stored) if stored == secret => true,
+        _ => {
+            log::warn!("Secret of {:?} stored in keyring can't be read \
+                back", account);
+            false
+        }
+    }
+}
+
+fn delete(service: &str, account: &str) {
+    match Keyring::new(service, account).delete_password() {
+        Ok(()) | Err(KeyringError::NoPasswordFound) => {}
+        Err(e) => {
+            log::debug!("Cannot delete secret of {:?} from keyring: {}",
+                        account, e);
+        }
+    }
+}
+
+/// Returns password stored for the credentials file (if any)
+///
+/// Used only when the credentials file itself has no password, so
+/// passwords moved to the keyring are found even with `--no-keyring`.
+pub fn get_password(credentials_file: &Path) -> Option<String> {
+    get(SERVICE, &account(credentials_file)?)
+}
+
+/// Stores password in the keyring
+///
+/// Returns `false` if keyring is disabled or doesn't work, so the
+/// password must be stored in the credentials file itself.
+pub fn set_password(credentials_file: &Path, password: &str) -> bool {
+    if !enabled() {
+        return false;
+    }
+    match account(credentials_file) {
+        Some(account) => set(SERVICE, &account, password),
+        None => false,
+    }
+}
+
+/// Copies password stored in the keyring to the new credentials file name
+///
+/// Works even if keyring is disabled, as the password is already
+/// there. Returns `false` if the password could not be stored.
+pub fn copy_password(old_file: &Path, new_file: &Path, password: &str)
+    -> bool
+{
+    match (account(old_file), account(new_file)) {
+        (Some(_), Some(account)) => set(SERVICE, &account, password),
+        _ => false,
+    }
+}
+
+pub fn delete_password(credentials_file: &Path) {
+    if let Some(account) = account(credentials_file) {
+        delete(SERVICE, &account);
+    }
+}
+
+/// Returns access token of `edgedb cloud login` (if any)
+pub fn get_cloud_token() -> Option<String> {
+    get(CLOUD_SERVICE, CLOUD_ACCOUNT)
+}
+
+/// Stores the cloud access token, returns `false` if keyring doesn't work
+pub fn set_cloud_token(token: &str) -> bool {
+    set(CLOUD_SERVICE, CLOUD_ACCOUNT, token)
+}
+
+pub fn delete_cloud_token() {
+    delete(CLOUD_SERVICE, CLOUD_ACCOUNT)
+}
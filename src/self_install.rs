@@ -3,26 +3,35 @@
 
 use std::env;
 use std::fs;
-use std::io::{Write, stdout, BufWriter};
+use std::io::{self, Write, stdout, BufWriter};
 use std::path::{PathBuf, Path};
 use std::process::{Command, exit};
 use std::str::FromStr;
 
 use anyhow::Context;
-use clap::{Clap, IntoApp};
+use clap::{Clap, IntoApp, ValueHint};
 use clap_generate::{generate, generators};
 use fn_error_context::context;
 use prettytable::{Table, Row, Cell};
 
+use crate::i18n::{tr, tr_format};
+use crate::info;
+use crate::man;
 use crate::options::RawOptions;
 use crate::platform::{home_dir, get_current_uid};
 use crate::process;
 use crate::project::init;
 use crate::project::options::Init;
 use crate::question::{self, read_choice};
+use crate::server::options::StartConf;
+use crate::shell_completion;
 use crate::table;
 
 
+const BLOCK_START: &str = "# >>> edgedb initialize >>>";
+const BLOCK_END: &str = "# <<< edgedb initialize <<<";
+
+
 #[derive(Clap, Clone, Debug)]
 pub struct SelfInstall {
     /// Install nightly version of command-line tools
@@ -47,6 +56,21 @@ pub struct SelfInstall {
     /// in a new window.
     #[clap(long)]
     pub no_wait_for_exit_prompt: bool,
+    /// Install for all users: the binary is put into `PREFIX/bin` and
+    /// completions into `PREFIX/share`, shell profiles are not modified.
+    /// Usually requires running as root
+    #[clap(long)]
+    pub system: bool,
+    /// Installation prefix for `--system` installs
+    #[clap(long, requires="system", value_hint=ValueHint::DirPath)]
+    pub prefix: Option<PathBuf>,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub struct SelfUninstall {
+    /// Do not ask for a confirmation
+    #[clap(short='y', long)]
+    pub no_confirm: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -66,7 +90,7 @@ pub struct GenCompletions {
     ])]
     pub shell: Option<Shell>,
 
-    /// Install all completions into the prefix
+    /// Install all completions (and manual pages) into the prefix
     #[clap(long, conflicts_with="shell")]
     pub prefix: Option<PathBuf>,
 
@@ -77,10 +101,16 @@ pub struct GenCompletions {
 
 pub struct Settings {
     system: bool,
+    /// Prefix of the `--system` installation
+    prefix: Option<PathBuf>,
     installation_path: PathBuf,
     modify_path: bool,
+    /// Put installation path after the existing directories in PATH
+    append_path: bool,
     env_file: PathBuf,
     rc_files: Vec<PathBuf>,
+    /// Other `edgedb` binaries found in PATH (e.g. installed by homebrew)
+    existing_binaries: Vec<PathBuf>,
 }
 
 fn print_long_description(settings: &Settings) {
@@ -126,7 +156,46 @@ This path is already in your PATH environment variable, so no profile will
 be modified.
 "###.into()
         },
-    )
+    );
+    if !settings.existing_binaries.is_empty() {
+        println!("{}\n",
+                 tr("WARNING: other `edgedb` binaries are found in PATH:"));
+        for path in &settings.existing_binaries {
+            println!("  {}", tr_format("{} (installed via {})",
+                &[&path.display(), &info::installed_via(path)]));
+        }
+        println!(r###"
+The binary that comes first in PATH is used. Choose "Customize installation"
+to put the new binary after the existing ones or to keep PATH unchanged.
+"###);
+    }
+}
+
+/// Finds `edgedb` binaries in PATH except those in `installation_path`
+fn find_existing_binaries(installation_path: &Path) -> Vec<PathBuf> {
+    let name = if cfg!(windows) { "edgedb.exe" } else { "edgedb" };
+    let mut result = Vec::new();
+    if let Some(all_paths) = env::var_os("PATH") {
+        for dir in env::split_paths(&all_paths) {
+            let path = dir.join(name);
+            if dir != installation_path && path.is_file()
+                && !result.contains(&path)
+            {
+                result.push(path);
+            }
+        }
+    }
+    result
+}
+
+fn path_line(settings: &Settings) -> String {
+    if settings.append_path {
+        format!("export PATH=\"$PATH:{}\"",
+                settings.installation_path.display())
+    } else {
+        format!("export PATH=\"{}:$PATH\"",
+                settings.installation_path.display())
+    }
 }
 
 fn should_modify_path(dir: &Path) -> bool {
@@ -148,6 +217,38 @@ fn is_zsh() -> bool {
     return false;
 }
 
+fn is_fish_shell() -> bool {
+    if let Ok(shell) = env::var("SHELL") {
+        return shell.contains("fish");
+    }
+    return false;
+}
+
+/// Fish doesn't read POSIX profiles, so it gets a separate config file
+fn fish_conf_path(home_dir: &Path) -> PathBuf {
+    home_dir.join(".config/fish/conf.d/edgedb.fish")
+}
+
+fn is_fish_conf(path: &Path) -> bool {
+    path.extension().map(|e| e == "fish").unwrap_or(false)
+}
+
+fn fish_quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string()
+        .replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[context("cannot write {}", path.display())]
+fn write_fish_conf(path: &Path, settings: &Settings) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("fish_add_path --global{} {}\n",
+        if settings.append_path { " --append" } else { "" },
+        fish_quote(&settings.installation_path)))?;
+    Ok(())
+}
+
 fn get_rc_files() -> anyhow::Result<Vec<PathBuf>> {
     let mut rc_files = Vec::new();
 
@@ -169,21 +270,136 @@ fn get_rc_files() -> anyhow::Result<Vec<PathBuf>> {
         rc_files.push(bash_profile);
     }
 
+    if is_fish_shell() || home_dir.join(".config/fish").exists() {
+        rc_files.push(fish_conf_path(&home_dir));
+    }
+
     Ok(rc_files)
 }
 
-fn ensure_line(path: &PathBuf, line: &str) -> anyhow::Result<()> {
-    if path.exists() {
-        let text = fs::read_to_string(path)
-            .context("cannot read file")?;
-        if text.contains(line) {
-            return Ok(())
+/// Whether the line is written by an older version which used no markers
+fn is_unmarked_line(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("export PATH=\"")
+        && line.ends_with(".edgedb/bin:$PATH\""))
+    || (line.starts_with(". \"")
+        && line.ends_with("edgedb-completion.ps1\""))
+}
+
+/// Replaces the marked block in the profile `text` with `contents`
+///
+/// The block is updated in place if it exists, otherwise it's appended at
+/// the end. If `contents` is `None` the block is removed. Lines written by
+/// older versions without markers are removed in any case.
+fn replace_block(text: &str, contents: Option<&str>) -> String {
+    let block = contents.map(|c| format!("{}\n{}\n{}\n",
+                                         BLOCK_START, c, BLOCK_END));
+    let mut result = String::with_capacity(text.len());
+    let mut in_block = false;
+    let mut inserted = false;
+    for line in text.lines() {
+        if in_block {
+            if line.trim() == BLOCK_END {
+                in_block = false;
+            }
+            continue;
+        }
+        if line.trim() == BLOCK_START {
+            in_block = true;
+            if let Some(block) = block.as_ref().filter(|_| !inserted) {
+                result.push_str(block);
+                inserted = true;
+            }
+            continue;
         }
+        if is_unmarked_line(line) {
+            continue;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    if let Some(block) = block.as_ref().filter(|_| !inserted) {
+        if !result.is_empty() && !result.ends_with("\n\n") {
+            result.push('\n');
+        }
+        result.push_str(block);
     }
-    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)
-        .context("cannot file for append (writing)")?;
-    file.write(format!("{}\n", line).as_bytes(),)
-        .context("cannot append to file")?;
+    result
+}
+
+/// Updates marked block in the profile file, returns `true` if changed
+#[context("failed to update profile file {:?}", path)]
+fn update_profile(path: &Path, contents: Option<&str>)
+    -> anyhow::Result<bool>
+{
+    let text = if path.exists() {
+        fs::read_to_string(path).context("cannot read file")?
+    } else if contents.is_some() {
+        String::new()
+    } else {
+        return Ok(false);
+    };
+    let new_text = replace_block(&text, contents);
+    if new_text == text {
+        return Ok(false);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, new_text).context("cannot write file")?;
+    Ok(true)
+}
+
+fn remove_file(path: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            log::info!("Removed {}", path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e)
+            .with_context(|| format!("cannot remove {}", path.display())),
+    }
+}
+
+pub fn uninstall(options: &SelfUninstall) -> anyhow::Result<()> {
+    if cfg!(windows) {
+        anyhow::bail!("`self-uninstall` is not supported on Windows yet");
+    }
+    let home = home_dir()?;
+    let base = home.join(".edgedb");
+    let installation_path = base.join("bin");
+    if !options.no_confirm {
+        let q = question::Confirm::new_dangerous(format!(
+            "Remove EdgeDB command-line tools from {} \
+             and undo shell profile modifications?",
+            installation_path.display()));
+        if !q.ask()? {
+            eprintln!("Canceled.");
+            return Ok(());
+        }
+    }
+    for path in get_rc_files()? {
+        if is_fish_conf(&path) {
+            remove_file(&path)?;
+        } else if update_profile(&path, None)? {
+            log::info!("Updated {}", path.display());
+        }
+    }
+    for dir in powershell_dirs(&home) {
+        update_profile(&dir.join("Microsoft.PowerShell_profile.ps1"), None)?;
+        remove_file(&dir.join("edgedb-completion.ps1"))?;
+    }
+    remove_file(&base.join("env"))?;
+    remove_file(
+        &home.join(".local/share/bash-completion/completions/edgedb"))?;
+    remove_file(&home.join(".config/fish/completions/edgedb.fish"))?;
+    remove_file(&home.join(".zfunc/_edgedb"))?;
+    remove_file(&home.join(".elvish/lib/edgedb.elv"))?;
+    remove_file(&installation_path.join("edgedb"))?;
+    remove_file(&installation_path.join("edgedb.backup"))?;
+    eprintln!("EdgeDB command-line tools are uninstalled. \
+        Instance data and credentials in {} are kept.", base.display());
     Ok(())
 }
 
@@ -211,13 +427,18 @@ For this session please run:
   source {env_path}
 "###,
             dir=settings.installation_path.display(),
-            env_path=settings.env_file.display());
+            env_path=if is_fish_shell() {
+                home_dir().map(|h| fish_conf_path(&h))
+                    .unwrap_or_else(|_| settings.env_file.clone())
+            } else {
+                settings.env_file.clone()
+            }.display());
     } else {
         println!(r###"
 The EdgeDB command-line tool is now installed!
 "###);
     }
-    if is_zsh() {
+    if is_zsh() && !settings.system {
         let fpath = process::get_text(
             Command::new(env::var("SHELL").unwrap_or_else(|_| "zsh".into()))
             .arg("-ic")
@@ -237,18 +458,20 @@ to your ~/.zshrc before `compinit` command.
     }
     match init_result {
         Ok(true) => {
-            println!("`edgedb` without parameters will automatically \
-                      connect to the initialized project.");
+            println!("{}", tr("`edgedb` without parameters will automatically \
+                connect to the initialized project."));
         }
         Ok(false) => {
-            println!("To install the EdgeDB server and \
-                      initialize the project, run the following from \
-                      the project directory:");
+            println!("{}", tr("To install the EdgeDB server and \
+                initialize the project, run the following from \
+                the project directory:"));
             println!("  edgedb project init");
         }
         Err(e) => {
-            println!("There was an error while initializing project: {:#}", e);
-            println!("To restart project initialization, run:");
+            println!("{}", tr_format(
+                "There was an error while initializing project: {}",
+                &[&format!("{:#}", e)]));
+            println!("{}", tr("To restart project initialization, run:"));
             println!("  edgedb project init");
         }
     }
@@ -263,7 +486,7 @@ pub fn main(options: &SelfInstall) -> anyhow::Result<()> {
             {
                 // This is needed so user can read the message if console
                 // was open just for this process
-                eprintln!("Press the Enter key to continue");
+                eprintln!("{}", tr("Press the Enter key to continue"));
                 read_choice()?;
             }
             Ok(())
@@ -276,7 +499,7 @@ pub fn main(options: &SelfInstall) -> anyhow::Result<()> {
                 // This is needed so user can read the message if console
                 // was open just for this process
                 eprintln!("edgedb error: {:#}", e);
-                eprintln!("Press the Enter key to continue");
+                eprintln!("{}", tr("Press the Enter key to continue"));
                 read_choice()?;
                 exit(1);
             }
@@ -285,10 +508,32 @@ pub fn main(options: &SelfInstall) -> anyhow::Result<()> {
     }
 }
 
+#[derive(Clone, Copy)]
+enum PathChoice {
+    Prepend,
+    Append,
+    Keep,
+}
+
 fn customize(settings: &mut Settings) -> anyhow::Result<()> {
-    if should_modify_path(&settings.installation_path) {
+    let modify_path = !settings.system &&
+        should_modify_path(&settings.installation_path);
+    if modify_path && cfg!(unix) && !settings.existing_binaries.is_empty() {
+        let mut q = question::Numeric::new(tr_format(
+            "Where to put {} in PATH?",
+            &[&settings.installation_path.display()],
+        ));
+        q.option(tr("Before existing `edgedb` binaries \
+                     (the new binary is used)"), PathChoice::Prepend);
+        q.option(tr("After existing `edgedb` binaries \
+                     (the existing binary is used)"), PathChoice::Append);
+        q.option(tr("Do not modify PATH"), PathChoice::Keep);
+        let choice = q.ask()?;
+        settings.modify_path = !matches!(choice, PathChoice::Keep);
+        settings.append_path = matches!(choice, PathChoice::Append);
+    } else if modify_path {
         loop {
-            print!("Modify PATH variable? (Y/n)");
+            print!("{}", tr("Modify PATH variable? (Y/n)"));
 
             stdout().flush()?;
             match read_choice()?.as_ref() {
@@ -301,14 +546,14 @@ fn customize(settings: &mut Settings) -> anyhow::Result<()> {
                     break;
                 }
                 choice => {
-                    eprintln!("Invalid choice {:?}. \
+                    eprintln!("{}", tr_format("Invalid choice {}. \
                         Use single letter `y` or `n`.",
-                        choice);
+                        &[&format!("{:?}", choice)]));
                 }
             }
         }
     } else {
-        println!("No options to customize");
+        println!("{}", tr("No options to customize"));
     }
     Ok(())
 }
@@ -325,12 +570,12 @@ fn try_project_init() -> anyhow::Result<bool> {
         .context("failed to get current directory")?;
     let dir = init::search_dir(&base_dir)?;
     if let Some(dir) = dir {
-        println!("Command-line tools are installed successfully.");
+        println!("{}", tr("Command-line tools are installed successfully."));
         println!();
-        let q = question::Confirm::new(format!(
+        let q = question::Confirm::new(tr_format(
             "Do you want to initialize EdgeDB server instance for the project \
              defined in `{}`?",
-            dir.join("edgedb.toml").display(),
+            &[&dir.join("edgedb.toml").display()],
         ));
         if !q.ask()? {
             return Ok(false);
@@ -341,6 +586,10 @@ fn try_project_init() -> anyhow::Result<bool> {
             server_version: None,
             server_instance: None,
             server_install_method: None,
+            server_port: None,
+            server_start_conf: StartConf::Auto,
+            template: None,
+            no_migrations: false,
             non_interactive: false,
         };
         let dir = fs::canonicalize(&dir)
@@ -352,18 +601,58 @@ fn try_project_init() -> anyhow::Result<bool> {
     }
 }
 
+fn check_writable(dir: &Path) -> anyhow::Result<()> {
+    let probe = dir.join(".edgedb-install.tmp");
+    fs::create_dir_all(dir)?;
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)?;
+    Ok(())
+}
+
+fn system_settings(options: &SelfInstall) -> anyhow::Result<Settings> {
+    if cfg!(windows) {
+        anyhow::bail!("System-wide installation is not supported on Windows");
+    }
+    let prefix = options.prefix.clone()
+        .unwrap_or_else(|| PathBuf::from("/usr/local"));
+    let installation_path = prefix.join("bin");
+    for dir in &[&installation_path, &prefix.join("share")] {
+        if let Err(e) = check_writable(dir) {
+            anyhow::bail!("System-wide installation requires write access \
+                to {:?} ({}). Run the installer as root (e.g. with `sudo`) \
+                or choose another location with `--prefix`.", dir, e);
+        }
+    }
+    Ok(Settings {
+        system: true,
+        rc_files: Vec::new(),
+        modify_path: false,
+        append_path: false,
+        env_file: prefix.join("share/edgedb/env"),
+        existing_binaries: find_existing_binaries(&installation_path),
+        installation_path,
+        prefix: Some(prefix),
+    })
+}
+
 fn _main(options: &SelfInstall) -> anyhow::Result<()> {
-    let mut settings = if !cfg!(windows) && get_current_uid() == 0 {
+    let mut settings = if options.system {
+        system_settings(options)?
+    } else if !cfg!(windows) && get_current_uid() == 0 {
         anyhow::bail!("Installation as root is not supported. \
-            Try running without sudo.")
+            Try running without sudo, or use `--system` to install \
+            for all users.")
     } else {
         let base = home_dir()?.join(".edgedb");
         let installation_path = base.join("bin");
         Settings {
             rc_files: get_rc_files()?,
             system: false,
+            prefix: None,
             modify_path: !options.no_modify_path &&
                          should_modify_path(&installation_path),
+            append_path: false,
+            existing_binaries: find_existing_binaries(&installation_path),
             installation_path,
             env_file: base.join("env"),
         }
@@ -373,9 +662,9 @@ fn _main(options: &SelfInstall) -> anyhow::Result<()> {
         settings.print();
         if !options.no_confirm {
             loop {
-                println!("1) Proceed with installation (default)");
-                println!("2) Customize installation");
-                println!("3) Cancel installation");
+                println!("1) {}", tr("Proceed with installation (default)"));
+                println!("2) {}", tr("Customize installation"));
+                println!("3) {}", tr("Cancel installation"));
                 match read_choice()?.as_ref() {
                     "" | "1" => break,
                     "2" => {
@@ -383,7 +672,7 @@ fn _main(options: &SelfInstall) -> anyhow::Result<()> {
                         settings.print();
                     }
                     _ => {
-                        eprintln!("Aborting installation");
+                        eprintln!("{}", tr("Aborting installation"));
                         exit(7);
                     }
                 }
@@ -407,7 +696,11 @@ fn _main(options: &SelfInstall) -> anyhow::Result<()> {
         .with_context(|| format!("failed to write {:?}", tmp_path))?;
     fs::rename(&tmp_path, &path)
         .with_context(|| format!("failed to rename {:?}", tmp_path))?;
-    write_completions_home()?;
+    if let Some(prefix) = &settings.prefix {
+        write_completions_prefix(prefix)?;
+    } else {
+        write_completions_home()?;
+    }
 
     if settings.modify_path {
         #[cfg(windows)] {
@@ -415,19 +708,20 @@ fn _main(options: &SelfInstall) -> anyhow::Result<()> {
                 .context("failed adding a directory to PATH")?;
         }
         if cfg!(unix) {
-            let line = format!("\nexport PATH=\"{}:$PATH\"",
-                               settings.installation_path.display());
+            let line = path_line(&settings);
             for path in &settings.rc_files {
-                ensure_line(&path, &line)
-                    .with_context(|| format!(
-                        "failed to update profile file {:?}", path))?;
+                if is_fish_conf(path) {
+                    write_fish_conf(path, &settings)?;
+                    continue;
+                }
+                update_profile(&path, Some(&line))?;
             }
             fs::write(&settings.env_file, &(line + "\n"))
                 .context("failed to write env file")?;
         }
     }
 
-    let init_result = if options.no_confirm {
+    let init_result = if options.no_confirm || settings.system {
         Ok(false)
     } else {
         try_project_init()
@@ -573,17 +867,80 @@ fn write_completion(path: &Path, shell: Shell) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Returns PowerShell profile directories of the current user
+///
+/// On Windows both Windows PowerShell 5 and PowerShell 7 (if used) are
+/// configured, elsewhere only PowerShell 7 if it has been run before.
+fn powershell_dirs(home: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let docs = dirs::document_dir()
+            .unwrap_or_else(|| home.join("Documents"));
+        let mut dirs = vec![docs.join("WindowsPowerShell")];
+        let core = docs.join("PowerShell");
+        if core.exists() {
+            dirs.push(core);
+        }
+        dirs
+    } else {
+        let dir = home.join(".config").join("powershell");
+        if dir.exists() {
+            vec![dir]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn write_powershell_completion(dir: &Path) -> anyhow::Result<()> {
+    let script = dir.join("edgedb-completion.ps1");
+    write_completion(&script, Shell::PowerShell)?;
+    let profile = dir.join("Microsoft.PowerShell_profile.ps1");
+    update_profile(&profile, Some(&format!(". \"{}\"", script.display())))?;
+    Ok(())
+}
+
 pub fn write_completions_home() -> anyhow::Result<()> {
     let home = home_dir()?;
+    if cfg!(unix) {
+        write_completion(
+            &home.join(".local/share/bash-completion/completions/edgedb"),
+            Shell::Bash)?;
+        write_completion(
+            &home.join(".config/fish/completions/edgedb.fish"),
+            Shell::Fish)?;
+        write_completion(
+            &home.join(".zfunc/_edgedb"),
+            Shell::Zsh)?;
+    }
+    for dir in powershell_dirs(&home) {
+        write_powershell_completion(&dir)?;
+    }
+    // Elvish modules must be imported explicitly (`use edgedb`), so the
+    // module is only installed for existing users of elvish
+    let elvish = home.join(".elvish");
+    if elvish.exists() {
+        write_completion(&elvish.join("lib/edgedb.elv"), Shell::Elvish)?;
+    }
+    Ok(())
+}
+
+pub fn write_completions_prefix(prefix: &Path) -> anyhow::Result<()> {
     write_completion(
-        &home.join(".local/share/bash-completion/completions/edgedb"),
+        &prefix.join("share/bash-completion/completions/edgedb"),
         Shell::Bash)?;
     write_completion(
-        &home.join(".config/fish/completions/edgedb.fish"),
+        &prefix.join("share/fish/completions/edgedb.fish"),
         Shell::Fish)?;
     write_completion(
-        &home.join(".zfunc/_edgedb"),
+        &prefix.join("share/zsh/site-functions/_edgedb"),
         Shell::Zsh)?;
+    write_completion(
+        &prefix.join("share/powershell/edgedb-completion.ps1"),
+        Shell::PowerShell)?;
+    write_completion(
+        &prefix.join("share/elvish/lib/edgedb.elv"),
+        Shell::Elvish)?;
+    man::write_all(&prefix.join("share/man/man1"))?;
     Ok(())
 }
 
@@ -591,15 +948,7 @@ pub fn gen_completions(options: &GenCompletions) -> anyhow::Result<()> {
     if let Some(shell) = options.shell {
         shell.generate(&mut stdout());
     } else if let Some(prefix) = &options.prefix {
-        write_completion(
-            &prefix.join("share/bash-completion/completions/edgedb"),
-            Shell::Bash)?;
-        write_completion(
-            &prefix.join("share/fish/completions/edgedb.fish"),
-            Shell::Fish)?;
-        write_completion(
-            &prefix.join("share/zsh/site-functions/_edgedb"),
-            Shell::Zsh)?;
+        write_completions_prefix(prefix)?;
     } else if options.home {
         write_completions_home()?;
     } else {
@@ -617,7 +966,11 @@ impl Settings {
         ]));
         table.add_row(Row::new(vec![
             Cell::new("Modify PATH Variable"),
-            Cell::new(if self.modify_path { "yes" } else { "no" }),
+            Cell::new(match (self.modify_path, self.append_path) {
+                (false, _) => "no",
+                (true, false) => "yes",
+                (true, true) => "yes (append)",
+            }),
         ]));
         if self.modify_path && !self.rc_files.is_empty() {
             table.add_row(Row::new(vec![
@@ -654,14 +1007,65 @@ impl Shell {
 
         let mut app = RawOptions::into_app();
         let n = "edgedb";
+        let mut script = Vec::new();
         match self {
-            Bash => generate::<generators::Bash, _>(&mut app, n, buf),
-            Elvish => generate::<generators::Elvish, _>(&mut app, n, buf),
-            Fish => generate::<generators::Fish, _>(&mut app, n, buf),
+            Bash => generate::<generators::Bash, _>(&mut app, n, &mut script),
+            Elvish => {
+                generate::<generators::Elvish, _>(&mut app, n, &mut script)
+            }
+            Fish => generate::<generators::Fish, _>(&mut app, n, &mut script),
             PowerShell => {
-                generate::<generators::PowerShell, _>(&mut app, n, buf)
+                generate::<generators::PowerShell, _>(&mut app, n, &mut script)
             }
-            Zsh => generate::<generators::Zsh, _>(&mut app, n, buf),
+            Zsh => generate::<generators::Zsh, _>(&mut app, n, &mut script),
         }
+        let script = String::from_utf8_lossy(&script).into_owned();
+        let script = match self {
+            Bash => shell_completion::extend_bash(script),
+            Fish => shell_completion::extend_fish(script),
+            Zsh => shell_completion::extend_zsh(script),
+            Elvish | PowerShell => script,
+        };
+        buf.write_all(script.as_bytes()).expect("can write completions");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use super::{fish_quote, replace_block};
+
+    #[test]
+    fn fish_quoting() {
+        assert_eq!(fish_quote(Path::new("/home/user/.edgedb/bin")),
+                   "'/home/user/.edgedb/bin'");
+        assert_eq!(fish_quote(Path::new("/home/o'neil/bin")),
+                   r"'/home/o\'neil/bin'");
+    }
+
+    #[test]
+    fn profile_block() {
+        let block = "# >>> edgedb initialize >>>\n\
+                     export PATH=\"/a/.edgedb/bin:$PATH\"\n\
+                     # <<< edgedb initialize <<<\n";
+        let path = Some("export PATH=\"/a/.edgedb/bin:$PATH\"");
+        assert_eq!(replace_block("", path), block);
+        let text = format!("alias x=y\n\n{}", block);
+        assert_eq!(replace_block("alias x=y\n", path), text);
+        assert_eq!(replace_block(&text, path), text);
+        assert_eq!(replace_block(&text, None), "alias x=y\n\n");
+        // relocated in place
+        let text = format!("{}alias x=y\n", block);
+        assert_eq!(replace_block(&text,
+                                 Some("export PATH=\"/b/.edgedb/bin:$PATH\"")),
+                   "# >>> edgedb initialize >>>\n\
+                    export PATH=\"/b/.edgedb/bin:$PATH\"\n\
+                    # <<< edgedb initialize <<<\n\
+                    alias x=y\n");
+        // migrated from unmarked line
+        assert_eq!(replace_block(
+                "alias x=y\n\nexport PATH=\"/a/.edgedb/bin:$PATH\"\n",
+                path),
+            format!("alias x=y\n\n{}", block));
     }
 }
@@ -6,14 +6,16 @@ use std::time::{Duration};
 
 use anyhow::Context;
 use async_std::task;
-use clap::Clap;
+use clap::{Clap, ValueHint};
 use fn_error_context::context;
 use indicatif::{ProgressBar, ProgressStyle};
 use url::Url;
 
 use crate::async_util::timeout;
+use crate::checksum;
 use crate::platform::home_dir;
 use crate::process;
+use crate::server::detect::ARCH;
 use crate::server::package::RepositoryInfo;
 use crate::server::remote;
 use crate::server::version::Version;
@@ -30,6 +32,17 @@ pub struct SelfUpgrade {
     /// Reinstall even if there is no newer version
     #[clap(long)]
     pub force: bool,
+    /// Install pre-downloaded binary instead of fetching the latest one
+    #[clap(long, value_hint=ValueHint::FilePath)]
+    pub from_file: Option<PathBuf>,
+    /// Expected SHA-256 checksum of the `--from-file` binary. By default
+    /// it's read from the `<binary>.sha256` file
+    #[clap(long, requires="from_file")]
+    pub sha256: Option<String>,
+    /// Skip verification of checksums and signatures of the binary
+    /// (insecure)
+    #[clap(long, conflicts_with="sha256")]
+    pub no_verify: bool,
 }
 
 
@@ -50,8 +63,8 @@ pub fn get_repo(max_wait: Duration) -> anyhow::Result<RepositoryInfo> {
         ""
     };
     let url = format!(
-        "https://packages.edgedb.com/archive/.jsonindexes/{}-x86_64{}.json",
-        platform, suffix
+        "https://packages.edgedb.com/archive/.jsonindexes/{}-{}{}.json",
+        platform, ARCH, suffix
     );
 
     task::block_on(timeout(
@@ -125,11 +138,12 @@ async fn download(url: &str, path: &Path, quiet: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn main(options: &SelfUpgrade) -> anyhow::Result<()> {
-    let path = binary_path()?;
-    if !_can_upgrade(&path)? {
-        anyhow::bail!("Only binary installed at {:?} can be upgraded", path);
-    }
+/// Downloads the latest binary to `tmp_path`
+///
+/// Returns `None` if the current version is already the latest one.
+fn download_latest(options: &SelfUpgrade, tmp_path: &Path)
+    -> anyhow::Result<Option<(Version<String>, String)>>
+{
     let repo = get_repo(Duration::from_secs(120))?;
 
     let max = repo.packages.iter()
@@ -140,15 +154,55 @@ pub fn main(options: &SelfUpgrade) -> anyhow::Result<()> {
         pkg.version <= Version(env!("CARGO_PKG_VERSION").into())
     {
         log::info!("Version is the same. No update needed.");
-        return Ok(());
+        return Ok(None);
     }
 
     let url = Url::parse("https://packages.edgedb.com/")
         .expect("hardcoded URL is valid")
         .join(&pkg.installref)
         .context("package installref is invalid")?;
+    task::block_on(async {
+        download(url.as_str(), tmp_path, options.quiet).await?;
+        checksum::verify_download(tmp_path, url.as_str()).await
+    }).map_err(|e| {
+        fs::remove_file(tmp_path).ok();
+        e
+    })?;
+    Ok(Some((pkg.version.clone(), pkg.revision.clone())))
+}
+
+#[context("cannot copy {} -> {}", src.display(), tmp_path.display())]
+fn copy_file(src: &Path, tmp_path: &Path) -> anyhow::Result<()> {
+    fs::remove_file(tmp_path).ok();
+    fs::copy(src, tmp_path)?;
+    #[cfg(unix)] {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(tmp_path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+pub fn main(options: &SelfUpgrade) -> anyhow::Result<()> {
+    let path = binary_path()?;
+    if !_can_upgrade(&path)? {
+        anyhow::bail!("Only binary installed at {:?} can be upgraded", path);
+    }
+    if options.no_verify {
+        checksum::disable();
+    }
     let tmp_path = path.with_extension("download");
-    task::block_on(download(&url.to_string(), &tmp_path, options.quiet))?;
+    let upgraded_to = if let Some(src) = &options.from_file {
+        checksum::verify_file(src, options.sha256.as_deref())?;
+        copy_file(src, &tmp_path)?;
+        format!("the binary from {}", src.display())
+    } else {
+        match download_latest(options, &tmp_path)? {
+            Some((version, revision)) => {
+                format!("version {} (revision {})", version, revision)
+            }
+            None => return Ok(()),
+        }
+    };
     let backup_path = path.with_extension("backup");
     if cfg!(unix) {
         fs::remove_file(&backup_path).ok();
@@ -165,8 +219,7 @@ pub fn main(options: &SelfUpgrade) -> anyhow::Result<()> {
     }
     process::run(Command::new(&path).arg("_gen_completions").arg("--home"))?;
     if !options.quiet {
-        println!("Upgraded to version {} (revision {})",
-            pkg.version, pkg.revision);
+        println!("Upgraded to {}", upgraded_to);
     }
     Ok(())
 }
@@ -0,0 +1,310 @@
+//! Scheduled backups of local instances
+//!
+//! Backups are dumps of all databases of the instance stored under
+//! `~/.edgedb/backups/<instance>/<timestamp>`. The schedule is a systemd
+//! timer on Linux and a launchd agent on macOS, both of them run
+//! `edgedb server backup run`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use async_std::task;
+use fn_error_context::context;
+use indicatif::HumanBytes;
+use prettytable::{Table, Row, Cell};
+
+use crate::commands::ExitCode;
+use crate::format;
+use crate::platform::{self, home_dir};
+use crate::question;
+use crate::server::control::{self, read_metadata};
+use crate::server::detect;
+use crate::server::linux;
+use crate::server::macos;
+use crate::server::methods::InstallMethod;
+use crate::server::options::{Backup, BackupCommand, BackupEnable};
+use crate::server::options::{BackupDisable, BackupRun, BackupList};
+use crate::server::options::{BackupRestore, Schedule, Stop};
+use crate::server::unix;
+use crate::server::upgrade::{self, UpgradeMeta};
+use crate::server::version::Version;
+use crate::table;
+
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonBackup<'a> {
+    timestamp: &'a str,
+    path: PathBuf,
+    size: Option<u64>,
+}
+
+
+pub fn backups_dir(name: &str) -> anyhow::Result<PathBuf> {
+    Ok(home_dir()?.join(".edgedb").join("backups").join(name))
+}
+
+/// Formats timestamp as a file name, e.g. `20210304T120000Z`
+fn timestamp_name(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+        .replace(&['-', ':'][..], "")
+}
+
+fn parse_timestamp(name: &str) -> Option<SystemTime> {
+    if name.len() != 16 || !name.is_ascii() {
+        return None;
+    }
+    let rfc3339 = format!("{}-{}-{}T{}:{}:{}Z",
+        &name[0..4], &name[4..6], &name[6..8],
+        &name[9..11], &name[11..13], &name[13..15]);
+    humantime::parse_rfc3339(&rfc3339).ok()
+}
+
+/// Returns names of the complete backups ordered from the oldest one
+#[context("cannot read backups directory {}", dir.display())]
+fn list_backups(dir: &Path) -> anyhow::Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut result = Vec::new();
+    for item in fs::read_dir(dir)? {
+        let item = item?;
+        if !item.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(name) = item.file_name().to_str() {
+            if parse_timestamp(name).is_some() {
+                result.push(name.to_string());
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn rotate(dir: &Path, keep: usize) -> anyhow::Result<()> {
+    let backups = list_backups(dir)?;
+    let extra = backups.len().saturating_sub(keep);
+    for name in &backups[..extra] {
+        let path = dir.join(name);
+        log::info!("Removing old backup {}", path.display());
+        fs::remove_dir_all(&path)
+            .with_context(|| format!("cannot remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn create_schedule(name: &str, schedule: Schedule, keep: usize)
+    -> anyhow::Result<()>
+{
+    let exe = env::current_exe()
+        .context("cannot determine path of the edgedb executable")?;
+    if cfg!(target_os="linux") {
+        linux::create_backup_timer(name, &exe, schedule, keep)
+    } else if cfg!(target_os="macos") {
+        macos::create_backup_agent(name, &exe, schedule, keep)
+    } else {
+        anyhow::bail!("scheduled backups are not supported on this platform");
+    }
+}
+
+fn enable(options: &BackupEnable) -> anyhow::Result<()> {
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    control::get_instance(&methods, &options.name)?;
+    if options.keep == 0 {
+        anyhow::bail!("`--keep` must be at least 1");
+    }
+    create_schedule(&options.name, options.schedule, options.keep)?;
+    eprintln!("Backups of {:?} are scheduled {}, the last {} of them \
+        are kept in {}.",
+        options.name, options.schedule, options.keep,
+        backups_dir(&options.name)?.display());
+    Ok(())
+}
+
+/// Removes backup schedule of the instance, returns `false` if there was
+/// none
+pub fn remove_schedule(name: &str) -> anyhow::Result<bool> {
+    if cfg!(target_os="linux") {
+        linux::remove_backup_timer(name)
+    } else if cfg!(target_os="macos") {
+        macos::remove_backup_agent(name)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Moves backup schedule and existing backups of the instance to the new
+/// name, returns `false` if no backups were scheduled
+#[context("cannot move backups of {:?} to {:?}", old_name, new_name)]
+pub fn rename_schedule(old_name: &str, new_name: &str)
+    -> anyhow::Result<bool>
+{
+    let old_dir = backups_dir(old_name)?;
+    let new_dir = backups_dir(new_name)?;
+    if old_dir.exists() && !new_dir.exists() {
+        fs::rename(&old_dir, &new_dir)?;
+    }
+    let scheduled = if cfg!(target_os="linux") {
+        linux::read_backup_timer(old_name)?
+    } else if cfg!(target_os="macos") {
+        macos::read_backup_agent(old_name)?
+    } else {
+        None
+    };
+    let (schedule, keep) = match scheduled {
+        Some(scheduled) => scheduled,
+        None => return Ok(false),
+    };
+    create_schedule(new_name, schedule, keep)?;
+    remove_schedule(old_name)?;
+    Ok(true)
+}
+
+fn disable(options: &BackupDisable) -> anyhow::Result<()> {
+    if remove_schedule(&options.name)? {
+        eprintln!("Scheduled backups of {:?} are disabled. \
+            Existing backups are kept in {}.",
+            options.name, backups_dir(&options.name)?.display());
+    } else {
+        eprintln!("No backups are scheduled for {:?}.", options.name);
+    }
+    Ok(())
+}
+
+fn run(options: &BackupRun) -> anyhow::Result<()> {
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let inst = control::get_instance(&methods, &options.name)?;
+    let dir = backups_dir(&options.name)?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("cannot create {}", dir.display()))?;
+    let name = timestamp_name(SystemTime::now());
+    let partial = dir.join(format!("{}.partial", name));
+    let res = task::block_on(upgrade::dump_instance(
+        inst.as_ref(), &partial, inst.get_connector(false)?));
+    if let Err(e) = res {
+        fs::remove_dir_all(&partial).ok();
+        return Err(e);
+    }
+    let path = dir.join(&name);
+    fs::rename(&partial, &path)
+        .with_context(|| format!("cannot rename {}", partial.display()))?;
+    eprintln!("Instance {:?} is backed up to {}",
+        options.name, path.display());
+    if let Some(keep) = options.keep {
+        rotate(&dir, keep)?;
+    }
+    Ok(())
+}
+
+fn list(options: &BackupList) -> anyhow::Result<()> {
+    let dir = backups_dir(&options.name)?;
+    let backups = list_backups(&dir)?;
+    let sizes = backups.iter()
+        .map(|name| platform::dir_size(&dir.join(name)).ok())
+        .collect::<Vec<_>>();
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&backups.iter()
+            .zip(&sizes)
+            .map(|(name, size)| JsonBackup {
+                timestamp: name,
+                path: dir.join(name),
+                size: *size,
+            })
+            .collect::<Vec<_>>()
+        )?);
+        return Ok(());
+    }
+    if backups.is_empty() {
+        eprintln!("No backups of {:?} found in {}",
+            options.name, dir.display());
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Timestamp", "Created", "Size"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for (name, size) in backups.iter().zip(&sizes).rev() {
+        table.add_row(Row::new(vec![
+            Cell::new(name),
+            Cell::new(&parse_timestamp(name)
+                .map(|t| format::done_before(t).to_string())
+                .unwrap_or_default()),
+            Cell::new(&size.map(|s| HumanBytes(s).to_string())
+                .unwrap_or("?".into())),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn restore(options: &BackupRestore) -> anyhow::Result<()> {
+    let path = backups_dir(&options.name)?.join(&options.timestamp);
+    if parse_timestamp(&options.timestamp).is_none() || !path.is_dir() {
+        anyhow::bail!("no backup {:?} of instance {:?} found, \
+            run `edgedb server backup list {}` to see available ones",
+            options.timestamp, options.name, options.name);
+    }
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let inst = control::get_exact_instance(&methods, &options.name)?;
+    if inst.method().name() != InstallMethod::Package {
+        anyhow::bail!("restoring backups is only supported for instances \
+            installed using packages");
+    }
+    if !options.non_interactive {
+        let q = question::Confirm::new_dangerous(format!(
+            "All data of instance {:?} will be replaced by the backup {}. \
+             Continue?", options.name, options.timestamp));
+        if !q.ask()? {
+            return Err(ExitCode::new(1))?;
+        }
+    }
+    let meta = read_metadata(&unix::storage_dir(&options.name)?)?;
+    let version = meta.current_version.clone()
+        .unwrap_or_else(|| Version("unknown".into()));
+    inst.stop(&Stop { name: options.name.clone() })?;
+    unix::reinit_and_restore(inst.as_ref(), &meta, &UpgradeMeta {
+        source: version.clone(),
+        target: version,
+        started: SystemTime::now(),
+        pid: process::id(),
+    }, &path)?;
+    eprintln!("Instance {:?} is restored from backup {}. \
+        To bring the previous data back run:\n  edgedb server revert {}",
+        options.name, options.timestamp, options.name);
+    Ok(())
+}
+
+pub fn main(cmd: &Backup) -> anyhow::Result<()> {
+    use BackupCommand::*;
+    match &cmd.subcommand {
+        Enable(c) => enable(c),
+        Disable(c) => disable(c),
+        Run(c) => run(c),
+        List(c) => list(c),
+        Restore(c) => restore(c),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+    use super::{timestamp_name, parse_timestamp};
+
+    #[test]
+    fn timestamps() {
+        let time = UNIX_EPOCH + Duration::from_secs(1614859200);
+        assert_eq!(timestamp_name(time), "20210304T120000Z");
+        assert_eq!(parse_timestamp("20210304T120000Z"), Some(time));
+        assert_eq!(parse_timestamp("20210304T120000Z.partial"), None);
+        assert_eq!(parse_timestamp("latest"), None);
+    }
+}
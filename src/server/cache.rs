@@ -0,0 +1,100 @@
+//! Cache of downloaded server packages
+//!
+//! Packages are stored under `~/.edgedb/cache/downloads/<sha256>/<name>`,
+//! so the same package is downloaded only once whatever URL it's fetched
+//! from. Interrupted downloads are kept as `<name>.partial` and resumed on
+//! the next attempt.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use fn_error_context::context;
+use indicatif::HumanBytes;
+
+use crate::checksum;
+use crate::platform;
+use crate::server::options::{Cache, CacheCommand};
+use crate::server::remote;
+
+
+pub fn downloads_dir() -> anyhow::Result<PathBuf> {
+    Ok(platform::cache_dir()?.join("downloads"))
+}
+
+fn file_name(url: &str) -> &str {
+    url.rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or("download")
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_default();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+/// Downloads and verifies `url`, returns path of the file in the cache
+pub async fn download(url: &str) -> anyhow::Result<PathBuf> {
+    let dir = downloads_dir()?;
+    if !checksum::enabled() {
+        // Unverified files are not cached as their content is unknown
+        let path = dir.join("unverified").join(file_name(url));
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::remove_file(&path).ok();
+        remote::get_file_resumable(&path, url).await?;
+        return Ok(path);
+    }
+    let sum = checksum::fetch(url).await?;
+    let path = dir.join(&sum).join(file_name(url));
+    if path.exists() {
+        if checksum::check(&path, &sum).is_ok() {
+            log::info!("Using cached {}", path.display());
+            return Ok(path);
+        }
+        log::warn!("Cached {} is corrupted, downloading again",
+                   path.display());
+        fs::remove_file(&path)?;
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    let partial = partial_path(&path);
+    remote::get_file_resumable(&partial, url).await?;
+    if let Err(e) = checksum::check(&partial, &sum) {
+        fs::remove_file(&partial).ok();
+        return Err(e);
+    }
+    checksum::verify_signature(&partial, url).await?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("cannot rename {}", partial.display()))?;
+    Ok(path)
+}
+
+#[context("cannot clean download cache {}", dir.display())]
+fn clean(dir: &Path) -> anyhow::Result<()> {
+    if !dir.exists() {
+        eprintln!("Download cache is empty.");
+        return Ok(());
+    }
+    let size = platform::dir_size(dir)?;
+    fs::remove_dir_all(dir)?;
+    eprintln!("Removed {} of cached downloads.", HumanBytes(size));
+    Ok(())
+}
+
+pub fn main(cmd: &Cache) -> anyhow::Result<()> {
+    match &cmd.subcommand {
+        CacheCommand::Clean => clean(&downloads_dir()?),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::file_name;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("https://example.com/macos/edgedb.pkg"),
+                   "edgedb.pkg");
+        assert_eq!(file_name("https://example.com/macos/"), "download");
+    }
+}
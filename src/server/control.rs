@@ -3,12 +3,15 @@ use std::path::{Path};
 
 use fn_error_context::context;
 
+use crate::credentials;
+use crate::hint::HintExt;
 use crate::server::detect;
+use crate::server::init;
 use crate::server::options::InstanceCommand;
 use crate::server::metadata::Metadata;
 use crate::server::methods::Methods;
 use crate::server::revert;
-use crate::server::status;
+use crate::server::status::{self, Service, Port};
 use crate::server::os_trait::{InstanceRef};
 
 
@@ -20,6 +23,45 @@ pub fn read_metadata(dir: &Path) -> anyhow::Result<Metadata> {
 
 pub fn get_instance<'x>(methods: &'x Methods, name: &str)
     -> anyhow::Result<InstanceRef<'x>>
+{
+    let resolved = credentials::resolve_name(name);
+    let name = resolved.as_deref().unwrap_or(name);
+    let err = match find_instance(methods, name) {
+        Ok(inst) => return Ok(inst),
+        Err(err) => err,
+    };
+    if resolved.is_err() {
+        Err(err).with_hint(|| credentials::suggest_instances(name))
+            .map_err(Into::into)
+    } else {
+        Err(err)
+    }
+}
+
+/// Same as `get_instance` but doesn't resolve project names and names
+/// differing in case, for commands that destroy or replace data
+pub fn get_exact_instance<'x>(methods: &'x Methods, name: &str)
+    -> anyhow::Result<InstanceRef<'x>>
+{
+    let err = match find_instance(methods, name) {
+        Ok(inst) => return Ok(inst),
+        Err(err) => err,
+    };
+    match credentials::resolve_name(name) {
+        Ok(resolved) if resolved != name => {
+            Err(err).with_hint(|| format!("did you mean {:?}? \
+                    This command requires exact instance name", resolved))
+                .map_err(Into::into)
+        }
+        _ => {
+            Err(err).with_hint(|| credentials::suggest_instances(name))
+                .map_err(Into::into)
+        }
+    }
+}
+
+fn find_instance<'x>(methods: &'x Methods, name: &str)
+    -> anyhow::Result<InstanceRef<'x>>
 {
     let mut errors = Vec::new();
     for (meth_name, meth) in methods {
@@ -30,8 +72,32 @@ pub fn get_instance<'x>(methods: &'x Methods, name: &str)
             }
         }
     }
-    anyhow::bail!("Cannot find instance {:?}:\n{}", name,
-        errors.join("\n"))
+    anyhow::bail!("Cannot find instance {:?}:\n{}", name, errors.join("\n"))
+}
+
+/// Fails early if the port of the instance is taken by another instance or
+/// process, rather than letting the server fail in the background
+fn check_start_port(inst: &InstanceRef) -> anyhow::Result<()> {
+    let port = inst.get_port()?;
+    let name = inst.name();
+    let hint = || format!("run `edgedb server set-port {} <port>` \
+                           to use another port", name.escape_default());
+    if let Some((other, _)) = init::read_ports()?.iter()
+        .find(|(other, p)| **p == port && *other != name)
+    {
+        return Err(anyhow::anyhow!("port {} is assigned to both {:?} \
+                and {:?}", port, name, other))
+            .with_hint(hint).map_err(Into::into);
+    }
+    let status = inst.get_status();
+    if !matches!(status.service, Service::Running {..}) &&
+        matches!(status.port_status, Port::Occupied)
+    {
+        return Err(anyhow::anyhow!("port {} is already in use \
+                by another process", port))
+            .with_hint(hint).map_err(Into::into);
+    }
+    Ok(())
 }
 
 pub fn instance_command(cmd: &InstanceCommand) -> anyhow::Result<()> {
@@ -55,7 +121,10 @@ pub fn instance_command(cmd: &InstanceCommand) -> anyhow::Result<()> {
     let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
     let inst = get_instance(&methods, name)?;
     match cmd {
-        Start(c) => inst.start(c),
+        Start(c) => {
+            check_start_port(&inst)?;
+            inst.start(c)
+        }
         Stop(c) => inst.stop(c),
         Restart(c) => inst.restart(c),
         Logs(c) => inst.logs(c),
@@ -1,13 +1,20 @@
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use anyhow::Context;
 use fn_error_context::context;
 
 use crate::commands::{self, ExitCode};
+use crate::credentials;
+use crate::process;
 use crate::project::init::stash_base;
+use crate::server::backup;
 use crate::server::detect;
 use crate::server::errors::InstanceNotFound;
+use crate::server::init;
 use crate::server::options::Destroy;
 use crate::platform::{bytes_to_path};
 
@@ -63,13 +70,70 @@ pub fn print_warning(name: &str, project_dirs: &[PathBuf]) {
     eprintln!("  edgedb server destroy {:?} --force", name);
 }
 
+fn destroy_temporary() -> anyhow::Result<()> {
+    let mut failed = false;
+    for name in init::temporary_instances()? {
+        match destroy_temporary_instance(&name) {
+            Ok(()) => eprintln!("Instance {:?} is destroyed", name),
+            Err(e) => {
+                eprintln!("edgedb error: cannot destroy {:?}: {:#}",
+                          name, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err(ExitCode::new(1))?;
+    }
+    Ok(())
+}
+
+/// Stops the process running temporary instance, which cleans up after
+/// itself, and removes whatever is left if that process was killed
+fn destroy_temporary_instance(name: &str) -> anyhow::Result<()> {
+    let tmp = init::read_temporary(name)?;
+    if tmp.data_dir.exists() && process::exists(tmp.pid) {
+        log::info!(target: "edgedb::server::destroy",
+            "Stopping process {}", tmp.pid);
+        #[cfg(unix)] {
+            if unsafe { libc::kill(tmp.pid as i32, libc::SIGTERM) } != 0 {
+                return Err(io::Error::last_os_error())
+                    .context("cannot stop process")?;
+            }
+        }
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while process::exists(tmp.pid) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+    if tmp.data_dir.exists() {
+        log::info!(target: "edgedb::server::destroy",
+            "Removing data directory {}", tmp.data_dir.display());
+        fs::remove_dir_all(&tmp.data_dir)?;
+    }
+    let credentials = credentials::path(name)?;
+    if credentials.exists() {
+        fs::remove_file(&credentials)?;
+    }
+    init::release_port(name)?;
+    init::unmark_temporary(name)?;
+    Ok(())
+}
+
 pub fn destroy(options: &Destroy) -> anyhow::Result<()> {
-    let project_dirs = find_project_dirs(&options.name)?;
+    if options.all_temporary {
+        return destroy_temporary();
+    }
+    let name = options.instance()?;
+    let project_dirs = find_project_dirs(name)?;
     if !options.force && !project_dirs.is_empty() {
-        print_warning(&options.name, &project_dirs);
+        print_warning(name, &project_dirs);
         return Err(ExitCode::new(2))?;
     }
     do_destroy(options)?;
+    if let Err(e) = backup::remove_schedule(name) {
+        log::warn!("Cannot remove backup schedule of {:?}: {:#}", name, e);
+    }
     for dir in project_dirs {
         let path_path = dir.join("project-path");
         match read_path(&path_path) {
@@ -82,6 +146,7 @@ pub fn destroy(options: &Destroy) -> anyhow::Result<()> {
 }
 
 pub fn do_destroy(options: &Destroy) -> anyhow::Result<()> {
+    let name = options.instance()?;
     let os = detect::current_os()?;
     let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
     let mut errors = Vec::new();
@@ -94,11 +159,21 @@ pub fn do_destroy(options: &Destroy) -> anyhow::Result<()> {
             Err(e) => Err(e)?,
         }
     }
+    init::release_port(name)?;
     if errors.len() == methods.len() {
         eprintln!("No instances found:");
         for (meth, err) in errors {
             eprintln!("  * {}: {:#}", meth.title(), err);
         }
+        // names are not resolved here, as destroying the wrong instance
+        // can't be undone
+        match credentials::resolve_name(name) {
+            Ok(resolved) if resolved != name => {
+                eprintln!("Did you mean {:?}? This command \
+                    requires exact instance name", resolved);
+            }
+            _ => {}
+        }
         Err(commands::ExitCode::new(1).into())
     } else {
         Ok(())
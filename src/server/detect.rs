@@ -1,5 +1,6 @@
 use std::fmt;
 use std::collections::BTreeMap;
+use std::path::Path;
 
 use once_cell::sync::OnceCell;
 use serde::Serialize;
@@ -14,10 +15,21 @@ use anyhow::Context;
 
 #[cfg(target_arch="x86_64")]
 pub const ARCH: &str = "x86_64";
+#[cfg(target_arch="aarch64")]
+pub const ARCH: &str = "aarch64";
 #[cfg(not(any(
     target_arch="x86_64",
+    target_arch="aarch64",
 )))]
-compile_error!("Unsupported architecture, supported: x86_64");
+compile_error!("Unsupported architecture, supported: x86_64, aarch64");
+
+/// Targets which have native server packages, for error messages
+pub const PACKAGE_TARGETS: &[&str] = &[
+    "x86_64-linux (Debian, Ubuntu, CentOS)",
+    "aarch64-linux (Debian, Ubuntu)",
+    "x86_64-macos",
+    "aarch64-macos",
+];
 
 #[derive(Clone, Debug, Default)]
 pub struct Lazy<T>(once_cell::sync::OnceCell<T>);
@@ -73,6 +85,18 @@ impl<T> Lazy<T> {
     }
 }
 
+/// Whether the system uses musl libc (e.g. Alpine Linux)
+pub fn is_musl() -> bool {
+    cfg!(target_os="linux") &&
+        Path::new(&format!("/lib/ld-musl-{}.so.1", ARCH)).exists()
+}
+
+/// Target name of the current platform, e.g. `aarch64-macos`
+pub fn target() -> String {
+    format!("{}-{}{}", ARCH, std::env::consts::OS,
+            if is_musl() { "-musl" } else { "" })
+}
+
 pub fn current_os() -> anyhow::Result<Box<dyn CurrentOs>> {
     use crate::server::{windows, macos, linux, unknown_os};
 
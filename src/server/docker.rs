@@ -14,6 +14,7 @@ use linked_hash_map::LinkedHashMap;
 use serde::{Serialize, Deserialize};
 
 use crate::credentials::{self, get_connector};
+use crate::secrets;
 use crate::process;
 use crate::platform::home_dir;
 
@@ -25,6 +26,7 @@ use crate::server::errors::InstanceNotFound;
 use crate::server::init::{self, read_ports, Storage};
 use crate::server::init::{bootstrap_script, save_credentials};
 use crate::server::install;
+use crate::server::logs;
 use crate::server::options::{Start, Stop, Restart, Upgrade, Destroy, Logs};
 use crate::server::options::{StartConf};
 use crate::server::metadata::Metadata;
@@ -519,6 +521,9 @@ impl<'os, O: CurrentOs + ?Sized> DockerMethod<'os, O> {
         version_query: &Option<VersionQuery>,
         options: &Upgrade) -> anyhow::Result<()>
     {
+        if options.check {
+            anyhow::bail!("`--check` is not supported for docker instances");
+        }
         let inst = self._get_instance(name)?;
         let version_query = if let Some(q) = version_query {
             q
@@ -1081,13 +1086,14 @@ impl<'os, O: CurrentOs + ?Sized> Method for DockerMethod<'os, O> {
     }
     fn destroy(&self, options: &Destroy) -> anyhow::Result<()> {
         let mut found = false;
-        let container_name = format!("edgedb_{}", options.name);
+        let name = options.instance()?;
+        let container_name = format!("edgedb_{}", name);
         if self.delete_container(&container_name)? {
             log::info!(target: "edgedb::server::destroy",
                 "Removed container {:?}", container_name);
             found = true;
         }
-        let up_container = format!("edgedb_upgrade_{}", options.name);
+        let up_container = format!("edgedb_upgrade_{}", name);
         if self.delete_container(&up_container)? {
             log::info!(target: "edgedb::server::destroy",
                 "Removed container {:?}", up_container);
@@ -1106,18 +1112,19 @@ impl<'os, O: CurrentOs + ?Sized> Method for DockerMethod<'os, O> {
             Err(text) if text.contains("No such volume") => {},
             Err(text) => anyhow::bail!("docker error: {}", text),
         }
-        let credentials = credentials::path(&options.name)?;
+        let credentials = credentials::path(name)?;
         if credentials.exists() {
             found = true;
             log::info!(target: "edgedb::server::destroy",
                 "Removing credentials file {}", credentials.display());
             fs::remove_file(&credentials)?;
+            secrets::delete_password(&credentials);
         }
         if found {
             Ok(())
         } else {
             Err(InstanceNotFound(anyhow::anyhow!(
-                "no instance {:?} found", options.name)).into())
+                "no instance {:?} found", name)).into())
         }
     }
 }
@@ -1131,6 +1138,7 @@ impl<O: CurrentOs + ?Sized> DockerInstance<'_, O> {
             method: InstallMethod::Docker,
             port: self.get_port()?,
             start_conf: self.get_start_conf()?,
+            backend_dsn: None,
         })
     }
     fn get_backup(&self) -> anyhow::Result<BackupStatus> {
@@ -1363,7 +1371,7 @@ impl<O: CurrentOs + ?Sized> Instance for DockerInstance<'_, O> {
         if options.follow {
             cmd.arg("--follow");
         }
-        process::run(&mut cmd)
+        logs::run(&mut cmd, options)
     }
     fn service_status(&self) -> anyhow::Result<()> {
         process::run(Command::new(&self.method.cli)
@@ -0,0 +1,16 @@
+use std::process::Command;
+
+use crate::credentials;
+use crate::process;
+use crate::server::options::Exec;
+
+
+pub fn exec(options: &Exec) -> anyhow::Result<()> {
+    let name = credentials::resolve_name(&options.name)?;
+    let dsn = credentials::dsn(&name)?;
+    let (cmd, args) = options.command.split_first()
+        .ok_or_else(|| anyhow::anyhow!("command is required"))?;
+    process::exit_from(Command::new(cmd)
+        .args(args)
+        .env("EDGEDB_DSN", dsn))
+}
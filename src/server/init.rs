@@ -2,9 +2,11 @@ use std::collections::{BTreeSet, BTreeMap};
 use std::default::Default;
 use std::fmt;
 use std::fs;
-use std::io;
+use std::io::{self, Write};
+use std::net::TcpListener;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use async_std::task;
@@ -13,8 +15,11 @@ use prettytable::{Table, Row, Cell};
 use fn_error_context::context;
 
 use crate::commands::ExitCode;
+use crate::hint::HintExt;
 use crate::platform::{config_dir, home_dir};
+use crate::process;
 use crate::server::reset_password::{generate_password, write_credentials};
+use crate::server::reset_password::read_credentials;
 use crate::server::reset_password::{password_hash};
 use crate::server::detect::{self, VersionQuery};
 use crate::server::errors::CannotCreateService;
@@ -25,9 +30,12 @@ use crate::server::os_trait::{Method, CurrentOs, InstanceRef};
 use crate::server::version::Version;
 use crate::server::distribution::DistributionRef;
 use crate::server::package::Package;
+use crate::server::unix;
 use crate::table;
 
+use edgedb_client as client;
 use edgedb_client::credentials::Credentials;
+use url::Url;
 
 
 const MIN_PORT: u16 = 10700;
@@ -41,6 +49,13 @@ pub enum Storage {
 
 pub struct StorageDisplay<'a>(&'a Storage);
 
+/// Process running a temporary instance in foreground and its data
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Temporary {
+    pub pid: u32,
+    pub data_dir: PathBuf,
+}
+
 pub struct Settings {
     pub name: String,
     pub system: bool,
@@ -55,6 +70,7 @@ pub struct Settings {
     pub port: u16,
     pub start_conf: StartConf,
     pub suppress_messages: bool,
+    pub backend_dsn: Option<String>,
 }
 
 fn port_file() -> anyhow::Result<PathBuf> {
@@ -80,19 +96,22 @@ fn _read_ports(path: &Path) -> anyhow::Result<BTreeMap<String, u16>> {
     Ok(serde_json::from_str(&data)?)
 }
 
-fn next_min_port(port_map: &BTreeMap<String, u16>) -> u16 {
-    if port_map.len() == 0 {
-        return MIN_PORT;
-    }
+/// Returns the lowest port not in the mapping for which `in_use` is false
+fn next_min_port(port_map: &BTreeMap<String, u16>,
+                 in_use: impl Fn(u16) -> bool)
+    -> u16
+{
     let port_set: BTreeSet<u16> = port_map.values().cloned().collect();
-    let mut prev = MIN_PORT - 1;
-    for port in port_set {
-        if port > prev+1 {
-            return prev + 1;
-        }
-        prev = port;
+    let mut port = MIN_PORT;
+    while port_set.contains(&port) || in_use(port) {
+        port += 1;
     }
-    return prev+1;
+    return port;
+}
+
+/// Returns `true` if some process listens on the port
+pub fn port_in_use(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_err()
 }
 
 fn _write_ports(port_map: &BTreeMap<String, u16>, port_file: &Path)
@@ -107,13 +126,143 @@ fn _write_ports(port_map: &BTreeMap<String, u16>, port_file: &Path)
     Ok(())
 }
 
+/// Lock of the port mapping held while the mapping is changed, so that
+/// instances can be created in parallel
+struct PortLock {
+    path: PathBuf,
+}
+
+impl Drop for PortLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).map_err(|e| {
+            log::warn!("Cannot remove lock {}: {}", self.path.display(), e);
+        }).ok();
+    }
+}
+
+#[context("cannot lock port mapping")]
+fn lock_ports() -> anyhow::Result<PortLock> {
+    let config_dir = config_dir()?;
+    fs::create_dir_all(&config_dir)?;
+    let path = config_dir.join(".instance_ports.lock");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path)
+        {
+            Ok(mut file) => {
+                write!(file, "{}", std::process::id())?;
+                return Ok(PortLock { path });
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e)?,
+        }
+        // lock left by a crashed process
+        let owner = fs::read_to_string(&path).ok()
+            .and_then(|pid| pid.trim().parse().ok());
+        if owner.map(|pid| !process::exists(pid)).unwrap_or(false) {
+            fs::remove_file(&path).ok();
+            continue;
+        }
+        if Instant::now() > deadline {
+            anyhow::bail!("lock {} is held by process {:?}",
+                          path.display(), owner);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn temporary_dir() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("temporary_instances"))
+}
+
+/// Marks instance as temporary, with a file per instance (rather than a
+/// single list) so that instances can be created in parallel
+#[context("cannot mark instance {:?} as temporary", name)]
+pub fn mark_temporary(name: &str, temporary: &Temporary)
+    -> anyhow::Result<()>
+{
+    let dir = temporary_dir()?;
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(name), serde_json::to_vec(temporary)?)?;
+    Ok(())
+}
+
+#[context("cannot read temporary instance {:?}", name)]
+pub fn read_temporary(name: &str) -> anyhow::Result<Temporary> {
+    Ok(serde_json::from_slice(&fs::read(temporary_dir()?.join(name))?)?)
+}
+
+pub fn unmark_temporary(name: &str) -> anyhow::Result<()> {
+    match fs::remove_file(temporary_dir()?.join(name)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e)?,
+    }
+}
+
+#[context("cannot read temporary instances")]
+pub fn temporary_instances() -> anyhow::Result<Vec<String>> {
+    let dir = match fs::read_dir(temporary_dir()?) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e)?,
+    };
+    let mut names = Vec::new();
+    for item in dir {
+        names.push(item?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Checks that the port is neither assigned to other instance nor used by
+/// another process
+pub fn check_port(name: &str, port: u16) -> anyhow::Result<()> {
+    _check_port(&read_ports()?, name, port)
+}
+
+fn _check_port(port_map: &BTreeMap<String, u16>, name: &str, port: u16)
+    -> anyhow::Result<()>
+{
+    if let Some((other, _)) = port_map.iter()
+        .find(|(other, p)| **p == port && *other != name)
+    {
+        anyhow::bail!("port {} is already used by instance {:?}",
+            port, other);
+    }
+    if port_map.get(name) != Some(&port) && port_in_use(port) {
+        return Err(anyhow::anyhow!("port {} is already in use \
+                by another process", port))
+            .hint("choose another port")
+            .map_err(Into::into);
+    }
+    Ok(())
+}
+
+/// Records the port chosen by user in the port mapping, so it isn't
+/// allocated for other instances
+pub fn reserve_port(name: &str, port: u16) -> anyhow::Result<u16> {
+    let _lock = lock_ports()?;
+    let port_file = port_file()?;
+    let mut port_map = _read_ports(&port_file)?;
+    _check_port(&port_map, name, port)?;
+    port_map.insert(name.to_string(), port);
+    _write_ports(&port_map, &port_file).with_context(|| {
+        format!("failed writing port mapping {}", port_file.display())
+    })?;
+    Ok(port)
+}
+
 pub fn allocate_port(name: &str) -> anyhow::Result<u16> {
+    let _lock = lock_ports()?;
     let port_file = port_file()?;
     let mut port_map = _read_ports(&port_file)?;
     if let Some(port) = port_map.get(name) {
         return Ok(*port);
     }
-    let port = next_min_port(&port_map);
+    let port = next_min_port(&port_map, port_in_use);
     port_map.insert(name.to_string(), port);
     _write_ports(&port_map, &port_file).with_context(|| {
         format!("failed writing port mapping {}", port_file.display())
@@ -121,6 +270,33 @@ pub fn allocate_port(name: &str) -> anyhow::Result<u16> {
     Ok(port)
 }
 
+/// Moves the port of the renamed instance to the new name in the mapping
+pub fn rename_port(old_name: &str, new_name: &str) -> anyhow::Result<()> {
+    let _lock = lock_ports()?;
+    let port_file = port_file()?;
+    let mut port_map = _read_ports(&port_file)?;
+    if let Some(port) = port_map.remove(old_name) {
+        port_map.insert(new_name.to_string(), port);
+        _write_ports(&port_map, &port_file).with_context(|| {
+            format!("failed writing port mapping {}", port_file.display())
+        })?;
+    }
+    Ok(())
+}
+
+/// Removes the port of the destroyed instance from the port mapping
+pub fn release_port(name: &str) -> anyhow::Result<()> {
+    let _lock = lock_ports()?;
+    let port_file = port_file()?;
+    let mut port_map = _read_ports(&port_file)?;
+    if port_map.remove(name).is_some() {
+        _write_ports(&port_map, &port_file).with_context(|| {
+            format!("failed writing port mapping {}", port_file.display())
+        })?;
+    }
+    Ok(())
+}
+
 fn find_version<F>(methods: &Methods, mut cond: F)
     -> anyhow::Result<Option<(DistributionRef, InstallMethod)>>
     where F: FnMut(&DistributionRef) -> bool
@@ -215,7 +391,36 @@ pub fn init(options: &Init) -> anyhow::Result<()> {
     let (distr, meth_name, method) = find_distribution(
         &*current_os, &avail_methods,
         &version_query, &options.method)?;
-    let port = allocate_port(&options.name)?;
+    if let Some(dsn) = &options.backend_dsn {
+        if meth_name == InstallMethod::Docker {
+            anyhow::bail!("`--backend-dsn` is not supported \
+                for docker instances");
+        }
+        let url = Url::parse(dsn).context("invalid backend DSN")?;
+        if !matches!(url.scheme(), "postgres" | "postgresql") {
+            anyhow::bail!("backend DSN must start with `postgres://`");
+        }
+    }
+    let tmp_dir = if options.temporary {
+        if meth_name == InstallMethod::Docker || options.backend_dsn.is_some()
+            || options.system
+        {
+            anyhow::bail!("`--temporary` is only supported for local \
+                instances installed using packages");
+        }
+        Some(tempfile::Builder::new().prefix("edgedb-").tempdir()?)
+    } else {
+        None
+    };
+    let port = if let Some(port) = options.port {
+        reserve_port(&options.name, port)?
+    } else {
+        allocate_port(&options.name)?
+    };
+    let storage = match &tmp_dir {
+        Some(tmp_dir) => Storage::UserDir(tmp_dir.path().join("data")),
+        None => method.get_storage(options.system, &options.name)?,
+    };
     let settings = Settings {
         name: options.name.clone(),
         system: options.system,
@@ -223,7 +428,7 @@ pub fn init(options: &Init) -> anyhow::Result<()> {
         distribution: distr,
         nightly: version_query.is_nightly(),
         method: meth_name,
-        storage: method.get_storage(options.system, &options.name)?,
+        storage,
         credentials: home_dir()?.join(".edgedb").join("credentials")
             .join(format!("{}.json", &options.name)),
         user: options.default_user.clone(),
@@ -231,10 +436,13 @@ pub fn init(options: &Init) -> anyhow::Result<()> {
         port,
         start_conf: options.start_conf,
         suppress_messages: false,
+        backend_dsn: options.backend_dsn.clone(),
     };
-    settings.print();
-    println!("Initializing EdgeDB instance...");
-    
+    if !options.temporary {
+        settings.print();
+        println!("Initializing EdgeDB instance...");
+    }
+
     if settings.system {
         anyhow::bail!("System instances are not implemented yet"); // TODO
     } else {
@@ -244,6 +452,9 @@ pub fn init(options: &Init) -> anyhow::Result<()> {
                 You may run `--overwrite` to overwrite the instance.",
                 settings.credentials.display());
         }
+        if let Some(tmp_dir) = tmp_dir {
+            return unix::run_temporary(&settings, tmp_dir);
+        }
         if method.storage_exists(&settings.storage)? {
             if options.overwrite {
                 method.clean_storage(&settings.storage)
@@ -271,6 +482,30 @@ pub fn init(options: &Init) -> anyhow::Result<()> {
     }
 }
 
+pub fn print_temporary(settings: &Settings) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct Info<'a> {
+        name: &'a str,
+        host: &'a str,
+        port: u16,
+        user: &'a str,
+        database: &'a str,
+        password: Option<&'a str>,
+        credentials_file: &'a Path,
+    }
+    let creds = read_credentials(&settings.credentials)?;
+    println!("{}", serde_json::to_string(&Info {
+        name: &settings.name,
+        host: creds.host.as_deref().unwrap_or("localhost"),
+        port: creds.port,
+        user: &creds.user,
+        database: creds.database.as_deref().unwrap_or(&settings.database),
+        password: creds.password.as_deref(),
+        credentials_file: &settings.credentials,
+    })?);
+    Ok(())
+}
+
 pub fn try_bootstrap(method: &dyn Method, settings: &Settings)
     -> anyhow::Result<bool>
 {
@@ -339,10 +574,17 @@ pub fn save_credentials(settings: &Settings, password: &str)
 
 pub fn init_credentials(settings: &Settings, inst: &InstanceRef)
     -> anyhow::Result<()>
+{
+    init_credentials_at(settings, inst.get_connector(true)?)
+}
+
+/// Same as `init_credentials` for a server which is not an instance yet
+pub fn init_credentials_at(settings: &Settings,
+    mut conn_params: client::Builder)
+    -> anyhow::Result<()>
 {
     let password = generate_password();
 
-    let mut conn_params = inst.get_connector(true)?;
     conn_params.wait_until_available(Duration::from_secs(30));
     task::block_on(async {
         let mut cli = conn_params.connect().await?;
@@ -364,6 +606,7 @@ impl Settings {
             method: self.method.clone(),
             port: self.port,
             start_conf: self.start_conf,
+            backend_dsn: self.backend_dsn.clone(),
         }
     }
     pub fn print(&self) {
@@ -386,6 +629,12 @@ impl Settings {
             Cell::new("Data Directory"),
             Cell::new(&self.storage.display().to_string()),
         ]));
+        if let Some(dsn) = &self.backend_dsn {
+            table.add_row(Row::new(vec![
+                Cell::new("Postgres Cluster"),
+                Cell::new(&backend_display(dsn)),
+            ]));
+        }
         table.add_row(Row::new(vec![
             Cell::new("Credentials Path"),
             Cell::new(&self.credentials.display().to_string()),
@@ -415,6 +664,19 @@ impl Settings {
     }
 }
 
+/// Backend DSN with the password hidden
+pub fn backend_display(dsn: &str) -> String {
+    match Url::parse(dsn) {
+        Ok(mut url) => {
+            if url.password().is_some() {
+                url.set_password(Some("***")).ok();
+            }
+            url.to_string()
+        }
+        Err(_) => "<invalid DSN>".into(),
+    }
+}
+
 impl Storage {
     pub fn display(&self) -> StorageDisplay {
         StorageDisplay(self)
@@ -430,3 +692,21 @@ impl fmt::Display for StorageDisplay<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{next_min_port, MIN_PORT};
+
+    #[test]
+    fn min_port() {
+        let mut ports = BTreeMap::new();
+        assert_eq!(next_min_port(&ports, |_| false), MIN_PORT);
+        ports.insert("a".to_string(), MIN_PORT);
+        ports.insert("b".to_string(), MIN_PORT + 2);
+        assert_eq!(next_min_port(&ports, |_| false), MIN_PORT + 1);
+        assert_eq!(next_min_port(&ports, |p| p == MIN_PORT + 1),
+                   MIN_PORT + 3);
+    }
+}
@@ -1,12 +1,17 @@
 use std::env;
 use std::fs;
+use std::path::Path;
+use std::process::Command as StdCommand;
 
 use anyhow::Context;
 
+use crate::checksum;
 use crate::commands::ExitCode;
+use crate::process;
 use crate::server::options::Install;
 use crate::server::detect::{self, VersionQuery};
 use crate::server::methods::InstallMethod;
+use crate::server::unix;
 
 pub mod operation;
 pub mod exit_codes;
@@ -56,6 +61,12 @@ pub fn install(options: &Install) -> Result<(), anyhow::Error> {
             edgedb server install --method=docker");
         return Err(ExitCode::new(exit_codes::DOCKER_CONTAINER))?;
     }
+    if options.no_verify {
+        checksum::disable();
+    }
+    if let Some(path) = &options.from_file {
+        return install_file(path, options);
+    }
     let current_os = detect::current_os()?;
     let avail_methods = current_os.get_available_methods()?;
     let methods = avail_methods.instantiate_all(&*current_os, false)?;
@@ -98,3 +109,52 @@ pub fn install(options: &Install) -> Result<(), anyhow::Error> {
           arg=if options.nightly { " --nightly" } else { "" });
     Ok(())
 }
+
+fn file_operations(path: &Path) -> anyhow::Result<Vec<Operation>> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let cmd = match ext {
+        "deb" => {
+            Command::new("apt-get")
+            .arg("install")
+            .arg("-y")
+            .arg(path)
+            .env("DEBIAN_FRONTEND",
+                env::var("DEBIAN_FRONTEND")
+                .unwrap_or_else(|_| "noninteractive".into()))
+        }
+        "rpm" => Command::new("yum").arg("-y").arg("install").arg(path),
+        "pkg" => {
+            Command::new("installer")
+            .arg("-package").arg(path)
+            .arg("-target").arg("/")
+        }
+        _ => anyhow::bail!("unsupported package {}: \
+            expected `.deb`, `.rpm` or `.pkg` file \
+            (or a docker image archive with `--method=docker`)",
+            path.display()),
+    };
+    Ok(vec![Operation::PrivilegedCmd(
+        cmd.env("_EDGEDB_INSTALL_SKIP_BOOTSTRAP", "1"))])
+}
+
+fn install_file(path: &Path, options: &Install) -> anyhow::Result<()> {
+    let path = fs::canonicalize(path)
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    checksum::verify_file(&path, options.sha256.as_deref())?;
+    println!("Installing EdgeDB from {}...", path.display());
+    if options.method == Some(InstallMethod::Docker) {
+        process::run(StdCommand::new("docker")
+            .arg("image")
+            .arg("load")
+            .arg("--input")
+            .arg(&path))?;
+    } else {
+        unix::Unix::new().perform(file_operations(&path)?,
+            "installation",
+            "edgedb server install")?;
+    }
+    println!("\nEdgedb server is installed now. Great!\n\
+        Initialize and start a new database instance with:\n  \
+          edgedb server init <instance-name>");
+    Ok(())
+}
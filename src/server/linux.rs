@@ -9,16 +9,19 @@ use edgedb_client as client;
 use serde::Serialize;
 
 use crate::credentials::{self, get_connector};
+use crate::secrets;
 use crate::platform::{get_current_uid};
 use crate::process;
 use crate::server::control::read_metadata;
-use crate::server::detect::Lazy;
+use crate::server::detect::{self, Lazy};
 use crate::server::distribution::{MajorVersion};
 use crate::server::docker::DockerCandidate;
 use crate::server::errors::InstanceNotFound;
+use crate::server::logs as server_logs;
 use crate::server::metadata::Metadata;
 use crate::server::methods::{InstallationMethods, InstallMethod};
 use crate::server::options::{StartConf, Start, Stop, Restart, Logs, Destroy};
+use crate::server::options::Schedule;
 use crate::server::os_trait::{CurrentOs, Method, Instance, InstanceRef};
 use crate::server::package::PackageCandidate;
 use crate::server::status::{Service, Status};
@@ -87,6 +90,36 @@ impl Instance for LocalInstance<'_> {
     fn get_start_conf(&self) -> anyhow::Result<StartConf> {
         Ok(self.get_meta()?.start_conf)
     }
+    fn get_backend_dsn(&self) -> anyhow::Result<Option<String>> {
+        Ok(self.get_meta()?.backend_dsn.clone())
+    }
+    fn set_port(&self, port: u16) -> anyhow::Result<()> {
+        let mut meta = self.get_meta()?.clone();
+        meta.port = port;
+        unix::write_metadata(&self.path.join("metadata.json"), &meta)?;
+        update_systemd_service(&self.name, &meta)
+    }
+    fn rename(&self, new_name: &str) -> anyhow::Result<()> {
+        let meta = self.get_meta()?;
+        let new_path = unix::storage_dir(new_name)?;
+        if new_path.exists() {
+            anyhow::bail!("directory {} already exists", new_path.display());
+        }
+        let mut cmd = Command::new("systemctl");
+        cmd.arg("--user").arg("disable").arg(unit_name(&self.name));
+        if let Err(e) = process::run_or_stderr(&mut cmd)? {
+            log::info!("Cannot disable {}: {}",
+                       unit_name(&self.name), e.trim());
+        }
+        let unit_path = systemd_service_path(&self.name, false)?;
+        if unit_path.exists() {
+            fs::remove_file(&unit_path)?;
+        }
+        fs::rename(&self.path, &new_path)
+            .with_context(|| format!("cannot move {} to {}",
+                self.path.display(), new_path.display()))?;
+        create_systemd_service(new_name, meta)
+    }
     fn start(&self, options: &Start) -> anyhow::Result<()> {
         if options.foreground {
             process::run(&mut self.get_command()?)?;
@@ -151,8 +184,13 @@ impl Instance for LocalInstance<'_> {
     fn get_command(&self) -> anyhow::Result<Command> {
         let socket_dir = self.socket_dir()?;
         let mut cmd = Command::new(get_server_path(Some(self.get_slot()?)));
-        cmd.arg("--port").arg(self.get_meta()?.port.to_string());
-        cmd.arg("--data-dir").arg(&self.path);
+        let meta = self.get_meta()?;
+        cmd.arg("--port").arg(meta.port.to_string());
+        // DSN contains password, so it's not passed on the command line
+        match &meta.backend_dsn {
+            Some(dsn) => cmd.env("EDGEDB_BACKEND_DSN", dsn),
+            None => cmd.arg("--data-dir").arg(&self.path),
+        };
         cmd.arg("--runstate-dir").arg(&socket_dir);
         Ok(cmd)
     }
@@ -184,7 +222,11 @@ impl Instance for LocalInstance<'_> {
         if logs.follow {
             cmd.arg("--follow");
         }
-        process::run(&mut cmd)
+        if logs.filtered() {
+            // message only, records are parsed from the server's own headers
+            cmd.arg("--output=cat");
+        }
+        server_logs::run(&mut cmd, logs)
     }
 }
 
@@ -233,6 +275,17 @@ impl CurrentOs for Unknown {
 }
 
 pub fn detect_distro() -> Result<Box<dyn CurrentOs>, anyhow::Error> {
+    if detect::is_musl() {
+        let rel = os_release::OsRelease::new().ok();
+        return Ok(Box::new(Unknown {
+            distro_name: rel.as_ref().map(|r| r.id.clone())
+                .unwrap_or_else(|| "<unknown>".into()),
+            distro_version: rel.as_ref().map(|r| r.version_id.clone())
+                .unwrap_or_else(|| "<unknown>".into()),
+            error: anyhow::anyhow!("Native packages are not available \
+                for musl-based distributions"),
+        }));
+    }
     if Path::new("/etc/os-release").exists() {
         let rel = os_release::OsRelease::new()?;
         match &rel.id[..] {
@@ -292,9 +345,10 @@ Type=notify
 {userinfo}
 
 Environment=EDGEDATA={directory}
+{backend_env}
 RuntimeDirectory=edgedb-{instance_name}
 
-ExecStart={server_path} --data-dir=${{EDGEDATA}} --runstate-dir=%t/edgedb-{instance_name} --port={port}
+ExecStart={server_path} {storage} --runstate-dir=%t/edgedb-{instance_name} --port={port}
 ExecReload=/bin/kill -HUP ${{MAINPID}}
 KillMode=mixed
 TimeoutSec=0
@@ -306,6 +360,15 @@ WantedBy=multi-user.target
         directory=path.display(),
         server_path=get_server_path(meta.slot.as_ref()).display(),
         port=meta.port,
+        backend_env=meta.backend_dsn.as_ref()
+            .map(|dsn| format!("Environment={}", systemd_quote(
+                &format!("EDGEDB_BACKEND_DSN={}", dsn))))
+            .unwrap_or_default(),
+        storage=if meta.backend_dsn.is_some() {
+            ""
+        } else {
+            "--data-dir=${EDGEDATA}"
+        },
         userinfo=if system {
             "User=edgedb\n\
              Group=edgedb"
@@ -315,6 +378,24 @@ WantedBy=multi-user.target
     ))
 }
 
+/// Quotes a value for the unit file, so `%` specifiers aren't expanded
+fn systemd_quote(value: &str) -> String {
+    let mut buf = String::with_capacity(value.len() + 2);
+    buf.push('"');
+    for c in value.chars() {
+        match c {
+            '%' => buf.push_str("%%"),
+            '"' | '\\' => {
+                buf.push('\\');
+                buf.push(c);
+            }
+            c => buf.push(c),
+        }
+    }
+    buf.push('"');
+    buf
+}
+
 fn unit_dir(system: bool) -> anyhow::Result<PathBuf> {
     if system {
         Ok(PathBuf::from("/etc/systemd/system"))
@@ -335,17 +416,25 @@ pub fn systemd_service_path(name: &str, system: bool)
     Ok(unit_dir(system)?.join(&unit_name(name)))
 }
 
-pub fn create_systemd_service(name: &str, meta: &Metadata)
+/// Rewrites the unit file of the instance and reloads systemd
+fn update_systemd_service(name: &str, meta: &Metadata)
     -> anyhow::Result<()>
 {
-    let unit_dir = unit_dir(false)?;
-    fs::create_dir_all(&unit_dir)?;
-    let unit_name = unit_name(name);
-    let unit_path = unit_dir.join(&unit_name);
+    let unit_path = unit_dir(false)?.join(unit_name(name));
     fs::write(&unit_path, systemd_unit(name, meta)?)?;
     process::run(Command::new("systemctl")
         .arg("--user")
         .arg("daemon-reload"))?;
+    Ok(())
+}
+
+pub fn create_systemd_service(name: &str, meta: &Metadata)
+    -> anyhow::Result<()>
+{
+    let unit_dir = unit_dir(false)?;
+    fs::create_dir_all(&unit_dir)?;
+    let unit_name = unit_name(name);
+    update_systemd_service(name, meta)?;
     if meta.start_conf == StartConf::Auto {
         process::run(Command::new("systemctl")
             .arg("--user")
@@ -355,6 +444,118 @@ pub fn create_systemd_service(name: &str, meta: &Metadata)
     Ok(())
 }
 
+fn backup_unit_name(name: &str) -> String {
+    format!("edgedb-backup@{}", name)
+}
+
+fn backup_units(name: &str, exe: &Path, schedule: Schedule, keep: usize)
+    -> (String, String)
+{
+    let service = format!(r###"
+[Unit]
+Description=Backup of EdgeDB instance {instance_name:?}
+Documentation=https://edgedb.com/
+After=edgedb-server@{instance_name}.service
+
+[Service]
+Type=oneshot
+ExecStart="{exe}" server backup run {instance_name} --keep={keep}
+    "###,
+        instance_name=name,
+        exe=exe.display(),
+        keep=keep,
+    );
+    let timer = format!(r###"
+[Unit]
+Description=Scheduled backups of EdgeDB instance {instance_name:?}
+
+[Timer]
+OnCalendar={schedule}
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+    "###,
+        instance_name=name,
+        schedule=schedule,
+    );
+    (service, timer)
+}
+
+pub fn create_backup_timer(name: &str, exe: &Path,
+    schedule: Schedule, keep: usize)
+    -> anyhow::Result<()>
+{
+    let unit_dir = unit_dir(false)?;
+    fs::create_dir_all(&unit_dir)?;
+    let unit_name = backup_unit_name(name);
+    let (service, timer) = backup_units(name, exe, schedule, keep);
+    fs::write(unit_dir.join(format!("{}.service", unit_name)), service)?;
+    fs::write(unit_dir.join(format!("{}.timer", unit_name)), timer)?;
+    process::run(Command::new("systemctl")
+        .arg("--user")
+        .arg("daemon-reload"))?;
+    process::run(Command::new("systemctl")
+        .arg("--user")
+        .arg("enable")
+        .arg("--now")
+        .arg(format!("{}.timer", unit_name)))?;
+    Ok(())
+}
+
+/// Returns schedule and number of kept backups of the backup timer, if any
+pub fn read_backup_timer(name: &str)
+    -> anyhow::Result<Option<(Schedule, usize)>>
+{
+    let unit_dir = unit_dir(false)?;
+    let unit_name = backup_unit_name(name);
+    let timer_path = unit_dir.join(format!("{}.timer", unit_name));
+    if !timer_path.exists() {
+        return Ok(None);
+    }
+    let timer = fs::read_to_string(&timer_path)?;
+    let service = fs::read_to_string(
+        unit_dir.join(format!("{}.service", unit_name)))?;
+    let schedule = timer.lines()
+        .find_map(|line| line.strip_prefix("OnCalendar="))
+        .context("no schedule in the backup timer")?
+        .trim().parse()?;
+    let keep = service.split_whitespace()
+        .find_map(|arg| arg.strip_prefix("--keep="))
+        .context("no `--keep` in the backup service")?
+        .parse().context("invalid `--keep` in the backup service")?;
+    Ok(Some((schedule, keep)))
+}
+
+/// Removes backup timer, returns `false` if there was no timer
+pub fn remove_backup_timer(name: &str) -> anyhow::Result<bool> {
+    let unit_dir = unit_dir(false)?;
+    let unit_name = backup_unit_name(name);
+    let timer_path = unit_dir.join(format!("{}.timer", unit_name));
+    if !timer_path.exists() {
+        return Ok(false);
+    }
+    let mut cmd = Command::new("systemctl");
+    cmd.arg("--user");
+    cmd.arg("disable");
+    cmd.arg("--now");
+    cmd.arg(format!("{}.timer", unit_name));
+    match process::run_or_stderr(&mut cmd)? {
+        Ok(()) => {}
+        Err(e) if systemd_is_not_found_error(&e) => {}
+        Err(e) => Err(anyhow::anyhow!("Error running {:?}: {}", cmd, e))?,
+    }
+    fs::remove_file(&timer_path)?;
+    let service_path = unit_dir.join(format!("{}.service", unit_name));
+    if service_path.exists() {
+        fs::remove_file(&service_path)?;
+    }
+    process::run(Command::new("systemctl")
+        .arg("--user")
+        .arg("daemon-reload"))?;
+    Ok(true)
+}
+
 pub fn systemd_status(name: &str, system: bool) -> Service {
     use Service::*;
 
@@ -451,7 +652,7 @@ fn systemd_is_not_found_error(e: &str) -> bool {
 pub fn destroy(options: &Destroy) -> anyhow::Result<()> {
     let system = false;
     let mut found = false;
-    let name = &options.name;
+    let name = options.instance()?;
     let svc_name = format!("edgedb-server@{}", name);
     log::info!(target: "edgedb::server::destroy",
         "Stopping service {}", svc_name);
@@ -493,20 +694,21 @@ pub fn destroy(options: &Destroy) -> anyhow::Result<()> {
             "Removing data directory {}", dir.display());
         fs::remove_dir_all(&dir)?;
     }
-    let credentials = credentials::path(&options.name)?;
+    let credentials = credentials::path(name)?;
     if credentials.exists() {
         found = true;
         log::info!(target: "edgedb::server::destroy",
             "Removing credentials file {}", credentials.display());
         fs::remove_file(&credentials)?;
+        secrets::delete_password(&credentials);
     }
     if found {
         Ok(())
     } else if let Some(e) = not_found_error {
         Err(InstanceNotFound(anyhow::anyhow!(
-            "no instance {:?} found: {}", options.name, e.trim())).into())
+            "no instance {:?} found: {}", name, e.trim())).into())
     } else {
         Err(InstanceNotFound(anyhow::anyhow!(
-            "no instance {:?} found", options.name)).into())
+            "no instance {:?} found", name)).into())
     }
 }
@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use async_std::task;
+use edgedb_client::Builder;
+use futures_util::future::join_all;
+use prettytable::{Table, Row, Cell};
+
+use crate::async_util::timeout;
+use crate::credentials;
+use crate::server::detect;
+use crate::server::options::ListInstances;
+use crate::server::reset_password::read_credentials;
+use crate::server::status::Service;
+use crate::table;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+
+struct Instance {
+    name: String,
+    method: &'static str,
+    port: Option<u16>,
+    /// Major version from instance metadata (local instances only)
+    major_version: Option<String>,
+    /// Whether service manager reports the instance is running
+    service_running: Option<bool>,
+    connector: Option<Builder>,
+}
+
+struct Probed {
+    instance: Instance,
+    version: Option<String>,
+    status: &'static str,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonInstance<'a> {
+    name: &'a str,
+    method: &'a str,
+    port: Option<u16>,
+    version: Option<&'a str>,
+    status: &'a str,
+}
+
+
+fn local_instances() -> anyhow::Result<Vec<Instance>> {
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let mut result = Vec::new();
+    for meth in methods.values() {
+        for inst in meth.all_instances()? {
+            let status = inst.get_status();
+            let meta = status.metadata.as_ref().ok();
+            result.push(Instance {
+                name: status.name.clone(),
+                method: status.method.short_name(),
+                port: meta.map(|m| m.port),
+                major_version: meta.map(|m| m.version.title().to_string()),
+                service_running: Some(matches!(status.service,
+                                               Service::Running {..})),
+                connector: inst.get_connector(false).ok(),
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Instances that have credentials but are not managed locally
+fn linked_instances(local: &BTreeSet<String>)
+    -> anyhow::Result<Vec<Instance>>
+{
+    let mut result = Vec::new();
+    for name in credentials::all_instance_names()? {
+        if local.contains(&name) {
+            continue;
+        }
+        let path = credentials::path(&name)?;
+        let port = match read_credentials(&path) {
+            Ok(creds) => Some(creds.port),
+            Err(e) => {
+                log::warn!("Cannot read {}: {:#}", path.display(), e);
+                None
+            }
+        };
+        result.push(Instance {
+            connector: credentials::get_connector(&name).ok(),
+            name,
+            method: "remote",
+            port,
+            major_version: None,
+            service_running: None,
+        });
+    }
+    Ok(result)
+}
+
+async fn fetch_version(connector: &Builder) -> anyhow::Result<String> {
+    let mut cli = connector.connect().await?;
+    cli.get_version().await
+}
+
+async fn probe(instance: Instance) -> Probed {
+    if instance.service_running == Some(false) {
+        return Probed { instance, version: None, status: "stopped" };
+    }
+    let result = match &instance.connector {
+        Some(connector) => {
+            timeout(PROBE_TIMEOUT, fetch_version(connector)).await
+        }
+        None => Err(anyhow::anyhow!("no credentials")),
+    };
+    match result {
+        Ok(version) => Probed {
+            instance,
+            version: Some(version),
+            status: "running",
+        },
+        Err(e) => {
+            log::info!("Cannot connect to {:?}: {:#}", instance.name, e);
+            Probed { instance, version: None, status: "unreachable" }
+        }
+    }
+}
+
+impl Probed {
+    fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+            .or(self.instance.major_version.as_deref())
+    }
+    fn json(&self) -> JsonInstance<'_> {
+        JsonInstance {
+            name: &self.instance.name,
+            method: self.instance.method,
+            port: self.instance.port,
+            version: self.version(),
+            status: self.status,
+        }
+    }
+}
+
+pub fn list_instances(options: &ListInstances) -> anyhow::Result<()> {
+    let mut instances = local_instances()?;
+    let local = instances.iter().map(|i| i.name.clone()).collect();
+    instances.extend(linked_instances(&local)?);
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let probed = task::block_on(join_all(instances.into_iter().map(probe)));
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&probed
+            .iter()
+            .map(|p| p.json())
+            .collect::<Vec<_>>()
+        )?);
+        return Ok(());
+    }
+    if probed.is_empty() {
+        eprintln!("No instances found");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Name", "Version", "Port", "Status", "Method"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for item in &probed {
+        table.add_row(Row::new(vec![
+            Cell::new(&item.instance.name),
+            Cell::new(item.version().unwrap_or("?")),
+            Cell::new(&item.instance.port
+                .map(|p| p.to_string()).unwrap_or("?".into())),
+            Cell::new(item.status),
+            Cell::new(item.instance.method),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
@@ -0,0 +1,306 @@
+//! Filtering of the server logs for `edgedb server logs`
+//!
+//! Log lines of the server look like:
+//!
+//! ```text
+//! INFO 9742 2021-06-10 14:47:34,327 edb.server: Serving on 127.0.0.1:10700
+//! ```
+//!
+//! Lines that don't start with such a header (e.g. tracebacks) belong to the
+//! preceding record.
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::process;
+use crate::server::options::Logs;
+
+
+/// Time after which the last record is printed in `--follow` mode if no
+/// continuation lines arrive
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(200);
+
+static HEADER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?x)^
+        (?P<severity>[A-Z]+)\s+
+        (?:(?P<pid>\d+)\s+)?
+        (?P<timestamp>\d{4}-\d\d-\d\d[T\ ]\d\d:\d\d:\d\d(?:[.,]\d+)?)\s+
+        (?P<logger>[^\s:]+):\s?
+        (?P<message>.*)$
+    ").expect("valid regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(serde::Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum Severity {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+#[derive(Debug)]
+struct Record {
+    severity: Option<Severity>,
+    pid: Option<u32>,
+    timestamp: Option<NaiveDateTime>,
+    logger: Option<String>,
+    message: String,
+}
+
+#[derive(serde::Serialize)]
+struct JsonRecord<'a> {
+    severity: Option<Severity>,
+    pid: Option<u32>,
+    timestamp: Option<String>,
+    logger: Option<&'a str>,
+    message: &'a str,
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Severity> {
+        use Severity::*;
+        match &s.to_lowercase()[..] {
+            "d" | "debug" => Ok(Debug),
+            "i" | "info" => Ok(Info),
+            "w" | "warn" | "warning" => Ok(Warning),
+            "e" | "error" => Ok(Error),
+            "c" | "critical" => Ok(Critical),
+            _ => anyhow::bail!("unknown severity {:?}", s),
+        }
+    }
+}
+
+/// Parses `--since` value: either a duration back from now (e.g. `1h`)
+/// or a local time (`2021-06-10 14:00:00` or `2021-06-10`)
+pub fn parse_since(value: &str) -> anyhow::Result<NaiveDateTime> {
+    if let Ok(dur) = humantime::parse_duration(value) {
+        let dur = chrono::Duration::from_std(dur)
+            .context("duration is too large")?;
+        return Ok(Local::now().naive_local() - dur);
+    }
+    for fmt in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+    {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, fmt) {
+            return Ok(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms(0, 0, 0));
+    }
+    anyhow::bail!("expected duration (e.g. `30m`) or time \
+        (e.g. `2021-06-10 14:00:00`)")
+}
+
+fn parse_timestamp(value: &str) -> Option<NaiveDateTime> {
+    let value = value.replace(',', ".").replace('T', " ");
+    NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S%.f").ok()
+}
+
+fn parse_header(line: &str) -> Option<Record> {
+    let caps = HEADER.captures(line)?;
+    let severity = caps["severity"].parse().ok()?;
+    Some(Record {
+        severity: Some(severity),
+        pid: caps.name("pid").and_then(|p| p.as_str().parse().ok()),
+        timestamp: parse_timestamp(&caps["timestamp"]),
+        logger: Some(caps["logger"].to_string()),
+        message: caps["message"].to_string(),
+    })
+}
+
+/// Groups lines into records
+#[derive(Default)]
+struct Parser {
+    pending: Option<Record>,
+}
+
+impl Parser {
+    /// Returns the previous record if the line starts a new one
+    fn line(&mut self, line: &str) -> Option<Record> {
+        if let Some(record) = parse_header(line) {
+            return self.pending.replace(record);
+        }
+        match &mut self.pending {
+            Some(record) => {
+                record.message.push('\n');
+                record.message.push_str(line);
+                None
+            }
+            None => {
+                self.pending = Some(Record {
+                    severity: None,
+                    pid: None,
+                    timestamp: None,
+                    logger: None,
+                    message: line.to_string(),
+                });
+                None
+            }
+        }
+    }
+    fn flush(&mut self) -> Option<Record> {
+        self.pending.take()
+    }
+}
+
+impl Logs {
+    /// Whether logs have to be parsed rather than passed through
+    pub fn filtered(&self) -> bool {
+        self.severity.is_some() || self.since.is_some()
+            || self.grep.is_some() || self.json
+    }
+    fn matches(&self, record: &Record) -> bool {
+        if let (Some(min), Some(severity)) = (self.severity, record.severity)
+        {
+            if severity < min {
+                return false;
+            }
+        }
+        if let (Some(since), Some(time)) = (self.since, record.timestamp) {
+            if time < since {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.grep {
+            if !pattern.is_match(&record.message) {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+fn print_record(options: &Logs, record: &Record) -> anyhow::Result<()> {
+    if !options.matches(record) {
+        return Ok(());
+    }
+    if options.json {
+        println!("{}", serde_json::to_string(&JsonRecord {
+            severity: record.severity,
+            pid: record.pid,
+            timestamp: record.timestamp
+                .map(|t| t.format("%Y-%m-%dT%H:%M:%S%.3f").to_string()),
+            logger: record.logger.as_deref(),
+            message: &record.message,
+        })?);
+        return Ok(());
+    }
+    let mut header = Vec::new();
+    if let Some(severity) = record.severity {
+        header.push(format!("{:?}", severity).to_uppercase());
+    }
+    if let Some(pid) = record.pid {
+        header.push(pid.to_string());
+    }
+    if let Some(time) = record.timestamp {
+        header.push(time.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+    }
+    if let Some(logger) = &record.logger {
+        header.push(format!("{}:", logger));
+    }
+    if header.is_empty() {
+        println!("{}", record.message);
+    } else {
+        println!("{} {}", header.join(" "), record.message);
+    }
+    Ok(())
+}
+
+fn read_lines(input: impl Read + Send + 'static, tx: mpsc::Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(input).lines() {
+            match line {
+                Ok(line) => if tx.send(line).is_err() { break },
+                Err(e) => {
+                    log::warn!("Error reading logs: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Runs the command printing logs, filtering its output if requested
+///
+/// Both stdout and stderr are read, as some tools (e.g. `docker logs`)
+/// reproduce the server's stderr as is.
+pub fn run(cmd: &mut Command, options: &Logs) -> anyhow::Result<()> {
+    if !options.filtered() {
+        return process::run(cmd);
+    }
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("error running {:?}", cmd))?;
+    let (tx, rx) = mpsc::channel();
+    read_lines(child.stdout.take().expect("stdout is piped"), tx.clone());
+    read_lines(child.stderr.take().expect("stderr is piped"), tx);
+    let mut parser = Parser::default();
+    loop {
+        match rx.recv_timeout(FLUSH_TIMEOUT) {
+            Ok(line) => {
+                if let Some(record) = parser.line(&line) {
+                    print_record(options, &record)?;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if let Some(record) = parser.flush() {
+                    print_record(options, &record)?;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    if let Some(record) = parser.flush() {
+        print_record(options, &record)?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("process {:?} failed: {}", cmd, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Parser, Severity};
+
+    #[test]
+    fn records() {
+        let mut parser = Parser::default();
+        assert!(parser.line("INFO 9742 2021-06-10 14:47:34,327 edb.server: \
+            Serving on 127.0.0.1:10700").is_none());
+        let rec = parser.line("ERROR 9742 2021-06-10 14:48:00,001 \
+            edb.server.protocol: Traceback:").unwrap();
+        assert_eq!(rec.severity, Some(Severity::Info));
+        assert_eq!(rec.pid, Some(9742));
+        assert_eq!(rec.logger.as_deref(), Some("edb.server"));
+        assert_eq!(rec.message, "Serving on 127.0.0.1:10700");
+        assert_eq!(rec.timestamp.unwrap().to_string(),
+                   "2021-06-10 14:47:34.327");
+        assert!(parser.line("  File \"server.py\", line 1").is_none());
+        let rec = parser.flush().unwrap();
+        assert_eq!(rec.severity, Some(Severity::Error));
+        assert_eq!(rec.message, "Traceback:\n  File \"server.py\", line 1");
+        assert!(parser.flush().is_none());
+    }
+
+    #[test]
+    fn severity_order() {
+        assert!(Severity::Warning > Severity::Info);
+        assert_eq!("warn".parse::<Severity>().unwrap(), Severity::Warning);
+        assert!("verbose".parse::<Severity>().is_err());
+    }
+}
@@ -12,8 +12,10 @@ use once_cell::unsync::OnceCell;
 use serde::Serialize;
 
 use crate::credentials::{self, get_connector};
+use crate::secrets;
 use crate::platform::{get_current_uid, home_dir};
 use crate::process;
+use crate::server::cache;
 use crate::server::control::read_metadata;
 use crate::server::detect::{ARCH, Lazy, VersionQuery};
 use crate::server::distribution::{DistributionRef, Distribution, MajorVersion};
@@ -21,10 +23,11 @@ use crate::server::docker::DockerCandidate;
 use crate::server::errors::InstanceNotFound;
 use crate::server::init::{self, Storage};
 use crate::server::install::{self, Operation, Command};
+use crate::server::logs;
 use crate::server::metadata::Metadata;
 use crate::server::methods::{InstallationMethods, InstallMethod};
 use crate::server::options::{Start, Stop, Restart, Upgrade, Destroy, Logs};
-use crate::server::options::{StartConf};
+use crate::server::options::{StartConf, Schedule};
 use crate::server::os_trait::{CurrentOs, Method, Instance, InstanceRef};
 use crate::server::package::{PackageMethod, Package};
 use crate::server::package::{self, PackageCandidate, RepositoryInfo};
@@ -152,10 +155,8 @@ impl<'os> Method for PackageMethod<'os, Macos> {
     {
         let pkg = settings.distribution.downcast_ref::<Package>()
             .context("invalid macos package")?;
-        let tmpdir = tempfile::tempdir()?;
         let package_name = format!("edgedb-server-{}_{}.pkg",
             pkg.slot, pkg.version.as_ref().replace("-", "_"));
-        let pkg_path = tmpdir.path().join(&package_name);
         let url = if settings.distribution.major_version().is_nightly() {
             format!("https://packages.edgedb.com/archive/\
                 macos-{arch}.nightly/{name}",
@@ -165,7 +166,7 @@ impl<'os> Method for PackageMethod<'os, Macos> {
                 macos-{arch}/{name}",
                 arch=ARCH, name=package_name)
         };
-        task::block_on(remote::get_file(&pkg_path, &url))
+        let pkg_path = task::block_on(cache::download(&url))
             .context("failed to download package")?;
 
         let operations = vec![
@@ -335,9 +336,10 @@ impl<'os> Method for PackageMethod<'os, Macos> {
     }
     fn destroy(&self, options: &Destroy) -> anyhow::Result<()> {
         let mut found = false;
+        let name = options.instance()?;
         log::info!(target: "edgedb::server::destroy",
             "Unloading service");
-        let unit_path = unit_path(&options.name)?;
+        let unit_path = unit_path(name)?;
         process::run(&mut StdCommand::new("launchctl")
             .arg("unload").arg(&unit_path))?;
         if unit_path.exists() {
@@ -346,25 +348,26 @@ impl<'os> Method for PackageMethod<'os, Macos> {
                 "Removing unit file {}", unit_path.display());
             fs::remove_file(unit_path)?;
         }
-        let dir = unix::storage_dir(&options.name)?;
+        let dir = unix::storage_dir(name)?;
         if dir.exists() {
             found = true;
             log::info!(target: "edgedb::server::destroy",
                 "Removing data directory {}", dir.display());
             fs::remove_dir_all(&dir)?;
         }
-        let credentials = credentials::path(&options.name)?;
+        let credentials = credentials::path(name)?;
         if credentials.exists() {
             found = true;
             log::info!(target: "edgedb::server::destroy",
                 "Removing credentials file {}", credentials.display());
             fs::remove_file(&credentials)?;
+            secrets::delete_password(&credentials);
         }
         if found {
             Ok(())
         } else {
             Err(InstanceNotFound(anyhow::anyhow!(
-                "no instance {:?} found", options.name)).into())
+                "no instance {:?} found", name)).into())
         }
     }
 }
@@ -419,6 +422,44 @@ impl<'a> Instance for LocalInstance<'a> {
     fn get_start_conf(&self) -> anyhow::Result<StartConf> {
         Ok(self.get_meta()?.start_conf)
     }
+    fn get_backend_dsn(&self) -> anyhow::Result<Option<String>> {
+        Ok(self.get_meta()?.backend_dsn.clone())
+    }
+    fn set_port(&self, port: u16) -> anyhow::Result<()> {
+        let mut meta = self.get_meta()?.clone();
+        meta.port = port;
+        unix::write_metadata(&self.path.join("metadata.json"), &meta)?;
+        fs::write(launchd_plist_path(&self.name, false)?,
+                  plist_data(&self.name, &meta)?)?;
+        Ok(())
+    }
+    fn rename(&self, new_name: &str) -> anyhow::Result<()> {
+        let meta = self.get_meta()?;
+        let new_path = unix::storage_dir(new_name)?;
+        if new_path.exists() {
+            anyhow::bail!("directory {} already exists", new_path.display());
+        }
+        let unit_path = self.unit_path()?;
+        if unit_path.exists() {
+            let mut cmd = StdCommand::new("launchctl");
+            cmd.arg("unload").arg(&unit_path);
+            if let Err(e) = process::run_or_stderr(&mut cmd)? {
+                log::info!("Cannot unload {}: {}",
+                           unit_path.display(), e.trim());
+            }
+            fs::remove_file(&unit_path)?;
+        }
+        fs::rename(&self.path, &new_path)
+            .with_context(|| format!("cannot move {} to {}",
+                self.path.display(), new_path.display()))?;
+        fs::write(launchd_plist_path(new_name, false)?,
+                  plist_data(new_name, meta)?)?;
+        process::run(
+            StdCommand::new("launchctl").arg("enable")
+                .arg(launchd_name(new_name)),
+        )?;
+        Ok(())
+    }
     fn get_status(&self) -> Status {
         let system = false;
         let service = launchctl_status(&self.name, system,
@@ -480,8 +521,13 @@ impl<'a> Instance for LocalInstance<'a> {
     fn get_command(&self) -> anyhow::Result<StdCommand> {
         let socket_dir = self.socket_dir()?;
         let mut cmd = StdCommand::new(get_server_path(&self.get_slot()?));
-        cmd.arg("--port").arg(self.get_meta()?.port.to_string());
-        cmd.arg("--data-dir").arg(&self.path);
+        let meta = self.get_meta()?;
+        cmd.arg("--port").arg(meta.port.to_string());
+        // DSN contains password, so it's not passed on the command line
+        match &meta.backend_dsn {
+            Some(dsn) => cmd.env("EDGEDB_BACKEND_DSN", dsn),
+            None => cmd.arg("--data-dir").arg(&self.path),
+        };
         cmd.arg("--runstate-dir").arg(&socket_dir);
         Ok(cmd)
     }
@@ -513,7 +559,7 @@ impl<'a> Instance for LocalInstance<'a> {
             cmd.arg("-F");
         }
         cmd.arg(log_file(&self.name)?);
-        process::run(&mut cmd)
+        logs::run(&mut cmd, options)
     }
 }
 
@@ -543,6 +589,10 @@ pub fn launchd_plist_path(name: &str, system: bool)
     Ok(plist_dir(system)?.join(plist_name(name)))
 }
 
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
 fn plist_data(name: &str, meta: &Metadata)
     -> anyhow::Result<String>
 {
@@ -565,11 +615,13 @@ fn plist_data(name: &str, meta: &Metadata)
     <key>ProgramArguments</key>
     <array>
         <string>{server_path}</string>
-        <string>--data-dir={directory}</string>
+        {storage}
         <string>--runstate-dir={runtime_dir}</string>
         <string>--port={port}</string>
     </array>
 
+    {environment}
+
     <key>RunAtLoad</key>
     <true/>
 
@@ -589,7 +641,18 @@ fn plist_data(name: &str, meta: &Metadata)
 </plist>
 "###,
         instance_name=name,
-        directory=unix::storage_dir(name)?.display(),
+        storage=match &meta.backend_dsn {
+            Some(_) => String::new(),
+            None => format!("<string>--data-dir={}</string>",
+                            unix::storage_dir(name)?.display()),
+        },
+        environment=meta.backend_dsn.as_ref()
+            .map(|dsn| format!("<key>EnvironmentVariables</key>\n    \
+                <dict>\n        \
+                    <key>EDGEDB_BACKEND_DSN</key>\n        \
+                    <string>{}</string>\n    \
+                </dict>", xml_escape(dsn)))
+            .unwrap_or_default(),
         server_path=path.display(),
         runtime_dir=runtime_dir(&name)?.display(),
         log_path=log_file(&name)?.display(),
@@ -685,6 +748,122 @@ pub fn create_launchctl_service(name: &str, meta: &Metadata)
     Ok(())
 }
 
+fn backup_label(name: &str) -> String {
+    format!("edgedb-backup-{}", name)
+}
+
+fn backup_plist_path(name: &str) -> anyhow::Result<PathBuf> {
+    Ok(plist_dir(false)?
+        .join(format!("com.edgedb.edgedb-backup-{}.plist", name)))
+}
+
+fn backup_plist_data(name: &str, exe: &Path, schedule: Schedule, keep: usize)
+    -> anyhow::Result<String>
+{
+    Ok(format!(r###"
+<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN"
+        "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>server</string>
+        <string>backup</string>
+        <string>run</string>
+        <string>{instance_name}</string>
+        <string>--keep={keep}</string>
+    </array>
+
+    <key>StartCalendarInterval</key>
+    <dict>
+        {interval}
+    </dict>
+
+    <key>StandardOutPath</key>
+    <string>{log_path}</string>
+    <key>StandardErrorPath</key>
+    <string>{log_path}</string>
+</dict>
+</plist>
+"###,
+        label=backup_label(name),
+        instance_name=name,
+        exe=exe.display(),
+        keep=keep,
+        interval=match schedule {
+            Schedule::Hourly => "<key>Minute</key><integer>0</integer>",
+            Schedule::Daily => "<key>Hour</key><integer>3</integer>\n\
+                <key>Minute</key><integer>0</integer>",
+            Schedule::Weekly => "<key>Weekday</key><integer>0</integer>\n\
+                <key>Hour</key><integer>3</integer>\n\
+                <key>Minute</key><integer>0</integer>",
+        },
+        log_path=home_dir()?
+            .join(format!(".edgedb/logs/{}-backup.log", name)).display(),
+    ))
+}
+
+pub fn create_backup_agent(name: &str, exe: &Path,
+    schedule: Schedule, keep: usize)
+    -> anyhow::Result<()>
+{
+    // unload the previous schedule if any, so the new one is used
+    remove_backup_agent(name)?;
+    let plist_path = backup_plist_path(name)?;
+    fs::create_dir_all(plist_path.parent().unwrap())?;
+    fs::create_dir_all(home_dir()?.join(".edgedb/logs"))?;
+    fs::write(&plist_path, backup_plist_data(name, exe, schedule, keep)?)?;
+    process::run(StdCommand::new("launchctl")
+        .arg("bootstrap")
+        .arg(format!("gui/{}", get_current_uid()))
+        .arg(&plist_path))?;
+    Ok(())
+}
+
+/// Returns schedule and number of kept backups of the backup agent, if any
+pub fn read_backup_agent(name: &str)
+    -> anyhow::Result<Option<(Schedule, usize)>>
+{
+    let plist_path = backup_plist_path(name)?;
+    if !plist_path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(&plist_path)?;
+    let schedule = if data.contains("<key>Weekday</key>") {
+        Schedule::Weekly
+    } else if data.contains("<key>Hour</key>") {
+        Schedule::Daily
+    } else {
+        Schedule::Hourly
+    };
+    let keep = data.split("<string>--keep=").nth(1)
+        .and_then(|tail| tail.split('<').next())
+        .context("no `--keep` in the backup agent")?
+        .parse().context("invalid `--keep` in the backup agent")?;
+    Ok(Some((schedule, keep)))
+}
+
+/// Removes backup agent, returns `false` if there was no agent
+pub fn remove_backup_agent(name: &str) -> anyhow::Result<bool> {
+    let plist_path = backup_plist_path(name)?;
+    if !plist_path.exists() {
+        return Ok(false);
+    }
+    let mut cmd = StdCommand::new("launchctl");
+    cmd.arg("bootout");
+    cmd.arg(format!("gui/{}/{}", get_current_uid(), backup_label(name)));
+    if let Err(e) = process::run_or_stderr(&mut cmd)? {
+        log::info!("Cannot unload {}: {}", backup_label(name), e.trim());
+    }
+    fs::remove_file(&plist_path)?;
+    Ok(true)
+}
+
 fn unit_path(name: &str) -> anyhow::Result<PathBuf> {
     let plist = format!("com.edgedb.edgedb-server-{}.plist", &name);
     Ok(home_dir()?.join("Library/LaunchAgents").join(plist))
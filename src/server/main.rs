@@ -1,13 +1,21 @@
 use crate::server::options::{ServerCommand, Command};
 
+use crate::server::backup;
+use crate::server::cache;
 use crate::server::control;
 use crate::server::destroy;
 use crate::server::detect;
+use crate::server::exec;
 use crate::server::info;
 use crate::server::init;
 use crate::server::install;
+use crate::server::link;
+use crate::server::list_instances;
 use crate::server::list_versions;
+use crate::server::rename;
 use crate::server::reset_password;
+use crate::server::set_port;
+use crate::server::share;
 use crate::server::uninstall;
 use crate::server::upgrade;
 
@@ -21,10 +29,18 @@ pub fn main(cmd: &ServerCommand) -> Result<(), anyhow::Error> {
         Init(c) => init::init(c),
         Destroy(c) => destroy::destroy(c),
         ListVersions(c) => list_versions::list_versions(c),
+        ListInstances(c) => list_instances::list_instances(c),
         Instance(c) => control::instance_command(c),
         Upgrade(c) => upgrade::upgrade(c),
         ResetPassword(c) => reset_password::reset_password(c),
+        Share(c) => share::share(c),
+        SetPort(c) => set_port::set_port(c),
+        Rename(c) => rename::rename(c),
+        Link(c) => link::link(c),
+        Exec(c) => exec::exec(c),
         Info(c) => info::info(c),
+        Cache(c) => cache::main(c),
+        Backup(c) => backup::main(c),
         _Detect(c) => detect::main(c),
     }
 }
@@ -17,6 +17,8 @@ pub struct Metadata {
     pub method: InstallMethod,
     pub port: u16,
     pub start_conf: StartConf,
+    /// DSN of the external Postgres cluster the server runs against
+    pub backend_dsn: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -30,6 +32,8 @@ pub struct MetadataV2 {
     method: InstallMethod,
     port: u16,
     start_conf: StartConf,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    backend_dsn: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -85,6 +89,7 @@ impl From<MetadataV1> for Metadata {
             method: m.method,
             port: m.port,
             start_conf: m.start_conf,
+            backend_dsn: None,
         }
     }
 }
@@ -98,6 +103,7 @@ impl From<MetadataV2> for Metadata {
             method: m.method,
             port: m.port,
             start_conf: m.start_conf,
+            backend_dsn: m.backend_dsn,
         }
     }
 }
@@ -112,6 +118,7 @@ impl From<Metadata> for MetadataV2 {
             method: m.method,
             port: m.port,
             start_conf: m.start_conf,
+            backend_dsn: m.backend_dsn,
         }
     }
 }
@@ -136,6 +143,7 @@ mod test {
             method: InstallMethod::Package,
             port: 10700,
             start_conf: StartConf::Auto,
+            backend_dsn: None,
         });
 
         assert_eq!(serde_json::from_str::<Metadata>(r###"
@@ -148,6 +156,7 @@ mod test {
             method: InstallMethod::Package,
             port: 10700,
             start_conf: StartConf::Auto,
+            backend_dsn: None,
         });
     }
 
@@ -160,6 +169,7 @@ mod test {
             method: InstallMethod::Package,
             port: 10700,
             start_conf: StartConf::Auto,
+            backend_dsn: None,
         }).unwrap(), r###"{
   "format": 2,
   "version": "1-alpha5",
@@ -176,6 +186,7 @@ mod test {
             method: InstallMethod::Package,
             port: 10700,
             start_conf: StartConf::Auto,
+            backend_dsn: None,
         }).unwrap(), r###"{
   "format": 2,
   "version": "nightly",
@@ -186,4 +197,20 @@ mod test {
   "start_conf": "Auto"
 }"###);
     }
+
+    #[test]
+    fn backend_dsn() {
+        let meta = Metadata {
+            version: MajorVersion::Stable(Version("1-beta3".into())),
+            current_version: None,
+            slot: Some("1-beta3".into()),
+            method: InstallMethod::Package,
+            port: 10700,
+            start_conf: StartConf::Auto,
+            backend_dsn: Some("postgres://pg/db".into()),
+        };
+        let text = serde_json::to_string(&meta).unwrap();
+        assert!(text.contains(r#""backend_dsn":"postgres://pg/db""#));
+        assert_eq!(serde_json::from_str::<Metadata>(&text).unwrap(), meta);
+    }
 }
@@ -24,15 +24,24 @@ mod docker;
 pub mod package;
 
 // commands
+mod backup;
+mod cache;
 pub mod control;
 pub mod destroy;
 pub mod errors;
+mod exec;
 mod info;
 pub mod init;
 pub mod install;
+mod link;
+mod list_instances;
 mod list_versions;
-mod reset_password;
+mod logs;
+mod rename;
+pub mod reset_password;
 mod revert;
+mod set_port;
+mod share;
 mod status;
 mod uninstall;
 mod upgrade;
@@ -36,7 +36,24 @@ pub trait Instance: fmt::Debug {
     fn get_current_version(&self) -> anyhow::Result<Option<&Version<String>>>;
     fn get_port(&self) -> anyhow::Result<u16>;
     fn get_start_conf(&self) -> anyhow::Result<StartConf>;
+    /// DSN of the external Postgres cluster if the instance uses one
+    fn get_backend_dsn(&self) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
     fn get_status(&self) -> Status;
+    /// Updates the port in metadata and the service definition
+    fn set_port(&self, _port: u16) -> anyhow::Result<()> {
+        anyhow::bail!("changing the port of {} instances is not supported",
+            self.method().name().short_name());
+    }
+    /// Moves the data directory and the service definition to the new name
+    ///
+    /// The instance must be stopped. Credentials, port mapping and projects
+    /// are updated by the caller.
+    fn rename(&self, _new_name: &str) -> anyhow::Result<()> {
+        anyhow::bail!("renaming {} instances is not supported",
+            self.method().name().short_name());
+    }
     fn start(&self, start: &Start) -> anyhow::Result<()>;
     fn stop(&self, stop: &Stop) -> anyhow::Result<()>;
     fn restart(&self, restart: &Restart) -> anyhow::Result<()>;
@@ -149,6 +166,15 @@ impl InstanceRef<'_> {
     pub fn get_port(&self) -> anyhow::Result<u16> {
         self.0.get_port()
     }
+    pub fn get_backend_dsn(&self) -> anyhow::Result<Option<String>> {
+        self.0.get_backend_dsn()
+    }
+    pub fn set_port(&self, port: u16) -> anyhow::Result<()> {
+        self.0.set_port(port)
+    }
+    pub fn rename(&self, new_name: &str) -> anyhow::Result<()> {
+        self.0.rename(new_name)
+    }
     pub fn get_start_conf(&self) -> anyhow::Result<StartConf> {
         self.0.get_start_conf()
     }
@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
 
 use crate::server::version::Version;
-use crate::server::detect::{Lazy, VersionQuery};
+use crate::server::detect::{self, Lazy, VersionQuery};
 use crate::server::os_trait::{CurrentOs, PreciseVersion};
 use crate::server::distribution::{Distribution, DistributionRef, MajorVersion};
 
@@ -87,14 +87,19 @@ impl PackageCandidate {
 
         if self.distro_supported {
             write!(buf,
-                " * Note: native packages are not supported for {} {}",
+                " * Note: native packages are not supported for {} {} ({})",
                 self.distro_name,
-                self.distro_version).unwrap();
+                self.distro_version,
+                detect::target()).unwrap();
         } else {
-            buf.push_str(" * Note: native packages are \
-                             not supported for this platform");
+            write!(buf, " * Note: native packages are \
+                           not supported for this platform ({})",
+                           detect::target()).unwrap();
         }
         buf.push('\n');
+        write!(buf, "   Native packages are available for: {}",
+            detect::PACKAGE_TARGETS.join(", ")).unwrap();
+        buf.push('\n');
     }
     pub fn make_method<'os, O>(&self, os: &'os O)
         -> anyhow::Result<PackageMethod<'os, O>>
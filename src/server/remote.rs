@@ -5,6 +5,7 @@ use async_std::fs;
 use async_std::io;
 use fn_error_context::context;
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 
 pub const USER_AGENT: &str = "edgedb";
@@ -109,16 +110,68 @@ pub async fn get_json_opt<T>(url: &str, context: &'static str)
     }
 }
 
-#[context("failed to download file at URL: {}", url)]
-pub async fn get_file(dest: impl AsRef<Path>, url: &str)
+#[context("failed to fetch URL: {}", url)]
+pub async fn get_bytes_opt(url: &str)
+    -> Result<Option<Vec<u8>>, anyhow::Error>
+{
+    log::info!("Fetching optional {}", url);
+    match
+        surf::get(url)
+        .header("User-Agent", USER_AGENT)
+        .await
+    {
+        Ok(res) if res.status() == 404 => Ok(None),
+        Ok(res) if res.status() != 200 => Err(HttpFailure(res))?,
+        Ok(mut res) => Ok(Some(res.body_bytes().await.map_err(HttpError)?)),
+        Err(e) => Err(HttpError(e))?,
+    }
+}
+
+#[context("failed to post JSON to URL: {}", url)]
+pub async fn post_json<B: Serialize>(url: &str, body: &B)
     -> Result<(), anyhow::Error>
 {
-    let dest = dest.as_ref();
-    log::info!("Downloading {} -> {}", url, dest.display());
-    let response = surf::get(url)
+    log::info!("Posting JSON to {}", url);
+    let body = surf::Body::from_json(body).map_err(HttpError)?;
+    match
+        surf::post(url)
         .header("User-Agent", USER_AGENT)
-        .await.ensure200(url)?;
-    let file = fs::File::create(dest).await
+        .body(body)
+        .await
+    {
+        Ok(res) if !res.status().is_success() => Err(HttpFailure(res))?,
+        Ok(_) => Ok(()),
+        Err(e) => Err(HttpError(e))?,
+    }
+}
+
+/// Downloads `url` to `dest` continuing partial download if `dest` exists
+#[context("failed to download file at URL: {}", url)]
+pub async fn get_file_resumable(dest: &Path, url: &str)
+    -> Result<(), anyhow::Error>
+{
+    let offset = fs::metadata(dest).await.map(|m| m.len()).unwrap_or(0);
+    let mut req = surf::get(url).header("User-Agent", USER_AGENT);
+    if offset > 0 {
+        req = req.header("Range", format!("bytes={}-", offset));
+    }
+    let response = req.await.map_err(HttpError)?;
+    let mut opts = fs::OpenOptions::new();
+    match response.status() {
+        s if s == 206 => {
+            log::info!("Resuming download {} -> {} at {} bytes",
+                url, dest.display(), offset);
+            opts.append(true);
+        }
+        s if s == 200 => {
+            log::info!("Downloading {} -> {}", url, dest.display());
+            opts.write(true).create(true).truncate(true);
+        }
+        // Partial file is already complete
+        s if s == 416 && offset > 0 => return Ok(()),
+        _ => Err(HttpFailure(response))?,
+    }
+    let file = opts.open(dest).await
         .with_context(|| format!("writing {:?}", dest.display()))?;
     io::copy(response, file).await
         .with_context(|| format!("downloading {:?} -> {:?}",
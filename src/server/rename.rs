@@ -0,0 +1,86 @@
+use std::fs;
+
+use anyhow::Context;
+use fn_error_context::context;
+
+use crate::credentials;
+use crate::hint::HintExt;
+use crate::secrets;
+use crate::server::backup;
+use crate::server::control;
+use crate::server::destroy::find_project_dirs;
+use crate::server::detect;
+use crate::server::init;
+use crate::server::options::{Rename, Start, Stop};
+use crate::server::status::Service;
+
+
+#[context("cannot move credentials of {:?} to {:?}", old_name, new_name)]
+fn rename_credentials(old_name: &str, new_name: &str) -> anyhow::Result<()> {
+    let old_path = credentials::path(old_name)?;
+    let new_path = credentials::path(new_name)?;
+    // keyring entry is keyed by the file name, so move it along, storing
+    // it first so the password is never lost
+    let password = secrets::get_password(&old_path);
+    if let Some(password) = &password {
+        if !secrets::copy_password(&old_path, &new_path, password) {
+            anyhow::bail!("cannot store password in the keyring");
+        }
+    }
+    if let Err(e) = fs::rename(&old_path, &new_path) {
+        if password.is_some() {
+            secrets::delete_password(&new_path);
+        }
+        return Err(e.into());
+    }
+    if password.is_some() {
+        secrets::delete_password(&old_path);
+    }
+    Ok(())
+}
+
+pub fn rename(options: &Rename) -> anyhow::Result<()> {
+    let old_name = &options.old_name;
+    let new_name = &options.new_name;
+    if old_name == new_name {
+        anyhow::bail!("instance is already named {:?}", new_name);
+    }
+    if credentials::path(new_name)?.exists() {
+        return Err(anyhow::anyhow!("instance {:?} already exists", new_name))
+            .hint("choose another name or destroy the existing instance")
+            .map_err(Into::into);
+    }
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let inst = control::get_exact_instance(&methods, old_name)?;
+    let running = matches!(inst.get_status().service, Service::Running {..});
+    if running {
+        if !options.force {
+            return Err(anyhow::anyhow!("instance {:?} is running", old_name))
+                .hint("stop the instance first or use `--force` to stop \
+                       and start it again automatically")
+                .map_err(Into::into);
+        }
+        inst.stop(&Stop { name: old_name.clone() })?;
+    }
+
+    inst.rename(new_name)?;
+    rename_credentials(old_name, new_name)?;
+    init::rename_port(old_name, new_name)?;
+    for dir in find_project_dirs(old_name)? {
+        let path = dir.join("instance-name");
+        fs::write(&path, new_name)
+            .with_context(|| format!("cannot write {}", path.display()))?;
+    }
+    if let Err(e) = backup::rename_schedule(old_name, new_name) {
+        log::warn!("{:#}. Use `edgedb server backup enable {}` \
+            to schedule backups again.", e, new_name.escape_default());
+    }
+
+    eprintln!("Instance {:?} is renamed to {:?}.", old_name, new_name);
+    if running {
+        let inst = control::get_instance(&methods, new_name)?;
+        inst.start(&Start { name: new_name.clone(), foreground: false })?;
+    }
+    Ok(())
+}
@@ -15,6 +15,7 @@ use crate::server::options::ResetPassword;
 use crate::server::detect;
 use crate::server::control;
 use crate::platform::{home_dir, tmp_file_name};
+use crate::secrets;
 
 const PASSWORD_LENGTH: usize = 24;
 const PASSWORD_CHARS: &[u8] = b"0123456789\
@@ -30,9 +31,13 @@ pub fn generate_password() -> String {
 }
 
 #[context("error reading credentials at {}", path.display())]
-fn read_credentials(path: &Path) -> anyhow::Result<Credentials> {
+pub fn read_credentials(path: &Path) -> anyhow::Result<Credentials> {
     let data = fs::read(&path)?;
-    Ok(serde_json::from_slice(&data)?)
+    let mut credentials: Credentials = serde_json::from_slice(&data)?;
+    if credentials.password.is_none() {
+        credentials.password = secrets::get_password(path);
+    }
+    Ok(credentials)
 }
 
 #[context("cannot write credentials file {}", path.display())]
@@ -40,8 +45,18 @@ pub fn write_credentials(path: &Path, credentials: &Credentials)
     -> anyhow::Result<()>
 {
     fs::create_dir_all(path.parent().unwrap())?;
+    let mut data = serde_json::to_value(credentials)?;
+    match &credentials.password {
+        Some(password) if secrets::set_password(path, password) => {
+            if let Some(fields) = data.as_object_mut() {
+                fields.remove("password");
+            }
+        }
+        Some(_) => {}
+        None => secrets::delete_password(path),
+    }
     let tmp_path = path.with_file_name(tmp_file_name(path));
-    fs::write(&tmp_path, serde_json::to_vec_pretty(&credentials)?)?;
+    fs::write(&tmp_path, serde_json::to_vec_pretty(&data)?)?;
     fs::rename(&tmp_path, path)?;
     Ok(())
 }
@@ -84,7 +99,7 @@ pub fn reset_password(options: &ResetPassword) -> anyhow::Result<()> {
 
     let os = detect::current_os()?;
     let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
-    let conn_params = control::get_instance(&methods, &options.name)
+    let conn_params = control::get_exact_instance(&methods, &options.name)
         .and_then(|inst| inst.get_connector(true))
         .with_context(|| format!("cannot find instance {:?}", options.name))?;
     task::block_on(async {
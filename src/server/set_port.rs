@@ -0,0 +1,55 @@
+use crate::credentials;
+use crate::server::control;
+use crate::server::detect;
+use crate::server::init::{check_port, reserve_port};
+use crate::server::options::SetPort;
+use crate::server::os_trait::InstanceRef;
+use crate::server::reset_password::{read_credentials, write_credentials};
+use crate::server::status::Service;
+
+
+fn revert(inst: &InstanceRef, old_port: u16) {
+    inst.set_port(old_port).map_err(|e| {
+        log::error!("Cannot revert port of {:?} to {}: {:#}",
+                    inst.name(), old_port, e);
+    }).ok();
+}
+
+/// Changes the port in metadata, service definition, credentials and the
+/// port mapping, reverting the earlier steps if a later one fails
+pub fn set_port(options: &SetPort) -> anyhow::Result<()> {
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let inst = control::get_instance(&methods, &options.name)?;
+    let name = inst.name();
+    let old_port = inst.get_port()?;
+    if old_port == options.port {
+        eprintln!("Instance {:?} already uses port {}", name, old_port);
+        return Ok(());
+    }
+    check_port(name, options.port)?;
+    let credentials_file = credentials::path(name)?;
+    let mut creds = read_credentials(&credentials_file)?;
+
+    inst.set_port(options.port)?;
+    creds.port = options.port;
+    if let Err(e) = write_credentials(&credentials_file, &creds) {
+        revert(&inst, old_port);
+        return Err(e);
+    }
+    if let Err(e) = reserve_port(name, options.port) {
+        creds.port = old_port;
+        write_credentials(&credentials_file, &creds).map_err(|e| {
+            log::error!("Cannot revert credentials of {:?}: {:#}", name, e);
+        }).ok();
+        revert(&inst, old_port);
+        return Err(e);
+    }
+
+    eprintln!("Port of instance {:?} is changed to {}.", name, options.port);
+    if matches!(inst.get_status().service, Service::Running {..}) {
+        eprintln!("Restart the instance to apply: \
+            edgedb server restart {}", name.escape_default());
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use async_ctrlc::CtrlC;
+use async_std::prelude::{FutureExt, StreamExt};
+use async_std::task;
+use edgedb_client::Builder;
+use edgedb_protocol::value::Value;
+use edgeql_parser::helpers::{quote_string, quote_name};
+use qrcode::QrCode;
+use qrcode::render::unicode::Dense1x2;
+use rand::{Rng, SeedableRng};
+
+use crate::platform::config_dir;
+use crate::server::control;
+use crate::server::detect;
+use crate::server::options::Share;
+use crate::server::reset_password::generate_password;
+
+const ROLE_SUFFIX_LENGTH: usize = 8;
+const ROLE_SUFFIX_CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+
+fn generate_role_name() -> String {
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    let suffix: String = (0..ROLE_SUFFIX_LENGTH).map(|_| {
+        ROLE_SUFFIX_CHARS[rng.gen_range(0..ROLE_SUFFIX_CHARS.len())] as char
+    }).collect();
+    format!("shared_{}", suffix)
+}
+
+fn print_qr(dsn: &str) -> anyhow::Result<()> {
+    let code = QrCode::new(dsn.as_bytes())
+        .context("cannot encode DSN as QR code")?;
+    let image = code.render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+    println!("{}", image);
+    Ok(())
+}
+
+async fn drop_role(conn_params: &Builder, role: &str) -> anyhow::Result<()> {
+    let mut cli = conn_params.connect().await?;
+    let exists = cli.query_row::<bool>(
+        "SELECT EXISTS (SELECT sys::Role FILTER .name = <str>$0)",
+        &Value::Tuple(vec![Value::Str(role.into())])).await?;
+    if exists {
+        cli.execute(&format!("DROP ROLE {}", quote_name(role))).await?;
+    }
+    Ok(())
+}
+
+/// Roles created by `share` are recorded with their expiration time, one
+/// file per role, so that roles left by a killed process are dropped later
+fn roles_dir(instance: &str) -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("shared_roles").join(instance))
+}
+
+fn record_role(instance: &str, role: &str, expires: SystemTime)
+    -> anyhow::Result<()>
+{
+    let dir = roles_dir(instance)?;
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(role),
+              humantime::format_rfc3339_seconds(expires).to_string())?;
+    Ok(())
+}
+
+fn unrecord_role(instance: &str, role: &str) -> anyhow::Result<()> {
+    match fs::remove_file(roles_dir(instance)?.join(role)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e)?,
+    }
+}
+
+/// Drops expired roles that were not dropped by their `share` command
+async fn drop_expired_roles(conn_params: &Builder, instance: &str)
+    -> anyhow::Result<()>
+{
+    let dir = match fs::read_dir(roles_dir(instance)?) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => Err(e)?,
+    };
+    let now = SystemTime::now();
+    for item in dir {
+        let item = item?;
+        let role = item.file_name().to_string_lossy().into_owned();
+        let expires = fs::read_to_string(item.path()).ok()
+            .and_then(|text| humantime::parse_rfc3339(text.trim()).ok());
+        if matches!(expires, Some(time) if time > now) {
+            continue;
+        }
+        match drop_role(conn_params, &role).await {
+            Ok(()) => {
+                log::info!("Dropped expired temporary role {:?}", role);
+                unrecord_role(instance, &role)?;
+            }
+            Err(e) => {
+                log::warn!("Cannot drop expired temporary role {:?}: {:#}",
+                           role, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn share(options: &Share) -> anyhow::Result<()> {
+    let os = detect::current_os()?;
+    let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
+    let inst = control::get_instance(&methods, &options.name)?;
+    let conn_params = inst.get_connector(true)
+        .with_context(|| format!("cannot find instance {:?}", options.name))?;
+    let port = inst.get_port()?;
+    let host = options.host.clone().unwrap_or_else(whoami::hostname);
+    let database = options.database.clone()
+        .unwrap_or_else(|| conn_params.get_database().into());
+    let role = generate_role_name();
+    let password = generate_password();
+
+    task::block_on(async {
+        drop_expired_roles(&conn_params, &options.name).await?;
+        record_role(&options.name, &role,
+                    SystemTime::now() + options.expire)?;
+        let mut cli = conn_params.connect().await?;
+        // not a superuser role and not extending any other role, so it
+        // can't manage roles or configure the instance
+        cli.execute(&format!(r###"
+            CREATE ROLE {name} {{
+                SET password := {password};
+            }}"###,
+            name=quote_name(&role),
+            password=quote_string(&password))
+        ).await?;
+        Ok::<_, anyhow::Error>(())
+    })?;
+
+    let dsn = format!("edgedb://{}:{}@{}:{}/{}",
+        urlencoding::encode(&role),
+        urlencoding::encode(&password),
+        host, port,
+        urlencoding::encode(&database));
+    println!("{}", dsn);
+    if options.qr {
+        print_qr(&dsn)?;
+    }
+    eprintln!("Temporary role {:?} expires in {}. \
+        Keep this command running: the role is dropped on expiry \
+        or when interrupted with Ctrl+C.",
+        role, humantime::format_duration(options.expire));
+
+    let result = task::block_on(async {
+        let mut ctrlc = CtrlC::new()?;
+        task::sleep(options.expire)
+            .race(async { ctrlc.next().await; })
+            .await;
+        drop_role(&conn_params, &role).await
+    });
+    match result {
+        Ok(()) => {
+            unrecord_role(&options.name, &role)?;
+            eprintln!("Temporary role {:?} is revoked.", role);
+            Ok(())
+        }
+        Err(e) => {
+            Err(e).with_context(|| format!(
+                "cannot revoke temporary role {:?}, it will be dropped by \
+                the next `edgedb server share {}`, or run `DROP ROLE {}` \
+                manually",
+                role, options.name, quote_name(&role)))
+        }
+    }
+}
+
+pub fn parse_expire(value: &str) -> anyhow::Result<Duration> {
+    let dur = humantime::parse_duration(value)?;
+    if dur == Duration::from_secs(0) {
+        anyhow::bail!("expiration time must be non-zero");
+    }
+    Ok(dur)
+}
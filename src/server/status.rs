@@ -9,9 +9,11 @@ use async_std::task;
 use async_std::net::TcpStream;
 use async_std::io::timeout;
 use fn_error_context::context;
+use indicatif::HumanBytes;
 use prettytable::{Table, Row, Cell};
 
 use crate::format;
+use crate::platform;
 use crate::server::detect;
 use crate::server::distribution::MajorVersion;
 use crate::server::init::Storage;
@@ -100,6 +102,13 @@ impl Status {
             Service::Running { pid } => {
                 println!("running, pid {}", pid);
                 println!("  Pid: {}", pid);
+                match memory_usage(pid) {
+                    Ok(bytes) => println!("  Memory: {}", HumanBytes(bytes)),
+                    Err(e) => {
+                        log::info!("Cannot get memory usage: {:#}", e);
+                        println!("  Memory: unknown");
+                    }
+                }
             }
             Service::Failed { exit_code: Some(code) } => {
                 println!("stopped, exit code {}", code);
@@ -148,6 +157,18 @@ impl Status {
         });
 
         println!("  Data directory: {}", self.storage.display());
+        if let Storage::UserDir(path) = &self.storage {
+            if path.exists() {
+                println!("  Data size: {}", match platform::dir_size(path) {
+                    Ok(size) => HumanBytes(size).to_string(),
+                    Err(e) => format!("unknown ({:#})", e),
+                });
+                println!("  Backend: {}", match postgres_version(path) {
+                    Ok(version) => format!("PostgreSQL {}", version),
+                    Err(e) => format!("unknown ({:#})", e),
+                });
+            }
+        }
         println!("  Data status: {}", match &self.data_status {
             DataDirectory::Absent => "NOT FOUND".into(),
             DataDirectory::NoMetadata => "METADATA ERROR".into(),
@@ -329,6 +350,50 @@ pub fn print_status_all(extended: bool, debug: bool, json: bool)
     Ok(())
 }
 
+#[cfg(target_os="linux")]
+fn memory_usage(pid: u32) -> anyhow::Result<u64> {
+    let path = format!("/proc/{}/status", pid);
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("cannot read {}", path))?;
+    parse_vm_rss(&text)
+        .ok_or_else(|| anyhow::anyhow!("no VmRSS in {}", path))
+}
+
+#[cfg(all(unix, not(target_os="linux")))]
+fn memory_usage(pid: u32) -> anyhow::Result<u64> {
+    let text = crate::process::get_text(std::process::Command::new("ps")
+        .arg("-o").arg("rss=")
+        .arg("-p").arg(pid.to_string()))?;
+    let kbytes: u64 = text.trim().parse()
+        .with_context(|| format!("bad output of ps: {:?}", text))?;
+    Ok(kbytes * 1024)
+}
+
+#[cfg(windows)]
+fn memory_usage(_pid: u32) -> anyhow::Result<u64> {
+    anyhow::bail!("not supported on Windows");
+}
+
+/// Parses resident memory size out of `/proc/<pid>/status`
+#[cfg_attr(not(target_os="linux"), allow(dead_code))]
+fn parse_vm_rss(text: &str) -> Option<u64> {
+    let line = text.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let mut words = line["VmRSS:".len()..].split_whitespace();
+    let value: u64 = words.next()?.parse().ok()?;
+    match words.next() {
+        Some("kB") => Some(value * 1024),
+        _ => None,
+    }
+}
+
+/// Version of the PostgreSQL cluster in the data directory
+fn postgres_version(data_dir: &Path) -> anyhow::Result<String> {
+    let path = data_dir.join("PG_VERSION");
+    let version = fs::read_to_string(&path)
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    Ok(version.trim().to_string())
+}
+
 fn status_str(status: &Service) -> &'static str {
     match status {
         Service::Running {..} => "running",
@@ -336,3 +401,16 @@ fn status_str(status: &Service) -> &'static str {
         Service::Inactive {..} => "inactive",
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_vm_rss;
+
+    #[test]
+    fn vm_rss() {
+        let text = "Name:\tedgedb-server\nVmPeak:\t  300000 kB\n\
+                    VmRSS:\t  123456 kB\nThreads:\t4\n";
+        assert_eq!(parse_vm_rss(text), Some(123456*1024));
+        assert_eq!(parse_vm_rss("Name:\tzombie\n"), None);
+    }
+}
@@ -1,14 +1,17 @@
 use std::collections::{BTreeSet, BTreeMap};
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{self, Command};
+use std::process::{self, Command, Stdio};
 use std::time::SystemTime;
 
 use anyhow::Context;
 use async_std::task;
+use edgedb_client as client;
 use serde::Serialize;
 use linked_hash_map::LinkedHashMap;
 use fn_error_context::context;
+use tempfile::TempDir;
 
 use crate::commands::ExitCode;
 use crate::process::ProcessGuard;
@@ -116,7 +119,11 @@ pub fn bootstrap(method: &dyn Method, settings: &init::Settings)
     });
     cmd.arg("--bootstrap-only");
     cmd.arg("--log-level=warn");
-    cmd.arg("--data-dir").arg(&dir);
+    // with external Postgres the directory only keeps metadata
+    match &settings.backend_dsn {
+        Some(dsn) => cmd.env("EDGEDB_BACKEND_DSN", dsn),
+        None => cmd.arg("--data-dir").arg(&dir),
+    };
 
     log::debug!("Running bootstrap {:?}", cmd);
     match cmd.status() {
@@ -165,6 +172,73 @@ pub fn bootstrap(method: &dyn Method, settings: &init::Settings)
     Ok(())
 }
 
+/// Runs a temporary instance in foreground until interrupted
+///
+/// No service is installed, and all the data of the instance, including
+/// its credentials and port, is removed on exit.
+pub fn run_temporary(settings: &init::Settings, tmp_dir: TempDir)
+    -> anyhow::Result<()>
+{
+    init::mark_temporary(&settings.name, &init::Temporary {
+        pid: process::id(),
+        data_dir: tmp_dir.path().into(),
+    })?;
+    let result = _run_temporary(settings, tmp_dir.path());
+    match fs::remove_file(&settings.credentials) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Cannot remove {}: {}",
+                             settings.credentials.display(), e),
+    }
+    init::release_port(&settings.name)
+        .map_err(|e| log::warn!("{:#}", e)).ok();
+    init::unmark_temporary(&settings.name)
+        .map_err(|e| log::warn!("{:#}", e)).ok();
+    tmp_dir.close()
+        .map_err(|e| log::warn!("Cannot remove temporary data: {}", e)).ok();
+    result
+}
+
+fn _run_temporary(settings: &init::Settings, tmp_dir: &Path)
+    -> anyhow::Result<()>
+{
+    let data_dir = tmp_dir.join("data");
+    let runstate_dir = tmp_dir.join("run");
+    fs::create_dir_all(&data_dir)?;
+    fs::create_dir_all(&runstate_dir)?;
+    let pkg = settings.distribution.downcast_ref::<Package>()
+        .context("invalid unix package")?;
+
+    let mut cmd = Command::new(server_path(&pkg.slot));
+    cmd.arg("--bootstrap-only");
+    cmd.arg("--log-level=warn");
+    cmd.arg("--data-dir").arg(&data_dir);
+    log::debug!("Running bootstrap {:?}", cmd);
+    match cmd.status() {
+        Ok(s) if s.success() => {}
+        Ok(s) => anyhow::bail!("Command {:?} {}", cmd, s),
+        Err(e) => Err(e).context(format!("Failed running {:?}", cmd))?,
+    }
+
+    let mut cmd = Command::new(server_path(&pkg.slot));
+    cmd.arg("--port").arg(settings.port.to_string());
+    cmd.arg("--data-dir").arg(&data_dir);
+    cmd.arg("--runstate-dir").arg(&runstate_dir);
+    // stdout is reserved for the connection info
+    cmd.stdout(Stdio::null());
+    log::debug!("Running server: {:?}", cmd);
+    let mut child = ProcessGuard::run(&mut cmd)
+        .with_context(|| format!("error running server {:?}", cmd))?;
+    let mut conn_params = client::Builder::new();
+    conn_params.user("edgedb");
+    conn_params.database("edgedb");
+    conn_params.unix_addr(
+        runstate_dir.join(format!(".s.EDGEDB.admin.{}", settings.port)));
+    init::init_credentials_at(settings, conn_params)?;
+    init::print_temporary(settings)?;
+    child.wait_interrupted()
+}
+
 fn create_user_service(name: &str, meta: &Metadata) -> anyhow::Result<()> {
     if cfg!(target_os="macos") {
         macos::create_launchctl_service(&name, &meta)
@@ -182,7 +256,7 @@ fn write_upgrade(path: &Path, data: &str) -> anyhow::Result<()> {
 }
 
 #[context("failed to write metadata file {}", path.display())]
-fn write_metadata(path: &Path, metadata: &Metadata) -> anyhow::Result<()> {
+pub fn write_metadata(path: &Path, metadata: &Metadata) -> anyhow::Result<()> {
     let tmp_path = path.with_extension("tmp");
     fs::remove_file(&tmp_path).ok();
     fs::write(&tmp_path, serde_json::to_vec_pretty(&metadata)?)?;
@@ -380,6 +454,7 @@ fn do_minor_upgrade(method: &dyn Method, options: &Upgrade)
                 method: method.name(),
                 port: inst.get_port()?,
                 start_conf: inst.get_start_conf()?,
+                backend_dsn: inst.get_backend_dsn()?,
             };
             let metapath = storage_dir(inst.name())?.join("metadata.json");
             write_metadata(&metapath, &new_meta)?;
@@ -466,7 +541,13 @@ fn do_nightly_upgrade(method: &dyn Method, options: &Upgrade)
             method: method.name(),
             port: inst.get_port()?,
             start_conf: inst.get_start_conf()?,
+            backend_dsn: inst.get_backend_dsn()?,
         };
+        if new_meta.backend_dsn.is_some() {
+            errors.push(format!("instance {:?} uses external Postgres, \
+                upgrade it manually with dump and restore", inst.name()));
+            continue;
+        }
         upgrade::dump_and_stop(inst.as_ref(), &dump_path)?;
         let upgrade_meta = upgrade::UpgradeMeta {
             source: old.cloned().unwrap_or_else(|| Version("unknown".into())),
@@ -475,7 +556,9 @@ fn do_nightly_upgrade(method: &dyn Method, options: &Upgrade)
             pid: process::id(),
         };
         let inst = inst.upgrade(&new_meta)?;
-        match reinit_and_restore(inst.as_ref(), &new_meta, &upgrade_meta) {
+        match reinit_and_restore(inst.as_ref(), &new_meta, &upgrade_meta,
+                                 &dump_path)
+        {
             Ok(()) => {}
             Err(e) if e.is::<CannotStartService>() => {
                 errors.push(
@@ -489,9 +572,10 @@ fn do_nightly_upgrade(method: &dyn Method, options: &Upgrade)
     Ok(())
 }
 
+/// Moves data directory to the backup and restores `dump_path` instead
 #[context("failed to restore {:?}", inst.name())]
-fn reinit_and_restore(inst: &dyn Instance, new_meta: &Metadata,
-    upgrade_meta: &upgrade::UpgradeMeta)
+pub fn reinit_and_restore(inst: &dyn Instance, new_meta: &Metadata,
+    upgrade_meta: &upgrade::UpgradeMeta, dump_path: &Path)
     -> anyhow::Result<()>
 {
     let instance_dir = storage_dir(inst.name())?;
@@ -515,7 +599,7 @@ fn reinit_and_restore(inst: &dyn Instance, new_meta: &Metadata,
             timestamp: SystemTime::now(),
         })?;
     _reinit_and_restore(
-        &instance_dir, inst, new_meta, &upgrade_marker
+        &instance_dir, inst, new_meta, &upgrade_marker, dump_path
     ).map_err(|e| {
         eprintln!("edgedb error: failed to restore {:?}: {}", inst.name(), e);
         eprintln!("To undo run:\n  edgedb server revert {:?}", inst.name());
@@ -524,7 +608,7 @@ fn reinit_and_restore(inst: &dyn Instance, new_meta: &Metadata,
 }
 
 fn _reinit_and_restore(instance_dir: &Path, inst: &dyn Instance,
-    new_meta: &Metadata, upgrade_marker: &Path)
+    new_meta: &Metadata, upgrade_marker: &Path, dump_path: &Path)
     -> anyhow::Result<()>
 {
 
@@ -538,11 +622,8 @@ fn _reinit_and_restore(instance_dir: &Path, inst: &dyn Instance,
     let child = ProcessGuard::run(&mut cmd)
         .with_context(|| format!("error running server {:?}", cmd))?;
 
-    let dump_path = storage_dir(inst.name())?
-        .parent().expect("instance path can't be root")
-        .join(format!("{}.dump", inst.name()));
     task::block_on(
-        upgrade::restore_instance(inst, &dump_path, inst.get_connector(true)?)
+        upgrade::restore_instance(inst, dump_path, inst.get_connector(true)?)
     )?;
     log::info!(target: "edgedb::server::upgrade",
         "Restarting instance {:?} to apply changes from `restore --all`",
@@ -619,6 +700,10 @@ fn do_instance_upgrade(method: &dyn Method,
         extra: LinkedHashMap::new(),
     })?;
 
+    if options.check {
+        return check_upgrade(inst.as_ref(), &slot, &new_version);
+    }
+
     let dump_path = storage_dir(inst.name())?
         .parent().expect("instance path can't be root")
         .join(format!("{}.dump", inst.name()));
@@ -629,7 +714,12 @@ fn do_instance_upgrade(method: &dyn Method,
         method: method.name(),
         port: inst.get_port()?,
         start_conf: inst.get_start_conf()?,
+        backend_dsn: inst.get_backend_dsn()?,
     };
+    if new_meta.backend_dsn.is_some() {
+        anyhow::bail!("instance {:?} uses external Postgres, \
+            upgrade it manually with dump and restore", inst.name());
+    }
     upgrade::dump_and_stop(inst.as_ref(), &dump_path)?;
 
 
@@ -640,7 +730,9 @@ fn do_instance_upgrade(method: &dyn Method,
         pid: process::id(),
     };
     let inst = inst.upgrade(&new_meta)?;
-    match reinit_and_restore(inst.as_ref(), &new_meta, &upgrade_meta) {
+    match reinit_and_restore(inst.as_ref(), &new_meta, &upgrade_meta,
+                             &dump_path)
+    {
         Ok(()) => {}
         Err(e) if e.is::<CannotStartService>() => {
             eprintln!("Upgrade complete, but cannot start instance: {:#}", e);
@@ -652,6 +744,112 @@ fn do_instance_upgrade(method: &dyn Method,
     Ok(())
 }
 
+fn server_path(slot: &str) -> PathBuf {
+    if cfg!(target_os="macos") {
+        macos::get_server_path(slot)
+    } else {
+        linux::get_server_path(Some(&slot.to_string()))
+    }
+}
+
+/// Restores a dump of the instance into a temporary server of the new
+/// version, the instance itself is only dumped
+#[context("failed to check upgrade of {:?}", inst.name())]
+fn check_upgrade(inst: &dyn Instance, slot: &str,
+    new_version: &Version<String>)
+    -> anyhow::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let dump_path = tmp.path().join("dump");
+
+    let running = matches!(inst.get_status().service, Service::Running {..});
+    if !running {
+        log::info!(target: "edgedb::server::upgrade", "Starting instance");
+        inst.start(&Start { name: inst.name().into(), foreground: false })?;
+    }
+    let dumped = inst.get_connector(false).and_then(|conn_params| {
+        task::block_on(upgrade::dump_instance(inst, &dump_path, conn_params))
+    });
+    if !running {
+        log::info!(target: "edgedb::server::upgrade", "Stopping instance");
+        inst.stop(&Stop { name: inst.name().into() })
+            .map_err(|e| log::warn!("Cannot stop instance: {:#}", e)).ok();
+    }
+    dumped?;
+
+    // not a valid instance name, so it never clashes with real instances
+    let port_name = format!("{}~upgrade-check", inst.name());
+    let port = init::allocate_port(&port_name)?;
+    let result = restore_temporary(inst, slot, tmp.path(), port);
+    init::release_port(&port_name)
+        .map_err(|e| log::warn!("{:#}", e)).ok();
+    let (restored, deprecations) = result?;
+
+    if !deprecations.is_empty() {
+        eprintln!("Deprecation warnings of the server {}:", new_version);
+        for text in deprecations {
+            eprintln!("  {}", text);
+        }
+    }
+    match restored {
+        Ok(()) => {
+            eprintln!("Instance {:?} can be upgraded to {}: \
+                its data is restored into the new version successfully.",
+                inst.name(), new_version);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("edgedb error: instance {:?} cannot be upgraded \
+                to {}: {:#}", inst.name(), new_version, e);
+            Err(ExitCode::new(1))?
+        }
+    }
+}
+
+/// Runs the new server in `dir` and restores the dump into it, returns
+/// result of the restore and deprecation warnings of the server
+fn restore_temporary(inst: &dyn Instance, slot: &str, dir: &Path, port: u16)
+    -> anyhow::Result<(anyhow::Result<()>, Vec<String>)>
+{
+    let data_dir = dir.join("data");
+    let runstate_dir = dir.join("run");
+    let log_path = dir.join("server.log");
+    fs::create_dir_all(&data_dir)?;
+    fs::create_dir_all(&runstate_dir)?;
+
+    let mut cmd = Command::new(server_path(slot));
+    cmd.arg("--port").arg(port.to_string());
+    cmd.arg("--data-dir").arg(&data_dir);
+    cmd.arg("--runstate-dir").arg(&runstate_dir);
+    cmd.env("PYTHONWARNINGS", "always::DeprecationWarning");
+    cmd.stderr(fs::File::create(&log_path)?);
+    log::debug!("Running temporary server: {:?}", cmd);
+    // server is stopped when the guard is dropped, on error too
+    let child = ProcessGuard::run(&mut cmd)
+        .with_context(|| format!("error running server {:?}", cmd))?;
+    let mut conn_params = client::Builder::new();
+    conn_params.user("edgedb");
+    conn_params.database("edgedb");
+    conn_params.unix_addr(
+        runstate_dir.join(format!(".s.EDGEDB.admin.{}", port)));
+    let restored = task::block_on(
+        upgrade::restore_instance(inst, &dir.join("dump"), conn_params));
+    drop(child);
+
+    let log = fs::read_to_string(&log_path).unwrap_or_default();
+    Ok((restored, deprecation_warnings(&log)))
+}
+
+/// Finds warnings of `DeprecationWarning` category in the server log, which
+/// `warnings` module writes as `<file>:<line>: DeprecationWarning: <text>`
+fn deprecation_warnings(log: &str) -> Vec<String> {
+    const CATEGORY: &str = ": DeprecationWarning: ";
+    log.lines()
+        .filter_map(|line| line.find(CATEGORY)
+            .map(|pos| line[pos + CATEGORY.len()..].trim().to_string()))
+        .collect()
+}
+
 pub fn revert(instance: &dyn Instance, metadata: &Metadata)
     -> anyhow::Result<()>
 {
@@ -690,3 +888,19 @@ pub fn revert(instance: &dyn Instance, metadata: &Metadata)
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::deprecation_warnings;
+
+    #[test]
+    fn deprecations() {
+        let log = "INFO 123 edb.server: Serving on 127.0.0.1:10700\n\
+            /usr/lib/edb/schema.py:12: DeprecationWarning: \
+            `abstract` is deprecated\n  \
+            warnings.warn(\"deprecated\")\n\
+            WARNING 123 edb.server: Deprecated configuration\n";
+        assert_eq!(deprecation_warnings(log),
+                   vec!["`abstract` is deprecated"]);
+    }
+}
@@ -8,6 +8,7 @@ use fn_error_context::context;
 use serde::{Serialize, Deserialize};
 
 use edgedb_client as client;
+use crate::checksum;
 use crate::commands;
 use crate::connect::Connector;
 use crate::process::ProcessGuard;
@@ -64,6 +65,9 @@ fn interpret_options(options: &Upgrade) -> ToDo {
 }
 
 pub fn upgrade(options: &Upgrade) -> anyhow::Result<()> {
+    if options.no_verify {
+        checksum::disable();
+    }
     let todo = interpret_options(&options);
     let os = detect::current_os()?;
     let methods = os.get_available_methods()?.instantiate_all(&*os, true)?;
@@ -105,8 +109,10 @@ pub async fn dump_instance(inst: &dyn Instance, destination: &Path,
         command_line: true,
         styler: None,
         conn_params: Connector::new(Ok(conn_params)),
+        schema_cache: None,
     };
-    commands::dump_all(&mut cli, &options, destination.as_ref()).await?;
+    commands::dump_all(&mut cli, &options, destination.as_ref(), false, None)
+        .await?;
     Ok(())
 }
 
@@ -125,13 +131,20 @@ pub async fn restore_instance(inst: &dyn Instance,
         command_line: true,
         styler: None,
         conn_params: Connector::new(Ok(conn_params)),
+        schema_cache: None,
     };
     commands::restore_all(&mut cli, &options, &Restore {
         path: path.into(),
         all: true,
         allow_non_empty: false,
+        force_clean: false,
+        ignore_existing: false,
+        apply_incremental: false,
+        schema_only: false,
+        data_only: false,
+        non_interactive: true,
         verbose: false,
-    }).await?;
+    }, None).await?;
     Ok(())
 }
 
@@ -0,0 +1,456 @@
+//! Language server for editors, spoken over stdin/stdout
+//!
+//! Implements a small subset of the Language Server Protocol: full document
+//! sync, diagnostics, completion and hover. Connection parameters are
+//! resolved the same way as for other commands, so editors need no
+//! configuration inside a project.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+
+use anyhow::Context;
+use async_std::task;
+use clap::{Clap, AppSettings};
+use edgedb_client::client::Connection;
+use edgedb_protocol::value::Value;
+use edgeql_parser::keywords;
+use serde_json::{json, Value as Json};
+
+use crate::commands;
+use crate::commands::helpers::quote_namespaced;
+use crate::migrations;
+use crate::options::Options;
+use crate::schema_cache::{self, SchemaCache};
+
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+
+const SCHEMA_NAMES: &str = r###"
+    WITH MODULE schema
+    SELECT DISTINCT (ObjectType.name UNION ScalarType.name
+                     UNION Function.name)
+"###;
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct ServerProxy {
+    /// Speak the language server protocol over stdin/stdout (this is the
+    /// only transport supported)
+    #[clap(long)]
+    pub stdio: bool,
+}
+
+struct Proxy<'a> {
+    options: &'a Options,
+    cmd_options: commands::Options,
+    connection: Option<Connection>,
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+fn read_message(input: &mut impl BufRead) -> anyhow::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(pos) = line.find(':') {
+            if line[..pos].eq_ignore_ascii_case("content-length") {
+                length = Some(line[pos+1..].trim().parse::<usize>()
+                    .context("invalid Content-Length")?);
+            }
+        }
+    }
+    let length = length.context("no Content-Length header")?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Json)
+    -> anyhow::Result<()>
+{
+    let body = serde_json::to_vec(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n", body.len())?;
+    output.write_all(&body)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Converts byte offset into zero-based LSP position
+fn position(text: &str, offset: usize) -> Json {
+    let (line, column) = migrations::location(text, offset);
+    json!({"line": line - 1, "character": column - 1})
+}
+
+/// Converts LSP position into byte offset
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(pos) => start += pos + 1,
+            None => return text.len(),
+        }
+    }
+    text[start..].char_indices()
+        .take_while(|(_, c)| *c != '\n')
+        .nth(character)
+        .map(|(pos, _)| start + pos)
+        .unwrap_or_else(|| {
+            start + text[start..].find('\n').unwrap_or(text.len() - start)
+        })
+}
+
+/// Returns a possibly qualified name around the offset
+fn word_at(text: &str, offset: usize) -> Option<&str> {
+    let offset = offset.min(text.len());
+    let is_name = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    let start = text[..offset].rfind(|c| !is_name(c))
+        .map(|pos| pos + 1).unwrap_or(0);
+    let end = text[offset..].find(|c| !is_name(c))
+        .map(|pos| offset + pos).unwrap_or(text.len());
+    let word = text[start..end].trim_matches(':');
+    if word.is_empty() {
+        None
+    } else {
+        Some(word)
+    }
+}
+
+fn diagnostics(uri: &str, text: &str) -> Vec<Json> {
+    let diag = |start, end, severity, message: &str, code: Option<&str>| {
+        json!({
+            "range": {
+                "start": position(text, start),
+                "end": position(text, end),
+            },
+            "severity": severity,
+            "source": "edgedb",
+            "code": code,
+            "message": message,
+        })
+    };
+    if uri.ends_with(".esdl") {
+        return migrations::check_text(text, true).into_iter()
+            .map(|issue| {
+                let severity = if issue.rule.is_some() {
+                    SEVERITY_WARNING
+                } else {
+                    SEVERITY_ERROR
+                };
+                diag(issue.range.start, issue.range.end, severity,
+                     &issue.message, issue.rule.map(|r| r.name()))
+            })
+            .collect();
+    }
+    match migrations::check_tokens(text) {
+        Ok(()) => Vec::new(),
+        Err(e) => vec![diag(e.offset, e.offset, SEVERITY_ERROR,
+                            &e.message, None)],
+    }
+}
+
+fn param<'x>(params: &'x Json, pointer: &str) -> Result<&'x Json, RpcError> {
+    params.pointer(pointer).ok_or_else(|| RpcError {
+        code: INVALID_PARAMS,
+        message: format!("missing {}", pointer),
+    })
+}
+
+fn str_param<'x>(params: &'x Json, pointer: &str)
+    -> Result<&'x str, RpcError>
+{
+    param(params, pointer)?.as_str().ok_or_else(|| RpcError {
+        code: INVALID_PARAMS,
+        message: format!("{} must be a string", pointer),
+    })
+}
+
+fn usize_param(params: &Json, pointer: &str) -> Result<usize, RpcError> {
+    param(params, pointer)?.as_u64().map(|v| v as usize)
+        .ok_or_else(|| RpcError {
+            code: INVALID_PARAMS,
+            message: format!("{} must be an integer", pointer),
+        })
+}
+
+impl Proxy<'_> {
+    async fn ensure_connection(&mut self) -> anyhow::Result<()> {
+        if self.connection.as_ref().map(|c| c.is_consistent())
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        if let Some(cache) = &self.cmd_options.schema_cache {
+            cache.clear();
+        }
+        self.connection = Some(self.options.conn_params.connect().await?);
+        Ok(())
+    }
+    /// Runs introspection, dropping the connection on error, so the next
+    /// request reconnects
+    async fn schema_names(&mut self) -> Vec<String> {
+        let result = match self.ensure_connection().await {
+            Ok(()) => {
+                let cli = self.connection.as_mut()
+                    .expect("connection established");
+                schema_cache::query::<String>(cli, &self.cmd_options,
+                    SCHEMA_NAMES, &Value::empty_tuple()).await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(names) => names,
+            Err(e) => {
+                log::warn!("Cannot fetch schema: {:#}", e);
+                self.connection = None;
+                Vec::new()
+            }
+        }
+    }
+    async fn describe(&mut self, name: &str) -> Option<String> {
+        let query = format!("DESCRIBE OBJECT {} AS TEXT",
+                            quote_namespaced(name));
+        let result = match self.ensure_connection().await {
+            Ok(()) => {
+                let cli = self.connection.as_mut()
+                    .expect("connection established");
+                cli.query_row::<String>(&query, &Value::empty_tuple()).await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(text) => Some(text),
+            Err(e) => {
+                log::warn!("Cannot describe {:?}: {:#}", name, e);
+                None
+            }
+        }
+    }
+    fn document(&self, params: &Json) -> Result<(&str, usize), RpcError> {
+        let uri = str_param(params, "/textDocument/uri")?;
+        let text = self.documents.get(uri).ok_or_else(|| RpcError {
+            code: INVALID_PARAMS,
+            message: format!("document {} is not open", uri),
+        })?;
+        let line = usize_param(params, "/position/line")?;
+        let character = usize_param(params, "/position/character")?;
+        Ok((text, offset(text, line, character)))
+    }
+    async fn completion(&mut self, params: &Json) -> Result<Json, RpcError> {
+        let prefix = {
+            let (text, offset) = self.document(params)?;
+            word_at(&text[..offset], offset).unwrap_or("").to_lowercase()
+        };
+        let mut items = Vec::new();
+        for name in self.schema_names().await {
+            if name.to_lowercase().starts_with(&prefix) {
+                items.push(json!({"label": name, "kind": 7}));
+            }
+        }
+        let all_keywords = keywords::UNRESERVED_KEYWORDS.iter()
+            .chain(keywords::CURRENT_RESERVED_KEYWORDS.iter());
+        for keyword in all_keywords {
+            if keyword.starts_with(&prefix) {
+                items.push(json!({"label": keyword, "kind": 14}));
+            }
+        }
+        Ok(json!(items))
+    }
+    async fn hover(&mut self, params: &Json) -> Result<Json, RpcError> {
+        let word = {
+            let (text, offset) = self.document(params)?;
+            match word_at(text, offset) {
+                Some(word) => word.to_string(),
+                None => return Ok(Json::Null),
+            }
+        };
+        let names = self.schema_names().await;
+        let name = names.iter()
+            .find(|n| **n == word || n.rsplit("::").next() == Some(&word[..]));
+        let name = match name {
+            Some(name) => name.clone(),
+            None => return Ok(Json::Null),
+        };
+        match self.describe(&name).await {
+            Some(text) => Ok(json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": format!("```edgeql\n{}\n```", text.trim()),
+                },
+            })),
+            None => Ok(Json::Null),
+        }
+    }
+    fn publish(&self, uri: &str, output: &mut impl Write)
+        -> anyhow::Result<()>
+    {
+        let items = match self.documents.get(uri) {
+            Some(text) => diagnostics(uri, text),
+            None => Vec::new(),
+        };
+        write_message(output, &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": items},
+        }))
+    }
+    /// Handles notification, returns URI of the document changed
+    fn notification(&mut self, method: &str, params: &Json)
+        -> Result<Option<String>, RpcError>
+    {
+        match method {
+            "textDocument/didOpen" => {
+                let uri = str_param(params, "/textDocument/uri")?;
+                let text = str_param(params, "/textDocument/text")?;
+                self.documents.insert(uri.into(), text.into());
+                Ok(Some(uri.into()))
+            }
+            "textDocument/didChange" => {
+                let uri = str_param(params, "/textDocument/uri")?;
+                let changes = param(params, "/contentChanges")?.as_array();
+                // full sync, so the last change has the whole text
+                let last = changes.and_then(|c| c.last());
+                if let Some(text) = last.and_then(|c| c["text"].as_str()) {
+                    self.documents.insert(uri.into(), text.into());
+                }
+                Ok(Some(uri.into()))
+            }
+            "textDocument/didClose" => {
+                let uri = str_param(params, "/textDocument/uri")?;
+                self.documents.remove(uri);
+                Ok(Some(uri.into()))
+            }
+            _ => Ok(None),
+        }
+    }
+    async fn request(&mut self, method: &str, params: &Json)
+        -> Result<Json, RpcError>
+    {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                },
+                "serverInfo": {
+                    "name": "edgedb",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/completion" => self.completion(params).await,
+            "textDocument/hover" => self.hover(params).await,
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method {:?}", method),
+            }),
+        }
+    }
+}
+
+async fn serve(options: &Options) -> anyhow::Result<()> {
+    let mut proxy = Proxy {
+        options,
+        cmd_options: commands::Options {
+            command_line: false,
+            styler: None,
+            conn_params: options.conn_params.clone(),
+            schema_cache: Some(SchemaCache::new()),
+        },
+        connection: None,
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout();
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        if method == "exit" {
+            if proxy.shutdown {
+                return Ok(());
+            }
+            anyhow::bail!("exit is requested before shutdown");
+        }
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => {
+                match proxy.notification(method, params) {
+                    Ok(Some(uri)) => proxy.publish(&uri, &mut output)?,
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Bad {} notification: {}",
+                                         method, e.message),
+                }
+                continue;
+            }
+        };
+        let response = match proxy.request(method, params).await {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id,
+                                 "result": result}),
+            Err(e) => json!({"jsonrpc": "2.0", "id": id, "error": {
+                "code": e.code,
+                "message": e.message,
+            }}),
+        };
+        write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+pub fn main(cmd: &ServerProxy, options: &Options) -> anyhow::Result<()> {
+    if !cmd.stdio {
+        anyhow::bail!("only `--stdio` mode is supported");
+    }
+    task::block_on(serve(options))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{offset, read_message, word_at};
+
+    #[test]
+    fn framing() {
+        let mut input = Cursor::new(
+            b"Content-Length: 17\r\n\r\n{\"method\":\"exit\"}".to_vec());
+        let message = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(message["method"], "exit");
+        assert!(read_message(&mut input).unwrap().is_none());
+    }
+
+    #[test]
+    fn positions() {
+        let text = "SELECT User {\n  name\n};\n";
+        assert_eq!(offset(text, 0, 7), 7);
+        assert_eq!(offset(text, 1, 4), 18);
+        assert_eq!(offset(text, 1, 100), 20);
+        assert_eq!(offset(text, 10, 0), text.len());
+        assert_eq!(word_at(text, 9), Some("User"));
+        assert_eq!(word_at("SELECT default::User", 20),
+                   Some("default::User"));
+        assert_eq!(word_at("SELECT  x", 7), None);
+    }
+}
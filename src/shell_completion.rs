@@ -0,0 +1,247 @@
+//! Completion of dynamic values (instance names, databases, revisions)
+//!
+//! Static completions are generated by clap. The generated scripts are
+//! extended to call `edgedb _complete -- WORDS...` first and fall back to
+//! static completions if nothing is printed.
+
+use std::path::Path;
+use std::time::Duration;
+
+use async_std::future::timeout;
+use async_std::prelude::StreamExt;
+use async_std::task;
+use clap::{App, AppSettings, Clap, IntoApp};
+use edgedb_protocol::value::Value;
+
+use crate::connect::Connector;
+use crate::credentials;
+use crate::migrations;
+use crate::options::{Options, RawOptions};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Subcommands having `name` argument for a new (not existing) instance
+const NEW_INSTANCE_COMMANDS: &[&str] = &["init", "link"];
+
+const BASH: &str = r###"
+_edgedb_dynamic() {
+    local words
+    words=$(edgedb _complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null)
+    if [[ -n "$words" ]]; then
+        COMPREPLY=( $(compgen -W "$words" -- "${COMP_WORDS[COMP_CWORD]}") )
+        return 0
+    fi
+    _edgedb "$@"
+}
+
+complete -F _edgedb_dynamic -o bashdefault -o default edgedb
+"###;
+
+const FISH: &str = r###"
+function __edgedb_dynamic
+    edgedb _complete -- (commandline -opc)[2..-1] (commandline -ct) \
+        2>/dev/null
+end
+complete -c edgedb -a '(__edgedb_dynamic)'
+"###;
+
+const ZSH_CALL: &str = "_edgedb \"$@\"";
+const ZSH: &str = r###"_edgedb_dynamic() {
+    local -a candidates
+    candidates=(${(f)"$(edgedb _complete -- ${words[2,CURRENT]} 2>/dev/null)"})
+    if (( ${#candidates} )); then
+        compadd -a candidates
+    else
+        _edgedb "$@"
+    fi
+}
+
+_edgedb_dynamic "$@"
+"###;
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::TrailingVarArg)]
+#[clap(setting=AppSettings::AllowLeadingHyphen)]
+pub struct Complete {
+    /// Command-line words after `edgedb`, the last one is being completed
+    pub words: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Instance,
+    Database,
+    Revision,
+}
+
+pub fn extend_bash(script: String) -> String {
+    script + BASH
+}
+
+pub fn extend_fish(script: String) -> String {
+    let mut commands = vec![String::from("drop-database")];
+    if let Some(server) = server_app(&RawOptions::into_app()) {
+        instance_commands(server, &mut commands);
+    }
+    commands.sort();
+    commands.dedup();
+    // files are still completed for the rest of the commands
+    format!("{}{}complete -c edgedb -f \\\n    \
+             -n '__fish_seen_subcommand_from {}'\n",
+            script, FISH, commands.join(" "))
+}
+
+fn server_app<'x, 'h>(app: &'x App<'h>) -> Option<&'x App<'h>> {
+    app.get_subcommands().find(|s| s.get_name() == "server")
+}
+
+fn is_instance_command(app: &App) -> bool {
+    !NEW_INSTANCE_COMMANDS.contains(&app.get_name()) &&
+    app.get_arguments()
+        .find(|a| a.get_short().is_none() && a.get_long().is_none())
+        .map(|a| a.get_name() == "name")
+        .unwrap_or(false)
+}
+
+fn instance_commands(app: &App, result: &mut Vec<String>) {
+    for sub in app.get_subcommands() {
+        if is_instance_command(sub) {
+            result.push(sub.get_name().into());
+        }
+        instance_commands(sub, result);
+    }
+}
+
+/// Checks whether the server subcommand expects instance name next
+fn expects_instance(path: &[&str]) -> bool {
+    let path = match path.split_first() {
+        Some((&"server", path)) if !path.is_empty() => path,
+        _ => return false,
+    };
+    let root = RawOptions::into_app();
+    let mut app = match server_app(&root) {
+        Some(app) => app,
+        None => return false,
+    };
+    for word in path {
+        match app.get_subcommands().find(|s| s.get_name() == *word) {
+            Some(sub) => app = sub,
+            None => return false,
+        }
+    }
+    is_instance_command(app)
+}
+
+pub fn extend_zsh(script: String) -> String {
+    // generated script calls the completion function in the last line
+    match script.trim_end().strip_suffix(ZSH_CALL) {
+        Some(body) => format!("{}{}", body, ZSH),
+        None => script,
+    }
+}
+
+fn option_value<'x>(words: &'x [String], names: &[&str]) -> Option<&'x str> {
+    words.windows(2).rev()
+        .find(|pair| names.contains(&&pair[0][..]))
+        .map(|pair| &pair[1][..])
+}
+
+fn kind(words: &[String]) -> Option<Kind> {
+    match words.last().map(|w| &w[..]) {
+        Some("-I") | Some("--instance") => return Some(Kind::Instance),
+        Some("-d") | Some("--database") => return Some(Kind::Database),
+        Some("--to-revision") => return Some(Kind::Revision),
+        _ => {}
+    }
+    let positional = words.iter()
+        .filter(|w| !w.starts_with('-'))
+        .map(|w| &w[..])
+        .collect::<Vec<_>>();
+    match &positional[..] {
+        ["drop-database"] => Some(Kind::Database),
+        path if expects_instance(path) => Some(Kind::Instance),
+        _ => None,
+    }
+}
+
+fn instances() -> anyhow::Result<Vec<String>> {
+    Ok(credentials::all_instance_names()?.into_iter().collect())
+}
+
+async fn databases(conn_params: &Connector) -> anyhow::Result<Vec<String>> {
+    let mut cli = timeout(CONNECT_TIMEOUT, conn_params.connect()).await??;
+    let mut items = cli.query::<String>("SELECT sys::Database.name",
+        &Value::empty_tuple()).await?;
+    let mut result = Vec::new();
+    while let Some(name) = items.next().await.transpose()? {
+        result.push(name);
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn values(kind: Kind, words: &[String], options: &Options)
+    -> anyhow::Result<Vec<String>>
+{
+    match kind {
+        Kind::Instance => instances(),
+        Kind::Database => {
+            let conn_params = match option_value(words, &["-I", "--instance"])
+            {
+                Some(name) => Connector::new(credentials::get_connector(name)),
+                None => options.conn_params.clone(),
+            };
+            task::block_on(databases(&conn_params))
+        }
+        Kind::Revision => {
+            let schema_dir = option_value(words, &["--schema-dir"])
+                .map(Path::new);
+            task::block_on(migrations::revisions(schema_dir))
+        }
+    }
+}
+
+pub fn main(cmd: &Complete, options: &Options) -> anyhow::Result<()> {
+    let (current, before) = match cmd.words.split_last() {
+        Some(pair) => pair,
+        None => return Ok(()),
+    };
+    let kind = match kind(before) {
+        Some(kind) => kind,
+        None => return Ok(()),
+    };
+    // no messages here, errors would garble the command line
+    let values = values(kind, before, options).unwrap_or_default();
+    for value in values.iter().filter(|v| v.starts_with(&current[..])) {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{kind, Kind};
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn completion_kind() {
+        assert_eq!(kind(&words("-I")), Some(Kind::Instance));
+        assert_eq!(kind(&words("-I inst --database")), Some(Kind::Database));
+        assert_eq!(kind(&words("server start")), Some(Kind::Instance));
+        assert_eq!(kind(&words("server start --foreground")),
+                   Some(Kind::Instance));
+        assert_eq!(kind(&words("server start inst")), None);
+        assert_eq!(kind(&words("server reset-password")),
+                   Some(Kind::Instance));
+        assert_eq!(kind(&words("server backup run")), Some(Kind::Instance));
+        assert_eq!(kind(&words("server init")), None);
+        assert_eq!(kind(&words("server link")), None);
+        assert_eq!(kind(&words("migrate --to-revision")),
+                   Some(Kind::Revision));
+        assert_eq!(kind(&words("query")), None);
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use clap::{Clap, AppSettings};
+
+use crate::credentials;
+use crate::hint::HintExt;
+use crate::options::Options;
+
+
+/// Print connection environment variables in the shell syntax
+///
+/// Use as `eval $(edgedb env -I myinstance)` to make tools that read
+/// `EDGEDB_DSN` connect to the instance (or the instance of the project
+/// when no connection options are given).
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Env {
+    /// Syntax of the output
+    #[clap(long, default_value="sh",
+           possible_values=&["sh", "fish", "powershell"][..])]
+    pub shell: Shell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Sh,
+    Fish,
+    PowerShell,
+}
+
+impl FromStr for Shell {
+    type Err = anyhow::Error;
+    fn from_str(v: &str) -> anyhow::Result<Shell> {
+        match v {
+            "sh" => Ok(Shell::Sh),
+            "fish" => Ok(Shell::Fish),
+            "powershell" => Ok(Shell::PowerShell),
+            _ => anyhow::bail!("unknown shell {:?}", v),
+        }
+    }
+}
+
+fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Sh => format!("'{}'", value.replace('\'', r"'\''")),
+        Shell::Fish => {
+            format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+        }
+        Shell::PowerShell => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+fn export(shell: Shell, name: &str, value: &str) -> String {
+    let value = quote(shell, value);
+    match shell {
+        Shell::Sh => format!("export {}={}", name, value),
+        Shell::Fish => format!("set -gx {} {}", name, value),
+        Shell::PowerShell => format!("$env:{} = {}", name, value),
+    }
+}
+
+pub fn main(cmd: &Env, options: &Options) -> anyhow::Result<()> {
+    let explicit = options.plugin_env.iter()
+        .find(|(name, _)| *name == "EDGEDB_DSN")
+        .map(|(_, dsn)| dsn.clone());
+    let dsn = match (explicit, &options.instance_name) {
+        (Some(dsn), _) => dsn,
+        (None, Some(name)) => credentials::dsn(name)?,
+        (None, None) => {
+            // report the reason instance couldn't be determined, if any
+            options.conn_params.get()?;
+            return Err(anyhow::anyhow!("no instance to print \
+                environment for"))
+                .hint("specify `-I <instance>` or `--dsn`, \
+                    or run in a project directory")
+                .map_err(Into::into);
+        }
+    };
+    println!("{}", export(cmd.shell, "EDGEDB_DSN", &dsn));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export, Shell};
+
+    #[test]
+    fn quoting() {
+        let value = r"it's\x";
+        assert_eq!(export(Shell::Sh, "NAME", value),
+                   r"export NAME='it'\''s\x'");
+        assert_eq!(export(Shell::Fish, "NAME", value),
+                   r"set -gx NAME 'it\'s\\x'");
+        assert_eq!(export(Shell::PowerShell, "NAME", value),
+                   r"$env:NAME = 'it''s\x'");
+    }
+}
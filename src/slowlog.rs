@@ -0,0 +1,216 @@
+//! Log of slow statements of the REPL, enabled by `\set log-slow-queries`
+//!
+//! Entries are appended as JSON lines to `slow-queries.jsonl` in the stash
+//! directory of the project, or in the config directory if the REPL is not
+//! run within an initialized project.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use clap::{Clap, AppSettings, ValueHint};
+use fn_error_context::context;
+use prettytable::{Table, Row, Cell};
+
+use crate::format;
+use crate::platform::config_dir;
+use crate::project;
+use crate::table;
+
+const FILE_NAME: &str = "slow-queries.jsonl";
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SlowlogCommand {
+    #[clap(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum Subcommand {
+    /// Show statements recorded by `\set log-slow-queries`
+    Show(Show),
+    /// Remove all the recorded statements
+    Clear(Clear),
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Show {
+    /// Project directory (default is the current directory or its parent
+    /// containing `edgedb.toml`)
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub project_dir: Option<PathBuf>,
+    /// Show only this number of the latest entries
+    #[clap(long, short='n')]
+    pub limit: Option<usize>,
+    /// Show the slowest statements first
+    #[clap(long)]
+    pub sort_by_duration: bool,
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Clear {
+    /// Project directory (default is the current directory or its parent
+    /// containing `edgedb.toml`)
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub project_dir: Option<PathBuf>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all="kebab-case")]
+pub struct Entry {
+    pub timestamp: String,
+    pub duration_ms: f64,
+    pub instance: Option<String>,
+    pub database: String,
+    pub query: String,
+}
+
+impl Entry {
+    pub fn new(query: &str, duration: Duration,
+        instance: Option<&str>, database: &str)
+        -> Entry
+    {
+        Entry {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now())
+                .to_string(),
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            instance: instance.map(|s| s.to_string()),
+            database: database.to_string(),
+            query: query.trim().to_string(),
+        }
+    }
+}
+
+/// Path of the log for the project in `project_dir` (or the current one)
+pub fn path(project_dir: Option<&Path>) -> anyhow::Result<PathBuf> {
+    if let Some(dir) = project::project_dir_opt(project_dir)? {
+        let stash = project::stash_path(&dir)?;
+        if stash.exists() {
+            return Ok(stash.join(FILE_NAME));
+        }
+    }
+    Ok(config_dir()?.join(FILE_NAME))
+}
+
+#[context("cannot write slow query log {}", path.display())]
+pub fn append(path: &Path, entry: &Entry) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    fs::OpenOptions::new().create(true).append(true).open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+#[context("cannot read slow query log {}", path.display())]
+fn read(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => Err(e)?,
+    };
+    Ok(parse(&text))
+}
+
+fn parse(text: &str) -> Vec<Entry> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line)
+            .map_err(|e| log::warn!("Skipping invalid log entry: {}", e))
+            .ok())
+        .collect()
+}
+
+fn show(options: &Show) -> anyhow::Result<()> {
+    let path = path(options.project_dir.as_deref())?;
+    let mut entries = read(&path)?;
+    if let Some(limit) = options.limit {
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+    }
+    if options.sort_by_duration {
+        entries.sort_by(|a, b| b.duration_ms.partial_cmp(&a.duration_ms)
+            .unwrap_or(std::cmp::Ordering::Equal));
+    }
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        eprintln!("No slow queries recorded in {}", path.display());
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Time", "Duration", "Database", "Query"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    let query_width = term_size::dimensions_stdout()
+        .map(|(w, _h)| w).unwrap_or(80).saturating_sub(50).max(20);
+    for entry in &entries {
+        let database = match &entry.instance {
+            Some(instance) => format!("{}/{}", instance, entry.database),
+            None => entry.database.clone(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.timestamp),
+            Cell::new(&format::duration(
+                Duration::from_secs_f64(entry.duration_ms / 1000.0))),
+            Cell::new(&database),
+            Cell::new(&textwrap::fill(&entry.query, query_width)),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn clear(options: &Clear) -> anyhow::Result<()> {
+    let path = path(options.project_dir.as_deref())?;
+    match fs::remove_file(&path) {
+        Ok(()) => eprintln!("Removed {}", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("No slow queries recorded in {}", path.display());
+        }
+        Err(e) => Err(e)?,
+    }
+    Ok(())
+}
+
+pub fn main(cmd: &SlowlogCommand) -> anyhow::Result<()> {
+    match &cmd.subcommand {
+        Subcommand::Show(c) => show(c),
+        Subcommand::Clear(c) => clear(c),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{parse, Entry};
+
+    #[test]
+    fn entries() {
+        let entry = Entry::new("SELECT 1;\n", Duration::from_millis(250),
+                               Some("inst"), "edgedb");
+        let mut text = serde_json::to_string(&entry).unwrap();
+        text.push_str("\nnot json\n\n");
+        let entries = parse(&text);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].query, "SELECT 1;");
+        assert_eq!(entries[0].duration_ms, 250.0);
+        assert_eq!(entries[0].instance.as_deref(), Some("inst"));
+    }
+}
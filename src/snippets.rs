@@ -0,0 +1,287 @@
+//! Named queries saved with `\save` in the REPL
+//!
+//! Each snippet is a file `NAME.edgeql` in the `snippets` subdirectory of
+//! the schema directory of the project, so snippets can be committed along
+//! with the schema. Outside of projects they are stored in the config
+//! directory.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_std::task;
+use clap::{Clap, AppSettings, ValueHint};
+use edgeql_parser::preparser::{self, full_statement};
+use fn_error_context::context;
+use prettytable::{Table, Row, Cell};
+
+use crate::hint::HintExt;
+use crate::non_interactive;
+use crate::options::{self, Options};
+use crate::platform::{config_dir, tmp_file_path};
+use crate::project;
+use crate::table;
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct SnippetCommand {
+    #[clap(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum Subcommand {
+    /// List snippets saved by `\save`
+    List(List),
+    /// Execute queries of the snippet
+    Run(Run),
+    /// Delete the snippet
+    Delete(Delete),
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct List {
+    /// Project directory (default is the current directory or its parent
+    /// containing `edgedb.toml`)
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub project_dir: Option<PathBuf>,
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Run {
+    /// Name of the snippet
+    pub name: String,
+    /// Set query parameter, e.g. `--param name=value` for `<str>$name`
+    #[clap(long="param", value_name="NAME=VALUE", number_of_values=1,
+           parse(try_from_str=options::parse_param))]
+    pub params: Vec<(String, String)>,
+    /// Read the parameter from the terminal with input hidden
+    /// (unless set by `--param`)
+    #[clap(long="sensitive", value_name="NAME", number_of_values=1)]
+    pub sensitive: Vec<String>,
+    /// Project directory (default is the current directory or its parent
+    /// containing `edgedb.toml`)
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub project_dir: Option<PathBuf>,
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Delete {
+    /// Name of the snippet
+    pub name: String,
+    /// Project directory (default is the current directory or its parent
+    /// containing `edgedb.toml`)
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub project_dir: Option<PathBuf>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct JsonSnippet<'a> {
+    name: &'a str,
+    query: &'a str,
+}
+
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() {
+        anyhow::bail!("snippet name must not be empty");
+    }
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if !name.chars().all(valid) {
+        anyhow::bail!("invalid snippet name {:?}: only letters, digits, \
+            `_` and `-` are allowed", name);
+    }
+    Ok(())
+}
+
+/// Directory of snippets of the project in `project_dir` (or the current
+/// one)
+pub fn dir(project_dir: Option<&Path>) -> anyhow::Result<PathBuf> {
+    if let Some(dir) = project::project_dir_opt(project_dir)? {
+        let config = project::config::read(&dir.join("edgedb.toml"))?;
+        return Ok(config.project.schema_dir.join("snippets"));
+    }
+    Ok(config_dir()?.join("snippets"))
+}
+
+fn path(dir: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    validate_name(name)?;
+    Ok(dir.join(format!("{}.edgeql", name)))
+}
+
+/// Returns names of the snippets and their queries ordered by name
+#[context("cannot read snippets directory {}", dir.display())]
+pub fn list(dir: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let items = match fs::read_dir(dir) {
+        Ok(items) => items,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(e) => Err(e)?,
+    };
+    let mut result = Vec::new();
+    for item in items {
+        let item = item?;
+        let file_name = item.file_name();
+        let file_name = file_name.to_string_lossy();
+        let name = match file_name.strip_suffix(".edgeql") {
+            Some(name) if validate_name(name).is_ok() => name,
+            _ => continue,
+        };
+        if !item.file_type()?.is_file() {
+            continue;
+        }
+        result.push((name.to_string(), fs::read_to_string(item.path())?));
+    }
+    result.sort();
+    Ok(result)
+}
+
+pub fn read(dir: &Path, name: &str) -> anyhow::Result<String> {
+    let path = path(dir, name)?;
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(anyhow::anyhow!("no snippet {:?} in {}",
+                                name, dir.display()))
+                .hint("run `edgedb snippet list` to see saved snippets")
+                .map_err(Into::into)
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!("cannot read {}", path.display())
+        }),
+    }
+}
+
+/// Writes the snippet, returns `true` if it replaced an existing one
+#[context("cannot save snippet {:?}", name)]
+pub fn save(dir: &Path, name: &str, query: &str) -> anyhow::Result<bool> {
+    let path = path(dir, name)?;
+    let existed = path.exists();
+    fs::create_dir_all(dir)?;
+    let mut text = query.trim().to_string();
+    text.push('\n');
+    let tmp = tmp_file_path(&path);
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, &path)?;
+    Ok(existed)
+}
+
+/// Splits snippet text into statements, skipping empty ones
+pub fn statements(text: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut rest = text;
+    while !preparser::is_empty(rest) {
+        match full_statement(rest.as_bytes(), None) {
+            Ok(len) => {
+                let (statement, tail) = rest.split_at(len);
+                if !preparser::is_empty(statement) {
+                    result.push(statement);
+                }
+                rest = tail;
+            }
+            // last statement without a semicolon
+            Err(_) => {
+                result.push(rest);
+                break;
+            }
+        }
+    }
+    result
+}
+
+fn print_list(cmd: &List) -> anyhow::Result<()> {
+    let dir = dir(cmd.project_dir.as_deref())?;
+    let snippets = list(&dir)?;
+    if cmd.json {
+        let items = snippets.iter()
+            .map(|(name, query)| JsonSnippet { name, query: query.trim() })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if snippets.is_empty() {
+        eprintln!("No snippets saved in {}", dir.display());
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Name", "Query"].iter().map(|x| table::header_cell(x)).collect()));
+    for (name, query) in &snippets {
+        table.add_row(Row::new(vec![
+            Cell::new(name),
+            Cell::new(query.trim()),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run(cmd: &Run, options: &Options) -> anyhow::Result<()> {
+    let text = read(&dir(cmd.project_dir.as_deref())?, &cmd.name)?;
+    task::block_on(async {
+        let mut conn = options.conn_params.connect().await?;
+        let mut used = BTreeSet::new();
+        for statement in statements(&text) {
+            non_interactive::query_with_params(&mut conn, statement,
+                options, &cmd.params, &cmd.sensitive, &mut used).await?;
+        }
+        non_interactive::check_unused(&cmd.params, &used)
+    })
+}
+
+fn delete(cmd: &Delete) -> anyhow::Result<()> {
+    let dir = dir(cmd.project_dir.as_deref())?;
+    let path = path(&dir, &cmd.name)?;
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            eprintln!("Deleted snippet {:?}", cmd.name);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            anyhow::bail!("no snippet {:?} in {}", cmd.name, dir.display());
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!("cannot delete {}", path.display())
+        }),
+    }
+}
+
+pub fn main(cmd: &SnippetCommand, options: &Options) -> anyhow::Result<()> {
+    match &cmd.subcommand {
+        Subcommand::List(c) => print_list(c),
+        Subcommand::Run(c) => run(c, options),
+        Subcommand::Delete(c) => delete(c),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{statements, validate_name};
+
+    #[test]
+    fn names() {
+        assert!(validate_name("active-users_2").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../users").is_err());
+        assert!(validate_name("all users").is_err());
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(statements("SELECT 1;\n SELECT 2;\n"),
+                   ["SELECT 1;", "\n SELECT 2;"]);
+        assert_eq!(statements("SELECT 1;;SELECT User"),
+                   ["SELECT 1;", "SELECT User"]);
+        assert!(statements("  \n").is_empty());
+    }
+}
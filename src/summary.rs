@@ -0,0 +1,68 @@
+//! Footer printed after results of the query in the interactive shell,
+//! e.g. `12 objects returned, 3 fields, 41.0ms`
+use std::fmt;
+use std::time::Duration;
+
+use edgedb_protocol::descriptors::{Descriptor, OutputTypedesc};
+
+use crate::format;
+
+
+pub struct Summary {
+    pub rows: usize,
+    /// Number of fields if the query returns objects
+    pub fields: Option<usize>,
+    pub elapsed: Duration,
+}
+
+/// Returns number of visible fields if the query returns objects
+pub fn object_fields(desc: &OutputTypedesc) -> Option<usize> {
+    let root = desc.root_pos().and_then(|pos| desc.get(pos).ok())?;
+    match root {
+        Descriptor::ObjectShape(shape) => {
+            Some(shape.elements.iter().filter(|e| !e.flag_implicit).count())
+        }
+        _ => None,
+    }
+}
+
+fn plural(num: usize, word: &str) -> String {
+    if num == 1 {
+        format!("{} {}", num, word)
+    } else {
+        format!("{} {}s", num, word)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.fields {
+            Some(fields) => write!(f, "{} returned, {}, {}",
+                plural(self.rows, "object"), plural(fields, "field"),
+                format::duration(self.elapsed)),
+            None => write!(f, "{} returned, {}",
+                plural(self.rows, "row"),
+                format::duration(self.elapsed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use super::Summary;
+
+    #[test]
+    fn display() {
+        assert_eq!(Summary {
+            rows: 12,
+            fields: Some(3),
+            elapsed: Duration::from_micros(41_000),
+        }.to_string(), "12 objects returned, 3 fields, 41.0ms");
+        assert_eq!(Summary {
+            rows: 1,
+            fields: None,
+            elapsed: Duration::from_micros(345),
+        }.to_string(), "1 row returned, 345µs");
+    }
+}
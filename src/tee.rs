@@ -0,0 +1,91 @@
+//! Copy of the session output to a log file (`--tee`)
+//!
+//! Statements entered, query results and errors are appended to the file
+//! as plain text (colors are stripped), while still being printed to the
+//! terminal.
+
+use std::borrow::Cow;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+
+
+static FILE: Lazy<Mutex<Option<fs::File>>> = Lazy::new(|| Mutex::new(None));
+
+
+/// Opens the log file, output is appended if it exists
+pub fn open(path: &Path) -> anyhow::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true)
+        .open(path)
+        .with_context(|| format!("cannot open {}", path.display()))?;
+    writeln!(file, "# Session started at {}",
+        humantime::format_rfc3339_seconds(SystemTime::now()))
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    *FILE.lock().expect("tee file lock") = Some(file);
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    FILE.lock().map(|f| f.is_some()).unwrap_or(false)
+}
+
+/// Appends text to the log file (if enabled)
+pub fn write(text: &str) {
+    let mut file = match FILE.lock() {
+        Ok(file) => file,
+        Err(_) => return,
+    };
+    if let Some(f) = &mut *file {
+        if let Err(e) = f.write_all(strip_ansi(text).as_bytes()) {
+            // don't interrupt the session, but don't spam errors either
+            log::error!("Cannot write to the tee file, disabling: {}", e);
+            *file = None;
+        }
+    }
+}
+
+/// Removes ANSI escape sequences (colors) from the text
+fn strip_ansi(text: &str) -> Cow<str> {
+    if !text.contains('\x1b') {
+        return text.into();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'[') {
+            chars.next();
+            // parameters and intermediate bytes, then the final byte
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+    result.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::strip_ansi;
+
+    #[test]
+    fn strip() {
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(strip_ansi("\x1b[38;5;2m{1}\x1b[0m"), "{1}");
+        assert_eq!(strip_ansi("a\x1b[1;31mb\x1b[mc"), "abc");
+        assert_eq!(strip_ansi("\x1b(Bx"), "x");
+        assert_eq!(strip_ansi("юникод\x1b[2m!"), "юникод!");
+    }
+}
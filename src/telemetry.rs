@@ -0,0 +1,414 @@
+//! Opt-in anonymous usage statistics
+//!
+//! Only names of the subcommands and categories of errors are counted,
+//! never their arguments, instance names or query text. Counters are kept
+//! in a local file, which can be inspected with `telemetry show-pending`,
+//! and sent about once a day in a background thread, so commands never
+//! wait for the network.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use async_std::task;
+use clap::{App, Arg, ArgSettings, Clap, AppSettings, IntoApp};
+use edgedb_protocol::error_response::ErrorResponse;
+use fn_error_context::context;
+use serde::{Serialize, Deserialize};
+
+use crate::async_util::timeout;
+use crate::bug::Bug;
+use crate::commands::ExitCode;
+use crate::connect::is_authentication_error;
+use crate::options::RawOptions;
+use crate::platform::{cache_dir, config_dir, tmp_file_path};
+use crate::server::remote;
+use crate::table;
+
+const DEFAULT_URL: &str = "https://telemetry.edgedb.com/v1/cli";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(24*3600);
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct TelemetryCommand {
+    #[clap(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+#[derive(Clap, Clone, Debug)]
+pub enum Subcommand {
+    /// Start sending anonymous usage statistics
+    Enable(Enable),
+    /// Stop sending usage statistics and remove the ones not sent yet
+    Disable(Disable),
+    /// Show whether telemetry is enabled and where statistics are sent
+    Status(Status),
+    /// Show statistics that are collected but not sent yet
+    ShowPending(ShowPending),
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Enable {
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Disable {
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Status {
+}
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct ShowPending {
+    /// Output in JSON format (exactly what will be sent)
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Settings {
+    enabled: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Pending {
+    #[serde(with="humantime_serde", default)]
+    since: Option<SystemTime>,
+    /// Last time sending was started, so that concurrently running
+    /// commands don't send the same counters
+    #[serde(with="humantime_serde", default)]
+    last_attempt: Option<SystemTime>,
+    #[serde(default)]
+    commands: BTreeMap<String, u64>,
+    #[serde(default)]
+    errors: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    version: &'static str,
+    platform: String,
+    commands: &'a BTreeMap<String, u64>,
+    errors: &'a BTreeMap<String, u64>,
+}
+
+
+fn settings_path() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("telemetry.json"))
+}
+
+fn pending_path() -> anyhow::Result<PathBuf> {
+    Ok(cache_dir()?.join("telemetry_pending.json"))
+}
+
+fn url() -> String {
+    env::var("EDGEDB_TELEMETRY_URL").unwrap_or_else(|_| DEFAULT_URL.into())
+}
+
+fn read_json<T: Default + serde::de::DeserializeOwned>(path: &Path)
+    -> anyhow::Result<T>
+{
+    match fs::read(path) {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e)?,
+    }
+}
+
+#[context("cannot write {}", path.display())]
+fn write_json<T: Serialize>(path: &Path, data: &T) -> anyhow::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp = tmp_file_path(path);
+    fs::write(&tmp, serde_json::to_vec_pretty(data)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn write_settings(settings: &Settings) -> anyhow::Result<()> {
+    write_json(&settings_path()?, settings)
+}
+
+/// Telemetry is enabled by `telemetry enable` and can be overridden by
+/// setting `EDGEDB_NO_TELEMETRY`
+fn enabled() -> bool {
+    if env::var_os("EDGEDB_NO_TELEMETRY")
+        .map(|x| !x.is_empty()).unwrap_or(false)
+    {
+        return false;
+    }
+    settings_path()
+        .and_then(|p| read_json::<Settings>(&p))
+        .map(|s| s.enabled)
+        .unwrap_or(false)
+}
+
+fn report(pending: &Pending) -> Report {
+    Report {
+        version: env!("CARGO_PKG_VERSION"),
+        platform: format!("{}-{}", env::consts::ARCH, env::consts::OS),
+        commands: &pending.commands,
+        errors: &pending.errors,
+    }
+}
+
+/// Returns `true` if the option (e.g. `-I`) is followed by a value
+fn takes_value(apps: &[&App], option: &str) -> bool {
+    let matches = |arg: &&Arg| match option.strip_prefix("--") {
+        Some(long) => arg.get_long() == Some(long),
+        None => option.len() == 2 &&
+            arg.get_short().map(|c| c.to_string()).as_deref()
+            == Some(&option[1..]),
+    };
+    apps.iter().rev()
+        .find_map(|app| app.get_arguments().find(matches))
+        .map(|arg| arg.is_set(ArgSettings::TakesValue))
+        .unwrap_or(false)
+}
+
+/// Names of the subcommands, e.g. `server init`, without any arguments
+fn command_name(app: &App, args: impl Iterator<Item=String>) -> String {
+    let mut apps = vec![app];
+    let mut name = Vec::new();
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg.starts_with('-') {
+            if !arg.contains('=') && takes_value(&apps, &arg) {
+                args.next();
+            }
+            continue;
+        }
+        let app = apps[apps.len()-1];
+        match app.get_subcommands().find(|s| s.get_name() == arg) {
+            Some(sub) => {
+                name.push(sub.get_name().to_string());
+                apps.push(sub);
+            }
+            // an argument or the external subcommand which can be
+            // anything
+            None => break,
+        }
+    }
+    if name.is_empty() {
+        "-".into()
+    } else {
+        name.join(" ")
+    }
+}
+
+/// Category of the error that doesn't contain any user data
+fn error_category(err: &anyhow::Error) -> String {
+    if err.is::<ExitCode>() {
+        return "exit-code".into();
+    }
+    if is_authentication_error(err) {
+        return "authentication".into();
+    }
+    for item in err.chain() {
+        if item.is::<Bug>() {
+            return "bug".into();
+        }
+        if let Some(e) = item.downcast_ref::<ErrorResponse>() {
+            return format!("server-{:02x}", e.code >> 24);
+        }
+        if item.is::<native_tls::Error>() {
+            return "tls".into();
+        }
+        if let Some(e) = item.downcast_ref::<io::Error>() {
+            return format!("io-{:?}", e.kind()).to_lowercase();
+        }
+    }
+    "other".into()
+}
+
+#[context("cannot update telemetry counters")]
+fn _record(command: &str, result: &anyhow::Result<()>)
+    -> anyhow::Result<()>
+{
+    let path = pending_path()?;
+    let mut pending: Pending = read_json(&path)?;
+    pending.since.get_or_insert_with(SystemTime::now);
+    *pending.commands.entry(command.into()).or_insert(0) += 1;
+    if let Err(e) = result {
+        *pending.errors.entry(error_category(e)).or_insert(0) += 1;
+    }
+    write_json(&path, &pending)
+}
+
+/// Subcommands of the current process, e.g. `server init`
+pub fn current_command() -> String {
+    command_name(&RawOptions::into_app(), env::args().skip(1))
+}
+
+/// Counts the command just finished, if telemetry is enabled
+pub fn record(result: &anyhow::Result<()>) {
+    if !enabled() {
+        return;
+    }
+    if let Err(e) = _record(&current_command(), result) {
+        log::debug!("{:#}", e);
+    }
+}
+
+fn _flush() -> anyhow::Result<()> {
+    let path = pending_path()?;
+    let mut pending: Pending = read_json(&path)?;
+    let now = SystemTime::now();
+    let due = |time: Option<SystemTime>| time
+        .map(|t| t + FLUSH_INTERVAL < now)
+        .unwrap_or(false);
+    if !due(pending.since) ||
+        (pending.last_attempt.is_some() && !due(pending.last_attempt))
+    {
+        return Ok(());
+    }
+    pending.last_attempt = Some(now);
+    write_json(&path, &pending)?;
+    task::block_on(timeout(Duration::from_secs(10),
+                           remote::post_json(&url(), &report(&pending))))?;
+
+    // other commands might have been counted while sending
+    let mut current: Pending = read_json(&path)?;
+    for (counters, sent) in [
+        (&mut current.commands, &pending.commands),
+        (&mut current.errors, &pending.errors),
+    ].iter_mut() {
+        for (key, num) in sent.iter() {
+            if let Some(value) = counters.get_mut(key) {
+                *value = value.saturating_sub(*num);
+            }
+        }
+        counters.retain(|_, value| *value > 0);
+    }
+    current.since = if current.commands.is_empty() {
+        None
+    } else {
+        Some(now)
+    };
+    current.last_attempt = None;
+    write_json(&path, &current)
+}
+
+/// Sends statistics in background if the last batch is old enough
+///
+/// If the process finishes earlier, the batch is sent by one of the
+/// subsequent commands.
+pub fn flush_in_background() {
+    if !enabled() {
+        return;
+    }
+    thread::spawn(|| {
+        if let Err(e) = _flush() {
+            log::debug!("Cannot send telemetry: {:#}", e);
+        }
+    });
+}
+
+fn enable(_: &Enable) -> anyhow::Result<()> {
+    write_settings(&Settings { enabled: true })?;
+    eprintln!("Telemetry is enabled. Anonymous statistics of commands used \
+        and errors encountered will be sent to {} about once a day. \
+        Run `edgedb telemetry show-pending` to see them.", url());
+    Ok(())
+}
+
+fn disable(_: &Disable) -> anyhow::Result<()> {
+    write_settings(&Settings { enabled: false })?;
+    let pending = pending_path()?;
+    match fs::remove_file(&pending) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => Err(e)?,
+    }
+    eprintln!("Telemetry is disabled.");
+    Ok(())
+}
+
+fn status(_: &Status) -> anyhow::Result<()> {
+    let enabled = enabled();
+    let pending_path = pending_path()?;
+    let pending: Pending = read_json(&pending_path)?;
+    let since = pending.since
+        .map(|t| humantime::format_rfc3339_seconds(t).to_string())
+        .unwrap_or_else(|| "-".into());
+    table::settings(&[
+        ("Enabled", if enabled { "yes" } else { "no" }),
+        ("Endpoint", &url()),
+        ("Pending file", &pending_path.display().to_string()),
+        ("Collected since", &since),
+    ]);
+    if !enabled && env::var_os("EDGEDB_NO_TELEMETRY").is_some() {
+        eprintln!("Note: telemetry is disabled by EDGEDB_NO_TELEMETRY");
+    }
+    Ok(())
+}
+
+fn show_pending(options: &ShowPending) -> anyhow::Result<()> {
+    let pending: Pending = read_json(&pending_path()?)?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report(&pending))?);
+        return Ok(());
+    }
+    if pending.commands.is_empty() {
+        eprintln!("No statistics are pending.");
+        return Ok(());
+    }
+    println!("Commands:");
+    for (name, num) in &pending.commands {
+        println!("  {:<32} {}", name, num);
+    }
+    if !pending.errors.is_empty() {
+        println!("Errors:");
+        for (name, num) in &pending.errors {
+            println!("  {:<32} {}", name, num);
+        }
+    }
+    Ok(())
+}
+
+pub fn main(cmd: &TelemetryCommand) -> anyhow::Result<()> {
+    match &cmd.subcommand {
+        Subcommand::Enable(c) => enable(c),
+        Subcommand::Disable(c) => disable(c),
+        Subcommand::Status(c) => status(c),
+        Subcommand::ShowPending(c) => show_pending(c),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::IntoApp;
+
+    use crate::options::RawOptions;
+    use super::command_name;
+
+    fn name(args: &[&str]) -> String {
+        command_name(&RawOptions::into_app(),
+                     args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn command_names() {
+        assert_eq!(name(&[]), "-");
+        assert_eq!(name(&["-I", "inst", "query", "SELECT secret"]),
+                   "query");
+        assert_eq!(name(&["server", "init", "--port=5000", "my_instance"]),
+                   "server init");
+        assert_eq!(name(&["--port=5000", "--admin", "dump", "x.dump"]),
+                   "dump");
+        assert_eq!(name(&["--", "server"]), "-");
+        assert_eq!(name(&["some-plugin", "server"]), "-");
+    }
+}
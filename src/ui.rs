@@ -0,0 +1,123 @@
+//! Opening the web UI served by the instance
+
+use std::process::Command;
+
+use anyhow::Context;
+use async_std::task;
+use clap::{Clap, AppSettings};
+
+use crate::commands::ExitCode;
+use crate::hint::HintExt;
+use crate::http::Endpoint;
+use crate::options::Options;
+
+
+#[derive(Clap, Clone, Debug)]
+#[clap(setting=AppSettings::DisableVersionFlag)]
+pub struct Ui {
+    /// Print the URL instead of opening the browser (for headless
+    /// environments)
+    #[clap(long)]
+    pub print_url: bool,
+    /// Base URL of the server HTTP port (discovered from connection options
+    /// by default)
+    #[clap(long)]
+    pub url: Option<String>,
+}
+
+/// Appends login token issued by the server to the UI URL
+///
+/// The token is put in the fragment so it's never sent to the server and
+/// doesn't end up in access logs; the UI picks it up on load.
+fn login_url(ui_url: &str, token: Option<&str>) -> String {
+    match token {
+        Some(token) => {
+            format!("{}#authToken={}", ui_url, urlencoding::encode(token))
+        }
+        None => ui_url.to_string(),
+    }
+}
+
+async fn check_enabled(endpoint: &Endpoint) -> anyhow::Result<()> {
+    let url = endpoint.ui_url();
+    let status = endpoint.get_status(&url).await?;
+    if status == 404 {
+        return Err(anyhow::anyhow!("web UI is not enabled at {}", url))
+            .hint("restart the server with `--admin-ui=enabled` \
+                   (requires EdgeDB 2.0 or later)")
+            .map_err(Into::into);
+    }
+    if !status.is_success() {
+        anyhow::bail!("HTTP failure at {}: {} {}", url,
+            status, status.canonical_reason());
+    }
+    Ok(())
+}
+
+pub fn open_browser(url: &str) -> anyhow::Result<()> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        // the empty argument is a window title, otherwise `start` takes
+        // the quoted URL as one
+        cmd.arg("/C").arg("start").arg("").arg(url);
+        cmd
+    } else if cfg!(target_os="macos") {
+        let mut cmd = Command::new("open");
+        cmd.arg(url);
+        cmd
+    } else {
+        let mut cmd = Command::new("xdg-open");
+        cmd.arg(url);
+        cmd
+    };
+    let status = cmd.status()
+        .with_context(|| format!("cannot run {:?}", cmd))?;
+    if !status.success() {
+        anyhow::bail!("{:?} failed: {}", cmd, status);
+    }
+    Ok(())
+}
+
+pub fn main(cmd: &Ui, options: &Options) -> anyhow::Result<()> {
+    let endpoint = Endpoint::from_options(options, cmd.url.as_deref())?;
+    let token = task::block_on(async {
+        check_enabled(&endpoint).await?;
+        endpoint.login_token().await
+    })?;
+    if token.is_none() && !cmd.print_url {
+        eprintln!("Cannot obtain login token for the instance, \
+            the UI will ask to log in.");
+    }
+    let url = login_url(&endpoint.ui_url(), token.as_deref());
+    if cmd.print_url {
+        println!("{}", url);
+        return Ok(());
+    }
+    match open_browser(&url) {
+        Ok(()) => {
+            eprintln!("Opening URL in browser: {}", endpoint.ui_url());
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("edgedb error: cannot open browser: {:#}", e);
+            eprintln!("Open the following URL manually:");
+            println!("{}", url);
+            Err(ExitCode::new(1).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::login_url;
+
+    #[test]
+    fn urls() {
+        assert_eq!(login_url("http://localhost:10700/ui", None),
+                   "http://localhost:10700/ui");
+        assert_eq!(login_url("http://localhost:10700/ui",
+                             Some("edbt_eyJhbGc.eyJzdWI=.c2ln")),
+                   "http://localhost:10700/ui\
+                    #authToken=edbt_eyJhbGc.eyJzdWI%3D.c2ln");
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt;
 use std::error::Error;
 
 use anyhow::{Context};
+use uuid::Uuid;
 
 use edgedb_protocol::value::Value;
 use edgedb_protocol::codec;
@@ -14,7 +15,10 @@ use crate::prompt;
 pub struct Canceled;
 
 
-pub async fn input_variables(desc: &InputTypedesc, state: &mut repl::PromptRpc)
+/// Asks values of the query parameters in the REPL, hiding the input of
+/// ones listed in `sensitive`
+pub async fn input_variables(desc: &InputTypedesc, state: &mut repl::PromptRpc,
+    sensitive: &[String])
     -> Result<Value, anyhow::Error>
 {
     if desc.is_empty_tuple() {
@@ -25,7 +29,7 @@ pub async fn input_variables(desc: &InputTypedesc, state: &mut repl::PromptRpc)
             let mut val = Vec::with_capacity(tuple.element_types.len());
             for (idx, el) in tuple.element_types.iter().enumerate() {
                 val.push(input_item(&format!("{}", idx),
-                    desc.get(*el)?, desc, state).await?);
+                    desc.get(*el)?, desc, state, sensitive).await?);
             }
             return Ok(Value::Tuple(val));
         }
@@ -34,7 +38,7 @@ pub async fn input_variables(desc: &InputTypedesc, state: &mut repl::PromptRpc)
             let shape = tuple.elements[..].into();
             for el in tuple.elements.iter() {
                 fields.push(input_item(&el.name,
-                    desc.get(el.type_pos)?, desc, state).await?);
+                    desc.get(el.type_pos)?, desc, state, sensitive).await?);
             }
             return Ok(Value::NamedTuple { shape, fields });
         }
@@ -45,9 +49,8 @@ pub async fn input_variables(desc: &InputTypedesc, state: &mut repl::PromptRpc)
     }
 }
 
-async fn input_item(name: &str, mut item: &Descriptor, all: &InputTypedesc,
-    state: &mut repl::PromptRpc)
-    -> Result<Value, anyhow::Error>
+fn scalar_type_id(mut item: &Descriptor, all: &InputTypedesc)
+    -> Result<Uuid, anyhow::Error>
 {
     match item {
         Descriptor::Scalar(s) => {
@@ -56,49 +59,115 @@ async fn input_item(name: &str, mut item: &Descriptor, all: &InputTypedesc,
         _ => {},
     }
     match item {
-        Descriptor::BaseScalar(s) => {
-            let type_name = match s.id {
-                codec::STD_STR => "str",
-                codec::STD_UUID => "uuid",
-                codec::STD_INT16 => "int16",
-                codec::STD_INT32 => "int32",
-                codec::STD_INT64 => "int64",
-                _ => return Err(anyhow::anyhow!(
-                        "Unimplemented input type {}", s.id))
-            };
+        Descriptor::BaseScalar(s) => Ok(s.id),
+        _ => Err(anyhow::anyhow!(
+                "Unimplemented input type descriptor: {:?}", item)),
+    }
+}
 
-            let val = match state.variable_input(name, type_name, "").await? {
-                | prompt::Input::Text(val) => val,
-                | prompt::Input::Interrupt
-                | prompt::Input::Eof => Err(Canceled)?,
-            };
+fn type_name(type_id: Uuid) -> Result<&'static str, anyhow::Error> {
+    match type_id {
+        codec::STD_STR => Ok("str"),
+        codec::STD_UUID => Ok("uuid"),
+        codec::STD_INT16 => Ok("int16"),
+        codec::STD_INT32 => Ok("int32"),
+        codec::STD_INT64 => Ok("int64"),
+        codec::STD_FLOAT32 => Ok("float32"),
+        codec::STD_FLOAT64 => Ok("float64"),
+        codec::STD_BOOL => Ok("bool"),
+        _ => Err(anyhow::anyhow!("Unimplemented input type {}", type_id))
+    }
+}
 
-            match s.id {
-                codec::STD_STR => {
-                    Ok(Value::Str(val))
-                }
-                codec::STD_UUID => {
-                    let v = val.parse().context("invalid uuid value")?;
-                    Ok(Value::Uuid(v))
-                }
-                codec::STD_INT16 => {
-                    let v = val.parse::<i16>().context("invalid int16 value")?;
-                    Ok(Value::Int16(v))
-                }
-                codec::STD_INT32 => {
-                    let v = val.parse::<i32>().context("invalid int32 value")?;
-                    Ok(Value::Int32(v))
-                }
-                codec::STD_INT64 => {
-                    let v = val.parse::<i64>().context("invalid int64 value")?;
-                    Ok(Value::Int64(v))
-                }
-                _ => Err(anyhow::anyhow!(
-                         "Unimplemented input type {}", s.id))
+fn parse_value(type_id: Uuid, val: String) -> Result<Value, anyhow::Error> {
+    match type_id {
+        codec::STD_STR => {
+            Ok(Value::Str(val))
+        }
+        codec::STD_UUID => {
+            let v = val.parse().context("invalid uuid value")?;
+            Ok(Value::Uuid(v))
+        }
+        codec::STD_INT16 => {
+            let v = val.parse::<i16>().context("invalid int16 value")?;
+            Ok(Value::Int16(v))
+        }
+        codec::STD_INT32 => {
+            let v = val.parse::<i32>().context("invalid int32 value")?;
+            Ok(Value::Int32(v))
+        }
+        codec::STD_INT64 => {
+            let v = val.parse::<i64>().context("invalid int64 value")?;
+            Ok(Value::Int64(v))
+        }
+        codec::STD_FLOAT32 => {
+            let v = val.parse::<f32>().context("invalid float32 value")?;
+            Ok(Value::Float32(v))
+        }
+        codec::STD_FLOAT64 => {
+            let v = val.parse::<f64>().context("invalid float64 value")?;
+            Ok(Value::Float64(v))
+        }
+        codec::STD_BOOL => {
+            let v = val.parse::<bool>().context("invalid bool value")?;
+            Ok(Value::Bool(v))
+        }
+        _ => Err(anyhow::anyhow!("Unimplemented input type {}", type_id))
+    }
+}
+
+async fn input_item(name: &str, item: &Descriptor, all: &InputTypedesc,
+    state: &mut repl::PromptRpc, sensitive: &[String])
+    -> Result<Value, anyhow::Error>
+{
+    let type_id = scalar_type_id(item, all)?;
+    let type_name = type_name(type_id)?;
+    let hidden = sensitive.iter().any(|s| s == name);
+    let val = match state.variable_input(name, type_name, "", hidden).await? {
+        | prompt::Input::Text(val) => val,
+        | prompt::Input::Interrupt
+        | prompt::Input::Eof => Err(Canceled)?,
+    };
+    parse_value(type_id, val)
+}
+
+/// Builds query arguments without interactive prompt
+///
+/// The `get_value` callback receives parameter name and type name and
+/// returns a textual value which is then converted to the specified type.
+pub fn make_arguments<F>(desc: &InputTypedesc, mut get_value: F)
+    -> Result<Value, anyhow::Error>
+    where F: FnMut(&str, &str) -> Result<String, anyhow::Error>
+{
+    if desc.is_empty_tuple() {
+        return Ok(Value::Tuple(Vec::new()));
+    }
+    let mut item = |name: &str, el: &Descriptor| {
+        let type_id = scalar_type_id(el, desc)?;
+        let val = get_value(name, type_name(type_id)?)?;
+        parse_value(type_id, val)
+            .with_context(|| format!("bad value for parameter ${}", name))
+    };
+    match desc.root() {
+        Descriptor::Tuple(tuple) => {
+            let mut val = Vec::with_capacity(tuple.element_types.len());
+            for (idx, el) in tuple.element_types.iter().enumerate() {
+                val.push(item(&format!("{}", idx), desc.get(*el)?)?);
             }
+            return Ok(Value::Tuple(val));
+        }
+        Descriptor::NamedTuple(tuple) => {
+            let mut fields = Vec::with_capacity(tuple.elements.len());
+            let shape = tuple.elements[..].into();
+            for el in tuple.elements.iter() {
+                fields.push(item(&el.name, desc.get(el.type_pos)?)?);
+            }
+            return Ok(Value::NamedTuple { shape, fields });
+        }
+        root => {
+            return Err(anyhow::anyhow!(
+                "Unknown input type descriptor: {:?}", root));
         }
-        _ => Err(anyhow::anyhow!(
-                "Unimplemented input type descriptor: {:?}", item)),
     }
 }
 
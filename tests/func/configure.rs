@@ -1,6 +1,8 @@
 use std::cmp::min;
 use std::collections::BTreeSet;
 
+use predicates::str::contains;
+
 use crate::SERVER;
 
 
@@ -98,3 +100,11 @@ fn configure_all_parameters() {
         assert_eq!(db_reset_options, cmd_reset_options); // nice diff
     }
 }
+
+#[test]
+fn configure_show() {
+    SERVER.admin_cmd()
+        .arg("configure").arg("show")
+        .assert().success()
+        .stdout(contains("listen_port\t"));
+}
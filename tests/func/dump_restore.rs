@@ -1,3 +1,5 @@
+use predicates::str::contains;
+
 use crate::{SERVER, ServerGuard};
 
 #[test]
@@ -28,6 +30,216 @@ fn dump_restore_cycle() {
     println!("query");
 }
 
+#[test]
+fn restore_into_non_empty() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    SERVER.admin_cmd().arg("create-database").arg("dump_03")
+        .assert().success();
+    SERVER.database_cmd("dump_03").arg("query")
+        .arg("CREATE TYPE Hello { CREATE REQUIRED PROPERTY name -> str; }")
+        .arg("INSERT Hello { name := 'world' }")
+        .assert().success();
+    SERVER.database_cmd("dump_03").arg("dump").arg("./tmp/dump_03.dump")
+        .assert().success();
+    SERVER.database_cmd("dump_03").arg("restore").arg("./tmp/dump_03.dump")
+        .assert().code(1)
+        .stderr(contains("--force-clean"));
+    SERVER.database_cmd("dump_03").arg("restore")
+        .arg("--ignore-existing").arg("./tmp/dump_03.dump")
+        .assert().success()
+        .stderr(contains("the database is not empty"));
+    SERVER.database_cmd("dump_03").arg("query")
+        .arg("INSERT Hello { name := 'extra' }")
+        .assert().success();
+    SERVER.database_cmd("dump_03").arg("restore")
+        .arg("--force-clean").arg("--non-interactive")
+        .arg("--verbose").arg("./tmp/dump_03.dump")
+        .assert().success()
+        .stderr(contains("Wiping the database"));
+    SERVER.database_cmd("dump_03").arg("query")
+        .arg("SELECT Hello.name")
+        .assert().success()
+        .stdout("\"world\"\n");
+}
+
+#[test]
+fn restore_migrations_force_clean() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    let migrate = |database: &str, schema: &str| {
+        SERVER.database_cmd(database).arg("query")
+            .arg(format!("START MIGRATION TO {{ {} }}", schema))
+            .arg("POPULATE MIGRATION")
+            .arg("COMMIT MIGRATION")
+            .assert().success();
+    };
+    SERVER.admin_cmd().arg("create-database").arg("dump_08")
+        .assert().success();
+    migrate("dump_08",
+            "module default { type Hello { property name -> str } }");
+    SERVER.database_cmd("dump_08").arg("query")
+        .arg("INSERT Hello { name := 'world' }")
+        .assert().success();
+    SERVER.database_cmd("dump_08").arg("dump").arg("./tmp/dump_08.dump")
+        .assert().success();
+    SERVER.admin_cmd().arg("create-database").arg("restore_08")
+        .assert().success();
+    migrate("restore_08", "module default { type Other; }");
+    SERVER.database_cmd("restore_08").arg("restore")
+        .arg("--force-clean").arg("--non-interactive")
+        .arg("./tmp/dump_08.dump")
+        .assert().success();
+    SERVER.database_cmd("restore_08").arg("query")
+        .arg("SELECT Hello.name")
+        .arg("SELECT count(schema::Migration)")
+        .assert().success()
+        .stdout("\"world\"\n1\n");
+}
+
+#[test]
+fn restore_schema_only_and_data_only() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    SERVER.admin_cmd().arg("create-database").arg("dump_04")
+        .assert().success();
+    SERVER.database_cmd("dump_04").arg("query")
+        .arg("CREATE TYPE Hello { CREATE REQUIRED PROPERTY name -> str; }")
+        .arg("INSERT Hello { name := 'world' }")
+        .assert().success();
+    SERVER.database_cmd("dump_04").arg("dump").arg("./tmp/dump_04.dump")
+        .assert().success();
+    SERVER.admin_cmd().arg("create-database").arg("restore_04")
+        .assert().success();
+    SERVER.database_cmd("restore_04").arg("restore")
+        .arg("--schema-only").arg("./tmp/dump_04.dump")
+        .assert().success();
+    SERVER.database_cmd("restore_04").arg("query")
+        .arg("SELECT count(Hello)")
+        .assert().success()
+        .stdout("0\n");
+    SERVER.database_cmd("restore_04").arg("query")
+        .arg("INSERT Hello { name := 'stale' }")
+        .assert().success();
+    SERVER.database_cmd("restore_04").arg("restore")
+        .arg("--data-only").arg("--non-interactive")
+        .arg("./tmp/dump_04.dump")
+        .assert().success();
+    SERVER.database_cmd("restore_04").arg("query")
+        .arg("SELECT Hello.name")
+        .assert().success()
+        .stdout("\"world\"\n");
+    SERVER.database_cmd("restore_04").arg("query")
+        .arg("CREATE TYPE Extra")
+        .assert().success();
+    SERVER.database_cmd("restore_04").arg("restore")
+        .arg("--data-only").arg("--non-interactive")
+        .arg("./tmp/dump_04.dump")
+        .assert().code(1)
+        .stderr(contains("schema of the database differs"));
+}
+
+#[test]
+fn incremental_dump() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    SERVER.admin_cmd().arg("create-database").arg("dump_07")
+        .assert().success();
+    SERVER.database_cmd("dump_07").arg("query")
+        .arg("CREATE TYPE Note { \
+              CREATE PROPERTY body -> str; \
+              CREATE PROPERTY updated_at -> datetime; }")
+        .arg("INSERT Note { body := 'first', \
+              updated_at := datetime_of_statement() }")
+        .assert().success();
+    SERVER.database_cmd("dump_07").arg("dump").arg("--manifest")
+        .arg("./tmp/dump_07.dump")
+        .assert().success();
+    SERVER.admin_cmd().arg("create-database").arg("restore_07")
+        .assert().success();
+    SERVER.database_cmd("restore_07").arg("restore")
+        .arg("./tmp/dump_07.dump")
+        .assert().success();
+    SERVER.database_cmd("dump_07").arg("query")
+        .arg("UPDATE Note SET { body := 'edited', \
+              updated_at := datetime_of_statement() }")
+        .arg("INSERT Note { body := 'second', \
+              updated_at := datetime_of_statement() }")
+        .assert().success();
+    SERVER.database_cmd("dump_07").arg("dump")
+        .arg("--since=./tmp/dump_07.dump").arg("./tmp/dump_07_inc.json")
+        .assert().success();
+    // applying the same dump again doesn't duplicate objects
+    for _ in 0..2 {
+        SERVER.database_cmd("restore_07").arg("restore")
+            .arg("--apply-incremental").arg("./tmp/dump_07_inc.json")
+            .assert().success();
+    }
+    SERVER.database_cmd("restore_07").arg("query")
+        .arg("SELECT Note.body ORDER BY Note.body")
+        .assert().success()
+        .stdout("\"edited\"\n\"second\"\n");
+}
+
+#[test]
+fn dump_anonymized() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    std::fs::write("./tmp/anonymize_05.toml", r###"
+        ["default::Person"]
+        email = { fake = "user{n}@example.com" }
+        phone = "null"
+
+        ["default::Missing"]
+        name = "hash"
+    "###).expect("can write rules");
+    SERVER.admin_cmd().arg("create-database").arg("dump_05")
+        .assert().success();
+    SERVER.database_cmd("dump_05").arg("query")
+        .arg("CREATE TYPE Person { \
+              CREATE REQUIRED PROPERTY email -> str; \
+              CREATE PROPERTY phone -> str; }")
+        .arg("INSERT Person { email := 'alice@corp.com', phone := '555' }")
+        .assert().success();
+    SERVER.database_cmd("dump_05").arg("dump")
+        .arg("--anonymize=./tmp/anonymize_05.toml").arg("./tmp/dump_05.dump")
+        .assert().success()
+        .stderr(contains("\"default::Missing\""));
+    SERVER.admin_cmd().arg("create-database").arg("restore_05")
+        .assert().success();
+    SERVER.database_cmd("restore_05").arg("restore").arg("./tmp/dump_05.dump")
+        .assert().success();
+    SERVER.database_cmd("restore_05").arg("query")
+        .arg("SELECT Person.email")
+        .arg("SELECT count(Person.phone)")
+        .assert().success()
+        .stdout("\"user1@example.com\"\n0\n");
+
+    std::fs::write("./tmp/anonymize_06.toml", r###"
+        ["default::Person"]
+        email = "null"
+    "###).expect("can write rules");
+    SERVER.database_cmd("dump_05").arg("dump")
+        .arg("--anonymize=./tmp/anonymize_06.toml").arg("./tmp/dump_06.dump")
+        .assert().code(1)
+        .stderr(contains("is required"));
+}
+
+#[test]
+fn clone_database() {
+    SERVER.admin_cmd().arg("create-database").arg("clone_01")
+        .assert().success();
+    SERVER.database_cmd("clone_01").arg("query")
+        .arg("CREATE TYPE Hello { CREATE REQUIRED PROPERTY name -> str; }")
+        .arg("INSERT Hello { name := 'world' }")
+        .assert().success();
+    SERVER.database_cmd("clone_01").arg("clone").arg("--to=/clone_02")
+        .assert().success()
+        .stderr(contains("Copied"));
+    SERVER.database_cmd("clone_02").arg("query")
+        .arg("SELECT Hello.name")
+        .assert().success()
+        .stdout("\"world\"\n");
+    SERVER.database_cmd("clone_01").arg("clone").arg("--to=/clone_02")
+        .assert().code(1)
+        .stderr(contains("is not empty"));
+}
+
 #[test]
 fn dump_all_without_a_format() {
     SERVER.admin_cmd().arg("dump").arg("--all").arg("dump01-dir")
@@ -63,3 +275,72 @@ fn dump_restore_all() {
         .stdout("\"world\"\n");
     println!("query");
 }
+
+#[test]
+fn import_csv_and_json() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    SERVER.admin_cmd().arg("create-database").arg("import_01")
+        .assert().success();
+    SERVER.database_cmd("import_01").arg("query")
+        .arg("CREATE TYPE Item { \
+              CREATE REQUIRED PROPERTY name -> str; \
+              CREATE PROPERTY qty -> int64; }")
+        .assert().success();
+    std::fs::write("./tmp/import_01.csv",
+        "name,qty\napple,3\npear,\nplum,x\n")
+        .expect("can write file");
+    SERVER.database_cmd("import_01").arg("import")
+        .arg("--table=Item").arg("--error-log=./tmp/import_01.log")
+        .arg("./tmp/import_01.csv")
+        .assert().success()
+        .stderr(contains("Imported 2 rows, 1 rejected"));
+    let log = std::fs::read_to_string("./tmp/import_01.log")
+        .expect("error log is written");
+    assert!(log.contains("plum"));
+    std::fs::write("./tmp/import_01.json",
+        r#"[{"name": "fig", "qty": 7}]"#)
+        .expect("can write file");
+    SERVER.database_cmd("import_01").arg("import")
+        .arg("--table=default::Item").arg("--format=json")
+        .arg("./tmp/import_01.json")
+        .assert().success();
+    SERVER.database_cmd("import_01").arg("query")
+        .arg("SELECT sum(Item.qty)")
+        .assert().success()
+        .stdout("10\n");
+    SERVER.database_cmd("import_01").arg("import")
+        .arg("--table=Item").arg("--format=json")
+        .arg("./tmp/import_01.json").arg("--batch-size=0")
+        .assert().code(1);
+}
+
+#[test]
+fn export_csv_and_json() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    SERVER.admin_cmd().arg("create-database").arg("export_01")
+        .assert().success();
+    SERVER.database_cmd("export_01").arg("query")
+        .arg("CREATE TYPE Item { \
+              CREATE REQUIRED PROPERTY name -> str; \
+              CREATE PROPERTY qty -> int64; }")
+        .arg("FOR x IN {1, 2, 3} UNION (INSERT Item { \
+              name := 'item' ++ <str>x, qty := x })")
+        .assert().success();
+    SERVER.database_cmd("export_01").arg("export")
+        .arg("--type=Item").arg("--format=csv").arg("--page-size=2")
+        .arg("--out=./tmp/export_01.csv")
+        .assert().success()
+        .stderr(contains("Exported 3 objects"));
+    let csv = std::fs::read_to_string("./tmp/export_01.csv")
+        .expect("export is written");
+    assert_eq!(csv.lines().count(), 4);
+    assert!(csv.lines().any(|line| line.ends_with(",item2,2")));
+    SERVER.database_cmd("export_01").arg("export")
+        .arg("--type=Item").arg("--out=./tmp/export_01.json")
+        .assert().success();
+    let json: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string("./tmp/export_01.json")
+            .expect("export is written"))
+        .expect("valid json");
+    assert_eq!(json.as_array().map(|a| a.len()), Some(3));
+}
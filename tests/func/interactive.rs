@@ -24,6 +24,28 @@ fn two_queries() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+fn multi_line_query() -> Result<(), Box<dyn Error>> {
+    let mut cmd = SERVER.admin_interactive();
+    cmd.exp_string("edgedb>")?;
+    cmd.send_line("SELECT 1 +\n")?;
+    cmd.exp_string(".......")?;
+    cmd.send_line("7;\n")?;
+    cmd.exp_string("{\u{1b}[38;5;2m8\u{1b}[0m}\r\n")?;
+    Ok(())
+}
+
+#[test]
+fn pasted_script() -> Result<(), Box<dyn Error>> {
+    let mut cmd = SERVER.admin_interactive();
+    cmd.exp_string("edgedb>")?;
+    cmd.send("\x1b[200~SELECT 1+2;\nSELECT 2+3;\x1b[201~")?;
+    cmd.send_line("")?;
+    cmd.exp_string("{\u{1b}[38;5;2m3\u{1b}[0m}\r\n")?;
+    cmd.exp_string("{\u{1b}[38;5;2m5\u{1b}[0m}\r\n")?;
+    Ok(())
+}
+
 #[test]
 fn create_report() -> Result<(), Box<dyn Error>> {
     let mut cmd = SERVER.admin_interactive();
@@ -48,6 +48,27 @@ fn version() {
         .stdout(concat!("edgedb-cli ", env!("CARGO_PKG_VERSION"), "\n"));
 }
 
+#[cfg(not(windows))]
+#[test]
+fn info_json() {
+    let cmd = SERVER.admin_cmd().arg("info").arg("--json").assert();
+    let out = String::from_utf8(cmd.success().get_output().stdout.clone())
+        .unwrap();
+    let info: serde_json::Value = from_str(&out).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn gen_man() {
+    let dir = "./tmp/man";
+    SERVER.admin_cmd().arg("gen-man").arg("--out-dir").arg(dir)
+        .assert().success();
+    let page = std::fs::read_to_string(format!("{}/edgedb-server-init.1", dir))
+        .expect("page for a nested command is written");
+    assert!(page.starts_with(".TH EDGEDB\\-SERVER\\-INIT 1"));
+    assert!(std::path::Path::new(dir).join("edgedb.1").exists());
+}
+
 pub struct ShutdownInfo {
     process: process::Child,
     thread: Option<JoinHandle<()>>,
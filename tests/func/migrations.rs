@@ -23,6 +23,26 @@ Some migrations are missing, use `edgedb create-migration`
     Ok(())
 }
 
+#[test]
+fn schema_diff() -> anyhow::Result<()> {
+    SERVER.admin_cmd()
+        .arg("create-database").arg("schema_diff")
+        .assert().success();
+    SERVER.admin_cmd()
+        .arg("schema").arg("diff")
+        .arg("/schema_diff").arg("tests/migrations/db1/bare")
+        .assert().code(1)
+        .stdout("+ CREATE TYPE default::Type1\n    \
+                 + CREATE PROPERTY field1 -> std::str\n");
+    SERVER.admin_cmd()
+        .arg("schema").arg("diff")
+        .arg("tests/migrations/db1/bare/default.esdl")
+        .arg("tests/migrations/db1/bare")
+        .assert().success()
+        .stderr(ends_with("Schemas are identical\n"));
+    Ok(())
+}
+
 #[test]
 fn initial() -> anyhow::Result<()> {
     fs::remove_file("tests/migrations/db1/initial/migrations/00002.edgeql")
@@ -253,6 +273,13 @@ Some migrations are missing, use `edgedb create-migration`
         .stderr(ends_with("Database is up to date. \
             Last migration: \
             m1caxjxlggy5xv63isfp5oxdbucx35efhgevxdklvlcgjgpdus3j3q.\n"));
+    SERVER.admin_cmd()
+        .arg("--database=modified1")
+        .arg("verify-migrations")
+        .arg("--schema-dir=tests/migrations/db1/modified1")
+        .assert().success()
+        .stderr(ends_with("Migrations are intact: 2 files, \
+            2 of them applied to the database.\n"));
     SERVER.admin_cmd()
         .arg("--database=modified1")
         .arg("create-migration")
@@ -1,3 +1,5 @@
+use predicates::str::contains;
+
 use crate::SERVER;
 
 
@@ -7,3 +9,152 @@ fn with_comment() {
         .write_stdin("SELECT 1; # comment")
         .assert().success();
 }
+
+#[test]
+fn json_error() {
+    SERVER.admin_cmd()
+        .arg("--output-format=json")
+        .arg("query").arg("SELECT nonexistent")
+        .assert().failure()
+        // InvalidReferenceError
+        .stderr(contains(r#""code":67305472"#));
+}
+
+#[test]
+fn concurrent_script() {
+    SERVER.admin_cmd()
+        .arg("--file=-")
+        .arg("--single-transaction=false")
+        .arg("--concurrency=2")
+        .write_stdin("SELECT 1; SELECT 2; SELECT 3;")
+        .assert().success()
+        .stdout("1\n2\n3\n");
+    SERVER.admin_cmd()
+        .arg("--stop-on-error=false")
+        .arg("query").arg("SELECT 1")
+        .assert().failure()
+        .stderr(contains("`--stop-on-error` requires `--file`"));
+}
+
+#[test]
+fn query_params() {
+    SERVER.admin_cmd()
+        .arg("query").arg("--param=x=1").arg("--param=y=2")
+        .arg("SELECT <int64>$x").arg("SELECT <str>$y").arg("SELECT 3")
+        .assert().success()
+        .stdout("1\n\"2\"\n3\n");
+    SERVER.admin_cmd()
+        .arg("query").arg("--param=x=1").arg("--param=z=2")
+        .arg("SELECT <int64>$x").arg("SELECT 3")
+        .assert().failure()
+        .stderr(contains("no query has parameter $z"));
+}
+
+#[test]
+fn graphql_query() {
+    SERVER.admin_cmd().arg("create-database").arg("graphql_01")
+        .assert().success();
+    SERVER.database_cmd("graphql_01").arg("query")
+        .arg("CREATE EXTENSION graphql")
+        .arg("CREATE TYPE Item { CREATE PROPERTY name -> str; }")
+        .arg("INSERT Item { name := 'apple' }")
+        .assert().success();
+    let url = format!("http://localhost:{}", SERVER.port);
+    SERVER.database_cmd("graphql_01").arg("graphql")
+        .arg("--url").arg(&url)
+        .arg("--var=name=apple")
+        .arg("query ($name: String) { Item(filter: {name: {eq: $name}}) \
+              { name } }")
+        .assert().success()
+        .stdout(contains("\"apple\""));
+    SERVER.database_cmd("graphql_01").arg("graphql")
+        .arg("--url").arg(&url)
+        .arg("{ NoSuchType { name } }")
+        .assert().code(1)
+        .stderr(contains("edgedb error:"));
+}
+
+#[test]
+fn http_query() {
+    SERVER.admin_cmd().arg("create-database").arg("http_01")
+        .assert().success();
+    SERVER.database_cmd("http_01").arg("query")
+        .arg("CREATE EXTENSION edgeql_http")
+        .assert().success();
+    let url = format!("http://localhost:{}", SERVER.port);
+    SERVER.database_cmd("http_01").arg("query")
+        .arg("--connection=http").arg("--http-url").arg(&url)
+        .arg("--param=x=hello")
+        .arg("SELECT <str>$x ++ '!'")
+        .assert().success()
+        .stdout("\"hello!\"\n");
+    SERVER.database_cmd("http_01").arg("--output-format=json")
+        .arg("query")
+        .arg("--connection=http").arg("--http-url").arg(&url)
+        .arg("--param=x=hello")
+        .arg("SELECT <str>$x ++ '!'")
+        .assert().success()
+        .stdout("[\"hello!\"]\n");
+    SERVER.database_cmd("http_01").arg("--output-format=json")
+        .arg("query")
+        .arg("--connection=http").arg("--http-url").arg(&url)
+        .arg("SELECT 1/0")
+        .assert().code(1)
+        .stderr(contains("DivisionByZeroError"));
+}
+
+#[test]
+fn diff_against() {
+    std::fs::create_dir_all("./tmp").expect("can create directory");
+    std::fs::write("./tmp/diff_01.json", "[1, 2]\n").expect("can write");
+    SERVER.admin_cmd()
+        .arg("query").arg("--diff-against=./tmp/diff_01.json")
+        .arg("SELECT {2, 3}")
+        .assert().code(1)
+        .stdout("+ 3\n- 1\n");
+    SERVER.admin_cmd()
+        .arg("query").arg("--diff-against=./tmp/diff_01.json")
+        .arg("SELECT {1, 2}")
+        .assert().success()
+        .stderr(contains("Result is the same"));
+}
+
+#[test]
+fn set_global_expression() {
+    SERVER.admin_cmd()
+        .arg("query")
+        .arg("--set-global=x=1); DROP TYPE default::User; SELECT (1")
+        .arg("SELECT 1")
+        .assert().failure()
+        .stderr(contains("invalid value of global x"));
+}
+
+#[test]
+fn read_only_debug_protocol() {
+    SERVER.admin_cmd()
+        .arg("--read-only")
+        .arg("query").arg("--debug-protocol")
+        .arg("CREATE TYPE default::ReadOnly01")
+        .assert().failure();
+    SERVER.admin_cmd()
+        .arg("--read-only")
+        .arg("query").arg("--debug-protocol")
+        .arg("SELECT 1")
+        .assert().success();
+}
+
+#[test]
+fn no_ddl_bench() {
+    SERVER.admin_cmd()
+        .arg("--no-ddl")
+        .arg("query").arg("--debug-protocol")
+        .arg("CREATE TYPE default::NoDdl01")
+        .assert().failure()
+        .stderr(contains("not allowed"));
+    SERVER.admin_cmd()
+        .arg("--no-ddl")
+        .arg("bench").arg("--duration=1s")
+        .arg("--query=CREATE TYPE default::NoDdl02")
+        .assert().failure()
+        .stderr(contains("not allowed"));
+}
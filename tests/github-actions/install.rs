@@ -1,5 +1,5 @@
 use std::fs;
-use std::io::Write;
+use std::io::{BufRead, Write};
 
 use assert_cmd::Command;
 use async_std::prelude::FutureExt;
@@ -227,6 +227,45 @@ fn github_action_install() -> anyhow::Result<()> {
                 .assert()
                 .success();
 
+            // HOME is overridden to keep credentials and ports of the
+            // temporary instance out of the user's config
+            println!("Run temporary instance");
+            let home = tempfile::tempdir()?;
+            let mut temp = std::process::Command::new(&edgedb)
+                .env("HOME", home.path())
+                .arg("server").arg("init").arg("temp1").arg("--temporary")
+                .stdout(std::process::Stdio::piped())
+                .spawn()?;
+            let mut info = String::new();
+            std::io::BufReader::new(temp.stdout.take().unwrap())
+                .read_line(&mut info)?;
+            assert!(info.contains(r#""name":"temp1""#));
+            Command::new(&edgedb)
+                .env("HOME", home.path())
+                .arg("--instance").arg("temp1")
+                .arg("query").arg("SELECT 1")
+                .assert()
+                .success();
+
+            println!("Destroy temporary instances");
+            Command::new(&edgedb)
+                .env("HOME", home.path())
+                .arg("server").arg("destroy").arg("--all-temporary")
+                .assert()
+                .success();
+            assert!(temp.wait()?.success());
+            Command::new(&edgedb)
+                .env("HOME", home.path())
+                .arg("--instance").arg("temp1")
+                .arg("query").arg("SELECT 1")
+                .assert()
+                .failure();
+
+            println!("Destroy without instance name");
+            Command::new(&edgedb)
+                .arg("server").arg("destroy")
+                .assert()
+                .failure();
         }
 
         println!("Uninstall the old version");